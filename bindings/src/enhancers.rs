@@ -2,24 +2,39 @@
 //!
 //! See `enhancers.pyi` for documentation on classes and functions.
 
-use pyo3::exceptions::PyRuntimeError;
+use std::cell::Cell;
+use std::collections::BTreeMap;
+use std::fmt::Write;
+use std::hash::{DefaultHasher, Hash, Hasher};
+use std::num::NonZeroUsize;
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::Duration;
+
+use pyo3::create_exception;
+use pyo3::exceptions::{PyIndexError, PyKeyError, PyRuntimeError, PyTypeError, PyValueError};
 use pyo3::prelude::*;
-use pyo3::types::PyList;
+use pyo3::types::{PyByteArray, PyBytes, PyDict, PyInt, PyList, PyMemoryView};
 use rust_ophio::enhancers;
 
 #[derive(FromPyObject)]
 #[pyo3(from_item_all)]
-pub struct Frame {
+pub struct Frame<'py> {
     category: OptStr,
     family: OptStr,
     function: OptStr,
     module: OptStr,
-    package: OptStr,
-    path: OptStr,
+    package: Option<Bound<'py, PyBytes>>,
+    path: Option<Bound<'py, PyBytes>>,
     in_app: Option<bool>,
     orig_in_app: Option<i8>,
 }
 
+/// An optional string value given as bytes.
+///
+/// Invalid UTF-8, which e.g. native paths may contain, is replaced with U+FFFD rather than
+/// rejecting the whole event.
+#[derive(Default)]
 struct OptStr(Option<enhancers::StringField>);
 
 impl FromPyObject<'_> for OptStr {
@@ -28,12 +43,14 @@ impl FromPyObject<'_> for OptStr {
             return Ok(Self(None));
         }
         let s: &[u8] = ob.extract()?;
-        let s = std::str::from_utf8(s)?;
-        Ok(Self(Some(enhancers::StringField::new(s))))
+        Ok(Self(Some(enhancers::StringField::new(
+            String::from_utf8_lossy(s),
+        ))))
     }
 }
 
-#[pyclass]
+#[pyclass(frozen, eq, hash)]
+#[derive(PartialEq, Eq, Hash)]
 pub struct AssembleResult {
     #[pyo3(get)]
     contributes: bool,
@@ -41,34 +58,430 @@ pub struct AssembleResult {
     hint: Option<String>,
     #[pyo3(get)]
     invert_stacktrace: bool,
+    #[pyo3(get)]
+    budget_exhausted_at: Option<usize>,
+}
+
+#[pymethods]
+impl AssembleResult {
+    fn __repr__(&self, py: Python) -> PyResult<String> {
+        Ok(format!(
+            "AssembleResult(contributes={}, hint={}, invert_stacktrace={}, budget_exhausted_at={})",
+            py_repr(py, self.contributes)?,
+            py_repr(py, &self.hint)?,
+            py_repr(py, self.invert_stacktrace)?,
+            py_repr(py, self.budget_exhausted_at)?,
+        ))
+    }
+}
+
+/// The new category and `in_app` flag of a frame.
+type ModificationResult = (Option<String>, Option<bool>);
+
+/// The text of a rule and the number of frames whose `in_app` flag it changed.
+type InAppChanges = (String, usize);
+
+#[pyclass(frozen, eq, hash)]
+#[derive(PartialEq, Eq, Hash)]
+pub struct ModifyResult {
+    #[pyo3(get)]
+    frames: Vec<ModificationResult>,
+    #[pyo3(get)]
+    has_vars: Vec<Option<bool>>,
+    #[pyo3(get)]
+    in_app_changes: Vec<InAppChanges>,
+    #[pyo3(get)]
+    in_app_last_changed: Vec<Option<usize>>,
+    #[pyo3(get)]
+    matched: bool,
+    #[pyo3(get)]
+    changed: bool,
+}
+
+#[pymethods]
+impl ModifyResult {
+    fn __repr__(&self, py: Python) -> PyResult<String> {
+        Ok(format!(
+            "ModifyResult(frames={}, has_vars={}, in_app_changes={}, in_app_last_changed={}, \
+             matched={}, changed={})",
+            py_repr(py, &self.frames)?,
+            py_repr(py, &self.has_vars)?,
+            py_repr(py, &self.in_app_changes)?,
+            py_repr(py, &self.in_app_last_changed)?,
+            py_repr(py, self.matched)?,
+            py_repr(py, self.changed)?,
+        ))
+    }
+}
+
+/// The text of a rule, the seconds spent on it, and the number of frames it matched.
+type RuleProfile = (String, f64, usize);
+
+#[pyclass(frozen, eq, hash)]
+#[derive(PartialEq, Eq, Hash)]
+pub struct StacktraceState {
+    #[pyo3(get)]
+    max_frames: usize,
+    #[pyo3(get)]
+    max_frames_setter: Option<String>,
+    #[pyo3(get)]
+    min_frames: usize,
+    #[pyo3(get)]
+    min_frames_setter: Option<String>,
+    #[pyo3(get)]
+    invert_stacktrace: bool,
+    #[pyo3(get)]
+    invert_stacktrace_setter: Option<String>,
+}
+
+#[pymethods]
+impl StacktraceState {
+    fn __repr__(&self, py: Python) -> PyResult<String> {
+        Ok(format!(
+            "StacktraceState(max_frames={}, min_frames={}, invert_stacktrace={})",
+            self.max_frames,
+            self.min_frames,
+            py_repr(py, self.invert_stacktrace)?,
+        ))
+    }
+}
+
+#[pyclass(frozen, eq, hash)]
+#[derive(PartialEq, Eq, Hash)]
+pub struct ApplyStats {
+    #[pyo3(get)]
+    skipped_by_families: usize,
+    #[pyo3(get)]
+    skipped_by_exception: usize,
+    #[pyo3(get)]
+    evaluated: usize,
+    #[pyo3(get)]
+    truncated: bool,
+    #[pyo3(get)]
+    budget_exhausted_at: Option<usize>,
+}
+
+#[pymethods]
+impl ApplyStats {
+    fn __repr__(&self, py: Python) -> PyResult<String> {
+        Ok(format!(
+            "ApplyStats(skipped_by_families={}, skipped_by_exception={}, evaluated={}, \
+             truncated={}, budget_exhausted_at={})",
+            self.skipped_by_families,
+            self.skipped_by_exception,
+            self.evaluated,
+            py_repr(py, self.truncated)?,
+            py_repr(py, self.budget_exhausted_at)?,
+        ))
+    }
+}
+
+#[pyclass(frozen, eq, hash)]
+#[derive(PartialEq, Eq, Hash)]
+pub struct PatternCensus {
+    #[pyo3(get)]
+    rules: usize,
+    #[pyo3(get)]
+    matchers: BTreeMap<String, usize>,
+    #[pyo3(get)]
+    negated_matchers: usize,
+    #[pyo3(get)]
+    caller_matchers: usize,
+    #[pyo3(get)]
+    callee_matchers: usize,
+    #[pyo3(get)]
+    glob_features: BTreeMap<String, usize>,
+}
+
+#[pymethods]
+impl PatternCensus {
+    fn __repr__(&self, py: Python) -> PyResult<String> {
+        Ok(format!(
+            "PatternCensus(rules={}, matchers={}, negated_matchers={}, caller_matchers={}, \
+             callee_matchers={}, glob_features={})",
+            self.rules,
+            py_repr(py, &self.matchers)?,
+            self.negated_matchers,
+            self.caller_matchers,
+            self.callee_matchers,
+            py_repr(py, &self.glob_features)?,
+        ))
+    }
 }
 
 #[pyclass]
+pub struct RuleView {
+    #[pyo3(get)]
+    text: String,
+    #[pyo3(get)]
+    matchers: Vec<Py<PyDict>>,
+    #[pyo3(get)]
+    actions: Vec<Py<PyDict>>,
+    #[pyo3(get)]
+    is_modifier: bool,
+    #[pyo3(get)]
+    is_updater: bool,
+}
+
+impl RuleView {
+    fn new(py: Python, rule: &enhancers::Rule) -> PyResult<Self> {
+        let description = rule.describe();
+        let matchers = description
+            .matchers
+            .iter()
+            .map(|m| convert_matcher_description(py, m))
+            .collect::<PyResult<_>>()?;
+        let actions = description
+            .actions
+            .iter()
+            .map(|a| convert_action_description(py, a))
+            .collect::<PyResult<_>>()?;
+
+        Ok(Self {
+            text: description.text,
+            matchers,
+            actions,
+            is_modifier: rule.has_modifier_action(),
+            is_updater: rule.has_updater_action(),
+        })
+    }
+}
+
+#[pymethods]
+impl RuleView {
+    fn __repr__(&self, py: Python) -> PyResult<String> {
+        Ok(format!("RuleView({})", py_repr(py, &self.text)?))
+    }
+
+    // Rules are displayed canonically, so equal rules have the same text.
+    fn __eq__(&self, other: &Self) -> bool {
+        self.text == other.text
+    }
+
+    fn __hash__(&self) -> u64 {
+        let mut hasher = DefaultHasher::new();
+        self.text.hash(&mut hasher);
+        hasher.finish()
+    }
+}
+
+#[pyclass(eq)]
+#[derive(PartialEq)]
 pub struct Component {
     #[pyo3(get, set)]
     contributes: Option<bool>,
     #[pyo3(get)]
     hint: Option<String>,
+    #[pyo3(get)]
+    hints: Vec<String>,
+    #[pyo3(get, set)]
+    is_sentinel_frame: bool,
+    #[pyo3(get, set)]
+    is_prefix_frame: bool,
+    #[pyo3(get, set)]
+    id: Option<String>,
+    #[pyo3(get, set)]
+    values: Option<Vec<String>>,
 }
 
 #[pymethods]
 impl Component {
     #[new]
-    #[pyo3(signature = (contributes=None))]
-    fn new(contributes: Option<bool>) -> Self {
+    #[pyo3(signature = (
+        contributes=None,
+        hint=None,
+        *,
+        is_sentinel_frame=false,
+        is_prefix_frame=false,
+        id=None,
+        values=None,
+    ))]
+    fn new(
+        contributes: Option<bool>,
+        hint: Option<String>,
+        is_sentinel_frame: bool,
+        is_prefix_frame: bool,
+        id: Option<String>,
+        values: Option<Vec<String>>,
+    ) -> Self {
         Self {
             contributes,
-            hint: None,
+            hints: hint.iter().cloned().collect(),
+            hint,
+            is_sentinel_frame,
+            is_prefix_frame,
+            id,
+            values,
         }
     }
+
+    #[staticmethod]
+    fn from_dict(dict: &Bound<'_, PyDict>) -> PyResult<Self> {
+        let contributes = dict
+            .get_item("contributes")?
+            .map(|v| v.extract::<Option<bool>>())
+            .transpose()?
+            .flatten();
+        let hint = dict
+            .get_item("hint")?
+            .map(|v| v.extract::<Option<String>>())
+            .transpose()?
+            .flatten();
+        let hints = dict
+            .get_item("hints")?
+            .map(|v| v.extract::<Vec<String>>())
+            .transpose()?;
+        let is_sentinel_frame = dict
+            .get_item("is_sentinel_frame")?
+            .map(|v| v.extract::<bool>())
+            .transpose()?
+            .unwrap_or_default();
+        let is_prefix_frame = dict
+            .get_item("is_prefix_frame")?
+            .map(|v| v.extract::<bool>())
+            .transpose()?
+            .unwrap_or_default();
+        let id = dict
+            .get_item("id")?
+            .map(|v| v.extract::<Option<String>>())
+            .transpose()?
+            .flatten();
+        let values = dict
+            .get_item("values")?
+            .map(|v| v.extract::<Option<Vec<String>>>())
+            .transpose()?
+            .flatten();
+        let mut component = Self::new(
+            contributes,
+            hint,
+            is_sentinel_frame,
+            is_prefix_frame,
+            id,
+            values,
+        );
+        if let Some(hints) = hints {
+            component.hints = hints;
+        }
+        Ok(component)
+    }
+
+    fn to_dict<'py>(&self, py: Python<'py>) -> PyResult<Bound<'py, PyDict>> {
+        let dict = PyDict::new_bound(py);
+        dict.set_item("contributes", self.contributes)?;
+        dict.set_item("hint", &self.hint)?;
+        dict.set_item("hints", &self.hints)?;
+        if self.is_sentinel_frame {
+            dict.set_item("is_sentinel_frame", true)?;
+        }
+        if self.is_prefix_frame {
+            dict.set_item("is_prefix_frame", true)?;
+        }
+        if let Some(id) = &self.id {
+            dict.set_item("id", id)?;
+        }
+        if let Some(values) = &self.values {
+            dict.set_item("values", values)?;
+        }
+        Ok(dict)
+    }
+
+    fn __repr__(&self, py: Python) -> PyResult<String> {
+        let mut repr = format!(
+            "Component(contributes={}, hint={}",
+            py_repr(py, self.contributes)?,
+            py_repr(py, &self.hint)?,
+        );
+        // The keyword-only arguments are only shown if they differ from their defaults.
+        if self.is_sentinel_frame {
+            repr.push_str(", is_sentinel_frame=True");
+        }
+        if self.is_prefix_frame {
+            repr.push_str(", is_prefix_frame=True");
+        }
+        if self.id.is_some() {
+            write!(repr, ", id={}", py_repr(py, &self.id)?).unwrap();
+        }
+        if self.values.is_some() {
+            write!(repr, ", values={}", py_repr(py, &self.values)?).unwrap();
+        }
+        repr.push(')');
+        Ok(repr)
+    }
 }
 
-#[derive(FromPyObject)]
-#[pyo3(from_item_all)]
 pub struct ExceptionData {
     ty: OptStr,
     value: OptStr,
+    module: OptStr,
     mechanism: OptStr,
+    thread: ThreadData,
+}
+
+impl FromPyObject<'_> for ExceptionData {
+    fn extract_bound(ob: &Bound<'_, PyAny>) -> PyResult<Self> {
+        // `module` and `thread` are optional, unlike the other keys.
+        let module = match get_optional_item(ob, "module")? {
+            Some(module) => module.extract()?,
+            None => OptStr(None),
+        };
+        let thread = match get_optional_item(ob, "thread")? {
+            Some(thread) if !thread.is_none() => thread.extract()?,
+            _ => ThreadData::default(),
+        };
+        Ok(Self {
+            ty: ob.get_item("ty")?.extract()?,
+            value: ob.get_item("value")?.extract()?,
+            module,
+            mechanism: ob.get_item("mechanism")?.extract()?,
+            thread,
+        })
+    }
+}
+
+/// The thread of [`ExceptionData`], a mapping whose keys are all optional.
+#[derive(Default)]
+struct ThreadData {
+    name: OptStr,
+    id: OptStr,
+    main: Option<bool>,
+    crashed: Option<bool>,
+}
+
+impl FromPyObject<'_> for ThreadData {
+    fn extract_bound(ob: &Bound<'_, PyAny>) -> PyResult<Self> {
+        let get = |key| -> PyResult<_> { Ok(get_optional_item(ob, key)?.filter(|v| !v.is_none())) };
+        // Thread ids are usually numbers, but they are matched as strings.
+        let id = match get("id")? {
+            Some(id) if id.is_instance_of::<PyInt>() => {
+                OptStr(Some(enhancers::StringField::new(id.str()?.to_string())))
+            }
+            Some(id) => id.extract()?,
+            None => OptStr(None),
+        };
+        Ok(Self {
+            name: get("name")?
+                .map(|name| name.extract())
+                .transpose()?
+                .unwrap_or_default(),
+            id,
+            main: get("main")?.map(|main| main.extract()).transpose()?,
+            crashed: get("crashed")?
+                .map(|crashed| crashed.extract())
+                .transpose()?,
+        })
+    }
+}
+
+/// Returns the item `key` of the mapping `ob`, or `None` if it doesn't have the key.
+fn get_optional_item<'py>(
+    ob: &Bound<'py, PyAny>,
+    key: &str,
+) -> PyResult<Option<Bound<'py, PyAny>>> {
+    match ob.get_item(key) {
+        Ok(item) => Ok(Some(item)),
+        Err(err) if err.is_instance_of::<PyKeyError>(ob.py()) => Ok(None),
+        Err(err) => Err(err),
+    }
 }
 
 #[pyclass]
@@ -77,109 +490,1202 @@ pub struct Cache(enhancers::Cache);
 #[pymethods]
 impl Cache {
     #[new]
-    fn new(size: usize) -> PyResult<Self> {
-        Ok(Self(enhancers::Cache::new(size)))
+    #[pyo3(signature = (size, lazy_regexes=false, *, ttl=None, admission=false))]
+    fn new(size: usize, lazy_regexes: bool, ttl: Option<f64>, admission: bool) -> PyResult<Self> {
+        let ttl = ttl
+            .map(Duration::try_from_secs_f64)
+            .transpose()
+            .map_err(|_| PyValueError::new_err("ttl must be a non-negative number of seconds"))?;
+        Ok(Self(
+            enhancers::Cache::new(size)
+                .with_lazy_regexes(lazy_regexes)
+                .with_ttl(ttl)
+                .with_admission(admission),
+        ))
+    }
+
+    /// Pickles the cache as its size and policies. The cached contents are not preserved.
+    fn __reduce__<'py>(slf: &Bound<'py, Self>) -> PyResult<(Bound<'py, PyAny>, (usize, bool))> {
+        let py = slf.py();
+        let cache = &slf.borrow().0;
+        let kwargs = PyDict::new_bound(py);
+        kwargs.set_item("ttl", cache.ttl().map(|ttl| ttl.as_secs_f64()))?;
+        kwargs.set_item("admission", cache.admission())?;
+        // `__reduce__` can't pass keyword arguments, so they are bound with `functools.partial`.
+        let partial = py.import_bound("functools")?.getattr("partial")?;
+        let constructor = partial.call((slf.get_type(),), Some(&kwargs))?;
+        Ok((constructor, (cache.size(), cache.lazy_regexes())))
+    }
+
+    fn __repr__(&self, py: Python) -> PyResult<String> {
+        let mut repr = format!(
+            "Cache(size={}, lazy_regexes={}",
+            self.0.size(),
+            py_repr(py, self.0.lazy_regexes())?,
+        );
+        // The keyword-only arguments are only shown if they differ from their defaults.
+        if let Some(ttl) = self.0.ttl() {
+            write!(repr, ", ttl={}", py_repr(py, ttl.as_secs_f64())?).unwrap();
+        }
+        if self.0.admission() {
+            repr.push_str(", admission=True");
+        }
+        write!(
+            repr,
+            ", cached_rules={}, cached_regexes={})",
+            self.0.cached_rules(),
+            self.0.cached_regexes(),
+        )
+        .unwrap();
+        Ok(repr)
+    }
+
+    fn save_to(&self, path: PathBuf) -> PyResult<usize> {
+        self.0.save_to(path).map_err(convert_cache_error)
+    }
+
+    fn load_from(&mut self, path: PathBuf) -> PyResult<usize> {
+        self.0.load_from(path).map_err(convert_cache_error)
+    }
+}
+
+#[pyclass]
+pub struct ResultCache(enhancers::ResultCache);
+
+#[pymethods]
+impl ResultCache {
+    #[new]
+    fn new(size: usize) -> Self {
+        Self(enhancers::ResultCache::new(size))
+    }
+
+    fn stats<'py>(&self, py: Python<'py>) -> PyResult<Bound<'py, PyDict>> {
+        let stats = self.0.stats();
+        let dict = PyDict::new_bound(py);
+        dict.set_item("hits", stats.hits)?;
+        dict.set_item("misses", stats.misses)?;
+        dict.set_item("entries", stats.entries)?;
+        dict.set_item("hit_rate", stats.hit_rate())?;
+        Ok(dict)
+    }
+
+    fn __repr__(&self) -> String {
+        let stats = self.0.stats();
+        format!(
+            "ResultCache(hits={}, misses={}, entries={})",
+            stats.hits, stats.misses, stats.entries
+        )
     }
 }
 
+thread_local! {
+    /// The buffer reused by every call of `assemble_stacktrace_component` on this thread, e.g.
+    /// for the stack traces of all threads of an event.
+    static COMPONENT_BUFFER: Cell<enhancers::ComponentBuffer> = Cell::default();
+}
+
 #[pyclass]
-pub struct Enhancements(enhancers::Enhancements);
+pub struct Enhancements(Arc<enhancers::Enhancements>);
+
+impl Enhancements {
+    /// Returns a mutable reference to the inner enhancements, copying them first if they are shared.
+    fn make_mut(&mut self) -> &mut enhancers::Enhancements {
+        Arc::make_mut(&mut self.0)
+    }
+
+    /// Converts `frames` and `exception_data` and applies the modifier rules to them.
+    fn modify(
+        &self,
+        frames: Bound<'_, PyList>,
+        exception_data: ExceptionData,
+        options: &enhancers::ModifyOptions,
+    ) -> PyResult<(Vec<enhancers::Frame>, enhancers::ModifyResult)> {
+        let mut frames: Vec<_> = frames
+            .into_iter()
+            .map(convert_frame_from_py)
+            .collect::<PyResult<_>>()?;
+        let exception_data = convert_exception_data_from_py(exception_data);
+
+        let result = self.0.apply_modifications_to_frames_with_options(
+            &mut frames,
+            &exception_data,
+            options,
+        );
+        Ok((frames, result))
+    }
+}
 
 #[pymethods]
 impl Enhancements {
     #[staticmethod]
     fn empty() -> Self {
-        Self(enhancers::Enhancements::default())
+        Self(Default::default())
+    }
+
+    #[staticmethod]
+    fn default(version: &str, cache: &mut Cache) -> PyResult<Self> {
+        let inner =
+            enhancers::Enhancements::default_config(version, &mut cache.0).map_err(pretty_error)?;
+        Ok(Self(Arc::new(inner)))
     }
 
     #[staticmethod]
-    fn parse(input: &str, cache: &mut Cache) -> PyResult<Self> {
-        let inner = enhancers::Enhancements::parse(input, &mut cache.0).map_err(pretty_error)?;
-        Ok(Self(inner))
+    fn default_contributes(version: &str, cache: &mut Cache) -> PyResult<Self> {
+        let inner = enhancers::Enhancements::default_contributes(version, &mut cache.0)
+            .map_err(pretty_error)?;
+        Ok(Self(Arc::new(inner)))
+    }
+
+    fn with_default_contributes(&self, version: &str, cache: &mut Cache) -> PyResult<Self> {
+        let inner = self
+            .0
+            .with_default_contributes(version, &mut cache.0)
+            .map_err(pretty_error)?;
+        Ok(Self(Arc::new(inner)))
     }
 
     #[staticmethod]
-    fn from_config_structure(input: &[u8], cache: &mut Cache) -> PyResult<Self> {
-        let inner = enhancers::Enhancements::from_config_structure(input, &mut cache.0)
+    #[pyo3(signature = (
+        input,
+        cache,
+        allowed_categories=None,
+        *,
+        glob_braces=true,
+        glob_classes=true,
+        strict_globs=false,
+    ))]
+    fn parse(
+        input: &str,
+        cache: &mut Cache,
+        allowed_categories: Option<Vec<String>>,
+        glob_braces: bool,
+        glob_classes: bool,
+        strict_globs: bool,
+    ) -> PyResult<Self> {
+        let options =
+            convert_parse_options(allowed_categories, glob_braces, glob_classes, strict_globs);
+        let inner = enhancers::Enhancements::parse_with_options(input, &mut cache.0, &options)
             .map_err(pretty_error)?;
-        Ok(Self(inner))
+        Ok(Self(Arc::new(inner)))
+    }
+
+    #[staticmethod]
+    #[pyo3(signature = (
+        input,
+        cache,
+        allowed_categories=None,
+        *,
+        glob_braces=true,
+        glob_classes=true,
+        strict_globs=false,
+    ))]
+    fn parse_lenient(
+        input: &str,
+        cache: &mut Cache,
+        allowed_categories: Option<Vec<String>>,
+        glob_braces: bool,
+        glob_classes: bool,
+        strict_globs: bool,
+    ) -> (Self, Vec<(usize, String)>) {
+        let options =
+            convert_parse_options(allowed_categories, glob_braces, glob_classes, strict_globs);
+        let (inner, errors) =
+            enhancers::Enhancements::parse_lenient_with_options(input, &mut cache.0, &options);
+        let errors = errors
+            .into_iter()
+            .map(|err| (err.line, format!("{:#}", rust_ophio::Error::from(err))))
+            .collect();
+        (Self(Arc::new(inner)), errors)
+    }
+
+    #[staticmethod]
+    #[pyo3(signature = (
+        input,
+        cache,
+        allowed_categories=None,
+        *,
+        resolve_base=None,
+        glob_braces=true,
+        glob_classes=true,
+        strict_globs=false,
+    ))]
+    fn from_config_structure(
+        input: &Bound<'_, PyAny>,
+        cache: &mut Cache,
+        allowed_categories: Option<Vec<String>>,
+        resolve_base: Option<Bound<'_, PyAny>>,
+        glob_braces: bool,
+        glob_classes: bool,
+        strict_globs: bool,
+    ) -> PyResult<Self> {
+        let input = config_structure_bytes(input)?;
+        let input = input.as_bytes();
+        let options =
+            convert_parse_options(allowed_categories, glob_braces, glob_classes, strict_globs);
+        let Some(resolve_base) = resolve_base else {
+            let inner = enhancers::Enhancements::from_config_structure_with_options(
+                input,
+                &mut cache.0,
+                &options,
+            )
+            .map_err(pretty_error)?;
+            return Ok(Self(Arc::new(inner)));
+        };
+
+        // An exception raised by the resolver stops the resolution and is raised again as is.
+        let mut resolver_err = None;
+        let resolve = |name: &str| {
+            if resolver_err.is_some() {
+                return None;
+            }
+            let bytes = resolve_base.call1((name,)).and_then(|bytes| {
+                if bytes.is_none() {
+                    return Ok(None);
+                }
+                Ok(Some(config_structure_bytes(&bytes)?.as_bytes().to_vec()))
+            });
+            match bytes {
+                Ok(bytes) => bytes,
+                Err(err) => {
+                    resolver_err = Some(err);
+                    None
+                }
+            }
+        };
+        let result = enhancers::Enhancements::from_config_structure_with_bases(
+            input,
+            &mut cache.0,
+            &options,
+            resolve,
+        );
+        if let Some(err) = resolver_err {
+            return Err(err);
+        }
+        Ok(Self(Arc::new(result.map_err(pretty_error)?)))
+    }
+
+    /// Pickles the enhancements as the text of their rules, which is parsed again when unpickling.
+    fn __reduce__<'py>(slf: &Bound<'py, Self>) -> PyResult<(Bound<'py, PyAny>, (String, Cache))> {
+        let rules: Vec<_> = slf.borrow().0.rules().map(|r| format!("{r:#}")).collect();
+        let parse = slf.get_type().getattr("parse")?;
+        Ok((parse, (rules.join("\n"), Cache(enhancers::Cache::new(0)))))
+    }
+
+    fn to_json(&self) -> String {
+        self.0.to_json()
+    }
+
+    fn to_config_structure<'py>(&self, py: Python<'py>) -> PyResult<Bound<'py, PyBytes>> {
+        let encoded = self.0.to_config_structure().map_err(pretty_error)?;
+        Ok(PyBytes::new_bound(py, &encoded))
+    }
+
+    fn pattern_census(&self) -> PatternCensus {
+        convert_pattern_census(self.0.pattern_census())
+    }
+
+    fn warnings<'py>(&self, py: Python<'py>) -> PyResult<Vec<Bound<'py, PyDict>>> {
+        (self.0.warnings().into_iter())
+            .map(|warning| {
+                let dict = PyDict::new_bound(py);
+                dict.set_item("rule", warning.rule)?;
+                dict.set_item("matcher", warning.matcher)?;
+                dict.set_item("kind", warning.kind.as_str())?;
+                dict.set_item("message", warning.message)?;
+                Ok(dict)
+            })
+            .collect()
+    }
+
+    fn __repr__(&self) -> String {
+        let modifiers = self.0.rules().filter(|r| r.has_modifier_action()).count();
+        let updaters = self.0.rules().filter(|r| r.has_updater_action()).count();
+        format!(
+            "Enhancements(rules={}, modifier_rules={modifiers}, updater_rules={updaters})",
+            self.0.rules().count(),
+        )
+    }
+
+    fn __eq__(&self, other: &Self) -> bool {
+        Arc::ptr_eq(&self.0, &other.0) || self.0.rules().eq(other.0.rules())
+    }
+
+    fn __len__(&self) -> usize {
+        self.0.rules().count()
+    }
+
+    fn __getitem__(&self, py: Python, index: isize) -> PyResult<RuleView> {
+        let len = self.__len__() as isize;
+        let index = if index < 0 { index + len } else { index };
+        let rule = usize::try_from(index)
+            .ok()
+            .and_then(|index| self.0.rules().nth(index))
+            .ok_or_else(|| PyIndexError::new_err("rule index out of range"))?;
+        RuleView::new(py, rule)
+    }
+
+    fn rules_setting(&self, var: &str) -> PyResult<Vec<String>> {
+        let kind = enhancers::VarKind::from_name(var)
+            .ok_or_else(|| PyValueError::new_err(format!("unknown variable `{var}`")))?;
+        Ok(self.0.rules_setting(kind).map(|r| r.to_string()).collect())
+    }
+
+    fn copy(&self) -> Self {
+        Self(Arc::clone(&self.0))
+    }
+
+    fn with_additional_rules(&self, other: &Self) -> Self {
+        Self(Arc::new(self.0.with_additional_rules(&other.0)))
     }
 
     fn extend_from(&mut self, other: &Self) {
-        self.0.extend_from(&other.0)
+        let other = Arc::clone(&other.0);
+        self.make_mut().extend_from(&other)
     }
 
+    fn retain_updater_only(&mut self) {
+        self.make_mut().retain_updater_only()
+    }
+
+    fn retain_modifier_only(&mut self) {
+        self.make_mut().retain_modifier_only()
+    }
+
+    fn dedup(&mut self) {
+        self.make_mut().dedup()
+    }
+
+    #[pyo3(signature = (
+        frames,
+        exception_data,
+        *,
+        match_raw_function=false,
+        frame_limit=0,
+        normalize_exception_value=false,
+        time_budget_ms=0,
+        derive_default_in_app=false,
+    ))]
+    #[allow(clippy::too_many_arguments)]
     fn apply_modifications_to_frames(
         &self,
-        py: Python,
         frames: Bound<'_, PyList>,
         exception_data: ExceptionData,
-    ) -> PyResult<Vec<PyObject>> {
+        match_raw_function: bool,
+        frame_limit: usize,
+        normalize_exception_value: bool,
+        time_budget_ms: u64,
+        derive_default_in_app: bool,
+    ) -> PyResult<Vec<ModificationResult>> {
+        let result = self.apply_modifications(
+            frames,
+            exception_data,
+            match_raw_function,
+            frame_limit,
+            normalize_exception_value,
+            time_budget_ms,
+            derive_default_in_app,
+            false,
+            false,
+        )?;
+        Ok(result.frames)
+    }
+
+    #[pyo3(signature = (
+        frames,
+        exception_data,
+        *,
+        match_raw_function=false,
+        frame_limit=0,
+        normalize_exception_value=false,
+        time_budget_ms=0,
+        derive_default_in_app=false,
+        summarize_in_app_changes=false,
+        track_in_app_last_changed=false,
+    ))]
+    #[allow(clippy::too_many_arguments)]
+    fn apply_modifications(
+        &self,
+        frames: Bound<'_, PyList>,
+        exception_data: ExceptionData,
+        match_raw_function: bool,
+        frame_limit: usize,
+        normalize_exception_value: bool,
+        time_budget_ms: u64,
+        derive_default_in_app: bool,
+        summarize_in_app_changes: bool,
+        track_in_app_last_changed: bool,
+    ) -> PyResult<ModifyResult> {
+        let options = enhancers::ModifyOptions {
+            match_raw_function,
+            frame_limit: NonZeroUsize::new(frame_limit),
+            normalize_exception_value,
+            time_budget: convert_time_budget(time_budget_ms),
+            derive_default_in_app,
+            summarize_in_app_changes,
+            track_in_app_last_changed,
+            ..Default::default()
+        };
+        let (frames, result) = self.modify(frames, exception_data, &options)?;
+
+        Ok(ModifyResult {
+            frames: convert_modifications(&frames),
+            has_vars: frames.iter().map(|f| f.has_vars).collect(),
+            in_app_changes: result
+                .in_app_changes
+                .into_iter()
+                .map(|changes| (changes.rule.to_string(), changes.frames))
+                .collect(),
+            in_app_last_changed: result
+                .in_app_last_changed
+                .into_iter()
+                .map(|id| id.map(enhancers::RuleId::index))
+                .collect(),
+            matched: result.matched,
+            changed: result.changed,
+        })
+    }
+
+    fn apply_modifications_to_frames_cached(
+        &self,
+        frames: Bound<'_, PyList>,
+        exception_data: ExceptionData,
+        mut result_cache: PyRefMut<ResultCache>,
+    ) -> PyResult<Vec<ModificationResult>> {
         let mut frames: Vec<_> = frames
             .into_iter()
             .map(convert_frame_from_py)
             .collect::<PyResult<_>>()?;
 
-        let exception_data = enhancers::ExceptionData {
-            ty: exception_data.ty.0,
-            value: exception_data.value.0,
-            mechanism: exception_data.mechanism.0,
-        };
+        let exception_data = convert_exception_data_from_py(exception_data);
 
         self.0
-            .apply_modifications_to_frames(&mut frames, &exception_data);
+            .apply_modifications_cached(&mut frames, &exception_data, &mut result_cache.0);
+
+        let result = convert_modifications(&frames);
+
+        Ok(result)
+    }
+
+    #[pyo3(signature = (frames, exception_data, *, frame_limit=0, time_budget_ms=0))]
+    fn apply_modifications_to_frames_with_stats(
+        &self,
+        frames: Bound<'_, PyList>,
+        exception_data: ExceptionData,
+        frame_limit: usize,
+        time_budget_ms: u64,
+    ) -> PyResult<(Vec<ModificationResult>, ApplyStats)> {
+        let options = enhancers::ModifyOptions {
+            collect_stats: true,
+            frame_limit: NonZeroUsize::new(frame_limit),
+            time_budget: convert_time_budget(time_budget_ms),
+            ..Default::default()
+        };
+        let (frames, modify_result) = self.modify(frames, exception_data, &options)?;
+
+        let result = convert_modifications(&frames);
+
+        let stats = modify_result.stats.unwrap_or_default();
+        let stats = ApplyStats {
+            skipped_by_families: stats.skipped_by_families,
+            skipped_by_exception: stats.skipped_by_exception,
+            evaluated: stats.evaluated,
+            truncated: stats.truncated,
+            budget_exhausted_at: modify_result.budget_exhausted_at.map(|id| id.index()),
+        };
+
+        Ok((result, stats))
+    }
+
+    #[pyo3(signature = (frames, exception_data, top_n=10))]
+    fn profile_apply(
+        &self,
+        frames: Bound<'_, PyList>,
+        exception_data: ExceptionData,
+        top_n: usize,
+    ) -> PyResult<(Vec<ModificationResult>, Vec<RuleProfile>)> {
+        let options = enhancers::ModifyOptions {
+            profile_rules: true,
+            ..Default::default()
+        };
+        let (frames, modify_result) = self.modify(frames, exception_data, &options)?;
+
+        let result = convert_modifications(&frames);
+
+        let profiles = modify_result
+            .rule_profiles
+            .into_iter()
+            .take(top_n)
+            .map(|profile| {
+                (
+                    profile.rule.to_string(),
+                    profile.elapsed.as_secs_f64(),
+                    profile.matched_frames,
+                )
+            })
+            .collect();
+
+        Ok((result, profiles))
+    }
+
+    fn apply_categories(
+        &self,
+        frames: Bound<'_, PyList>,
+        exception_data: ExceptionData,
+    ) -> PyResult<Vec<Option<String>>> {
+        let mut frames: Vec<_> = frames
+            .into_iter()
+            .map(convert_frame_from_py)
+            .collect::<PyResult<_>>()?;
+
+        let exception_data = convert_exception_data_from_py(exception_data);
+
+        self.0.apply_categories(&mut frames, &exception_data);
 
         let result = frames
             .into_iter()
-            .map(|f| (f.category.as_ref().map(|c| c.as_str()), f.in_app).into_py(py))
+            .map(|f| f.category.map(|c| c.to_string()))
             .collect();
 
         Ok(result)
     }
 
+    #[pyo3(signature = (
+        frames,
+        exception_data,
+        grouping_components,
+        *,
+        max_frames=0,
+        min_frames=0,
+        invert_stacktrace=false,
+        trim_to_sentinel=false,
+        match_raw_function=false,
+        frame_limit=0,
+        normalize_exception_value=false,
+        time_budget_ms=0,
+    ))]
+    #[allow(clippy::too_many_arguments)]
     fn assemble_stacktrace_component(
         &self,
         frames: Bound<'_, PyList>,
         exception_data: ExceptionData,
         mut grouping_components: Vec<PyRefMut<Component>>,
+        max_frames: usize,
+        min_frames: usize,
+        invert_stacktrace: bool,
+        trim_to_sentinel: bool,
+        match_raw_function: bool,
+        frame_limit: usize,
+        normalize_exception_value: bool,
+        time_budget_ms: u64,
     ) -> PyResult<AssembleResult> {
         let frames: Vec<_> = frames
             .into_iter()
             .map(convert_frame_from_py)
             .collect::<PyResult<_>>()?;
 
-        let exception_data = enhancers::ExceptionData {
-            ty: exception_data.ty.0,
-            value: exception_data.value.0,
-            mechanism: exception_data.mechanism.0,
+        let exception_data = convert_exception_data_from_py(exception_data);
+
+        let mut buffer = COMPONENT_BUFFER.take();
+        let components = buffer.reset(grouping_components.len());
+        for (component, py_component) in components.iter_mut().zip(&grouping_components) {
+            component.contributes = py_component.contributes;
+            component.is_sentinel_frame = py_component.is_sentinel_frame;
+            component.is_prefix_frame = py_component.is_prefix_frame;
+            component.id.clone_from(&py_component.id);
+            component.values.clone_from(&py_component.values);
+        }
+
+        let options = enhancers::AssembleOptions {
+            trim_to_sentinel,
+            frame_limit: NonZeroUsize::new(frame_limit),
+            normalize_exception_value,
+            time_budget: convert_time_budget(time_budget_ms),
+            ..convert_assemble_options(
+                max_frames,
+                min_frames,
+                invert_stacktrace,
+                match_raw_function,
+            )
         };
 
+        let assemble_result = self.0.assemble_stacktrace_component_in(
+            &mut buffer,
+            &frames,
+            &exception_data,
+            &options,
+        );
+
+        let components = buffer.components_mut();
+        for (py_component, rust_component) in grouping_components.iter_mut().zip(components) {
+            py_component.contributes = rust_component.contributes;
+            py_component.hint = rust_component.primary_hint().map(str::to_owned);
+            py_component.hints = rust_component
+                .hints
+                .iter()
+                .map(|h| h.text.clone())
+                .collect();
+            py_component.id = rust_component.id.take();
+            py_component.values = rust_component.values.take();
+        }
+        COMPONENT_BUFFER.set(buffer);
+
+        Ok(AssembleResult {
+            contributes: assemble_result.contributes,
+            hint: assemble_result.hint,
+            invert_stacktrace: assemble_result.invert_stacktrace,
+            budget_exhausted_at: assemble_result.budget_exhausted_at.map(|id| id.index()),
+        })
+    }
+
+    #[pyo3(signature = (
+        frames,
+        exception_data,
+        *,
+        max_frames=0,
+        min_frames=0,
+        invert_stacktrace=false,
+        match_raw_function=false,
+    ))]
+    fn stacktrace_state(
+        &self,
+        frames: Bound<'_, PyList>,
+        exception_data: ExceptionData,
+        max_frames: usize,
+        min_frames: usize,
+        invert_stacktrace: bool,
+        match_raw_function: bool,
+    ) -> PyResult<StacktraceState> {
+        let frames: Vec<_> = frames
+            .into_iter()
+            .map(convert_frame_from_py)
+            .collect::<PyResult<_>>()?;
+
+        let exception_data = convert_exception_data_from_py(exception_data);
+        let options = convert_assemble_options(
+            max_frames,
+            min_frames,
+            invert_stacktrace,
+            match_raw_function,
+        );
+
+        let state = self.0.stacktrace_state(&frames, &exception_data, &options);
+        Ok(convert_stacktrace_state(state))
+    }
+
+    #[pyo3(signature = (
+        frames,
+        exception_data,
+        grouping_components,
+        *,
+        max_frames=0,
+        min_frames=0,
+        invert_stacktrace=false,
+        trim_to_sentinel=false,
+        match_raw_function=false,
+        frame_limit=0,
+        normalize_exception_value=false,
+        time_budget_ms=0,
+    ))]
+    #[allow(clippy::too_many_arguments)]
+    fn apply_all(
+        &self,
+        py: Python,
+        frames: Bound<'_, PyList>,
+        exception_data: ExceptionData,
+        mut grouping_components: Vec<PyRefMut<Component>>,
+        max_frames: usize,
+        min_frames: usize,
+        invert_stacktrace: bool,
+        trim_to_sentinel: bool,
+        match_raw_function: bool,
+        frame_limit: usize,
+        normalize_exception_value: bool,
+        time_budget_ms: u64,
+    ) -> PyResult<(Vec<PyObject>, AssembleResult, StacktraceState)> {
+        let mut frames: Vec<_> = frames
+            .into_iter()
+            .map(convert_frame_from_py)
+            .collect::<PyResult<_>>()?;
+
+        let exception_data = convert_exception_data_from_py(exception_data);
+
         let mut components: Vec<_> = grouping_components
             .iter()
             .map(|c| convert_component_from_py(c))
             .collect();
 
-        let assemble_result =
+        let options = enhancers::AssembleOptions {
+            trim_to_sentinel,
+            frame_limit: NonZeroUsize::new(frame_limit),
+            normalize_exception_value,
+            time_budget: convert_time_budget(time_budget_ms),
+            ..convert_assemble_options(
+                max_frames,
+                min_frames,
+                invert_stacktrace,
+                match_raw_function,
+            )
+        };
+
+        let result =
             self.0
-                .assemble_stacktrace_component(&mut components, &frames, &exception_data);
+                .apply_all_with_options(&mut frames, &mut components, &exception_data, &options);
 
-        for (py_component, rust_component) in
-            grouping_components.iter_mut().zip(components.into_iter())
-        {
+        for (py_component, rust_component) in grouping_components.iter_mut().zip(components) {
             py_component.contributes = rust_component.contributes;
-            py_component.hint = rust_component.hint;
+            py_component.hint = rust_component.primary_hint().map(str::to_owned);
+            py_component.hints = rust_component.hints.into_iter().map(|h| h.text).collect();
+            py_component.id = rust_component.id;
+            py_component.values = rust_component.values;
         }
 
-        Ok(AssembleResult {
-            contributes: assemble_result.contributes,
-            hint: assemble_result.hint,
-            invert_stacktrace: assemble_result.invert_stacktrace,
-        })
+        let modifications = frames
+            .into_iter()
+            .map(|f| (f.category.as_ref().map(|c| c.as_str()), f.in_app).into_py(py))
+            .collect();
+
+        let assemble_result = AssembleResult {
+            contributes: result.assemble.contributes,
+            hint: result.assemble.hint,
+            invert_stacktrace: result.assemble.invert_stacktrace,
+            budget_exhausted_at: result.assemble.budget_exhausted_at.map(|id| id.index()),
+        };
+
+        Ok((
+            modifications,
+            assemble_result,
+            convert_stacktrace_state(result.stacktrace_state),
+        ))
     }
+
+    #[pyo3(signature = (
+        frames,
+        exception_data,
+        grouping_components,
+        *,
+        max_frames=0,
+        min_frames=0,
+        invert_stacktrace=false,
+        trim_to_sentinel=false,
+        match_raw_function=false,
+        frame_limit=0,
+        normalize_exception_value=false,
+        time_budget_ms=0,
+    ))]
+    #[allow(clippy::too_many_arguments)]
+    fn assemble_variants(
+        &self,
+        frames: Bound<'_, PyList>,
+        exception_data: ExceptionData,
+        grouping_components: Vec<PyRef<Component>>,
+        max_frames: usize,
+        min_frames: usize,
+        invert_stacktrace: bool,
+        trim_to_sentinel: bool,
+        match_raw_function: bool,
+        frame_limit: usize,
+        normalize_exception_value: bool,
+        time_budget_ms: u64,
+    ) -> PyResult<BTreeMap<&'static str, (Vec<Component>, AssembleResult)>> {
+        let frames: Vec<_> = frames
+            .into_iter()
+            .map(convert_frame_from_py)
+            .collect::<PyResult<_>>()?;
+
+        let exception_data = convert_exception_data_from_py(exception_data);
+
+        let components: Vec<_> = grouping_components
+            .iter()
+            .map(|c| convert_component_from_py(c))
+            .collect();
+
+        let options = enhancers::AssembleOptions {
+            trim_to_sentinel,
+            frame_limit: NonZeroUsize::new(frame_limit),
+            normalize_exception_value,
+            time_budget: convert_time_budget(time_budget_ms),
+            ..convert_assemble_options(
+                max_frames,
+                min_frames,
+                invert_stacktrace,
+                match_raw_function,
+            )
+        };
+
+        let variants = self
+            .0
+            .assemble_variants(&frames, &components, &exception_data, &options);
+
+        let convert_variant = |variant: enhancers::StacktraceVariant| {
+            let components = variant
+                .components
+                .into_iter()
+                .map(convert_component_to_py)
+                .collect();
+            let assemble_result = AssembleResult {
+                contributes: variant.result.contributes,
+                hint: variant.result.hint,
+                invert_stacktrace: variant.result.invert_stacktrace,
+                budget_exhausted_at: variant.result.budget_exhausted_at.map(|id| id.index()),
+            };
+            (components, assemble_result)
+        };
+
+        Ok(BTreeMap::from([
+            ("app", convert_variant(variants.app)),
+            ("system", convert_variant(variants.system)),
+        ]))
+    }
+
+    fn matching_rules(
+        &self,
+        frames: Bound<'_, PyList>,
+        index: usize,
+        exception_data: ExceptionData,
+    ) -> PyResult<Vec<usize>> {
+        let frames: Vec<_> = frames
+            .into_iter()
+            .map(convert_frame_from_py)
+            .collect::<PyResult<_>>()?;
+        if index >= frames.len() {
+            return Err(PyIndexError::new_err("frame index out of range"));
+        }
+
+        let exception_data = convert_exception_data_from_py(exception_data);
+
+        Ok(self
+            .0
+            .matching_rules(&frames, index, &exception_data)
+            .into_iter()
+            .map(|id| id.index())
+            .collect())
+    }
+
+    fn apply_and_encode_changes<'py>(
+        &self,
+        py: Python<'py>,
+        frames: Bound<'py, PyList>,
+        exception_data: ExceptionData,
+    ) -> PyResult<(Vec<PyObject>, Bound<'py, PyBytes>)> {
+        let mut frames: Vec<_> = frames
+            .into_iter()
+            .map(convert_frame_from_py)
+            .collect::<PyResult<_>>()?;
+
+        let exception_data = convert_exception_data_from_py(exception_data);
+
+        let changes = self
+            .0
+            .apply_and_encode_changes(&mut frames, &exception_data);
+
+        let modifications = frames
+            .into_iter()
+            .map(|f| (f.category.as_ref().map(|c| c.as_str()), f.in_app).into_py(py))
+            .collect();
+
+        Ok((modifications, PyBytes::new_bound(py, &changes)))
+    }
+
+    #[pyo3(signature = (
+        shadow,
+        frames,
+        exception_data,
+        *,
+        max_frames=0,
+        min_frames=0,
+        invert_stacktrace=false,
+        match_raw_function=false,
+    ))]
+    #[allow(clippy::too_many_arguments)]
+    fn shadow_diff(
+        &self,
+        py: Python,
+        shadow: &Self,
+        frames: Bound<'_, PyList>,
+        exception_data: ExceptionData,
+        max_frames: usize,
+        min_frames: usize,
+        invert_stacktrace: bool,
+        match_raw_function: bool,
+    ) -> PyResult<Vec<ShadowDifference>> {
+        let frames: Vec<_> = frames
+            .into_iter()
+            .map(convert_frame_from_py)
+            .collect::<PyResult<_>>()?;
+
+        let exception_data = convert_exception_data_from_py(exception_data);
+        let options = convert_assemble_options(
+            max_frames,
+            min_frames,
+            invert_stacktrace,
+            match_raw_function,
+        );
+
+        let differences = self
+            .0
+            .shadow_diff(&shadow.0, &frames, &exception_data, &options);
+
+        let category =
+            |c: Option<enhancers::StringField>| c.as_ref().map(|c| c.as_str()).into_py(py);
+        let frame_count = |n: Option<NonZeroUsize>| n.map_or(0, NonZeroUsize::get).into_py(py);
+
+        Ok(differences
+            .into_iter()
+            .map(|difference| match difference {
+                enhancers::ShadowDifference::InApp {
+                    frame,
+                    primary,
+                    shadow,
+                } => (
+                    "in_app",
+                    Some(frame),
+                    primary.into_py(py),
+                    shadow.into_py(py),
+                ),
+                enhancers::ShadowDifference::Category {
+                    frame,
+                    primary,
+                    shadow,
+                } => ("category", Some(frame), category(primary), category(shadow)),
+                enhancers::ShadowDifference::HasVars {
+                    frame,
+                    primary,
+                    shadow,
+                } => (
+                    "has_vars",
+                    Some(frame),
+                    primary.into_py(py),
+                    shadow.into_py(py),
+                ),
+                enhancers::ShadowDifference::MaxFrames { primary, shadow } => (
+                    "max_frames",
+                    None,
+                    frame_count(primary),
+                    frame_count(shadow),
+                ),
+                enhancers::ShadowDifference::MinFrames { primary, shadow } => (
+                    "min_frames",
+                    None,
+                    frame_count(primary),
+                    frame_count(shadow),
+                ),
+                enhancers::ShadowDifference::InvertStacktrace { primary, shadow } => (
+                    "invert_stacktrace",
+                    None,
+                    primary.into_py(py),
+                    shadow.into_py(py),
+                ),
+            })
+            .collect())
+    }
+}
+
+/// A difference reported by `Enhancements.shadow_diff`: the name of the differing value, the
+/// index of the frame it belongs to, and its primary and shadow values.
+type ShadowDifference = (&'static str, Option<usize>, PyObject, PyObject);
+
+#[pyclass]
+pub struct EnhancementsRegistry(enhancers::EnhancementsRegistry);
+
+#[pymethods]
+impl EnhancementsRegistry {
+    #[new]
+    fn new(size: usize) -> Self {
+        Self(enhancers::EnhancementsRegistry::new(size))
+    }
+
+    fn register_base(&mut self, name: &str, enhancements: &Enhancements) {
+        self.0.register_base(name, Arc::clone(&enhancements.0))
+    }
+
+    fn __repr__(&self, py: Python) -> PyResult<String> {
+        let mut bases: Vec<_> = self.0.base_names().collect();
+        bases.sort_unstable();
+        Ok(format!(
+            "EnhancementsRegistry(bases={})",
+            py_repr(py, bases)?
+        ))
+    }
+
+    fn get(&mut self, base: &str, custom: &str, cache: &mut Cache) -> PyResult<Enhancements> {
+        let inner = self
+            .0
+            .get_or_compile(base, custom, &mut cache.0)
+            .map_err(pretty_error)?;
+        Ok(Enhancements(inner))
+    }
+}
+
+#[pyfunction]
+pub fn normalize_exception_value(value: &str) -> String {
+    enhancers::normalize_exception_value(value).into_owned()
+}
+
+#[pyfunction]
+pub fn trim_function_name(function: &str, family: &str) -> String {
+    enhancers::function_name::trim(function, enhancers::Families::new(family)).into_owned()
+}
+
+#[pyfunction]
+pub fn default_in_app(frame: Bound<'_, PyAny>) -> PyResult<Option<bool>> {
+    let frame = convert_frame_from_py(frame)?;
+    Ok(enhancers::default_in_app(&frame))
+}
+
+#[pyfunction]
+#[pyo3(signature = (attributes, language=None))]
+pub fn otel_frame<'py>(
+    attributes: &Bound<'py, PyDict>,
+    language: Option<&str>,
+) -> PyResult<Bound<'py, PyDict>> {
+    let py = attributes.py();
+    // Only string values are collected, since none of the attributes of frames have other types.
+    let attributes: Vec<(String, String)> = attributes
+        .iter()
+        .filter_map(|(key, value)| Some((key.extract().ok()?, value.extract().ok()?)))
+        .collect();
+    let attributes = attributes.iter().map(|(k, v)| (k.as_str(), v.as_str()));
+    let frame = enhancers::Frame::from(enhancers::OtelFrame::from_attributes(attributes, language));
+
+    let bytes = |value: Option<&enhancers::StringField>| {
+        value.map(|value| PyBytes::new_bound(py, value.as_bytes()))
+    };
+    let dict = PyDict::new_bound(py);
+    dict.set_item("category", bytes(frame.category.as_ref()))?;
+    dict.set_item("family", bytes(frame.raw_family.as_ref()))?;
+    dict.set_item("function", bytes(frame.function.as_ref()))?;
+    dict.set_item("module", bytes(frame.module.as_ref()))?;
+    dict.set_item("package", bytes(frame.package.as_ref()))?;
+    dict.set_item("path", bytes(frame.path.as_ref()))?;
+    dict.set_item("in_app", frame.in_app)?;
+    dict.set_item("orig_in_app", None::<i8>)?;
+    Ok(dict)
+}
+
+#[pyfunction]
+pub fn apply_encoded_changes(
+    py: Python,
+    frames: Bound<'_, PyList>,
+    changes: &[u8],
+) -> PyResult<Vec<PyObject>> {
+    let mut frames: Vec<_> = frames
+        .into_iter()
+        .map(convert_frame_from_py)
+        .collect::<PyResult<_>>()?;
+
+    enhancers::apply_encoded_changes(&mut frames, changes).map_err(convert_changes_error)?;
+
+    Ok(frames
+        .into_iter()
+        .map(|f| (f.category.as_ref().map(|c| c.as_str()), f.in_app).into_py(py))
+        .collect())
+}
+
+/// The 0-based line number and a description of a change made by `migrate`.
+type MigrationTransformation = (usize, String);
+
+#[pyfunction]
+pub fn migrate(input: &str, cache: &mut Cache) -> PyResult<(String, Vec<MigrationTransformation>)> {
+    let migration = enhancers::migrate(input, &mut cache.0).map_err(pretty_error)?;
+    let transformations = migration
+        .transformations
+        .into_iter()
+        .map(|t| (t.line, t.kind.to_string()))
+        .collect();
+    Ok((migration.text, transformations))
+}
+
+fn convert_matcher_description(
+    py: Python,
+    matcher: &enhancers::MatcherDescription,
+) -> PyResult<Py<PyDict>> {
+    let kind = match matcher.kind {
+        enhancers::MatcherKind::Frame => "frame",
+        enhancers::MatcherKind::Exception => "exception",
+        enhancers::MatcherKind::Thread => "thread",
+    };
+    let offset = matcher.offset.map(|offset| match offset {
+        enhancers::MatcherOffset::Caller => "caller",
+        enhancers::MatcherOffset::Callee => "callee",
+    });
+
+    let dict = PyDict::new_bound(py);
+    dict.set_item("kind", kind)?;
+    dict.set_item("key", &matcher.key)?;
+    dict.set_item("pattern", &matcher.pattern)?;
+    dict.set_item("negated", matcher.negated)?;
+    dict.set_item("offset", offset)?;
+    Ok(dict.unbind())
+}
+
+fn convert_action_description(
+    py: Python,
+    action: &enhancers::ActionDescription,
+) -> PyResult<Py<PyDict>> {
+    let dict = PyDict::new_bound(py);
+    match action {
+        enhancers::ActionDescription::Flag { flag, value, range } => {
+            dict.set_item("type", "flag")?;
+            dict.set_item("flag", flag)?;
+            dict.set_item("value", value)?;
+            dict.set_item("range", range)?;
+        }
+        enhancers::ActionDescription::Var { name, value } => {
+            dict.set_item("type", "var")?;
+            dict.set_item("name", name)?;
+            match value {
+                enhancers::VarValue::Int(value) => dict.set_item("value", value)?,
+                enhancers::VarValue::Bool(value) => dict.set_item("value", value)?,
+                enhancers::VarValue::Str(value) => dict.set_item("value", value)?,
+            }
+        }
+    }
+    Ok(dict.unbind())
 }
 
-fn pretty_error(err: anyhow::Error) -> PyErr {
-    use std::fmt::Write;
+fn py_repr(py: Python, value: impl ToPyObject) -> PyResult<String> {
+    Ok(value.to_object(py).into_bound(py).repr()?.to_string())
+}
+
+create_exception!(
+    _bindings,
+    InvalidEnhancerConfig,
+    PyRuntimeError,
+    "Enhancement rules in the text format could not be parsed or are invalid."
+);
+create_exception!(
+    _bindings,
+    InvalidConfigStructure,
+    PyRuntimeError,
+    "Enhancement rules in the msgpack format could not be decoded or are invalid."
+);
+create_exception!(
+    _bindings,
+    RegexTooComplex,
+    PyRuntimeError,
+    "A matcher pattern compiles to a regex exceeding the size limit."
+);
+
+/// Checks whether `err` is caused by a regex exceeding the size limit.
+fn is_regex_too_complex(err: &rust_ophio::Error) -> bool {
+    let is_too_big = |err: &rust_ophio::Error| {
+        matches!(
+            err,
+            rust_ophio::Error::Regex(regex::Error::CompiledTooBig(_))
+        )
+    };
+
+    match err {
+        rust_ophio::Error::Parse(e) | rust_ophio::Error::ConfigStructure(e) => e
+            .chain()
+            .filter_map(|cause| cause.downcast_ref())
+            .any(is_too_big),
+        err => is_too_big(err),
+    }
+}
+
+fn pretty_error(err: rust_ophio::Error) -> PyErr {
+    use std::error::Error;
     let mut err_str = format!(
         "Invalid syntax {err}{}",
         if err.source().is_some() { ":" } else { "" }
@@ -191,32 +1697,239 @@ fn pretty_error(err: anyhow::Error) -> PyErr {
         source = err.source();
     }
 
-    PyRuntimeError::new_err(err_str)
+    if is_regex_too_complex(&err) {
+        return RegexTooComplex::new_err(err_str);
+    }
+
+    match err {
+        rust_ophio::Error::Parse(_) | rust_ophio::Error::Glob(_) | rust_ophio::Error::Regex(_) => {
+            let py_err = InvalidEnhancerConfig::new_err(err_str);
+            Python::with_gil(|py| set_location(py, &py_err, err.parse_error()))
+                .err()
+                .unwrap_or(py_err)
+        }
+        rust_ophio::Error::ConfigStructure(_) => InvalidConfigStructure::new_err(err_str),
+        _ => PyRuntimeError::new_err(err_str),
+    }
+}
+
+/// Sets the location of a parse error in the rules as attributes of `InvalidEnhancerConfig`,
+/// which are `None` if the error can't be located.
+fn set_location(
+    py: Python,
+    py_err: &PyErr,
+    parse_error: Option<&enhancers::ParseError>,
+) -> PyResult<()> {
+    let value = py_err.value_bound(py);
+    value.setattr("line", parse_error.map(|e| e.line))?;
+    value.setattr("column_start", parse_error.map(|e| e.columns.start))?;
+    value.setattr("column_end", parse_error.map(|e| e.columns.end))?;
+    value.setattr("token", parse_error.map(|e| e.token.as_str()))?;
+    value.setattr("kind", parse_error.map(|e| e.kind.as_str()))?;
+    Ok(())
+}
+
+/// Converts errors of persisting caches, raising `OSError`s for failed file operations.
+fn convert_cache_error(err: rust_ophio::Error) -> PyErr {
+    match err {
+        rust_ophio::Error::Io(err) => err.into(),
+        err => pretty_error(err),
+    }
+}
+
+/// Converts errors of applying change-sets, raising `ValueError`s with the whole chain of causes.
+fn convert_changes_error(err: rust_ophio::Error) -> PyErr {
+    use std::error::Error;
+    let mut err_str = err.to_string();
+    let mut source = err.source();
+    while let Some(err) = source {
+        err_str.push_str(": ");
+        err_str.push_str(&err.to_string());
+        source = err.source();
+    }
+    PyValueError::new_err(err_str)
+}
+
+fn convert_parse_options(
+    allowed_categories: Option<Vec<String>>,
+    glob_braces: bool,
+    glob_classes: bool,
+    strict_globs: bool,
+) -> enhancers::ParseOptions {
+    let mut options = enhancers::ParseOptions {
+        glob: enhancers::GlobOptions {
+            braces: glob_braces,
+            classes: glob_classes,
+            reject_invalid: strict_globs,
+        },
+        ..Default::default()
+    };
+    if let Some(categories) = allowed_categories {
+        options = options.with_allowed_categories(categories);
+    }
+    options
+}
+
+fn convert_assemble_options(
+    max_frames: usize,
+    min_frames: usize,
+    invert_stacktrace: bool,
+    match_raw_function: bool,
+) -> enhancers::AssembleOptions {
+    enhancers::AssembleOptions {
+        max_frames: NonZeroUsize::new(max_frames),
+        min_frames: NonZeroUsize::new(min_frames),
+        invert_stacktrace,
+        trim_to_sentinel: false,
+        match_raw_function,
+        collect_stats: false,
+        frame_limit: None,
+        normalize_exception_value: false,
+        time_budget: None,
+        memoize_shared_matchers: false,
+    }
+}
+
+/// Converts a time budget in milliseconds, where `0` means that there is no budget.
+fn convert_time_budget(time_budget_ms: u64) -> Option<Duration> {
+    (time_budget_ms > 0).then(|| Duration::from_millis(time_budget_ms))
+}
+
+fn convert_pattern_census(census: enhancers::PatternCensus) -> PatternCensus {
+    PatternCensus {
+        rules: census.rules,
+        matchers: census.matchers,
+        negated_matchers: census.negated_matchers,
+        caller_matchers: census.caller_matchers,
+        callee_matchers: census.callee_matchers,
+        glob_features: (census.glob_features.into_iter())
+            .map(|(feature, count)| (feature.as_str().to_owned(), count))
+            .collect(),
+    }
+}
+
+fn convert_stacktrace_state(state: enhancers::StacktraceState) -> StacktraceState {
+    let setter = |rule: Option<enhancers::Rule>| rule.map(|r| r.to_string());
+
+    StacktraceState {
+        max_frames: state.max_frames.value.map_or(0, NonZeroUsize::get),
+        max_frames_setter: setter(state.max_frames.setter),
+        min_frames: state.min_frames.value.map_or(0, NonZeroUsize::get),
+        min_frames_setter: setter(state.min_frames.setter),
+        invert_stacktrace: state.invert_stacktrace.value,
+        invert_stacktrace_setter: setter(state.invert_stacktrace.setter),
+    }
 }
 
 fn convert_frame_from_py(frame: Bound<'_, PyAny>) -> PyResult<enhancers::Frame> {
+    // `has_vars`, `raw_function` and `trust` are optional, unlike the other keys.
+    let has_vars = match frame.get_item("has_vars") {
+        Ok(has_vars) => has_vars.extract()?,
+        Err(err) if err.is_instance_of::<PyKeyError>(frame.py()) => None,
+        Err(err) => return Err(err),
+    };
+    let raw_function = match frame.get_item("raw_function") {
+        Ok(raw_function) => raw_function.extract::<OptStr>()?.0,
+        Err(err) if err.is_instance_of::<PyKeyError>(frame.py()) => None,
+        Err(err) => return Err(err),
+    };
+    let trust = match frame.get_item("trust") {
+        Ok(trust) => trust.extract::<OptStr>()?.0,
+        Err(err) if err.is_instance_of::<PyKeyError>(frame.py()) => None,
+        Err(err) => return Err(err),
+    };
     let frame: Frame = frame.extract()?;
-    let frame = enhancers::Frame {
+    // Like `OptStr`, these are converted lossily.
+    let package = frame
+        .package
+        .as_ref()
+        .map(|package| String::from_utf8_lossy(package.as_bytes()));
+    let path = frame
+        .path
+        .as_ref()
+        .map(|path| String::from_utf8_lossy(path.as_bytes()));
+
+    Ok(enhancers::Frame::from_raw_parts(enhancers::RawFrame {
         category: frame.category.0,
-        family: enhancers::Families::new(frame.family.0.as_deref().unwrap_or("other")),
+        platform: frame.family.0,
         function: frame.function.0,
+        raw_function,
         module: frame.module.0,
-        package: frame.package.0,
-        path: frame.path.0,
-
+        package: package.as_deref(),
+        path: path.as_deref(),
+        path_lowercase: None,
+        trust,
         in_app: frame.in_app,
         orig_in_app: frame.orig_in_app.map(|in_app| match in_app {
             0 => Some(false),
             1 => Some(true),
             _ => None,
         }),
-    };
-    Ok(frame)
+        has_vars,
+    }))
+}
+
+/// Returns the msgpack representation of enhancements given as `bytes`, `bytearray`, or
+/// `memoryview`.
+///
+/// `bytes` are decoded in place. The stable ABI has no access to the buffers of other objects
+/// before Python 3.11, so those are copied into `bytes` once.
+fn config_structure_bytes<'py>(input: &Bound<'py, PyAny>) -> PyResult<Bound<'py, PyBytes>> {
+    if let Ok(bytes) = input.downcast::<PyBytes>() {
+        return Ok(bytes.clone());
+    }
+    if input.is_instance_of::<PyByteArray>() || input.is_instance_of::<PyMemoryView>() {
+        let bytes = input.py().get_type_bound::<PyBytes>().call1((input,))?;
+        return Ok(bytes.downcast_into::<PyBytes>()?);
+    }
+    Err(PyTypeError::new_err(format!(
+        "expected bytes, bytearray, or memoryview, got {}",
+        input.get_type().name()?
+    )))
+}
+
+/// Returns the new category and `in_app` flag of each of the modified `frames`.
+fn convert_modifications(frames: &[enhancers::Frame]) -> Vec<ModificationResult> {
+    frames
+        .iter()
+        .map(|f| (f.category.as_ref().map(|c| c.as_str().into()), f.in_app))
+        .collect()
+}
+
+fn convert_exception_data_from_py(exception_data: ExceptionData) -> enhancers::ExceptionData {
+    enhancers::ExceptionData {
+        ty: exception_data.ty.0,
+        value: exception_data.value.0,
+        module: exception_data.module.0,
+        mechanism: exception_data.mechanism.0,
+        thread: enhancers::ThreadData {
+            name: exception_data.thread.name.0,
+            id: exception_data.thread.id.0,
+            main: exception_data.thread.main,
+            crashed: exception_data.thread.crashed,
+        },
+    }
 }
 
 fn convert_component_from_py(component: &Component) -> enhancers::Component {
     enhancers::Component {
         contributes: component.contributes,
-        hint: None,
+        hints: Vec::new(),
+        is_sentinel_frame: component.is_sentinel_frame,
+        is_prefix_frame: component.is_prefix_frame,
+        id: component.id.clone(),
+        values: component.values.clone(),
+    }
+}
+
+fn convert_component_to_py(component: enhancers::Component) -> Component {
+    Component {
+        contributes: component.contributes,
+        hint: component.primary_hint().map(str::to_owned),
+        hints: component.hints.into_iter().map(|h| h.text).collect(),
+        is_sentinel_frame: component.is_sentinel_frame,
+        is_prefix_frame: component.is_prefix_frame,
+        id: component.id,
+        values: component.values,
     }
 }
@@ -0,0 +1,38 @@
+use pyo3::exceptions::PyValueError;
+use pyo3::prelude::*;
+use rust_ophio::hashing::{self, Lowercase};
+
+fn convert_lowercase(lowercase: &str) -> PyResult<Lowercase> {
+    match lowercase {
+        "none" => Ok(Lowercase::None),
+        "ascii" => Ok(Lowercase::Ascii),
+        "unicode" => Ok(Lowercase::Unicode),
+        _ => Err(PyValueError::new_err(format!(
+            "invalid lowercase mode `{lowercase}`, expected one of `none`, `ascii`, or `unicode`"
+        ))),
+    }
+}
+
+#[pyfunction]
+#[pyo3(signature = (parts, *, lowercase="none"))]
+pub fn md5_hex(parts: Vec<String>, lowercase: &str) -> PyResult<String> {
+    Ok(hashing::md5_hex(parts, convert_lowercase(lowercase)?))
+}
+
+#[pyfunction]
+#[pyo3(signature = (parts, *, lowercase="none"))]
+pub fn crc32(parts: Vec<String>, lowercase: &str) -> PyResult<u32> {
+    Ok(hashing::crc32(parts, convert_lowercase(lowercase)?))
+}
+
+#[pyfunction]
+#[pyo3(signature = (parts, *, lowercase="none"))]
+pub fn fnv1a_32(parts: Vec<String>, lowercase: &str) -> PyResult<u32> {
+    Ok(hashing::fnv1a_32(parts, convert_lowercase(lowercase)?))
+}
+
+#[pyfunction]
+#[pyo3(signature = (parts, *, lowercase="none"))]
+pub fn fnv1a_64(parts: Vec<String>, lowercase: &str) -> PyResult<u64> {
+    Ok(hashing::fnv1a_64(parts, convert_lowercase(lowercase)?))
+}
@@ -1,13 +1,49 @@
+// `#[pymethods]` returning `PyResult` trigger this lint with newer versions of clippy.
+#![allow(clippy::useless_conversion)]
+// `create_exception!` checks for a `gil-refs` feature in our crate with this version of PyO3.
+#![allow(unexpected_cfgs)]
+
 use pyo3::prelude::*;
 
 mod enhancers;
+mod hashing;
 
 #[pymodule]
-fn _bindings(_py: Python, m: Bound<PyModule>) -> PyResult<()> {
+fn _bindings(py: Python, m: Bound<PyModule>) -> PyResult<()> {
     m.add_class::<enhancers::Cache>()?;
     m.add_class::<enhancers::Component>()?;
     m.add_class::<enhancers::Enhancements>()?;
+    m.add_class::<enhancers::EnhancementsRegistry>()?;
     m.add_class::<enhancers::AssembleResult>()?;
+    m.add_class::<enhancers::StacktraceState>()?;
+    m.add_class::<enhancers::RuleView>()?;
+    m.add_class::<enhancers::ApplyStats>()?;
+    m.add_class::<enhancers::ModifyResult>()?;
+    m.add_class::<enhancers::PatternCensus>()?;
+    m.add_class::<enhancers::ResultCache>()?;
+    m.add_function(wrap_pyfunction!(enhancers::migrate, &m)?)?;
+    m.add_function(wrap_pyfunction!(enhancers::normalize_exception_value, &m)?)?;
+    m.add_function(wrap_pyfunction!(enhancers::trim_function_name, &m)?)?;
+    m.add_function(wrap_pyfunction!(enhancers::default_in_app, &m)?)?;
+    m.add_function(wrap_pyfunction!(enhancers::apply_encoded_changes, &m)?)?;
+    m.add_function(wrap_pyfunction!(enhancers::otel_frame, &m)?)?;
+    m.add_function(wrap_pyfunction!(hashing::md5_hex, &m)?)?;
+    m.add_function(wrap_pyfunction!(hashing::crc32, &m)?)?;
+    m.add_function(wrap_pyfunction!(hashing::fnv1a_32, &m)?)?;
+    m.add_function(wrap_pyfunction!(hashing::fnv1a_64, &m)?)?;
+
+    m.add(
+        "InvalidEnhancerConfig",
+        py.get_type_bound::<enhancers::InvalidEnhancerConfig>(),
+    )?;
+    m.add(
+        "InvalidConfigStructure",
+        py.get_type_bound::<enhancers::InvalidConfigStructure>(),
+    )?;
+    m.add(
+        "RegexTooComplex",
+        py.get_type_bound::<enhancers::RegexTooComplex>(),
+    )?;
 
     Ok(())
 }
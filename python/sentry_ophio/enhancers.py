@@ -1,6 +1,43 @@
-from ._bindings import AssembleResult, Cache, Component, Enhancements
+from ._bindings import (
+    ApplyStats,
+    AssembleResult,
+    Cache,
+    Component,
+    Enhancements,
+    EnhancementsRegistry,
+    InvalidConfigStructure,
+    InvalidEnhancerConfig,
+    ModifyResult,
+    PatternCensus,
+    RegexTooComplex,
+    ResultCache,
+    RuleView,
+    StacktraceState,
+    apply_encoded_changes,
+    default_in_app,
+    migrate,
+    normalize_exception_value,
+    otel_frame,
+    trim_function_name,
+)
 
+ApplyStats.__module__ = __name__
 AssembleResult.__module__ = __name__
 Cache.__module__ = __name__
 Component.__module__ = __name__
 Enhancements.__module__ = __name__
+EnhancementsRegistry.__module__ = __name__
+ModifyResult.__module__ = __name__
+PatternCensus.__module__ = __name__
+ResultCache.__module__ = __name__
+StacktraceState.__module__ = __name__
+RuleView.__module__ = __name__
+InvalidConfigStructure.__module__ = __name__
+InvalidEnhancerConfig.__module__ = __name__
+RegexTooComplex.__module__ = __name__
+apply_encoded_changes.__module__ = __name__
+default_in_app.__module__ = __name__
+migrate.__module__ = __name__
+normalize_exception_value.__module__ = __name__
+otel_frame.__module__ = __name__
+trim_function_name.__module__ = __name__
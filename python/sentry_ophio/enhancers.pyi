@@ -1,24 +1,249 @@
+import os
+from collections.abc import Callable
 from typing import Any
 from typing_extensions import Self
 
-ExceptionData = dict[str, bytes | None]
+# The "module" of an exception is the module or package its type is defined in,
+# e.g. b"java.lang". If it is set, `error.type` matchers match both the bare type
+# and the type qualified with the module.
+#
+# The "thread" is the thread whose stack trace is processed, checked by `thread.*`
+# matchers. Its keys are all optional: "name" (bytes), "id" (bytes or int), and the
+# booleans "main" and "crashed".
+ExceptionData = dict[str, Any]
 Frame = dict[str, Any]
 ModificationResult = tuple[str | None, bool | None]
 
 
+class InvalidEnhancerConfig(RuntimeError):
+    """
+    Enhancement rules in the text format could not be parsed or are invalid.
+
+    If the error is in a line of the rules passed to `Enhancements.parse`, the
+    attributes locate it, and are `None` otherwise. `line` is the number of the
+    line, starting at 1. `column_start` and `column_end` are the columns of the
+    offending `token`, e.g. a matcher or an action, counted in characters
+    starting at 0. If the error can't be located more precisely, the token is
+    the whole line. `kind` is what is wrong with the line: "syntax",
+    "missing_matcher", "invalid_matcher", "missing_action", "invalid_action",
+    "invalid_pattern", or "invalid_rule".
+    """
+
+    line: int | None
+    column_start: int | None
+    column_end: int | None
+    token: str | None
+    kind: str | None
+
+
+class InvalidConfigStructure(RuntimeError):
+    """
+    Enhancement rules in the msgpack format could not be decoded or are invalid.
+    """
+
+
+class RegexTooComplex(RuntimeError):
+    """
+    A matcher pattern compiles to a regex exceeding the size limit.
+    """
+
+
 class Component:
+    """
+    A frame grouping component.
+
+    `id` is the id of the grouping component, e.g. "frame". `values` are the
+    grouping values of the component's contributing children, if known. A
+    component whose `contributes` is `None` contributes if and only if it has
+    any `values`.
+
+    `hints` are all hints explaining `contributes`, in the order they were
+    added and without duplicates. `hint` is the one taking precedence, e.g. a
+    `-group` hint over a later `-app` hint. Hints passed in are replaced by the
+    ones set when assembling the stack trace component.
+
+    Components compare equal if all their attributes are equal.
+    """
+
     contributes: bool | None
     hint: str | None
+    hints: list[str]
+    is_sentinel_frame: bool
+    is_prefix_frame: bool
+    id: str | None
+    values: list[str] | None
 
     def __new__(
-        cls, contributes: bool | None
+        cls,
+        contributes: bool | None = None,
+        hint: str | None = None,
+        *,
+        is_sentinel_frame: bool = False,
+        is_prefix_frame: bool = False,
+        id: str | None = None,
+        values: list[str] | None = None,
     ) -> Self: ...
 
+    @staticmethod
+    def from_dict(d: dict[str, Any]) -> Component:
+        """
+        Creates a Component from a dict with the keys "contributes" and "hint",
+        and optionally "hints", "is_sentinel_frame", "is_prefix_frame", "id",
+        and "values".
+
+        Missing "hints" default to the "hint", if any, missing flags to `False`,
+        and other missing keys to `None`.
+        """
+
+    def to_dict(self) -> dict[str, Any]:
+        """
+        Returns this Component as a dict with the keys "contributes", "hint",
+        and "hints".
+
+        The keys "is_sentinel_frame", "is_prefix_frame", "id", and "values" are
+        only included if they are set.
+        """
+
 
 class AssembleResult:
+    """
+    The attributes of the `stacktrace` grouping component.
+
+    `budget_exhausted_at` is the index of the first rule that wasn't applied
+    because the `time_budget_ms` was exhausted, if any.
+
+    Results are immutable, hashable, and compare equal if all their attributes
+    are equal.
+    """
+
     contributes: bool
     hint: str | None
     invert_stacktrace: bool
+    budget_exhausted_at: int | None
+
+
+class StacktraceState:
+    """
+    The stacktrace-level variables set by the rules of an Enhancements object.
+
+    Frame counts of `0` mean that the variable is unset. Each `*_setter`
+    attribute contains the text of the rule that last set the variable, if any.
+
+    States are immutable, hashable, and compare equal if all their attributes
+    are equal.
+    """
+
+    max_frames: int
+    max_frames_setter: str | None
+    min_frames: int
+    min_frames_setter: str | None
+    invert_stacktrace: bool
+    invert_stacktrace_setter: str | None
+
+
+class ApplyStats:
+    """
+    Counts of how the rules of an Enhancements object were evaluated.
+
+    Every rule is counted once: it is either skipped because it can't match the
+    families of the frames, skipped because its exception matchers don't match,
+    or evaluated against the frames.
+
+    `truncated` is set if rules were only applied to the first and last frames
+    because of a `frame_limit`. `budget_exhausted_at` is the index of the first
+    rule that wasn't applied because the `time_budget_ms` was exhausted, if any.
+    Such rules are not counted.
+
+    Stats are immutable, hashable, and compare equal if all their attributes
+    are equal.
+    """
+
+    skipped_by_families: int
+    skipped_by_exception: int
+    evaluated: int
+    truncated: bool
+    budget_exhausted_at: int | None
+
+
+class ModifyResult:
+    """
+    The result of `Enhancements.apply_modifications`.
+
+    `frames` contains the new values of the "category" and "in_app" fields for
+    each frame, like `apply_modifications_to_frames` returns them.
+
+    `has_vars` contains the new value of the "has_vars" field for each frame.
+    It is `False` for frames whose local variables should be stripped because
+    of a `-vars` action, and `None` for frames without the field.
+
+    `in_app_changes` contains the text of every rule that changed the "in_app"
+    field of at least one frame, together with the number of frames it changed,
+    in the order the rules were applied. It is empty unless
+    `summarize_in_app_changes` is set.
+
+    `in_app_last_changed` contains, for each frame, the index of the rule that
+    last changed its "in_app" field, or `None` if no rule changed it. The rule
+    can be looked up by indexing the Enhancements object. It is empty unless
+    `track_in_app_last_changed` is set.
+
+    `matched` is whether any rule matched at least one frame, and `changed`
+    whether any frame field was set to a different value. If nothing changed,
+    the frames don't need to be written back. A field still counts as changed
+    if a later rule set it back to its original value.
+
+    Results are immutable, hashable, and compare equal if all their attributes
+    are equal.
+    """
+
+    frames: list[ModificationResult]
+    has_vars: list[bool | None]
+    in_app_changes: list[tuple[str, int]]
+    in_app_last_changed: list[int | None]
+    matched: bool
+    changed: bool
+
+
+class PatternCensus:
+    """
+    Counts of the matchers and glob features used by the rules of an
+    Enhancements object.
+
+    `matchers` maps the canonical key of every matcher, e.g. "function" or
+    "type", to the number of matchers with that key. `glob_features` maps
+    the glob features "wildcard" (`*`), "recursive_wildcard" (`**`),
+    "single_character" (`?`), "character_class" (`[...]`), "brace_expansion"
+    (`{...}`), "alternatives" (comma-separated `function` patterns), "escape"
+    (`\\` in patterns other than paths), and "literal" (patterns starting with
+    `=`) to the number of patterns using them. Features that aren't used are
+    left out.
+
+    Censuses are immutable, hashable, and compare equal if all their
+    attributes are equal.
+    """
+
+    rules: int
+    matchers: dict[str, int]
+    negated_matchers: int
+    caller_matchers: int
+    callee_matchers: int
+    glob_features: dict[str, int]
+
+
+class RuleView:
+    """
+    A read-only view of a single rule of an Enhancements object.
+
+    `matchers` and `actions` are dicts with the same keys as the matchers and
+    actions returned by `Enhancements.to_json`.
+
+    Views are hashable and compare equal if their rules are the same.
+    """
+
+    text: str
+    matchers: list[dict[str, Any]]
+    actions: list[dict[str, Any]]
+    is_modifier: bool
+    is_updater: bool
 
 
 class Cache:
@@ -26,14 +251,77 @@ class Cache:
     An LRU cache for memoizing the construction of regexes and enhancement rules.
 
     :param size: The number of both rules and regexes that will be cached.
+    :param lazy_regexes: Whether to compile the regexes of frame matchers when
+                         they are first matched against a frame instead of when
+                         rules are parsed. This makes parsing large configurations
+                         faster, at the cost of compiling regexes while applying them.
+    :param ttl: The number of seconds after which cached rules and regexes
+                expire, counted from when they were cached. By default, they
+                never expire.
+    :param admission: Whether a full cache only admits new rules and regexes
+                      that were requested more often than the least recently
+                      used ones they would evict. This keeps frequently used
+                      rules, like the default rules, cached while many rules
+                      that are only used once pass through.
     """
 
-    def __new__(cls, size: int) -> Cache: ...
+    def __new__(
+        cls,
+        size: int,
+        lazy_regexes: bool = False,
+        *,
+        ttl: float | None = None,
+        admission: bool = False,
+    ) -> Cache: ...
+
+    def save_to(self, path: str | os.PathLike[str]) -> int:
+        """
+        Writes the cached rules to the file at `path`, replacing it.
+
+        Regexes are not stored. Rules that the compact msgpack format can't
+        represent are skipped. Returns the number of rules that were written.
+        """
+
+    def load_from(self, path: str | os.PathLike[str]) -> int:
+        """
+        Adds the rules stored in the file at `path` by `save_to` to this cache.
+
+        Files written by a different version of this package are ignored.
+        Returns the number of rules that were added.
+
+        Raises `OSError` if the file can't be read.
+        """
+
+
+class ResultCache:
+    """
+    An LRU cache of the changes `Enhancements` made to stacktraces, for
+    `Enhancements.apply_modifications_to_frames_cached`.
+
+    Entries are keyed by a hash of the rules, all frame fields rules can match
+    on, and the exception data, so identical stacktraces skip applying the
+    rules. A single cache can be used with any number of `Enhancements`.
+
+    :param size: The number of stacktraces whose changes will be cached. With
+                 `0`, nothing is cached, but lookups are still counted.
+    """
+
+    def __new__(cls, size: int) -> ResultCache: ...
+
+    def stats(self) -> dict[str, int | float]:
+        """
+        Returns the number of "hits" and "misses" since the cache was created,
+        the number of cached "entries", and the "hit_rate", which is the share
+        of hits among all lookups, or `0.0` before the first lookup.
+        """
 
 
 class Enhancements:
     """
     A suite of enhancement rules.
+
+    Enhancements objects compare equal if they contain the same rules in the
+    same order. They are not hashable, as they can be modified.
     """
 
     @staticmethod
@@ -43,21 +331,147 @@ class Enhancements:
         """
 
     @staticmethod
-    def parse(input: str, cache: Cache) -> Enhancements:
+    def default(version: str, cache: Cache) -> Enhancements:
+        """
+        Parses the copy of a default Sentry configuration embedded in this module.
+
+        :param version: The identifier of the configuration, e.g. "newstyle:2023-01-11".
+        :param cache: A cache that memoizes rule and regex construction.
+        """
+
+    @staticmethod
+    def default_contributes(version: str, cache: Cache) -> Enhancements:
+        """
+        Returns the built-in rules deciding which frames contribute to grouping
+        by default, e.g. not native frames that weren't symbolicated.
+
+        :param version: The identifier of the grouping configuration, e.g.
+                        "newstyle:2023-01-11".
+        :param cache: A cache that memoizes rule and regex construction.
+        """
+
+    def with_default_contributes(self, version: str, cache: Cache) -> Enhancements:
+        """
+        Returns a copy of this Enhancements object with the rules of
+        `default_contributes(version, cache)` applied before its own rules.
+        """
+
+    @staticmethod
+    def parse(
+        input: str,
+        cache: Cache,
+        allowed_categories: list[str] | None = None,
+        *,
+        glob_braces: bool = True,
+        glob_classes: bool = True,
+        strict_globs: bool = False,
+    ) -> Enhancements:
         """
         Parses an Enhancements object from a string.
 
+        Lines of the form `@define <name> <matchers>` define named patterns that
+        can be used as `@<name>` in place of matchers in the rules.
+
+        Matcher arguments starting with `=` match literally, except that a
+        trailing `*` matches anything, e.g. `function:=-[UIApplication*`.
+
         :param input: The input string.
         :param cache: A cache that memoizes rule and regex construction.
+        :param allowed_categories: If given, `category=` actions setting any other
+                                   category are rejected.
+        :param glob_braces: Whether patterns may use brace expansion like
+                            `{foo,bar}`. Sentry's Python implementation
+                            matches braces literally. If disabled, rules using
+                            it raise `InvalidEnhancerConfig`.
+        :param glob_classes: Whether patterns may use character classes like
+                             `[a-z]`. If disabled, rules using them raise
+                             `InvalidEnhancerConfig`. `[[]` and `[{]` match
+                             `[` and `{` literally.
+        :param strict_globs: Whether rules with invalid frame matcher patterns,
+                             like the unclosed character class in `-[*`, raise
+                             `InvalidEnhancerConfig` instead of never matching.
         """
 
     @staticmethod
-    def from_config_structure(input: bytes, cache: Cache) -> Enhancements:
+    def parse_lenient(
+        input: str,
+        cache: Cache,
+        allowed_categories: list[str] | None = None,
+        *,
+        glob_braces: bool = True,
+        glob_classes: bool = True,
+        strict_globs: bool = False,
+    ) -> tuple[Enhancements, list[tuple[int, str]]]:
+        """
+        Parses an Enhancements object from a string like `parse`, skipping lines
+        that can't be parsed instead of raising `InvalidEnhancerConfig`.
+
+        Returns the rules of all other lines, together with the errors of the
+        skipped lines as `(line_number, message)` tuples in the order of the
+        lines. Line numbers start at 1. Rules using a pattern whose definition
+        is skipped are skipped as well.
+
+        The parameters are the same as for `parse`.
+        """
+
+    @staticmethod
+    def from_config_structure(
+        input: bytes | bytearray | memoryview,
+        cache: Cache,
+        allowed_categories: list[str] | None = None,
+        *,
+        resolve_base: Callable[[str], bytes | bytearray | memoryview | None] | None = None,
+        glob_braces: bool = True,
+        glob_classes: bool = True,
+        strict_globs: bool = False,
+    ) -> Enhancements:
         """
         Parses an Enhancements object from the msgpack representation.
 
-        :param input: The input in msgpack format.
+        :param input: The input in msgpack format. `bytes` are decoded in place,
+                      without copying them, and rules are decoded one at a time.
+                      `bytearray` and `memoryview` inputs are copied once.
         :param cache: A cache that memoizes rule and regex construction.
+        :param allowed_categories: If given, `category=` actions setting any other
+                                   category are rejected.
+        :param resolve_base: If given, the bases named by the input are resolved
+                             by calling this with the name of each base, which
+                             returns its msgpack representation, or `None` if
+                             there is no such base. The rules of the bases come
+                             first, and bases of bases are resolved recursively.
+                             Unknown bases and bases naming themselves raise
+                             `InvalidConfigStructure`. Exceptions raised by
+                             `resolve_base` are propagated. Without it, bases
+                             are ignored.
+        :param glob_braces: Like for `parse`, but rules using brace expansion
+                            raise `InvalidConfigStructure` if disabled.
+        :param glob_classes: Like for `parse`, but rules using character classes
+                             raise `InvalidConfigStructure` if disabled.
+        :param strict_globs: Like for `parse`, but invalid patterns raise
+                             `InvalidConfigStructure`.
+        """
+
+    def rules_setting(self, var: str) -> list[str]:
+        """
+        Returns all rules that set the given variable, in their original order.
+
+        Raises `ValueError` if the variable is unknown.
+
+        :param var: The name of the variable, e.g. "max-frames".
+        """
+
+    def copy(self) -> Enhancements:
+        """
+        Returns a copy of this Enhancements object.
+
+        The copy shares the rules with this object until either of them is
+        modified, so this is cheap.
+        """
+
+    def with_additional_rules(self, other: Enhancements) -> Enhancements:
+        """
+        Returns a copy of this Enhancements object with all rules from the other
+        one added to it, leaving both unchanged.
         """
 
     def extend_from(self, other: Enhancements):
@@ -65,10 +479,94 @@ class Enhancements:
         Adds all rules from the other Enhancements object to this one.
         """
 
+    def retain_updater_only(self):
+        """
+        Drops all rules that don't update grouping metadata.
+
+        Afterwards, `apply_modifications_to_frames` does not modify any frames.
+        """
+
+    def retain_modifier_only(self):
+        """
+        Drops all rules that don't modify frames.
+
+        Afterwards, `assemble_stacktrace_component` does not update any components.
+        """
+
+    def to_json(self) -> str:
+        """
+        Returns a JSON array describing every rule in this Enhancements object.
+
+        Each rule is an object with the keys "text", "matchers", and "actions".
+        Matchers have the keys "kind" ("frame", "exception", or "thread"), "key",
+        "pattern", "negated", and "offset" ("caller", "callee", or null). Actions have the
+        key "type": flag actions ("flag") additionally have "flag", "value", and
+        "range", var actions ("var") have "name" and "value".
+        """
+
+    def to_config_structure(self) -> bytes:
+        """
+        Encodes this Enhancements object in the msgpack representation that
+        `from_config_structure` and Sentry decode.
+
+        The encoding names no bases, since the rules of the bases are already
+        part of this object. Raises `InvalidConfigStructure` if a rule can't be
+        encoded, e.g. because it has a `has_vars` matcher or a `vars` action, or
+        uses an extension Sentry doesn't know, like `thread.*` and `trust`
+        matchers, literal `=` patterns, `~` ranges, or `priority` actions.
+        """
+
+    def warnings(self) -> list[dict[str, str]]:
+        """
+        Returns warnings about matchers of this Enhancements object that never
+        match, in the order of their rules.
+
+        Matchers with invalid glob patterns, or `app` and `has_vars` matchers
+        with non-boolean values, are accepted when parsing, but never match, or
+        match every frame with the field when negated. Every warning is a dict
+        with the keys "rule" and "matcher" (their text), "kind"
+        ("invalid_pattern" or "invalid_flag"), and "message". This works the
+        same for rules parsed from text and from the msgpack representation.
+        """
+
+    def pattern_census(self) -> PatternCensus:
+        """
+        Counts the matchers and glob features used by the rules of this
+        Enhancements object, e.g. to find out whether a config relies on a
+        feature before its semantics change.
+        """
+
+    def __len__(self) -> int:
+        """
+        Returns the number of rules in this Enhancements object.
+        """
+
+    def __getitem__(self, index: int) -> RuleView:
+        """
+        Returns a view of the rule at the given index.
+
+        Negative indices count from the end. Raises `IndexError` if the index
+        is out of range.
+        """
+
+    def dedup(self):
+        """
+        Removes duplicate rules, keeping the last occurrence of each.
+
+        Earlier occurrences are only removed if this doesn't change the result
+        of applying the rules.
+        """
+
     def apply_modifications_to_frames(
         self,
         frames: list[Frame],
         exception_data: ExceptionData,
+        *,
+        match_raw_function: bool = False,
+        frame_limit: int = 0,
+        normalize_exception_value: bool = False,
+        time_budget_ms: int = 0,
+        derive_default_in_app: bool = False,
     ) -> list[ModificationResult]:
         """
         Modifies a list of frames according to the rules in this Enhancements object.
@@ -76,9 +574,146 @@ class Enhancements:
         The returned list contains the new values of the "category" and
         "in_app" fields for each frame.
 
+        :param frames: The list of frames to modify. Frames may have an optional
+                       "raw_function" field containing the untrimmed function name
+                       and an optional "trust" field saying how a native frame
+                       was found while unwinding, e.g. "cfi" or "scan".
+                       String values are bytes, and invalid UTF-8 in them is
+                       replaced with U+FFFD before matching.
+                       An "in_app" of None is unset, which `app:unset` matches,
+                       but neither `app:yes` nor `app:no`.
+        :param exception_data: Exception data to match against rules. Supported
+                               fields are "ty", "value", "mechanism", and optionally
+                               "module" and "thread".
+        :param match_raw_function: Whether `function` matchers also match against
+                                   the "raw_function" field of frames.
+        :param frame_limit: If there are more than twice as many frames, rules are
+                            only applied to the first and last `frame_limit` frames.
+                            `0` means no limit.
+        :param normalize_exception_value: Whether to match rules against the
+                                          exception value as normalized by
+                                          `normalize_exception_value`.
+        :param time_budget_ms: Once this many milliseconds are spent, no further
+                               rules are applied. `0` means no limit.
+        :param derive_default_in_app: Whether to set the "in_app" field of frames
+                                      without one to its `default_in_app` before
+                                      applying any rules.
+        """
+
+    def apply_modifications(
+        self,
+        frames: list[Frame],
+        exception_data: ExceptionData,
+        *,
+        match_raw_function: bool = False,
+        frame_limit: int = 0,
+        normalize_exception_value: bool = False,
+        time_budget_ms: int = 0,
+        derive_default_in_app: bool = False,
+        summarize_in_app_changes: bool = False,
+        track_in_app_last_changed: bool = False,
+    ) -> ModifyResult:
+        """
+        Modifies a list of frames like `apply_modifications_to_frames`, and
+        returns the new values together with what was recorded while applying
+        the rules.
+
+        All options can be combined. The options shared with
+        `apply_modifications_to_frames` behave the same way.
+
+        :param frames: The list of frames to modify, like for
+                       `apply_modifications_to_frames`. Frames may have an
+                       optional "has_vars" field saying whether they contain
+                       local variables.
+
+        :param summarize_in_app_changes: Whether to summarize which rules
+                                         changed the "in_app" field, in
+                                         `ModifyResult.in_app_changes`.
+        :param track_in_app_last_changed: Whether to track which rule last
+                                          changed the "in_app" field of each
+                                          frame, in
+                                          `ModifyResult.in_app_last_changed`.
+        """
+
+    def apply_modifications_to_frames_cached(
+        self,
+        frames: list[Frame],
+        exception_data: ExceptionData,
+        result_cache: ResultCache,
+    ) -> list[ModificationResult]:
+        """
+        Modifies a list of frames like `apply_modifications_to_frames`, reusing
+        the changes cached in `result_cache` if the same rules were already
+        applied to identical frames and exception data.
+
         :param frames: The list of frames to modify.
         :param exception_data: Exception data to match against rules. Supported
-                               fields are "ty", "value", and "mechanism".
+                               fields are "ty", "value", "mechanism", and optionally
+                               "module" and "thread".
+        :param result_cache: The cache of changes, which is updated on a miss.
+        """
+
+    def apply_modifications_to_frames_with_stats(
+        self,
+        frames: list[Frame],
+        exception_data: ExceptionData,
+        *,
+        frame_limit: int = 0,
+        time_budget_ms: int = 0,
+    ) -> tuple[list[ModificationResult], ApplyStats]:
+        """
+        Modifies a list of frames like `apply_modifications_to_frames`.
+
+        Additionally returns counts of how many rules were skipped and how many
+        were evaluated against the frames.
+
+        :param frames: The list of frames to modify.
+        :param exception_data: Exception data to match against rules. Supported
+                               fields are "ty", "value", "mechanism", and optionally
+                               "module" and "thread".
+        :param frame_limit: Limits the frames rules are applied to, like in
+                            `apply_modifications_to_frames`.
+        :param time_budget_ms: Limits the time spent applying rules, like in
+                               `apply_modifications_to_frames`.
+        """
+
+    def profile_apply(
+        self,
+        frames: list[Frame],
+        exception_data: ExceptionData,
+        top_n: int = 10,
+    ) -> tuple[list[ModificationResult], list[tuple[str, float, int]]]:
+        """
+        Modifies a list of frames like `apply_modifications_to_frames`, and
+        additionally measures the time spent matching and applying each rule.
+
+        The profile contains the text of the `top_n` most expensive rules, together
+        with the time spent on them in seconds and the number of frames they matched,
+        most expensive first.
+
+        :param frames: The list of frames to modify.
+        :param exception_data: Exception data to match against rules. Supported
+                               fields are "ty", "value", "mechanism", and optionally
+                               "module" and "thread".
+        :param top_n: The maximum number of rules to include in the profile.
+        """
+
+    def apply_categories(
+        self,
+        frames: list[Frame],
+        exception_data: ExceptionData,
+    ) -> list[str | None]:
+        """
+        Applies only the `category` actions of the rules in this Enhancements object.
+
+        This is considerably faster than `apply_modifications_to_frames`, but does
+        not modify `in_app`. The returned list contains the new value of the
+        "category" field for each frame.
+
+        :param frames: The list of frames to categorize.
+        :param exception_data: Exception data to match against rules. Supported
+                               fields are "ty", "value", "mechanism", and optionally
+                               "module" and "thread".
         """
 
     def assemble_stacktrace_component(
@@ -86,6 +721,15 @@ class Enhancements:
         frames: list[Frame],
         exception_data: ExceptionData,
         components: list[Component],
+        *,
+        max_frames: int = 0,
+        min_frames: int = 0,
+        invert_stacktrace: bool = False,
+        trim_to_sentinel: bool = False,
+        match_raw_function: bool = False,
+        frame_limit: int = 0,
+        normalize_exception_value: bool = False,
+        time_budget_ms: int = 0,
     ) -> AssembleResult:
         """
         Modifies a list of `Component`s according to the rules in this Enhancements object.
@@ -96,7 +740,307 @@ class Enhancements:
 
         :param frames: The list of frames to analyze.
         :param exception_data: Exception data to match against rules. Supported
-                               fields are "ty", "value", and "mechanism".
+                               fields are "ty", "value", "mechanism", and optionally
+                               "module" and "thread".
         :param components: The list of `Component`s to modify.
                            The `Component` objects are mutated in place.
+        :param max_frames: The maximum number of contributing frames, unless a rule
+                           sets `max-frames`. `0` means no limit.
+        :param min_frames: The minimum number of contributing frames, unless a rule
+                           sets `min-frames`. `0` means no minimum.
+        :param invert_stacktrace: Whether to invert the stacktrace, unless a rule sets
+                                  `invert-stacktrace`.
+        :param trim_to_sentinel: Whether to ignore the frames above the last component
+                                 with `is_sentinel_frame`, except for the chain of
+                                 components with `is_prefix_frame` directly above it.
+        :param match_raw_function: Whether `function` matchers also match against
+                                   the "raw_function" field of frames.
+        :param frame_limit: Limits the frames rules are applied to, like in
+                            `apply_modifications_to_frames`.
+        :param normalize_exception_value: Whether to normalize the exception value,
+                                          like in `apply_modifications_to_frames`.
+        :param time_budget_ms: Limits the time spent applying rules, like in
+                               `apply_modifications_to_frames`. Components are
+                               still trimmed to `max_frames`.
         """
+
+
+    def stacktrace_state(
+        self,
+        frames: list[Frame],
+        exception_data: ExceptionData,
+        *,
+        max_frames: int = 0,
+        min_frames: int = 0,
+        invert_stacktrace: bool = False,
+        match_raw_function: bool = False,
+    ) -> StacktraceState:
+        """
+        Computes only the stacktrace-level variables, without modifying any components.
+
+        The result matches the state `assemble_stacktrace_component` uses internally.
+        The keyword arguments are defaults that rules may override, as in
+        `assemble_stacktrace_component`.
+
+        :param frames: The list of frames to analyze.
+        :param exception_data: Exception data to match against rules. Supported
+                               fields are "ty", "value", "mechanism", and optionally
+                               "module" and "thread".
+        """
+
+    def apply_all(
+        self,
+        frames: list[Frame],
+        exception_data: ExceptionData,
+        components: list[Component],
+        *,
+        max_frames: int = 0,
+        min_frames: int = 0,
+        invert_stacktrace: bool = False,
+        trim_to_sentinel: bool = False,
+        match_raw_function: bool = False,
+        frame_limit: int = 0,
+        normalize_exception_value: bool = False,
+        time_budget_ms: int = 0,
+    ) -> tuple[list[ModificationResult], AssembleResult, StacktraceState]:
+        """
+        Modifies frames and components in a single pass over the rules.
+
+        This is equivalent to calling `apply_modifications_to_frames` and then
+        `assemble_stacktrace_component` with the modified frames, but rules that
+        do both only match the frames once.
+
+        The keyword arguments are the same as for `assemble_stacktrace_component`.
+        The `time_budget_ms` is shared by both passes.
+
+        :param frames: The list of frames to modify.
+        :param exception_data: Exception data to match against rules. Supported
+                               fields are "ty", "value", "mechanism", and optionally
+                               "module" and "thread".
+        :param components: The list of `Component`s to modify.
+                           The `Component` objects are mutated in place.
+        """
+
+    def assemble_variants(
+        self,
+        frames: list[Frame],
+        exception_data: ExceptionData,
+        components: list[Component],
+        *,
+        max_frames: int = 0,
+        min_frames: int = 0,
+        invert_stacktrace: bool = False,
+        trim_to_sentinel: bool = False,
+        match_raw_function: bool = False,
+        frame_limit: int = 0,
+        normalize_exception_value: bool = False,
+        time_budget_ms: int = 0,
+    ) -> dict[str, tuple[list[Component], AssembleResult]]:
+        """
+        Assembles the "app" and "system" grouping variants of a stacktrace at once.
+
+        Returns a dict with the keys "app" and "system", each mapping to a list
+        of new `Component`s and the `AssembleResult` of the variant. The "system"
+        variant is what `assemble_stacktrace_component` computes. In the "app"
+        variant, components of frames that are not in-app stop contributing
+        with the hint "non app frame" before the rules are applied.
+
+        The keyword arguments are the same as for `assemble_stacktrace_component`.
+
+        :param frames: The list of frames to analyze.
+        :param exception_data: Exception data to match against rules. Supported
+                               fields are "ty", "value", "mechanism", and optionally
+                               "module" and "thread".
+        :param components: The list of `Component`s to start from. They are not
+                           modified.
+        """
+
+    def matching_rules(
+        self, frames: list[Frame], index: int, exception_data: ExceptionData
+    ) -> list[int]:
+        """
+        Returns the indices of all rules matching the frame at `frames[index]`
+        and the exception data, in their original order.
+
+        Both rules that modify frames and rules that update grouping metadata are
+        checked. The frames are matched as they are given, without applying any
+        rules first. The rules can be looked up by index on this object.
+
+        Raises `IndexError` if `index` is out of range.
+
+        :param frames: The list of frames the frame belongs to, which caller and
+                       callee matchers are checked against.
+        :param index: The index of the frame to check.
+        :param exception_data: Exception data to match against rules. Supported
+                               fields are "ty", "value", "mechanism", and optionally
+                               "module" and "thread".
+        """
+
+    def apply_and_encode_changes(
+        self, frames: list[Frame], exception_data: ExceptionData
+    ) -> tuple[list[ModificationResult], bytes]:
+        """
+        Modifies a list of frames like `apply_modifications_to_frames` and
+        additionally encodes the changes as a compact, versioned msgpack blob.
+
+        The blob only contains the fields that changed. It can be applied to
+        the same frames elsewhere with `apply_encoded_changes`.
+
+        :param frames: The list of frames to modify.
+        :param exception_data: Exception data to match against rules. Supported
+                               fields are "ty", "value", "mechanism", and optionally
+                               "module" and "thread".
+        """
+
+    def shadow_diff(
+        self,
+        shadow: Enhancements,
+        frames: list[Frame],
+        exception_data: ExceptionData,
+        *,
+        max_frames: int = 0,
+        min_frames: int = 0,
+        invert_stacktrace: bool = False,
+        match_raw_function: bool = False,
+    ) -> list[tuple[str, int | None, Any, Any]]:
+        """
+        Applies both these and the `shadow` Enhancements to copies of the frames
+        and returns how their results differ.
+
+        Each difference is a tuple of the name of the value ("in_app", "category",
+        "has_vars", "max_frames", "min_frames" or "invert_stacktrace"), the index
+        of the frame for per-frame values or `None`, and the primary and shadow
+        values. Unset frame counts are reported as `0`. The list is empty if both
+        produce the same results, and `frames` are not modified.
+
+        This is meant for rolling out rule changes in shadow mode. Deciding which
+        events to sample is left to the caller.
+
+        The keyword arguments are the same as for `stacktrace_state`.
+        """
+
+
+class EnhancementsRegistry:
+    """
+    A registry of named base Enhancements that memoizes their combinations
+    with custom rules.
+
+    :param size: The number of combined Enhancements objects that will be cached.
+    """
+
+    def __new__(cls, size: int) -> EnhancementsRegistry: ...
+
+    def register_base(self, name: str, enhancements: Enhancements):
+        """
+        Registers `enhancements` as the base called `name`, replacing any
+        previous base of the same name.
+        """
+
+    def get(self, base: str, custom: str, cache: Cache) -> Enhancements:
+        """
+        Returns the combination of the base called `base` and the `custom` rules.
+
+        The custom rules are appended after the base rules. The result is
+        memoized keyed by the base name and a hash of the custom rules.
+
+        :param base: The name of a registered base.
+        :param custom: The custom rules, in the same format as `Enhancements.parse`.
+        :param cache: A cache that memoizes rule and regex construction.
+        """
+
+
+def migrate(input: str, cache: Cache) -> tuple[str, list[tuple[int, str]]]:
+    """
+    Rewrites enhancement rules written in legacy syntax into canonical modern syntax.
+
+    Deprecated matcher names like "stack.abs_path" are replaced, and every rule
+    is reformatted. Comments and blank lines are kept as they are.
+
+    Returns the migrated rules and a list of changes, each consisting of the
+    0-based line number and a description of the change.
+
+    :param input: The rules in the text format of `Enhancements.parse`.
+    :param cache: A cache that memoizes regex construction.
+    """
+
+
+def normalize_exception_value(value: str) -> str:
+    """
+    Replaces the variable parts of an exception value with placeholders.
+
+    Quoted strings, UUIDs, hexadecimal numbers like memory addresses, and other
+    numbers are replaced with "<str>", "<uuid>", "<hex>", and "<int>" respectively,
+    so that the result can be used for matching and grouping.
+
+    :param value: The exception value.
+    """
+
+
+def trim_function_name(function: str, family: str) -> str:
+    """
+    Trims a function name the way Sentry's `trim_function_name` does.
+
+    Argument lists, template arguments, C++ trailers like `const`, and Rust
+    hashes are stripped, and compiler-specific lambda names are normalized to
+    "lambda". Only native function names are trimmed, all others are returned
+    unchanged.
+
+    :param function: The function name.
+    :param family: The family of the frame, like the "family" field of a frame.
+    """
+
+
+def default_in_app(frame: Frame) -> bool | None:
+    """
+    Derives the default "in_app" flag of a frame from where its code lives.
+
+    Native frames are not in-app if their package is a system library and in-app
+    if it is in an app bundle. JavaScript frames are not in-app if their path is
+    in `node_modules`, and Java frames are not in-app if their module is part of
+    the Java or Android standard library.
+
+    Returns `None` if none of these heuristics apply. The frame's current
+    "in_app" field is not taken into account.
+
+    :param frame: The frame, in the same format as for
+                  `Enhancements.apply_modifications_to_frames`.
+    """
+
+
+def otel_frame(attributes: dict[str, Any], language: str | None = None) -> Frame:
+    """
+    Converts a stack frame described by OpenTelemetry's semantic conventions
+    into a frame that enhancements can be applied to.
+
+    `code.function.name` (or the deprecated `code.function`) becomes the
+    function, `code.namespace` the module, and `code.file.path` (or the
+    deprecated `code.filepath`) the path. Other attributes and attributes with
+    non-string values are ignored.
+
+    :param attributes: The attributes of the frame.
+    :param language: The language of the SDK that recorded the frame, as in the
+                     `telemetry.sdk.language` resource attribute. `nodejs` and
+                     `webjs` frames belong to the "javascript" family, `cpp`,
+                     `rust` and `swift` frames to the "native" family, and all
+                     others to the "other" family.
+    """
+
+
+def apply_encoded_changes(
+    frames: list[Frame], changes: bytes
+) -> list[ModificationResult]:
+    """
+    Applies changes encoded by `Enhancements.apply_and_encode_changes` to the
+    frames they were encoded for.
+
+    Returns the new values of the "category" and "in_app" fields for each frame,
+    like `Enhancements.apply_modifications_to_frames`. Changes of fields this
+    version doesn't know are ignored.
+
+    Raises `ValueError` if the changes are malformed, were encoded by an
+    incompatible version, or for a different number of frames.
+
+    :param frames: The list of frames, in the same format as for
+                   `Enhancements.apply_modifications_to_frames`.
+    :param changes: The encoded changes.
+    """
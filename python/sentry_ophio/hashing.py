@@ -0,0 +1,6 @@
+from ._bindings import crc32, fnv1a_32, fnv1a_64, md5_hex
+
+crc32.__module__ = __name__
+fnv1a_32.__module__ = __name__
+fnv1a_64.__module__ = __name__
+md5_hex.__module__ = __name__
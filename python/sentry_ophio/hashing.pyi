@@ -0,0 +1,45 @@
+"""
+Deterministic string hashes that are identical in Python and Rust.
+
+Every function hashes the concatenation of the UTF-8 encodings of all `parts`,
+without separators between them. Before that, each part is lowercased
+according to `lowercase`:
+
+- "none": Parts are hashed as they are.
+- "ascii": Only ASCII letters are lowercased.
+- "unicode": All characters are lowercased according to Unicode.
+
+Raises `ValueError` for any other `lowercase` mode.
+"""
+
+from collections.abc import Sequence
+from typing import Literal
+
+LowercaseMode = Literal["none", "ascii", "unicode"]
+
+
+def md5_hex(parts: Sequence[str], *, lowercase: LowercaseMode = "none") -> str:
+    """
+    Returns the MD5 digest of `parts` as a lowercase hex string.
+
+    Without lowercasing, this is the hash Sentry computes for a list of
+    fingerprint values.
+    """
+
+
+def crc32(parts: Sequence[str], *, lowercase: LowercaseMode = "none") -> int:
+    """
+    Returns the CRC-32 checksum of `parts`, like `zlib.crc32`.
+    """
+
+
+def fnv1a_32(parts: Sequence[str], *, lowercase: LowercaseMode = "none") -> int:
+    """
+    Returns the 32-bit FNV-1a hash of `parts`.
+    """
+
+
+def fnv1a_64(parts: Sequence[str], *, lowercase: LowercaseMode = "none") -> int:
+    """
+    Returns the 64-bit FNV-1a hash of `parts`.
+    """
@@ -0,0 +1,54 @@
+//! Fixtures shared by the benchmarks.
+
+use std::path::PathBuf;
+
+use rust_ophio::enhancers::{Cache, Enhancements, ExceptionData, Frame, StringField, ThreadData};
+
+pub fn read_fixture(name: &str) -> Vec<u8> {
+    let path = PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+        .join("../tests/fixtures")
+        .join(name);
+    std::fs::read(path).unwrap()
+}
+
+pub fn read_enhancers_text() -> String {
+    String::from_utf8(read_fixture("newstyle@2023-01-11.txt")).unwrap()
+}
+
+pub fn enhancements_fixture() -> Enhancements {
+    Enhancements::parse(&read_enhancers_text(), &mut Cache::default()).unwrap()
+}
+
+pub fn read_cocoa_stacktraces() -> Vec<Vec<Frame>> {
+    let platform = "cocoa";
+
+    let stacktraces = read_fixture("cocoa-stacktraces.json");
+    let stacktraces: serde_json::Value = serde_json::from_slice(&stacktraces).unwrap();
+    stacktraces
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|frames| {
+            frames
+                .as_array()
+                .unwrap()
+                .iter()
+                .map(|f| Frame::from_test(f, platform))
+                .collect::<Vec<_>>()
+        })
+        .collect()
+}
+
+pub fn app_hang_exception_data() -> ExceptionData {
+    ExceptionData {
+        ty: Some(StringField::new("App Hanging")),
+        value: Some(StringField::new("App hanging for at least 2000 ms.")),
+        module: None,
+        mechanism: Some(StringField::new("AppHang")),
+        thread: ThreadData {
+            name: Some(StringField::new("com.apple.main-thread")),
+            main: Some(true),
+            ..Default::default()
+        },
+    }
+}
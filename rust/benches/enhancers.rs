@@ -3,27 +3,24 @@
 // and then profile with:
 // > DIVAN_MIN_TIME=2 samply record target/release/deps/enhancers-XXXX --bench
 
-use std::path::PathBuf;
-
 use divan::{black_box, Bencher};
 
-use rust_ophio::enhancers::{Cache, Enhancements, ExceptionData, Frame};
-use smol_str::SmolStr;
+use rust_ophio::enhancers::{AssembleOptions, Cache, Component, Enhancements};
+
+mod common;
+
+use common::{
+    app_hang_exception_data, enhancements_fixture, read_cocoa_stacktraces, read_enhancers_text,
+    read_fixture,
+};
 
 fn main() {
     divan::main();
 }
 
-fn read_fixture(name: &str) -> Vec<u8> {
-    let path = PathBuf::from(env!("CARGO_MANIFEST_DIR"))
-        .join("../tests/fixtures")
-        .join(name);
-    std::fs::read(path).unwrap()
-}
-
 #[divan::bench]
 fn parse_enhancers(bencher: Bencher) {
-    let enhancers = String::from_utf8(read_fixture("newstyle@2023-01-11.txt")).unwrap();
+    let enhancers = read_enhancers_text();
     bencher.bench(|| {
         black_box(Enhancements::parse(&enhancers, &mut Cache::default()).unwrap());
     })
@@ -31,13 +28,22 @@ fn parse_enhancers(bencher: Bencher) {
 
 #[divan::bench]
 fn parse_enhancers_cached(bencher: Bencher) {
-    let enhancers = String::from_utf8(read_fixture("newstyle@2023-01-11.txt")).unwrap();
+    let enhancers = read_enhancers_text();
     let mut cache = Cache::new(1_000);
     bencher.bench_local(|| {
         black_box(Enhancements::parse(&enhancers, &mut cache).unwrap());
     })
 }
 
+#[divan::bench]
+fn parse_enhancers_lazy(bencher: Bencher) {
+    let enhancers = read_enhancers_text();
+    bencher.bench(|| {
+        let mut cache = Cache::default().with_lazy_regexes(true);
+        black_box(Enhancements::parse(&enhancers, &mut cache).unwrap());
+    })
+}
+
 #[divan::bench]
 fn parse_encoded_enhancers(bencher: Bencher) {
     let enhancers = read_fixture("newstyle@2023-01-11.bin");
@@ -57,32 +63,10 @@ fn parse_encoded_enhancers_cached(bencher: Bencher) {
 
 #[divan::bench]
 fn apply_modifications(bencher: Bencher) {
-    let enhancers = String::from_utf8(read_fixture("newstyle@2023-01-11.txt")).unwrap();
-    let enhancers = Enhancements::parse(&enhancers, &mut Cache::default()).unwrap();
+    let enhancers = enhancements_fixture();
 
-    let platform = "cocoa";
-
-    let stacktraces = read_fixture("cocoa-stacktraces.json");
-    let stacktraces: serde_json::Value = serde_json::from_slice(&stacktraces).unwrap();
-    let mut stacktraces: Vec<_> = stacktraces
-        .as_array()
-        .unwrap()
-        .iter()
-        .map(|frames| {
-            frames
-                .as_array()
-                .unwrap()
-                .iter()
-                .map(|f| Frame::from_test(f, platform))
-                .collect::<Vec<_>>()
-        })
-        .collect();
-
-    let exception_data = ExceptionData {
-        ty: Some(SmolStr::new("App Hanging")),
-        value: Some(SmolStr::new("App hanging for at least 2000 ms.")),
-        mechanism: Some(SmolStr::new("AppHang")),
-    };
+    let mut stacktraces = read_cocoa_stacktraces();
+    let exception_data = app_hang_exception_data();
 
     bencher.bench_local(move || {
         for frames in &mut stacktraces {
@@ -90,3 +74,76 @@ fn apply_modifications(bencher: Bencher) {
         }
     })
 }
+
+#[divan::bench]
+fn apply_categories(bencher: Bencher) {
+    let enhancers = enhancements_fixture();
+
+    let mut stacktraces = read_cocoa_stacktraces();
+    let exception_data = app_hang_exception_data();
+
+    bencher.bench_local(move || {
+        for frames in &mut stacktraces {
+            enhancers.apply_categories(frames, &exception_data);
+        }
+    })
+}
+
+#[divan::bench]
+fn assemble_stacktrace_component(bencher: Bencher) {
+    let enhancers = enhancements_fixture();
+
+    let mut stacktraces = read_cocoa_stacktraces();
+    let exception_data = app_hang_exception_data();
+    for frames in &mut stacktraces {
+        enhancers.apply_modifications_to_frames(frames, &exception_data);
+    }
+
+    bencher.bench_local(move || {
+        for frames in &stacktraces {
+            let mut components: Vec<_> = frames
+                .iter()
+                .map(|frame| Component {
+                    contributes: frame.in_app,
+                    ..Default::default()
+                })
+                .collect();
+            black_box(enhancers.assemble_stacktrace_component(
+                &mut components,
+                frames,
+                &exception_data,
+            ));
+        }
+    })
+}
+
+#[divan::bench(args = [false, true])]
+fn assemble_shared_matchers(bencher: Bencher, memoize_shared_matchers: bool) {
+    // Custom rules commonly restrict many rules to the same packages.
+    let mut enhancers = read_enhancers_text();
+    for i in 0..100 {
+        enhancers.push_str(&format!(
+            "\npackage:**/System/Library/** function:handler{i}* -group"
+        ));
+    }
+    let enhancers = Enhancements::parse(&enhancers, &mut Cache::default()).unwrap();
+    let options = AssembleOptions {
+        memoize_shared_matchers,
+        ..Default::default()
+    };
+
+    let stacktraces = read_cocoa_stacktraces();
+    let exception_data = app_hang_exception_data();
+
+    bencher.bench_local(move || {
+        for frames in &stacktraces {
+            let mut components = vec![Component::default(); frames.len()];
+            black_box(enhancers.assemble_stacktrace_component_with_options(
+                &mut components,
+                frames,
+                &exception_data,
+                &options,
+            ));
+        }
+    })
+}
@@ -0,0 +1,157 @@
+// Runs the workloads of the `enhancers` benchmarks with fixed iteration counts and
+// writes the timings as JSON, to be compared against `benches/baseline.json`:
+// > cargo bench -p rust-ophio --all-features --bench regression -- results.json
+// > cargo run -p rust-ophio --bin compare-benchmarks -- benches/baseline.json results.json
+//
+// See `make bench-regression` for doing both in one go.
+
+use std::hint::black_box;
+use std::time::{Duration, Instant};
+
+use rust_ophio::enhancers::{Cache, Component, Enhancements};
+
+mod common;
+
+use common::{
+    app_hang_exception_data, enhancements_fixture, read_cocoa_stacktraces, read_enhancers_text,
+    read_fixture,
+};
+
+/// The number of untimed iterations run before measuring.
+const WARMUP_ITERATIONS: usize = 3;
+
+fn main() {
+    // `cargo bench` passes `--bench`, which is not meant for us.
+    let output = std::env::args().skip(1).find(|arg| !arg.starts_with("--"));
+
+    let results = vec![
+        parse_enhancers(),
+        parse_encoded_enhancers(),
+        apply_modifications(),
+        apply_categories(),
+        assemble_stacktrace_component(),
+    ];
+
+    for result in &results {
+        eprintln!(
+            "{:<32} median {:>12?} ({} iterations)",
+            result.name,
+            Duration::from_nanos(result.median_ns),
+            result.iterations
+        );
+    }
+
+    let benchmarks: Vec<_> = results
+        .iter()
+        .map(|result| {
+            serde_json::json!({
+                "name": result.name,
+                "iterations": result.iterations,
+                "median_ns": result.median_ns,
+                "min_ns": result.min_ns,
+            })
+        })
+        .collect();
+    let json =
+        serde_json::to_string_pretty(&serde_json::json!({ "benchmarks": benchmarks })).unwrap();
+
+    match output {
+        Some(path) => std::fs::write(path, json + "\n").unwrap(),
+        None => println!("{json}"),
+    }
+}
+
+/// The timings of one benchmark.
+struct BenchResult {
+    name: &'static str,
+    iterations: usize,
+    median_ns: u64,
+    min_ns: u64,
+}
+
+/// Runs `f` for a fixed number of `iterations` after a short warmup and records the timings.
+fn run(name: &'static str, iterations: usize, mut f: impl FnMut()) -> BenchResult {
+    for _ in 0..WARMUP_ITERATIONS {
+        f();
+    }
+
+    let mut timings: Vec<_> = (0..iterations)
+        .map(|_| {
+            let started = Instant::now();
+            f();
+            started.elapsed().as_nanos() as u64
+        })
+        .collect();
+    timings.sort_unstable();
+
+    BenchResult {
+        name,
+        iterations,
+        median_ns: timings[timings.len() / 2],
+        min_ns: timings[0],
+    }
+}
+
+fn parse_enhancers() -> BenchResult {
+    let enhancers = read_enhancers_text();
+    run("parse_enhancers", 50, || {
+        black_box(Enhancements::parse(&enhancers, &mut Cache::default()).unwrap());
+    })
+}
+
+fn parse_encoded_enhancers() -> BenchResult {
+    let enhancers = read_fixture("newstyle@2023-01-11.bin");
+    run("parse_encoded_enhancers", 50, || {
+        black_box(Enhancements::from_config_structure(&enhancers, &mut Cache::default()).unwrap());
+    })
+}
+
+fn apply_modifications() -> BenchResult {
+    let enhancers = enhancements_fixture();
+    let mut stacktraces = read_cocoa_stacktraces();
+    let exception_data = app_hang_exception_data();
+
+    run("apply_modifications", 200, || {
+        for frames in &mut stacktraces {
+            enhancers.apply_modifications_to_frames(frames, &exception_data);
+        }
+    })
+}
+
+fn apply_categories() -> BenchResult {
+    let enhancers = enhancements_fixture();
+    let mut stacktraces = read_cocoa_stacktraces();
+    let exception_data = app_hang_exception_data();
+
+    run("apply_categories", 200, || {
+        for frames in &mut stacktraces {
+            enhancers.apply_categories(frames, &exception_data);
+        }
+    })
+}
+
+fn assemble_stacktrace_component() -> BenchResult {
+    let enhancers = enhancements_fixture();
+    let mut stacktraces = read_cocoa_stacktraces();
+    let exception_data = app_hang_exception_data();
+    for frames in &mut stacktraces {
+        enhancers.apply_modifications_to_frames(frames, &exception_data);
+    }
+
+    run("assemble_stacktrace_component", 200, || {
+        for frames in &stacktraces {
+            let mut components: Vec<_> = frames
+                .iter()
+                .map(|frame| Component {
+                    contributes: frame.in_app,
+                    ..Default::default()
+                })
+                .collect();
+            black_box(enhancers.assemble_stacktrace_component(
+                &mut components,
+                frames,
+                &exception_data,
+            ));
+        }
+    })
+}
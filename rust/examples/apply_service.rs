@@ -0,0 +1,116 @@
+//! A minimal async service that applies enhancements to stack traces.
+//!
+//! The service loads a base configuration from a file and then accepts TCP connections. Every
+//! line a client sends is a JSON request, which is answered with one line of JSON:
+//!
+//! ```text
+//! {"rules": "function:my_app::* +app", "exception": {"type": "Panic"}, "frames": [{"function": "my_app::main"}]}
+//! {"frames":[{"function":"my_app::main","platform":"other","in_app":true}]}
+//! ```
+//!
+//! `rules` are custom rules applied after the base configuration, and `exception` and
+//! `frames` are in the shape of Sentry's event JSON. Run it with
+//!
+//! ```text
+//! cargo run -p rust-ophio --features serde --example apply_service -- tests/fixtures/newstyle@2023-01-11.txt
+//! ```
+//!
+//! and talk to it with e.g. `nc 127.0.0.1 7878`.
+
+use std::sync::Arc;
+
+use rust_ophio::enhancers::{Cache, EnhancementsRegistry, ExceptionData, Frame, SharedRegistry};
+use serde::{Deserialize, Serialize};
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+use tokio::net::{TcpListener, TcpStream};
+
+/// The name the configuration loaded at startup is registered as.
+const BASE: &str = "base";
+
+#[derive(Deserialize)]
+struct Request {
+    #[serde(default)]
+    rules: String,
+    #[serde(default)]
+    exception: ExceptionData,
+    frames: Vec<Frame>,
+}
+
+#[derive(Serialize)]
+#[serde(untagged)]
+enum Response {
+    Frames { frames: Vec<Frame> },
+    Error { error: String },
+}
+
+/// Answers a single request.
+///
+/// The custom rules of the request are parsed once and memoized by the registry. Parsing
+/// locks the registry, so it happens on a blocking thread rather than on the async runtime.
+async fn handle(registry: Arc<SharedRegistry>, line: String) -> Response {
+    let request: Request = match serde_json::from_str(&line) {
+        Ok(request) => request,
+        Err(err) => {
+            return Response::Error {
+                error: err.to_string(),
+            }
+        }
+    };
+
+    let result = tokio::task::spawn_blocking(move || {
+        let enhancements = registry.get_or_compile(BASE, &request.rules)?;
+        let mut frames = request.frames;
+        enhancements.apply_modifications_to_frames(&mut frames, &request.exception);
+        Ok::<_, rust_ophio::Error>(frames)
+    })
+    .await
+    .expect("applying enhancements panicked");
+
+    match result {
+        Ok(frames) => Response::Frames { frames },
+        Err(err) => Response::Error {
+            error: err.to_string(),
+        },
+    }
+}
+
+async fn serve(registry: Arc<SharedRegistry>, stream: TcpStream) -> std::io::Result<()> {
+    let (reader, mut writer) = stream.into_split();
+    let mut lines = BufReader::new(reader).lines();
+    while let Some(line) = lines.next_line().await? {
+        let response = handle(Arc::clone(&registry), line).await;
+        let mut response = serde_json::to_vec(&response)?;
+        response.push(b'\n');
+        writer.write_all(&response).await?;
+    }
+    Ok(())
+}
+
+#[tokio::main]
+async fn main() -> Result<(), Box<dyn std::error::Error>> {
+    let mut args = std::env::args().skip(1);
+    let config = args
+        .next()
+        .ok_or("usage: apply_service <rules file> [address]")?;
+    let address = args.next().unwrap_or_else(|| "127.0.0.1:7878".into());
+
+    let registry = Arc::new(SharedRegistry::new(
+        EnhancementsRegistry::new(1_000),
+        Cache::new(10_000).with_lazy_regexes(true),
+    ));
+    let base = registry.parse(&tokio::fs::read_to_string(&config).await?)?;
+    println!("loaded {} rules from {config}", base.rules().count());
+    registry.register_base(BASE, base);
+
+    let listener = TcpListener::bind(&address).await?;
+    println!("listening on {address}");
+    loop {
+        let (stream, peer) = listener.accept().await?;
+        let registry = Arc::clone(&registry);
+        tokio::spawn(async move {
+            if let Err(err) = serve(registry, stream).await {
+                eprintln!("{peer}: {err}");
+            }
+        });
+    }
+}
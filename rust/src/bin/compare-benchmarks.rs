@@ -0,0 +1,86 @@
+//! Compares the results of the `regression` benchmark against a baseline.
+//!
+//! Usage: `compare-benchmarks <baseline.json> <results.json> [threshold-percent]`
+//!
+//! Exits with a non-zero status if the median time of any benchmark regressed by more
+//! than the threshold, which defaults to 10%.
+
+use std::collections::HashMap;
+use std::process::ExitCode;
+
+use anyhow::Context;
+use serde::Deserialize;
+
+const DEFAULT_THRESHOLD_PERCENT: f64 = 10.0;
+
+#[derive(Debug, Deserialize)]
+struct BenchResults {
+    benchmarks: Vec<BenchResult>,
+}
+
+#[derive(Debug, Deserialize)]
+struct BenchResult {
+    name: String,
+    median_ns: u64,
+}
+
+fn read_results(path: &str) -> anyhow::Result<HashMap<String, u64>> {
+    let contents = std::fs::read(path).with_context(|| format!("failed to read `{path}`"))?;
+    let results: BenchResults = serde_json::from_slice(&contents)
+        .with_context(|| format!("failed to parse benchmark results in `{path}`"))?;
+    Ok(results
+        .benchmarks
+        .into_iter()
+        .map(|result| (result.name, result.median_ns))
+        .collect())
+}
+
+fn main() -> anyhow::Result<ExitCode> {
+    let args: Vec<_> = std::env::args().skip(1).collect();
+    let [baseline, results, rest @ ..] = args.as_slice() else {
+        anyhow::bail!(
+            "usage: compare-benchmarks <baseline.json> <results.json> [threshold-percent]"
+        );
+    };
+    let threshold = match rest {
+        [] => DEFAULT_THRESHOLD_PERCENT,
+        [threshold] => threshold
+            .parse()
+            .with_context(|| format!("invalid threshold `{threshold}`"))?,
+        _ => anyhow::bail!("too many arguments"),
+    };
+
+    let baseline = read_results(baseline)?;
+    let results = read_results(results)?;
+
+    let mut names: Vec<_> = results.keys().collect();
+    names.sort();
+
+    let mut regressed = false;
+    for name in names {
+        let current = results[name];
+        let Some(&previous) = baseline.get(name) else {
+            println!("{name:<32} {current:>12} ns  (not in baseline)");
+            continue;
+        };
+
+        let change = (current as f64 / previous as f64 - 1.0) * 100.0;
+        let verdict = if change > threshold {
+            regressed = true;
+            "REGRESSED"
+        } else {
+            "ok"
+        };
+        println!("{name:<32} {current:>12} ns  {change:>+7.1}%  {verdict}");
+    }
+
+    for name in baseline.keys().filter(|name| !results.contains_key(*name)) {
+        println!("{name:<32} missing from results");
+    }
+
+    if regressed {
+        println!("some benchmarks regressed by more than {threshold}%");
+        return Ok(ExitCode::FAILURE);
+    }
+    Ok(ExitCode::SUCCESS)
+}
@@ -5,20 +5,24 @@
 //! the different types of actions.
 
 use std::fmt;
+use std::num::NonZeroUsize;
 
-use smol_str::SmolStr;
-
-use super::{frame::Frame, Component, Rule, StacktraceState};
+use super::description::{ActionDescription, VarValue};
+use super::frame::{FrameField, FrameLike, FrameLikeMut, StringField};
+use super::{Component, Rule, StacktraceState};
 
 /// The range of an action.
 ///
 /// This determines if the action applies to the frames/components before or after the current one.
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub enum Range {
     /// The frames/components after the current one.
     Up,
     /// The frames/components before the current one.
     Down,
+    /// The current frame/component and all adjacent ones whose frames belong to
+    /// the same package as the current frame.
+    Siblings,
 }
 
 impl fmt::Display for Range {
@@ -26,20 +30,76 @@ impl fmt::Display for Range {
         match self {
             Range::Up => write!(f, "^"),
             Range::Down => write!(f, "v"),
+            Range::Siblings => write!(f, "~"),
         }
     }
 }
 
-/// The name of the flag a [`FlagAction`] sets.
+/// The kind of a hint on a [`Component`].
 ///
-/// The `app` flag is the only one of these that exists on stack frames,
-/// the others belong to grouping components.
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+/// Variants are declared in ascending order of precedence: the
+/// [primary hint](Component::primary_hint) is the last hint of the highest kind,
+/// which means that later rules take precedence over earlier ones.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum HintKind {
+    /// The frame was marked in-app or out of app by an `app` flag action.
+    InApp,
+    /// The frame was ignored or un-ignored by a `group` flag action.
+    Group,
+    /// The frame was ignored because it is above the sentinel frame.
+    Sentinel,
+    /// The frame was ignored because of the `max-frames` limit.
+    MaxFrames,
+}
+
+/// The priorities of the rules that last set the `in_app` flag and the category of each frame.
+///
+/// A rule only overwrites one of these fields if its [priority](Rule::priority) is at least
+/// as high as the priority of the rule that last set it. Among rules of the same priority,
+/// later rules take precedence over earlier ones as usual.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct FramePriorities {
+    in_app: Vec<usize>,
+    category: Vec<usize>,
+}
+
+impl FramePriorities {
+    /// Resets the priorities of `len` frames to the default priority of `0`.
+    pub(crate) fn reset(&mut self, len: usize) {
+        self.in_app.clear();
+        self.in_app.resize(len, 0);
+        self.category.clear();
+        self.category.resize(len, 0);
+    }
+
+    /// Records that a rule with `priority` sets a field of the frame at `idx`.
+    ///
+    /// Returns false, leaving the recorded priority untouched, if the field was
+    /// already set by a rule with a higher priority.
+    fn claim(priorities: &mut [usize], idx: usize, priority: usize) -> bool {
+        match priorities.get_mut(idx) {
+            Some(recorded) if *recorded > priority => false,
+            Some(recorded) => {
+                *recorded = priority;
+                true
+            }
+            None => true,
+        }
+    }
+}
+
+/// The name of the flag a flag action like `-app` sets.
+///
+/// The `app` and `vars` flags exist on stack frames, the `group` flag
+/// belongs to grouping components.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub enum FlagActionType {
     /// The `app` flag.
     App,
     /// The `group` flag.
     Group,
+    /// The `vars` flag, which can only be unset to strip a frame's local variables.
+    Vars,
 }
 
 impl fmt::Display for FlagActionType {
@@ -47,6 +107,7 @@ impl fmt::Display for FlagActionType {
         match self {
             FlagActionType::App => write!(f, "app"),
             FlagActionType::Group => write!(f, "group"),
+            FlagActionType::Vars => write!(f, "vars"),
         }
     }
 }
@@ -58,7 +119,7 @@ impl fmt::Display for FlagActionType {
 /// * whether it sets it to `true` or `false`;
 /// * whether it sets the flag on the current frame/compoent, all previous ones,
 ///   or all following ones.
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub struct FlagAction {
     /// The value the flag is set to.
     pub flag: bool,
@@ -66,63 +127,121 @@ pub struct FlagAction {
     pub ty: FlagActionType,
     /// Which frames/components this action applies to.
     ///
-    /// `None` means the current one, otherwise see the documentation of `Range`.
+    /// `None` means the current one, otherwise see the documentation of [`Range`].
     pub range: Option<Range>,
 }
 
 impl FlagAction {
-    /// Returns a mutable iterator over a subslice of `items`, depending on `self.range`.
+    /// Returns the range of indices this action applies to, depending on `self.range`.
     ///
-    /// * `self.range` == `None`: returns just `items[idx]`, if it exists.
-    /// * `self.range` == `Some(Up)`: returns `items[idx+1..]`.
-    /// * `self.range` == `Some(Down)`: returns `items[..idx]`.
-    fn slice_to_range_mut<'f, I>(
-        &self,
-        items: &'f mut [I],
-        idx: usize,
-    ) -> impl Iterator<Item = &'f mut I> {
-        let slice = match self.range {
-            Some(Range::Up) => items.get_mut(idx + 1..),
-            Some(Range::Down) => items.get_mut(..idx),
-            None => items.get_mut(idx..idx + 1),
+    /// * `self.range` == `None`: returns `idx..idx+1`.
+    /// * `self.range` == `Some(Up)`: returns `idx+1..`.
+    /// * `self.range` == `Some(Down)`: returns `..idx`.
+    /// * `self.range` == `Some(Siblings)`: returns `idx` extended in both directions
+    ///   as long as the frames have the same package as `frames[idx]`.
+    ///
+    /// The result is clamped to the length of `frames`.
+    fn range_bounds<F: FrameLike>(&self, frames: &[F], idx: usize) -> std::ops::Range<usize> {
+        let len = frames.len();
+        let (start, end) = match self.range {
+            Some(Range::Up) => (idx + 1, len),
+            Some(Range::Down) => (0, idx),
+            None => (idx, idx + 1),
+            Some(Range::Siblings) => {
+                let Some(package) = frames
+                    .get(idx)
+                    .and_then(|f| f.get_field(FrameField::Package))
+                else {
+                    return idx.min(len)..(idx + 1).min(len);
+                };
+                let is_sibling = |frame: &F| frame.get_field(FrameField::Package) == Some(package);
+
+                let start = frames[..idx]
+                    .iter()
+                    .rposition(|f| !is_sibling(f))
+                    .map_or(0, |pos| pos + 1);
+                let end = frames[idx + 1..]
+                    .iter()
+                    .position(|f| !is_sibling(f))
+                    .map_or(len, |pos| idx + 1 + pos);
+                (start, end)
+            }
         };
-        slice.unwrap_or_default().iter_mut()
+        start.min(len)..end.min(len)
     }
 
-    /// Returns an iterator over a subslice of `items`, depending on `self.range`.
+    /// Returns a mutable iterator over the subslice of `items` given by `bounds`.
     ///
-    /// * `self.range` == `None`: returns just `items[idx]`, if it exists.
-    /// * `self.range` == `Some(Up)`: returns `items[idx+1..]`.
-    /// * `self.range` == `Some(Down)`: returns `items[..idx]`.
-    fn slice_to_range<'f, I>(&self, items: &'f [I], idx: usize) -> impl Iterator<Item = &'f I> {
-        let slice = match self.range {
-            Some(Range::Up) => items.get(idx + 1..),
-            Some(Range::Down) => items.get(..idx),
-            None => items.get(idx..idx + 1),
-        };
-        slice.unwrap_or_default().iter()
+    /// `bounds` is computed by [`range_bounds`](Self::range_bounds).
+    fn slice_to_range_mut<I>(
+        items: &mut [I],
+        bounds: std::ops::Range<usize>,
+    ) -> impl Iterator<Item = &mut I> {
+        items.get_mut(bounds).unwrap_or_default().iter_mut()
+    }
+
+    /// Returns an iterator over the subslice of `items` given by `bounds`.
+    ///
+    /// `bounds` is computed by [`range_bounds`](Self::range_bounds).
+    fn slice_to_range<I>(items: &[I], bounds: std::ops::Range<usize>) -> impl Iterator<Item = &I> {
+        items.get(bounds).unwrap_or_default().iter()
     }
 
     /// Applies this action's modification to `frames` at the index `idx`.
-    pub fn apply_modifications_to_frame(&self, frames: &mut [Frame], idx: usize) {
-        if self.ty == FlagActionType::App {
-            for frame in self.slice_to_range_mut(frames, idx) {
-                frame.in_app = Some(self.flag);
+    ///
+    /// If `priority` is given, frames whose `in_app` flag was set by a rule with a
+    /// higher priority are skipped. Returns whether the value of any frame changed.
+    fn apply_modifications_with_priority<F: FrameLikeMut>(
+        &self,
+        frames: &mut [F],
+        idx: usize,
+        mut priority: Option<(usize, &mut FramePriorities)>,
+    ) -> bool {
+        let mut changed = false;
+        match self.ty {
+            FlagActionType::App => {
+                let bounds = self.range_bounds(frames, idx);
+                let start = bounds.start;
+                for (i, frame) in Self::slice_to_range_mut(frames, bounds).enumerate() {
+                    if let Some((priority, priorities)) = &mut priority {
+                        if !FramePriorities::claim(&mut priorities.in_app, start + i, *priority) {
+                            continue;
+                        }
+                    }
+                    changed |= frame.in_app() != Some(self.flag);
+                    frame.set_in_app(Some(self.flag));
+                }
+            }
+            FlagActionType::Vars => {
+                let bounds = self.range_bounds(frames, idx);
+                for frame in Self::slice_to_range_mut(frames, bounds) {
+                    changed |= frame.has_vars() != Some(self.flag);
+                    frame.set_has_vars(Some(self.flag));
+                }
             }
+            FlagActionType::Group => {}
         }
+        changed
     }
 
     /// Updates grouping component contribution information according to this action.
-    fn update_frame_components_contributions(
+    ///
+    /// This is a no-op for the `vars` flag.
+    fn update_frame_components_contributions<F: FrameLike>(
         &self,
         components: &mut [Component],
-        frames: &[Frame],
+        frames: &[F],
         idx: usize,
         rule: &Rule,
     ) {
+        if self.ty == FlagActionType::Vars {
+            return;
+        }
+
         let rule_hint = "stack trace rule";
-        let components = self.slice_to_range_mut(components, idx);
-        let frames = self.slice_to_range(frames, idx);
+        let bounds = self.range_bounds(frames, idx);
+        let components = Self::slice_to_range_mut(components, bounds.clone());
+        let frames = Self::slice_to_range(frames, bounds);
 
         for (component, frame) in components.zip(frames) {
             match self.ty {
@@ -130,29 +249,33 @@ impl FlagAction {
                     if component.contributes != Some(self.flag) {
                         component.contributes = Some(self.flag);
                         let state = if self.flag { "un-ignored" } else { "ignored" };
-                        component.hint = Some(format!("{state} by {rule_hint} ({rule})"));
+                        component
+                            .add_hint(HintKind::Group, format!("{state} by {rule_hint} ({rule})"));
                     }
                 }
                 FlagActionType::App => {
                     if in_app_changed(frame, component, self.flag) {
-                        let state = if frame.in_app.unwrap_or_default() {
+                        let state = if frame.in_app().unwrap_or_default() {
                             "in-app"
                         } else {
                             "out of app"
                         };
-                        component.hint =
-                            Some(format!("marked {state} by stack trace rule ({rule})"));
+                        component.add_hint(
+                            HintKind::InApp,
+                            format!("marked {state} by {rule_hint} ({rule})"),
+                        );
                     }
                 }
+                FlagActionType::Vars => {}
             }
         }
     }
 }
 
 /// Whether the `in_app` flag is considered to have changed
-fn in_app_changed(frame: &Frame, component: &Component, flag: bool) -> bool {
-    if let Some(orig_in_app) = frame.orig_in_app {
-        orig_in_app != frame.in_app
+fn in_app_changed(frame: &impl FrameLike, component: &Component, flag: bool) -> bool {
+    if let Some(orig_in_app) = frame.orig_in_app() {
+        orig_in_app != frame.in_app()
     } else {
         Some(flag) == component.contributes
     }
@@ -168,74 +291,213 @@ impl fmt::Display for FlagAction {
     }
 }
 
+/// A variable that can be set by the actions of a rule, like `max-frames=3`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum VarKind {
+    /// The `min-frames` variable.
+    MinFrames,
+    /// The `max-frames` variable.
+    MaxFrames,
+    /// The `category` variable.
+    Category,
+    /// The `invert-stacktrace` variable.
+    InvertStacktrace,
+    /// The `priority` variable.
+    Priority,
+}
+
+impl VarKind {
+    /// All variables, in no particular order.
+    pub const ALL: [VarKind; 5] = [
+        VarKind::MinFrames,
+        VarKind::MaxFrames,
+        VarKind::Category,
+        VarKind::InvertStacktrace,
+        VarKind::Priority,
+    ];
+
+    /// Returns the variable's name as written in rules, e.g. `max-frames`.
+    pub fn name(self) -> &'static str {
+        match self {
+            VarKind::MinFrames => "min-frames",
+            VarKind::MaxFrames => "max-frames",
+            VarKind::Category => "category",
+            VarKind::InvertStacktrace => "invert-stacktrace",
+            VarKind::Priority => "priority",
+        }
+    }
+
+    /// Returns the variable with the given name as written in rules.
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|kind| kind.name() == name)
+    }
+}
+
+impl fmt::Display for VarKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
 /// A variable action.
 ///
 /// These actions set variables to values. The type of the
 /// value depends on the variable. The variable set by a var action
 /// may be a property of a frame (`category`) or of the whole
 /// [`StacktraceState`] (the rest).
-#[derive(Debug, Clone, PartialEq, Eq)]
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
 pub enum VarAction {
     /// The `min-frames` variable on a [`StacktraceState`].
     ///
-    /// The value must be a number.
-    MinFrames(usize),
+    /// The value must be a non-negative number. `0`, represented as `None`,
+    /// means that there is no minimum.
+    MinFrames(Option<NonZeroUsize>),
     /// The `max-frames` variable on a [`StacktraceState`].
     ///
-    /// The value must be a number.
-    MaxFrames(usize),
-    /// The `category` variable on a [`Frame`].
+    /// The value must be a non-negative number. `0`, represented as `None`,
+    /// means that the number of frames is not limited.
+    MaxFrames(Option<NonZeroUsize>),
+    /// The `category` variable on a [`Frame`](super::Frame).
     ///
     /// The value must be a string.
-    Category(SmolStr),
+    Category(StringField),
     /// The `invert-stacktrace` variable on a [`StacktraceState`].
     ///
     /// The value must be a boolean.
     InvertStacktrace(bool),
+    /// The `priority` variable of a [`Rule`].
+    ///
+    /// The value must be a non-negative number. Rules without a `priority` action
+    /// have a priority of `0`. See [`Rule::priority`] for how it is used.
+    Priority(usize),
 }
 
 impl VarAction {
-    /// Applies this action's modification to `frames` at the index `idx`.
-    fn apply_modifications_to_frame(&self, frames: &mut [Frame], idx: usize) {
-        {
-            if let Self::Category(value) = self {
-                if let Some(frame) = frames.get_mut(idx) {
-                    frame.category = Some(value.clone());
-                }
+    /// Returns the variable this action sets.
+    pub fn kind(&self) -> VarKind {
+        match self {
+            VarAction::MinFrames(_) => VarKind::MinFrames,
+            VarAction::MaxFrames(_) => VarKind::MaxFrames,
+            VarAction::Category(_) => VarKind::Category,
+            VarAction::InvertStacktrace(_) => VarKind::InvertStacktrace,
+            VarAction::Priority(_) => VarKind::Priority,
+        }
+    }
+
+    /// Applies this action's modification to `frames` at the index `idx`, skipping the
+    /// frame if its category was set by a rule with a higher priority.
+    ///
+    /// Returns whether the category of the frame changed.
+    fn apply_modifications_with_priority<F: FrameLikeMut>(
+        &self,
+        frames: &mut [F],
+        idx: usize,
+        priority: Option<(usize, &mut FramePriorities)>,
+    ) -> bool {
+        let Self::Category(value) = self else {
+            return false;
+        };
+        if let Some((priority, priorities)) = priority {
+            if !FramePriorities::claim(&mut priorities.category, idx, priority) {
+                return false;
             }
         }
+        let Some(frame) = frames.get_mut(idx) else {
+            return false;
+        };
+        let changed = frame.get_field(FrameField::Category) != Some(value.as_str());
+        frame.set_category(Some(value.clone()));
+        changed
     }
 }
 
 impl fmt::Display for VarAction {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
-            VarAction::MinFrames(value) => write!(f, "min-frames={value}"),
-            VarAction::MaxFrames(value) => write!(f, "max-frames={value}"),
-            VarAction::Category(value) => write!(f, "category={value}"),
+            VarAction::MinFrames(value) => write!(f, "min-frames={}", frame_count(*value)),
+            VarAction::MaxFrames(value) => write!(f, "max-frames={}", frame_count(*value)),
+            VarAction::Category(value) => {
+                write!(f, "category=")?;
+                fmt_var_value(value, f)
+            }
             VarAction::InvertStacktrace(value) => write!(f, "invert-stacktrace={value}"),
+            VarAction::Priority(value) => write!(f, "priority={value}"),
         }
     }
 }
 
+/// Writes the string value of a [`VarAction`], quoted and escaped if it isn't an identifier.
+fn fmt_var_value(value: &str, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    let is_ident = !value.is_empty()
+        && value
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '_' | '.' | '-'));
+    if is_ident {
+        return f.write_str(value);
+    }
+
+    let escaped = value.replace('\\', "\\\\").replace('"', "\\\"");
+    write!(f, "\"{escaped}\"")
+}
+
+/// Returns the number written for an optional frame count, where `None` is written as `0`.
+fn frame_count(value: Option<NonZeroUsize>) -> usize {
+    value.map_or(0, NonZeroUsize::get)
+}
+
 /// An action.
 ///
 /// Every action is either a [`VarAction`] or a [`FlagAction`].
-#[derive(Debug, Clone, PartialEq, Eq)]
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
 pub enum Action {
     Flag(FlagAction),
     Var(VarAction),
 }
 
 impl Action {
+    /// Returns a structured description of this action.
+    pub(crate) fn describe(&self) -> ActionDescription {
+        match self {
+            Action::Flag(action) => ActionDescription::Flag {
+                flag: match action.ty {
+                    FlagActionType::App => "app",
+                    FlagActionType::Group => "group",
+                    FlagActionType::Vars => "vars",
+                },
+                value: action.flag,
+                range: action.range.map(|range| match range {
+                    Range::Up => "up",
+                    Range::Down => "down",
+                    Range::Siblings => "siblings",
+                }),
+            },
+            Action::Var(action) => {
+                let (name, value) = match action {
+                    VarAction::MinFrames(value) => {
+                        ("min-frames", VarValue::Int(frame_count(*value)))
+                    }
+                    VarAction::MaxFrames(value) => {
+                        ("max-frames", VarValue::Int(frame_count(*value)))
+                    }
+                    VarAction::Category(value) => ("category", VarValue::Str(value.to_string())),
+                    VarAction::InvertStacktrace(value) => {
+                        ("invert-stacktrace", VarValue::Bool(*value))
+                    }
+                    VarAction::Priority(value) => ("priority", VarValue::Int(*value)),
+                };
+                ActionDescription::Var { name, value }
+            }
+        }
+    }
+
     /// Returns true if this action modifies a stacktrace.
     ///
-    /// This is the case for the `app` flag action and the `category` var action.
+    /// This is the case for the `app` and `vars` flag actions and the `category` var action.
     pub fn is_modifier(&self) -> bool {
         matches!(
             self,
             Action::Flag(FlagAction {
-                ty: FlagActionType::App,
+                ty: FlagActionType::App | FlagActionType::Vars,
                 ..
             },) | Action::Var(VarAction::Category(_))
         )
@@ -243,26 +505,52 @@ impl Action {
 
     /// Returns true if this action updates stacktrace or component metadata.
     ///
-    /// This is true for all actions except the `category` var action.
+    /// This is true for all actions except the `vars` flag action and the `category` and
+    /// `priority` var actions.
     pub fn is_updater(&self) -> bool {
-        !matches!(self, Action::Var(VarAction::Category(_)))
+        !matches!(
+            self,
+            Action::Flag(FlagAction {
+                ty: FlagActionType::Vars,
+                ..
+            }) | Action::Var(VarAction::Category(_) | VarAction::Priority(_))
+        )
+    }
+
+    /// Applies this action's modification to `frames` at the index `idx`.
+    ///
+    /// Returns whether the value of any frame field changed.
+    pub fn apply_modifications_to_frame<F: FrameLikeMut>(
+        &self,
+        frames: &mut [F],
+        idx: usize,
+    ) -> bool {
+        self.apply_modifications_with_priority(frames, idx, None)
     }
 
     /// Applies this action's modification to `frames` at the index `idx`.
-    pub fn apply_modifications_to_frame(&self, frames: &mut [Frame], idx: usize) {
+    ///
+    /// If `priority` is given, the `in_app` flag and the category of frames are only
+    /// overwritten if they weren't set by a rule with a higher priority.
+    pub(crate) fn apply_modifications_with_priority<F: FrameLikeMut>(
+        &self,
+        frames: &mut [F],
+        idx: usize,
+        priority: Option<(usize, &mut FramePriorities)>,
+    ) -> bool {
         match self {
-            Action::Flag(action) => action.apply_modifications_to_frame(frames, idx),
-            Action::Var(action) => action.apply_modifications_to_frame(frames, idx),
+            Action::Flag(action) => action.apply_modifications_with_priority(frames, idx, priority),
+            Action::Var(action) => action.apply_modifications_with_priority(frames, idx, priority),
         }
     }
 
     /// Updates grouping component contribution information according to this action.
     ///
     /// This is a no-op for var actions.
-    pub fn update_frame_components_contributions(
+    pub fn update_frame_components_contributions<F: FrameLike>(
         &self,
         components: &mut [Component],
-        frames: &[Frame],
+        frames: &[F],
         idx: usize,
         rule: &Rule,
     ) {
@@ -278,7 +566,7 @@ impl Action {
     pub fn modify_stacktrace_state(&self, state: &mut StacktraceState, rule: Rule) {
         if let Self::Var(a) = self {
             match a {
-                VarAction::Category(_) => (),
+                VarAction::Category(_) | VarAction::Priority(_) => (),
                 VarAction::MinFrames(v) => {
                     state.min_frames.value = *v;
                     state.min_frames.setter = Some(rule);
@@ -309,22 +597,91 @@ impl fmt::Display for Action {
 mod tests {
     use serde_json::json;
 
-    use crate::enhancers::{Cache, Enhancements};
-
-    use super::*;
+    use crate::enhancers::{
+        Cache, Enhancements, Frame, MissingValue, MissingValuePolicy, ParseOptions,
+    };
 
     #[test]
     fn in_app_modification() {
-        let enhancements = Enhancements::parse("app:no +app", &mut Cache::default()).unwrap();
+        let in_app = |rules: &str, options: &ParseOptions| {
+            let enhancements =
+                Enhancements::parse_with_options(rules, &mut Cache::default(), options).unwrap();
+            let mut frames = vec![
+                Frame::from_test(&json!({"function": "foo"}), "native"),
+                Frame::from_test(&json!({"function": "foo", "in_app": false}), "native"),
+                Frame::from_test(&json!({"function": "foo", "in_app": true}), "native"),
+            ];
+            enhancements.apply_modifications_to_frames(&mut frames, &Default::default());
+            frames.iter().map(|f| f.in_app).collect::<Vec<_>>()
+        };
+        let default = ParseOptions::default();
+        let (t, f) = (Some(true), Some(false));
+
+        // An unset flag is neither `yes` nor `no`.
+        assert_eq!(in_app("app:no +app", &default), [None, t, t]);
+        assert_eq!(in_app("app:yes -app", &default), [None, f, f]);
+        assert_eq!(in_app("app:unset -app", &default), [f, f, t]);
+        assert_eq!(in_app("!app:no -app", &default), [f, f, f]);
+        assert_eq!(in_app("!app:unset +app", &default), [None, t, t]);
+
+        // Like other missing values, it follows the policy for frames.
+        let never = ParseOptions {
+            missing_values: MissingValuePolicy {
+                frames: MissingValue::NeverMatch,
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        assert_eq!(in_app("!app:no -app", &never), [None, f, f]);
+
+        // Rules written for the old behavior can keep treating it as `false`.
+        let legacy = ParseOptions {
+            missing_values: MissingValuePolicy {
+                unset_in_app_is_false: true,
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        assert_eq!(in_app("app:no +app", &legacy), [t, t, t]);
+        assert_eq!(in_app("!app:no -app", &legacy), [None, f, f]);
+        assert_eq!(in_app("app:unset -app", &legacy), [f, f, t]);
+    }
 
+    #[test]
+    fn sibling_range() {
+        let enhancements =
+            Enhancements::parse("function:foo ~+app", &mut Cache::default()).unwrap();
+        assert_eq!(
+            enhancements.rules().next().unwrap().to_string(),
+            "function:foo ~+app"
+        );
+
+        let frame = |function: &str, package: Option<&str>| {
+            Frame::from_test(
+                &json!({"function": function, "package": package, "in_app": false}),
+                "native",
+            )
+        };
         let mut frames = vec![
-            Frame::from_test(&json!({"function": "foo"}), "native"),
-            Frame::from_test(&json!({"function": "foo", "in_app": false}), "native"),
+            frame("a", Some("lib")),
+            frame("b", Some("app")),
+            frame("c", Some("app")),
+            frame("foo", Some("app")),
+            frame("d", Some("app")),
+            frame("e", None),
+            frame("f", Some("app")),
         ];
 
         enhancements.apply_modifications_to_frames(&mut frames, &Default::default());
 
-        assert_eq!(frames[0].in_app, Some(true));
-        assert_eq!(frames[1].in_app, Some(true));
+        let in_app: Vec<_> = frames.iter().map(|f| f.in_app.unwrap()).collect();
+        assert_eq!(in_app, [false, true, true, true, true, false, false]);
+
+        // Frames without a package have no siblings.
+        let mut frames = vec![frame("a", None), frame("foo", None), frame("b", None)];
+        enhancements.apply_modifications_to_frames(&mut frames, &Default::default());
+
+        let in_app: Vec<_> = frames.iter().map(|f| f.in_app.unwrap()).collect();
+        assert_eq!(in_app, [false, true, false]);
     }
 }
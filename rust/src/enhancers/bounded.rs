@@ -0,0 +1,275 @@
+//! The LRU cache behind [`Cache`](super::Cache), with optional expiry and admission policy.
+//!
+//! Under config churn, an LRU cache fills up with rules that are only ever used once, which
+//! evict the rules of the default configs that are used all the time. The admission policy
+//! protects against this in the style of TinyLFU: the cache keeps an approximate count of
+//! how often every key was requested, and once the cache is full, a new entry is only
+//! admitted if its key was requested more often than the key of the entry it would evict.
+
+use std::borrow::Borrow;
+use std::hash::{BuildHasher, Hash, RandomState};
+use std::num::NonZeroUsize;
+use std::time::{Duration, Instant};
+
+use lru::LruCache;
+
+/// An entry of a [`BoundedCache`].
+#[derive(Debug)]
+struct Entry<V> {
+    value: V,
+    inserted: Instant,
+}
+
+/// An LRU cache whose entries optionally expire, and which optionally only admits
+/// entries whose keys are requested frequently.
+#[derive(Debug)]
+pub(crate) struct BoundedCache<K: Hash + Eq, V> {
+    entries: LruCache<K, Entry<V>>,
+    /// The time after which entries expire, counted from their insertion.
+    ttl: Option<Duration>,
+    /// The request counts of the admission policy, if it is enabled.
+    sketch: Option<FrequencySketch>,
+}
+
+impl<K: Hash + Eq, V> BoundedCache<K, V> {
+    /// Creates a cache of the given capacity without expiry or admission policy.
+    pub(crate) fn new(cap: NonZeroUsize) -> Self {
+        Self {
+            entries: LruCache::new(cap),
+            ttl: None,
+            sketch: None,
+        }
+    }
+
+    /// Sets the time after which entries expire.
+    pub(crate) fn set_ttl(&mut self, ttl: Option<Duration>) {
+        self.ttl = ttl;
+    }
+
+    /// Enables or disables the admission policy.
+    pub(crate) fn set_admission(&mut self, admission: bool) {
+        self.sketch = admission.then(|| FrequencySketch::new(self.entries.cap()));
+    }
+
+    pub(crate) fn ttl(&self) -> Option<Duration> {
+        self.ttl
+    }
+
+    pub(crate) fn admission(&self) -> bool {
+        self.sketch.is_some()
+    }
+
+    pub(crate) fn cap(&self) -> NonZeroUsize {
+        self.entries.cap()
+    }
+
+    /// Returns the number of entries that haven't expired.
+    pub(crate) fn len(&self) -> usize {
+        self.iter().count()
+    }
+
+    /// Returns the value for `key` and marks it as most recently used.
+    ///
+    /// This counts as a request of `key` for the admission policy, whether it is cached or not.
+    pub(crate) fn get<Q>(&mut self, key: &Q) -> Option<&V>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        if let Some(sketch) = &mut self.sketch {
+            sketch.increment(key);
+        }
+
+        if self
+            .entries
+            .peek(key)
+            .is_some_and(|entry| self.is_expired(entry))
+        {
+            self.entries.pop(key);
+            return None;
+        }
+        self.entries.get(key).map(|entry| &entry.value)
+    }
+
+    /// Returns whether there is an entry for `key` that hasn't expired.
+    pub(crate) fn contains<Q>(&self, key: &Q) -> bool
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        self.entries
+            .peek(key)
+            .is_some_and(|entry| !self.is_expired(entry))
+    }
+
+    /// Inserts `value` for `key`, unless the admission policy rejects it.
+    ///
+    /// If the cache is full, the least recently used entry is evicted. With the admission
+    /// policy enabled, `value` is only inserted in that case if `key` was requested more often
+    /// than the key of that entry, or that entry has expired.
+    pub(crate) fn put(&mut self, key: K, value: V) {
+        if let Some(sketch) = &self.sketch {
+            let full = self.entries.len() == self.entries.cap().get();
+            if full && !self.entries.contains(&key) {
+                if let Some((victim_key, victim)) = self.entries.peek_lru() {
+                    if !self.is_expired(victim)
+                        && sketch.estimate(&key) <= sketch.estimate(victim_key)
+                    {
+                        return;
+                    }
+                }
+            }
+        }
+
+        let entry = Entry {
+            value,
+            inserted: Instant::now(),
+        };
+        self.entries.put(key, entry);
+    }
+
+    /// Iterates over the entries that haven't expired, most recently used first.
+    pub(crate) fn iter(&self) -> impl Iterator<Item = (&K, &V)> {
+        self.entries
+            .iter()
+            .filter(|(_, entry)| !self.is_expired(entry))
+            .map(|(key, entry)| (key, &entry.value))
+    }
+
+    fn is_expired(&self, entry: &Entry<V>) -> bool {
+        self.ttl.is_some_and(|ttl| entry.inserted.elapsed() >= ttl)
+    }
+}
+
+/// The number of counters of a [`FrequencySketch`] each key is counted in.
+const SKETCH_DEPTH: usize = 4;
+
+/// Odd multipliers deriving the counter of each row from the hash of a key.
+const SKETCH_SEEDS: [u64; SKETCH_DEPTH] = [
+    0x9e37_79b9_7f4a_7c15,
+    0xc2b2_ae3d_27d4_eb4f,
+    0x1656_67b1_9e37_79f9,
+    0xff51_afd7_ed55_8ccd,
+];
+
+/// A count-min sketch approximating how often keys were requested.
+///
+/// Counts saturate at 15 and are halved periodically, so that keys that were popular a long
+/// time ago don't stay protected forever.
+#[derive(Debug)]
+struct FrequencySketch {
+    hasher: RandomState,
+    /// `SKETCH_DEPTH` rows of `1 << width_bits` counters each.
+    counters: Vec<u8>,
+    width_bits: u32,
+    /// The number of increments since the counts were last halved.
+    increments: usize,
+    /// The number of increments after which the counts are halved.
+    sample_size: usize,
+}
+
+impl FrequencySketch {
+    const MAX_COUNT: u8 = 15;
+
+    fn new(cap: NonZeroUsize) -> Self {
+        let width = (cap.get().saturating_mul(4)).next_power_of_two().max(16);
+        Self {
+            hasher: RandomState::new(),
+            counters: vec![0; width * SKETCH_DEPTH],
+            width_bits: width.trailing_zeros(),
+            increments: 0,
+            sample_size: cap.get().saturating_mul(10),
+        }
+    }
+
+    /// The index of the counter of `key` in every row.
+    fn indices<Q: Hash + ?Sized>(&self, key: &Q) -> [usize; SKETCH_DEPTH] {
+        let hash = self.hasher.hash_one(key);
+        let width = 1 << self.width_bits;
+        std::array::from_fn(|row| {
+            let column = hash.wrapping_mul(SKETCH_SEEDS[row]) >> (64 - self.width_bits);
+            row * width + column as usize
+        })
+    }
+
+    /// Returns the approximate number of times `key` was counted.
+    fn estimate<Q: Hash + ?Sized>(&self, key: &Q) -> u8 {
+        self.indices(key)
+            .into_iter()
+            .map(|idx| self.counters[idx])
+            .min()
+            .unwrap_or_default()
+    }
+
+    /// Counts a request of `key`.
+    fn increment<Q: Hash + ?Sized>(&mut self, key: &Q) {
+        for idx in self.indices(key) {
+            let counter = &mut self.counters[idx];
+            *counter = (*counter + 1).min(Self::MAX_COUNT);
+        }
+
+        self.increments += 1;
+        if self.increments >= self.sample_size {
+            for counter in &mut self.counters {
+                *counter /= 2;
+            }
+            self.increments = 0;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn cache(cap: usize) -> BoundedCache<&'static str, usize> {
+        BoundedCache::new(NonZeroUsize::new(cap).unwrap())
+    }
+
+    #[test]
+    fn expires_entries() {
+        let mut cache = cache(2);
+        cache.put("a", 1);
+        assert_eq!(cache.get("a"), Some(&1));
+
+        cache.set_ttl(Some(Duration::from_secs(3600)));
+        assert!(cache.contains("a"));
+
+        cache.set_ttl(Some(Duration::ZERO));
+        assert!(!cache.contains("a"));
+        assert_eq!(cache.len(), 0);
+        assert_eq!(cache.get("a"), None);
+        assert_eq!(cache.entries.len(), 0);
+    }
+
+    #[test]
+    fn admits_frequent_keys() {
+        let mut cache = cache(2);
+        cache.set_admission(true);
+
+        for key in ["hot", "warm"] {
+            for _ in 0..3 {
+                if cache.get(key).is_none() {
+                    cache.put(key, 0);
+                }
+            }
+        }
+
+        // One-off keys don't evict keys that were requested more often.
+        for key in ["one", "two", "three"] {
+            assert_eq!(cache.get(key), None);
+            cache.put(key, 0);
+            assert!(!cache.contains(key));
+        }
+        assert!(cache.contains("hot") && cache.contains("warm"));
+
+        // Keys that were requested more often than the least recently used one are admitted.
+        for _ in 0..4 {
+            cache.get("new");
+        }
+        cache.put("new", 0);
+        assert!(cache.contains("new"));
+        assert!(cache.contains("warm"));
+        assert!(!cache.contains("hot"));
+    }
+}
@@ -0,0 +1,241 @@
+//! Composing [`Rule`]s from code, without writing and parsing their text.
+
+use std::num::NonZeroUsize;
+
+use anyhow::anyhow;
+
+use super::actions::{Action, FlagAction, FlagActionType, Range, VarAction};
+use super::matchers::{FrameOffset, Matcher};
+use super::{Cache, MatcherOffset, Rule, VarKind, VarValue};
+use crate::{Error, Result};
+
+/// Builds a [`Rule`] out of matchers and actions.
+///
+/// Every matcher and action is checked when it is added, so mistakes are reported where they
+/// are made. Matcher patterns are glob patterns like in the text form of rules, without any
+/// quoting or escaping of spaces.
+///
+/// ```
+/// use rust_ophio::enhancers::{Cache, FlagActionType, MatcherOffset, RuleBuilder};
+///
+/// let mut cache = Cache::default();
+/// let mut builder = RuleBuilder::new(&mut cache);
+/// builder
+///     .add_matcher("function", "my_app::*", false, None)?
+///     .add_matcher("module", "std::*", true, Some(MatcherOffset::Caller))?
+///     .add_flag_action(FlagActionType::App, true, None)?;
+/// let rule = builder.build()?;
+/// assert_eq!(rule.to_string(), "[!module:std::*] | function:my_app::* +app");
+/// # Ok::<_, rust_ophio::Error>(())
+/// ```
+#[derive(Debug)]
+pub struct RuleBuilder<'a> {
+    cache: &'a mut Cache,
+    matchers: Vec<Matcher>,
+    actions: Vec<Action>,
+}
+
+impl<'a> RuleBuilder<'a> {
+    /// Creates a builder of a rule without matchers and actions, using `cache` to memoize the
+    /// construction of regexes.
+    pub fn new(cache: &'a mut Cache) -> Self {
+        Self {
+            cache,
+            matchers: Vec::new(),
+            actions: Vec::new(),
+        }
+    }
+
+    /// Adds a matcher checking `field` against `pattern`.
+    ///
+    /// `field` is the name of the matcher as written in rules, e.g. `function`, `stack.module`,
+    /// or `error.type`. With an `offset`, the matcher checks the caller or callee frame instead
+    /// of the matching one. A rule can have at most one caller and one callee matcher, and
+    /// exception matchers can't have an offset.
+    pub fn add_matcher(
+        &mut self,
+        field: &str,
+        pattern: &str,
+        negated: bool,
+        offset: Option<MatcherOffset>,
+    ) -> Result<&mut Self> {
+        let frame_offset = match offset {
+            Some(MatcherOffset::Caller) => FrameOffset::Caller,
+            Some(MatcherOffset::Callee) => FrameOffset::Callee,
+            None => FrameOffset::None,
+        };
+        let matcher = Matcher::new(negated, field, pattern, frame_offset, &mut self.cache.regex)
+            .map_err(Error::Parse)?;
+
+        match &matcher {
+            Matcher::Exception(_) if offset.is_some() => {
+                return Err(invalid(format!(
+                    "exception matcher `{matcher}` can't check another frame"
+                )));
+            }
+            Matcher::Frame(_) if offset.is_some() && self.has_offset(frame_offset) => {
+                let offset = if frame_offset == FrameOffset::Caller {
+                    "caller"
+                } else {
+                    "callee"
+                };
+                return Err(invalid(format!(
+                    "at `{matcher}`: a rule can only have one {offset} matcher"
+                )));
+            }
+            _ => {}
+        }
+
+        self.matchers.push(matcher);
+        Ok(self)
+    }
+
+    /// Adds an action setting the flag `ty` to `value`, on the frames given by `range`.
+    ///
+    /// The `vars` flag can only be unset.
+    pub fn add_flag_action(
+        &mut self,
+        ty: FlagActionType,
+        value: bool,
+        range: Option<Range>,
+    ) -> Result<&mut Self> {
+        if ty == FlagActionType::Vars && value {
+            return Err(invalid("the `vars` flag can only be unset".into()));
+        }
+
+        self.actions.push(Action::Flag(FlagAction {
+            flag: value,
+            ty,
+            range,
+        }));
+        Ok(self)
+    }
+
+    /// Adds an action setting the variable `kind` to `value`.
+    ///
+    /// The value must have the type of the variable: a number for `min-frames`, `max-frames`,
+    /// and `priority`, a boolean for `invert-stacktrace`, and a string for `category`.
+    pub fn add_var_action(&mut self, kind: VarKind, value: VarValue) -> Result<&mut Self> {
+        let action = match (kind, value) {
+            (VarKind::MinFrames, VarValue::Int(n)) => VarAction::MinFrames(NonZeroUsize::new(n)),
+            (VarKind::MaxFrames, VarValue::Int(n)) => VarAction::MaxFrames(NonZeroUsize::new(n)),
+            (VarKind::Priority, VarValue::Int(n)) => VarAction::Priority(n),
+            (VarKind::InvertStacktrace, VarValue::Bool(b)) => VarAction::InvertStacktrace(b),
+            (VarKind::Category, VarValue::Str(category)) => VarAction::Category(category.into()),
+            (kind, value) => {
+                return Err(invalid(format!(
+                    "invalid value {value:?} for variable `{kind}`"
+                )));
+            }
+        };
+
+        self.actions.push(Action::Var(action));
+        Ok(self)
+    }
+
+    /// Builds the rule.
+    ///
+    /// Fails if no matcher or no action was added.
+    pub fn build(self) -> Result<Rule> {
+        if self.matchers.is_empty() {
+            return Err(invalid("expected at least one matcher".into()));
+        }
+        if self.actions.is_empty() {
+            return Err(invalid("expected at least one action".into()));
+        }
+
+        // Like in parsed rules, the caller matcher comes first and the callee matcher last.
+        let mut matchers = self.matchers;
+        matchers.sort_by_key(|matcher| match matcher {
+            Matcher::Frame(m) if m.frame_offset() == FrameOffset::Caller => 0,
+            Matcher::Frame(m) if m.frame_offset() == FrameOffset::Callee => 2,
+            _ => 1,
+        });
+        Ok(Rule::new(matchers, self.actions))
+    }
+
+    /// Returns true if a frame matcher with the given offset was already added.
+    fn has_offset(&self, offset: FrameOffset) -> bool {
+        self.matchers
+            .iter()
+            .any(|m| matches!(m, Matcher::Frame(m) if m.frame_offset() == offset))
+    }
+}
+
+/// Returns the error for an invalid combination of matchers and actions.
+fn invalid(message: String) -> Error {
+    Error::Parse(anyhow!(message))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::enhancers::Enhancements;
+
+    #[test]
+    fn builds_rules() {
+        let mut cache = Cache::default();
+        let mut builder = RuleBuilder::new(&mut cache);
+        builder
+            .add_matcher("function", "foo", false, Some(MatcherOffset::Callee))
+            .unwrap()
+            .add_matcher("error.type", "Panic", false, None)
+            .unwrap()
+            .add_matcher("stack.module", "my app::*", true, None)
+            .unwrap()
+            .add_matcher("family", "native", false, Some(MatcherOffset::Caller))
+            .unwrap()
+            .add_flag_action(FlagActionType::Group, false, Some(Range::Up))
+            .unwrap()
+            .add_var_action(VarKind::MaxFrames, VarValue::Int(3))
+            .unwrap()
+            .add_var_action(VarKind::Category, VarValue::Str("ui".into()))
+            .unwrap();
+        let rule = builder.build().unwrap();
+
+        let text = r#"[ family:native ] | error.type:Panic !stack.module:"my app::*" | [ function:foo ] ^-group max-frames=3 category=ui"#;
+        let parsed = Enhancements::parse(text, &mut cache).unwrap();
+        assert_eq!(&rule, parsed.rules().next().unwrap());
+        assert_eq!(rule.describe(), parsed.rules().next().unwrap().describe());
+    }
+
+    #[test]
+    fn rejects_invalid_combinations() {
+        let mut cache = Cache::default();
+        let mut builder = RuleBuilder::new(&mut cache);
+
+        let err = |result: Result<&mut RuleBuilder>| result.unwrap_err().to_string();
+        assert!(err(builder.add_matcher("nope", "foo", false, None)).contains("nope"));
+        assert_eq!(
+            err(builder.add_matcher("type", "Panic", false, Some(MatcherOffset::Caller))),
+            "exception matcher `type:Panic` can't check another frame"
+        );
+        builder
+            .add_matcher("function", "foo", false, Some(MatcherOffset::Caller))
+            .unwrap();
+        assert_eq!(
+            err(builder.add_matcher("module", "bar", false, Some(MatcherOffset::Caller))),
+            "at `[module:bar] |`: a rule can only have one caller matcher"
+        );
+        assert_eq!(
+            err(builder.add_flag_action(FlagActionType::Vars, true, None)),
+            "the `vars` flag can only be unset"
+        );
+        assert_eq!(
+            err(builder.add_var_action(VarKind::InvertStacktrace, VarValue::Int(1))),
+            "invalid value Int(1) for variable `invert-stacktrace`"
+        );
+
+        assert_eq!(
+            builder.build().unwrap_err().to_string(),
+            "expected at least one action"
+        );
+        assert_eq!(
+            RuleBuilder::new(&mut cache)
+                .build()
+                .unwrap_err()
+                .to_string(),
+            "expected at least one matcher"
+        );
+    }
+}
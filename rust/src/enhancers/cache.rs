@@ -1,42 +1,336 @@
 //! Caching logic to improve the performance of creating grouping enhancements.
 
-use std::sync::Arc;
+use std::borrow::Cow;
+use std::collections::BTreeSet;
+use std::path::Path;
+use std::sync::{Arc, OnceLock};
+use std::time::Duration;
 
-use globset::GlobBuilder;
-use lru::LruCache;
+use anyhow::{anyhow, bail, Context};
+use globset::{ErrorKind, GlobBuilder};
 use regex::bytes::{Regex, RegexBuilder};
+use serde::{Deserialize, Serialize};
 use smol_str::SmolStr;
 
-use super::{grammar::parse_rule, rules::Rule};
+use super::bounded::BoundedCache;
+use super::config_structure::{EncodedRule, Encoding};
+use super::{grammar::parse_rule, rules::Rule, GlobFeature, GlobOptions};
+use crate::{Error, Result};
+
+/// The key of a regex: its glob pattern, whether it is a path pattern, and whether
+/// it consists of comma-separated alternatives.
+pub(crate) type RegexKey = (SmolStr, bool, bool);
+
+/// A regex translated from a glob pattern that is compiled on first use, unless it
+/// was compiled right away.
+#[derive(Debug)]
+pub(crate) struct LazyRegex {
+    key: RegexKey,
+    /// The compiled regex, or `None` if the pattern is invalid.
+    regex: OnceLock<Option<Arc<Regex>>>,
+    /// Checks equivalent to the regex that don't need the regex engine, if the pattern is
+    /// simple enough. The regex matches if any of them does.
+    literals: Option<Box<[LiteralMatch]>>,
+}
+
+impl LazyRegex {
+    /// Compiles the regex for `key` right away.
+    fn compiled(key: RegexKey) -> Result<Self> {
+        let regex = translate_alternatives(&key.0, key.1, key.2)?;
+        Ok(Self::with_regex(key, regex))
+    }
+
+    /// Creates the regex for `key` without compiling it.
+    fn lazy(key: RegexKey) -> Self {
+        Self {
+            literals: LiteralMatch::for_key(&key),
+            key,
+            regex: OnceLock::new(),
+        }
+    }
+
+    /// Creates the regex for `key` from the already compiled `regex`.
+    fn with_regex(key: RegexKey, regex: Regex) -> Self {
+        Self {
+            literals: LiteralMatch::for_key(&key),
+            key,
+            regex: OnceLock::from(Some(Arc::new(regex))),
+        }
+    }
+
+    /// Creates the regex for `key` from `regex`, which is always used for matching, even if
+    /// it differs from the translation of the pattern.
+    #[cfg(feature = "compiled")]
+    fn with_translated(key: RegexKey, regex: Regex) -> Self {
+        Self {
+            literals: None,
+            ..Self::with_regex(key, regex)
+        }
+    }
+
+    /// The glob pattern this regex was translated from.
+    pub(crate) fn pattern(&self) -> &str {
+        &self.key.0
+    }
+
+    /// Returns the regex, compiling it if this is its first use.
+    ///
+    /// Returns `None` if the pattern can't be compiled.
+    fn get(&self) -> Option<&Arc<Regex>> {
+        self.regex
+            .get_or_init(|| {
+                let (pattern, is_path, alternatives) = &self.key;
+                translate_alternatives(pattern, *is_path, *alternatives)
+                    .ok()
+                    .map(Arc::new)
+            })
+            .as_ref()
+    }
+
+    /// Returns the error explaining why the pattern can't be compiled, or `None` if it is valid.
+    ///
+    /// This compiles the regex if this is its first use, unless it is matched without the
+    /// regex engine.
+    pub(crate) fn error(&self) -> Option<anyhow::Error> {
+        if self.literals.is_some() || self.get().is_some() {
+            return None;
+        }
+        let (pattern, is_path, alternatives) = &self.key;
+        let err = translate_alternatives(pattern, *is_path, *alternatives).err()?;
+        Some(describe_glob_error(pattern, err))
+    }
+
+    /// Returns the key of this regex and the regex source it was translated to, compiling it if
+    /// this is its first use.
+    ///
+    /// Returns `None` if the pattern can't be compiled.
+    #[cfg(feature = "compiled")]
+    pub(crate) fn translated(&self) -> Option<(&RegexKey, &str)> {
+        self.get().map(|regex| (&self.key, regex.as_str()))
+    }
+
+    /// Returns true if the regex matches `haystack`.
+    ///
+    /// A regex whose pattern can't be compiled matches nothing.
+    pub(crate) fn is_match(&self, haystack: &[u8]) -> bool {
+        if let Some(literals) = &self.literals {
+            return literals.iter().any(|literal| literal.is_match(haystack));
+        }
+        self.get().is_some_and(|regex| regex.is_match(haystack))
+    }
+}
+
+/// A check equivalent to the regex of a glob pattern that is a literal, optionally with a
+/// leading or trailing `*`, like `memset` or `std::*`.
+///
+/// These patterns make up most rules. Their regexes are anchored at both ends, so plain byte
+/// comparisons give the same result without the overhead of the regex engine. The `*` in the
+/// regexes doesn't match newlines, so values containing one only match exact patterns.
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum LiteralMatch {
+    /// Matches the literal itself.
+    Exact(Box<[u8]>),
+    /// Matches values starting with the literal, from a pattern like `foo*`.
+    Prefix(Box<[u8]>),
+    /// Matches values ending with the literal, from a pattern like `*foo`.
+    Suffix(Box<[u8]>),
+    /// Matches values containing the literal, from a pattern like `*foo*`.
+    Contains(Box<[u8]>),
+}
+
+impl LiteralMatch {
+    /// Returns the checks equivalent to the regex for `key`, if there are any.
+    ///
+    /// Path patterns, which are matched case-insensitively and whose `*` doesn't match `/`,
+    /// are always matched with their regex.
+    fn for_key((pattern, is_path, alternatives): &RegexKey) -> Option<Box<[LiteralMatch]>> {
+        if *is_path {
+            return None;
+        }
+        if let Some(literal) = pattern.strip_prefix(LITERAL_PREFIX) {
+            let literal = match literal.strip_suffix('*') {
+                Some(prefix) => Self::Prefix(prefix.as_bytes().into()),
+                None => Self::Exact(literal.as_bytes().into()),
+            };
+            return Some(Box::new([literal]));
+        }
+
+        if *alternatives {
+            split_alternatives(pattern)
+                .iter()
+                .map(|alternative| Self::for_glob(alternative))
+                .collect()
+        } else {
+            Some(Box::new([Self::for_glob(pattern)?]))
+        }
+    }
+
+    /// Returns the check equivalent to the regex of the glob pattern `pat`, if there is one.
+    fn for_glob(pat: &str) -> Option<Self> {
+        let (leading, pat) = match pat.strip_prefix('*') {
+            Some(rest) => (true, rest),
+            None => (false, pat),
+        };
+        let (trailing, pat) = match pat.strip_suffix('*') {
+            Some(rest) => (true, rest),
+            None => (false, pat),
+        };
+        if pat.contains(['*', '?', '[', ']', '{', '}', '\\', '\n']) {
+            return None;
+        }
+
+        let literal = pat.as_bytes().into();
+        Some(match (leading, trailing) {
+            (false, false) => Self::Exact(literal),
+            (false, true) => Self::Prefix(literal),
+            (true, false) => Self::Suffix(literal),
+            (true, true) => Self::Contains(literal),
+        })
+    }
+
+    fn is_match(&self, haystack: &[u8]) -> bool {
+        match self {
+            Self::Exact(literal) => haystack == &**literal,
+            _ if haystack.contains(&b'\n') => false,
+            Self::Prefix(literal) => haystack.starts_with(literal),
+            Self::Suffix(literal) => haystack.ends_with(literal),
+            Self::Contains(literal) => {
+                literal.is_empty() || haystack.windows(literal.len()).any(|w| w == &**literal)
+            }
+        }
+    }
+}
 
 /// An LRU cache for memoizing regex construction.
+///
+/// Regexes are keyed by their pattern, whether it is a path pattern, and whether
+/// it consists of comma-separated alternatives.
 #[derive(Debug, Default)]
-pub struct RegexCache(Option<LruCache<(SmolStr, bool), Arc<Regex>>>);
+pub struct RegexCache {
+    regexes: Option<BoundedCache<RegexKey, Arc<LazyRegex>>>,
+    /// Whether the regexes of frame matchers are compiled on first use.
+    lazy: bool,
+}
 
 impl RegexCache {
     /// Creates a new cache with the given size.
     ///
     /// If `size` is 0, no caching will be performed.
     pub fn new(size: usize) -> Self {
-        let regex = size.try_into().ok().map(LruCache::new);
-        Self(regex)
+        let regexes = size.try_into().ok().map(BoundedCache::new);
+        Self {
+            regexes,
+            lazy: false,
+        }
+    }
+
+    /// Sets whether the regexes of frame matchers are compiled when they are first
+    /// matched against a frame instead of when the rule is parsed.
+    ///
+    /// See [`Cache::with_lazy_regexes`].
+    pub fn with_lazy_regexes(mut self, lazy: bool) -> Self {
+        self.lazy = lazy;
+        self
     }
 
     /// Gets the regex for the string `key` and the boolean `is_path` from the cache or computes and inserts
     /// it using `translate_pattern` if it is not present.
-    pub fn get_or_try_insert(&mut self, key: &str, is_path: bool) -> anyhow::Result<Arc<Regex>> {
-        match self.0.as_mut() {
+    pub fn get_or_try_insert(&mut self, key: &str, is_path: bool) -> Result<Arc<Regex>> {
+        self.get_or_try_insert_regex(key, is_path, false)
+    }
+
+    /// Gets the regex for the comma-separated alternative patterns in `key` from the cache
+    /// or computes and inserts it if it is not present.
+    ///
+    /// The resulting regex matches if any of the alternatives matches. A literal comma
+    /// is written as `\,`. Commas within `{}` or `[]` don't separate alternatives.
+    pub fn get_or_try_insert_alternatives(
+        &mut self,
+        key: &str,
+        is_path: bool,
+    ) -> Result<Arc<Regex>> {
+        self.get_or_try_insert_regex(key, is_path, true)
+    }
+
+    /// Gets the regex for a frame matcher from the cache or inserts it if it is not present.
+    ///
+    /// If this cache is lazy, the regex isn't compiled until it is first used, and this
+    /// never fails.
+    pub(crate) fn get_or_try_insert_lazy(
+        &mut self,
+        key: &str,
+        is_path: bool,
+        alternatives: bool,
+    ) -> Result<Arc<LazyRegex>> {
+        if self.lazy {
+            let key = (key.into(), is_path, alternatives);
+            self.get_or_insert_with(key, |key| Ok(LazyRegex::lazy(key)))
+        } else {
+            self.get_or_try_insert_impl(key, is_path, alternatives)
+        }
+    }
+
+    /// Gets the compiled regex for `key` from the cache or compiles and inserts it.
+    fn get_or_try_insert_regex(
+        &mut self,
+        key: &str,
+        is_path: bool,
+        alternatives: bool,
+    ) -> Result<Arc<Regex>> {
+        let regex = self.get_or_try_insert_impl(key, is_path, alternatives)?;
+        match regex.get() {
+            Some(regex) => Ok(Arc::clone(regex)),
+            // A lazy regex cached earlier turned out to be invalid, so compile it again
+            // to get the error.
+            None => Ok(Arc::new(translate_alternatives(
+                key,
+                is_path,
+                alternatives,
+            )?)),
+        }
+    }
+
+    fn get_or_try_insert_impl(
+        &mut self,
+        key: &str,
+        is_path: bool,
+        alternatives: bool,
+    ) -> Result<Arc<LazyRegex>> {
+        let key = (key.into(), is_path, alternatives);
+        self.get_or_insert_with(key, LazyRegex::compiled)
+    }
+
+    /// Compiles the regex `source`, previously translated from the pattern of `key`, and inserts
+    /// it into the cache without translating the pattern again.
+    ///
+    /// This does nothing if the cache has size 0.
+    #[cfg(feature = "compiled")]
+    pub(crate) fn insert_translated(&mut self, key: RegexKey, source: &str) -> Result<()> {
+        if let Some(cache) = self.regexes.as_mut() {
+            let regex = RegexBuilder::new(source).build()?;
+            let regex = LazyRegex::with_translated(key.clone(), regex);
+            cache.put(key, Arc::new(regex));
+        }
+        Ok(())
+    }
+
+    /// Gets the regex for `key` from the cache or creates and inserts it with `f`.
+    fn get_or_insert_with(
+        &mut self,
+        key: RegexKey,
+        f: impl FnOnce(RegexKey) -> Result<LazyRegex>,
+    ) -> Result<Arc<LazyRegex>> {
+        match self.regexes.as_mut() {
             Some(cache) => {
-                let key = (key.into(), is_path);
                 if let Some(regex) = cache.get(&key) {
                     return Ok(Arc::clone(regex));
                 }
 
-                let regex = translate_pattern(&key.0, key.1).map(Arc::new)?;
+                let regex = Arc::new(f(key.clone())?);
                 cache.put(key, regex.clone());
                 Ok(regex)
             }
-            None => translate_pattern(key, is_path).map(Arc::new),
+            None => f(key).map(Arc::new),
         }
     }
 }
@@ -44,39 +338,51 @@ impl RegexCache {
 /// A cache for memoizing the parsing of [`Rules`](Rule) from their string
 /// representations.
 #[derive(Debug, Default)]
-pub struct RulesCache(Option<LruCache<SmolStr, Rule>>);
+pub struct RulesCache(Option<BoundedCache<SmolStr, Rule>>);
 
 impl RulesCache {
     /// Creates a new cache with the given size.
     ///
     /// If `size` is 0, no caching will be performed.
     pub fn new(size: usize) -> Self {
-        let rules = size.try_into().ok().map(LruCache::new);
+        let rules = size.try_into().ok().map(BoundedCache::new);
         Self(rules)
     }
 
     /// Gets the rule for the string `key` from the cache or parses and inserts
     /// it using `parse_rule` if it is not present.
-    pub fn get_or_try_insert(
-        &mut self,
-        key: &str,
-        regex_cache: &mut RegexCache,
-    ) -> anyhow::Result<Rule> {
+    pub fn get_or_try_insert(&mut self, key: &str, regex_cache: &mut RegexCache) -> Result<Rule> {
         match self.0.as_mut() {
             Some(cache) => {
                 if let Some(rule) = cache.get(key) {
                     return Ok(rule.clone());
                 }
 
-                let rule = parse_rule(key, regex_cache)?;
+                let rule = parse_rule(key, regex_cache).map_err(Error::Parse)?;
                 cache.put(key.into(), rule.clone());
                 Ok(rule)
             }
-            None => parse_rule(key, regex_cache),
+            None => parse_rule(key, regex_cache).map_err(Error::Parse),
         }
     }
 }
 
+/// The version of the files written by [`Cache::save_to`].
+///
+/// Bump the format number whenever the encoding of rules or their meaning changes, so that
+/// caches saved by older versions are discarded instead of producing outdated rules.
+const PERSISTED_CACHE_VERSION: &str = concat!(env!("CARGO_PKG_VERSION"), "+format.1");
+
+/// The contents of a file written by [`Cache::save_to`].
+#[derive(Debug, Deserialize, Serialize)]
+struct PersistedCache<'a> {
+    #[serde(borrow)]
+    version: Cow<'a, str>,
+    /// The text of every cached rule and its encoding, most recently used first.
+    #[serde(borrow)]
+    rules: Vec<(Cow<'a, str>, EncodedRule<'a>)>,
+}
+
 /// An LRU cache for memoizing the construction of [`Rules`](Rule) and [`Regexes`](Regex).
 #[derive(Debug, Default)]
 pub struct Cache {
@@ -94,29 +400,200 @@ impl Cache {
         Self { rules, regex }
     }
 
+    /// Sets whether the regexes of frame matchers are compiled when they are first
+    /// matched against a frame instead of when the rule is parsed.
+    ///
+    /// This makes parsing large configurations faster and lets regexes of rules that never
+    /// match anything go uncompiled, at the cost of compiling regexes while applying rules.
+    /// Lazily compiled regexes are still shared between rules through this cache. Frame
+    /// matchers with invalid patterns never match in either mode.
+    pub fn with_lazy_regexes(mut self, lazy: bool) -> Self {
+        self.regex = self.regex.with_lazy_regexes(lazy);
+        self
+    }
+
+    /// Sets the time after which cached rules and regexes expire, counted from when they were
+    /// inserted.
+    ///
+    /// Expired entries are parsed or compiled again on their next use.
+    pub fn with_ttl(mut self, ttl: Option<Duration>) -> Self {
+        self.rules.0.iter_mut().for_each(|cache| cache.set_ttl(ttl));
+        let regexes = self.regex.regexes.iter_mut();
+        regexes.for_each(|cache| cache.set_ttl(ttl));
+        self
+    }
+
+    /// Sets whether a full cache only admits new rules and regexes that are requested more
+    /// often than the least recently used ones they would evict.
+    ///
+    /// This keeps rules that are used all the time, like the default rules, cached while
+    /// many rules that are only used once pass through, e.g. the custom rules of projects
+    /// whose configs change often. Request counts are approximate and decay over time, so that
+    /// rules that are no longer used eventually get evicted.
+    pub fn with_admission(mut self, admission: bool) -> Self {
+        let rules = self.rules.0.iter_mut();
+        rules.for_each(|cache| cache.set_admission(admission));
+        let regexes = self.regex.regexes.iter_mut();
+        regexes.for_each(|cache| cache.set_admission(admission));
+        self
+    }
+
+    /// Returns the size this cache was created with.
+    pub fn size(&self) -> usize {
+        self.rules.0.as_ref().map_or(0, |cache| cache.cap().get())
+    }
+
+    /// Returns whether this cache compiles the regexes of frame matchers lazily.
+    pub fn lazy_regexes(&self) -> bool {
+        self.regex.lazy
+    }
+
+    /// Returns the time after which cached entries expire, if they do.
+    pub fn ttl(&self) -> Option<Duration> {
+        self.rules.0.as_ref().and_then(BoundedCache::ttl)
+    }
+
+    /// Returns whether this cache only admits frequently requested entries once it is full.
+    pub fn admission(&self) -> bool {
+        self.rules.0.as_ref().is_some_and(BoundedCache::admission)
+    }
+
+    /// Returns the number of rules currently in this cache.
+    pub fn cached_rules(&self) -> usize {
+        self.rules.0.as_ref().map_or(0, BoundedCache::len)
+    }
+
+    /// Returns the number of regexes currently in this cache.
+    pub fn cached_regexes(&self) -> usize {
+        self.regex.regexes.as_ref().map_or(0, BoundedCache::len)
+    }
+
     /// Gets the rule for the string `key` from the cache or parses and inserts
     /// it using `parse_rule` if it is not present.
-    pub fn get_or_try_insert_rule(&mut self, key: &str) -> anyhow::Result<Rule> {
+    pub fn get_or_try_insert_rule(&mut self, key: &str) -> Result<Rule> {
         self.rules.get_or_try_insert(key, &mut self.regex)
     }
 
     /// Gets the regex for the string `key` and the boolean `is_path` from the cache or computes and inserts
     /// it using `translate_pattern` if it is not present.
-    pub fn get_or_try_insert_regex(
-        &mut self,
-        key: &str,
-        is_path: bool,
-    ) -> anyhow::Result<Arc<Regex>> {
+    pub fn get_or_try_insert_regex(&mut self, key: &str, is_path: bool) -> Result<Arc<Regex>> {
         self.regex.get_or_try_insert(key, is_path)
     }
+
+    /// Writes the cached rules to the file at `path`, replacing it.
+    ///
+    /// Rules are stored in their compact encoding, keyed by their text. Regexes are not stored
+    /// and get compiled again when the rules are loaded with [`load_from`](Self::load_from).
+    /// Rules that can't be encoded, like rules with `has_vars` matchers or `vars` actions, are
+    /// skipped. The file is written to a temporary location first and then moved into place,
+    /// so concurrent readers never see a partially written file.
+    ///
+    /// Returns the number of rules that were written.
+    pub fn save_to(&self, path: impl AsRef<Path>) -> Result<usize> {
+        let rules: Vec<_> = self
+            .rules
+            .0
+            .iter()
+            .flat_map(|cache| cache.iter())
+            .filter_map(|(key, rule)| {
+                let encoded = EncodedRule::from_rule(rule, Encoding::Extended).ok()?;
+                Some((Cow::Borrowed(key.as_str()), encoded))
+            })
+            .collect();
+        let written = rules.len();
+
+        let persisted = PersistedCache {
+            version: PERSISTED_CACHE_VERSION.into(),
+            rules,
+        };
+        let encoded = rmp_serde::to_vec(&persisted)
+            .context("failed to encode cache")
+            .map_err(Error::ConfigStructure)?;
+
+        let path = path.as_ref();
+        let mut tmp_path = path.as_os_str().to_owned();
+        tmp_path.push(format!(".tmp-{}", std::process::id()));
+        std::fs::write(&tmp_path, encoded)?;
+        std::fs::rename(&tmp_path, path)?;
+
+        Ok(written)
+    }
+
+    /// Adds the rules stored in the file at `path` by [`save_to`](Self::save_to) to this cache.
+    ///
+    /// Rules that are already cached are kept. If the file was written by a different version of
+    /// this crate, it is ignored, since its rules may be outdated. Nothing is loaded into a cache
+    /// of size 0.
+    ///
+    /// Returns the number of rules that were added.
+    pub fn load_from(&mut self, path: impl AsRef<Path>) -> Result<usize> {
+        let Some(cache) = self.rules.0.as_mut() else {
+            return Ok(0);
+        };
+
+        let contents = std::fs::read(path)?;
+        let persisted: PersistedCache = rmp_serde::from_slice(&contents)
+            .context("failed to decode cache")
+            .map_err(Error::ConfigStructure)?;
+        if persisted.version != PERSISTED_CACHE_VERSION {
+            return Ok(0);
+        }
+
+        let mut loaded = 0;
+        // Insert the least recently used rules first so that they're evicted first again.
+        for (key, encoded) in persisted.rules.into_iter().rev() {
+            if cache.contains(&*key) {
+                continue;
+            }
+            let rule = encoded
+                .into_rule(&mut self.regex)
+                .with_context(|| format!("at `{key}`: failed to decode cached rule"))
+                .map_err(Error::ConfigStructure)?;
+            cache.put(SmolStr::new(key), rule);
+            loaded += 1;
+        }
+
+        Ok(loaded)
+    }
+}
+
+/// The prefix of literal patterns, which match their argument literally except for a trailing `*`.
+///
+/// This allows matching values like Objective-C selectors, e.g. `function:=-[UIApplication *`,
+/// without escaping the characters that have special meanings in glob patterns.
+pub(crate) const LITERAL_PREFIX: char = '=';
+
+/// Translates a glob pattern, or comma-separated alternative glob patterns if
+/// `alternatives` is true, to a regex.
+///
+/// [Literal patterns](LITERAL_PREFIX) never consist of alternatives.
+fn translate_alternatives(pat: &str, is_path_matcher: bool, alternatives: bool) -> Result<Regex> {
+    let regex = if let Some(literal) = pat.strip_prefix(LITERAL_PREFIX) {
+        translate_literal(literal, is_path_matcher)
+    } else if alternatives {
+        let mut regex = String::new();
+        for (i, alternative) in split_alternatives(pat).iter().enumerate() {
+            if i > 0 {
+                regex.push('|');
+            }
+            regex.push_str("(?:");
+            regex.push_str(&translate_pattern(alternative, is_path_matcher)?);
+            regex.push(')');
+        }
+        regex
+    } else {
+        translate_pattern(pat, is_path_matcher)?
+    };
+
+    Ok(RegexBuilder::new(&regex).build()?)
 }
 
-/// Translates a glob pattern to a regex.
+/// Translates a glob pattern to the source of a regex.
 ///
 /// If `is_path_matcher` is true, backslashes in the pattern will be normalized
 /// to slashes and `*` won't match path separators (i.e. `**` must be used to match
 /// multiple path segments).
-fn translate_pattern(pat: &str, is_path_matcher: bool) -> anyhow::Result<Regex> {
+fn translate_pattern(pat: &str, is_path_matcher: bool) -> Result<String> {
     let pat = if is_path_matcher {
         pat.replace('\\', "/")
     } else {
@@ -126,5 +603,365 @@ fn translate_pattern(pat: &str, is_path_matcher: bool) -> anyhow::Result<Regex>
     builder.literal_separator(is_path_matcher);
     builder.case_insensitive(is_path_matcher);
     let glob = builder.build()?;
-    Ok(RegexBuilder::new(glob.regex()).build()?)
+    Ok(glob.regex().to_owned())
+}
+
+/// Translates the argument of a [literal pattern](LITERAL_PREFIX) to the source of a regex.
+///
+/// The regex matches `literal`, or any value starting with it if it ends with `*`. Like glob
+/// patterns, path patterns have their backslashes normalized to slashes and are matched
+/// case-insensitively.
+fn translate_literal(literal: &str, is_path_matcher: bool) -> String {
+    let (literal, any_suffix) = match literal.strip_suffix('*') {
+        Some(prefix) => (prefix, ".*"),
+        None => (literal, ""),
+    };
+    let (literal, flags) = if is_path_matcher {
+        (Cow::Owned(literal.replace('\\', "/")), "(?i)")
+    } else {
+        (Cow::Borrowed(literal), "")
+    };
+    format!("(?-u){flags}^{}{any_suffix}$", regex::escape(&literal))
+}
+
+/// Checks that the glob pattern `pat`, or each of its comma-separated alternatives if
+/// `alternatives` is true, only uses the syntax allowed by `options`.
+///
+/// If `options` reject invalid patterns, this also checks that the pattern compiles.
+pub(crate) fn validate_glob(
+    pat: &str,
+    is_path_matcher: bool,
+    alternatives: bool,
+    options: &GlobOptions,
+) -> anyhow::Result<()> {
+    if *options == GlobOptions::default() || pat.starts_with(LITERAL_PREFIX) {
+        return Ok(());
+    }
+
+    let patterns = if alternatives {
+        split_alternatives(pat)
+    } else {
+        vec![pat.to_owned()]
+    };
+    for pattern in &patterns {
+        check_glob_syntax(pattern, is_path_matcher, options)?;
+        if options.reject_invalid {
+            translate_pattern(pattern, is_path_matcher)
+                .map_err(|err| describe_glob_error(pattern, err))?;
+        }
+    }
+    Ok(())
+}
+
+/// Checks that the glob pattern `pat` doesn't use brace expansion or character classes if
+/// `options` disable them.
+fn check_glob_syntax(
+    pat: &str,
+    is_path_matcher: bool,
+    options: &GlobOptions,
+) -> anyhow::Result<()> {
+    let mut chars = pat.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            // Backslashes are path separators in path patterns, and escapes otherwise.
+            '\\' if !is_path_matcher => {
+                chars.next();
+            }
+            '[' if !options.classes => {
+                bail!("`{pat}` uses a character class, which is disabled")
+            }
+            // Braces within character classes are literal.
+            '[' => {
+                chars.next_if(|&c| c == '!' || c == '^');
+                chars.next_if_eq(&']');
+                for c in chars.by_ref() {
+                    if c == ']' {
+                        break;
+                    }
+                }
+            }
+            '{' if !options.braces => bail!("`{pat}` uses brace expansion, which is disabled"),
+            _ => {}
+        }
+    }
+    Ok(())
+}
+
+/// Returns the glob features the pattern `pat` uses, each once, in no particular order.
+///
+/// `is_path_matcher` and `alternatives` are interpreted like in [`validate_glob`].
+pub(crate) fn glob_features(
+    pat: &str,
+    is_path_matcher: bool,
+    alternatives: bool,
+) -> BTreeSet<GlobFeature> {
+    let mut features = BTreeSet::new();
+    if pat.starts_with(LITERAL_PREFIX) {
+        features.insert(GlobFeature::Literal);
+        return features;
+    }
+
+    let patterns = if alternatives {
+        split_alternatives(pat)
+    } else {
+        vec![pat.to_owned()]
+    };
+    if patterns.len() > 1 {
+        features.insert(GlobFeature::Alternatives);
+    }
+    for pattern in &patterns {
+        let mut chars = pattern.chars().peekable();
+        while let Some(c) = chars.next() {
+            let feature = match c {
+                '\\' if !is_path_matcher => {
+                    chars.next();
+                    GlobFeature::Escape
+                }
+                '*' if chars.next_if_eq(&'*').is_some() => GlobFeature::RecursiveWildcard,
+                '*' => GlobFeature::Wildcard,
+                '?' => GlobFeature::SingleCharacter,
+                '[' => {
+                    chars.next_if(|&c| c == '!' || c == '^');
+                    chars.next_if_eq(&']');
+                    for c in chars.by_ref() {
+                        if c == ']' {
+                            break;
+                        }
+                    }
+                    GlobFeature::CharacterClass
+                }
+                '{' => GlobFeature::BraceExpansion,
+                _ => continue,
+            };
+            features.insert(feature);
+        }
+    }
+    features
+}
+
+/// Turns the error of compiling the glob pattern `pat` into an error explaining what is wrong
+/// with the pattern.
+pub(crate) fn describe_glob_error(pat: &str, err: Error) -> anyhow::Error {
+    let Error::Glob(err) = err else {
+        return err.into();
+    };
+    match err.kind() {
+        ErrorKind::UnclosedClass => {
+            anyhow!(
+                "unclosed character class in `{pat}`, write `[[]` to match `[` literally \
+                or `{LITERAL_PREFIX}{pat}` to match the whole pattern literally"
+            )
+        }
+        ErrorKind::InvalidRange(start, end) => {
+            anyhow!("invalid range `{start}-{end}` in a character class of `{pat}`")
+        }
+        ErrorKind::UnclosedAlternates => {
+            anyhow!("unclosed `{{` in `{pat}`, write `[{{]` to match `{{` literally")
+        }
+        ErrorKind::UnopenedAlternates => {
+            anyhow!("unopened `}}` in `{pat}`, write `[}}]` to match `}}` literally")
+        }
+        ErrorKind::NestedAlternates => anyhow!("nested braces in `{pat}` are not supported"),
+        ErrorKind::DanglingEscape => anyhow!("`{pat}` ends with an unescaped `\\`"),
+        _ => Error::Glob(err).into(),
+    }
+}
+
+/// Splits a pattern at the commas that are neither escaped as `\,` nor nested in `{}` or `[]`,
+/// and unescapes the escaped commas.
+fn split_alternatives(pat: &str) -> Vec<String> {
+    let mut alternatives = vec![String::new()];
+    let (mut braces, mut in_brackets) = (0usize, false);
+
+    let mut chars = pat.chars();
+    while let Some(c) = chars.next() {
+        let current = alternatives
+            .last_mut()
+            .expect("there is always an alternative");
+        match c {
+            '\\' => match chars.next() {
+                Some(',') => current.push(','),
+                Some(escaped) => {
+                    current.push('\\');
+                    current.push(escaped);
+                }
+                None => current.push('\\'),
+            },
+            ',' if braces == 0 && !in_brackets => alternatives.push(String::new()),
+            _ => {
+                match c {
+                    '[' => in_brackets = true,
+                    ']' => in_brackets = false,
+                    '{' if !in_brackets => braces += 1,
+                    '}' if !in_brackets => braces = braces.saturating_sub(1),
+                    _ => {}
+                }
+                current.push(c);
+            }
+        }
+    }
+
+    alternatives
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::enhancers::Enhancements;
+
+    #[test]
+    fn splits_alternatives() {
+        assert_eq!(split_alternatives("foo"), ["foo"]);
+        assert_eq!(
+            split_alternatives("malloc,calloc,"),
+            ["malloc", "calloc", ""]
+        );
+        assert_eq!(split_alternatives(r"a\,b,c"), ["a,b", "c"]);
+        assert_eq!(
+            split_alternatives(r"a\*b,{c,d}e,[,]"),
+            [r"a\*b", "{c,d}e", "[,]"]
+        );
+
+        let mut cache = RegexCache::new(0);
+        let regex = cache
+            .get_or_try_insert_alternatives(r"*malloc,calloc,std::map<a\,b>", false)
+            .unwrap();
+        for matching in ["je_malloc", "calloc", "std::map<a,b>"] {
+            assert!(regex.is_match(matching.as_bytes()), "{matching}");
+        }
+        for non_matching in ["realloc", "calloc2", "std::map<a", "b>"] {
+            assert!(!regex.is_match(non_matching.as_bytes()), "{non_matching}");
+        }
+    }
+
+    #[test]
+    fn matches_literals_like_regexes() {
+        let patterns = [
+            "memset",
+            "std::*",
+            "*::new",
+            "*alloc*",
+            "*",
+            "**",
+            "",
+            "=a*b",
+            "=a*b*",
+            "=[x",
+            "malloc,calloc",
+            "a*b",
+            "foo?",
+            "[ab]",
+            "{a,b}",
+            r"a\*",
+            "ü*",
+        ];
+        let haystacks = [
+            "",
+            "memset",
+            "memset2",
+            "std::",
+            "std::vec::Vec",
+            "xstd::",
+            "Vec::new",
+            "alloc",
+            "je_malloc_x",
+            "calloc",
+            "a*b",
+            "a*bc",
+            "a\nb",
+            "std::\n",
+            "\n",
+            "[x",
+            "üa",
+            "foo1",
+        ];
+
+        for pattern in patterns {
+            for alternatives in [false, true] {
+                let key = (pattern.into(), false, alternatives);
+                let Some(literals) = LiteralMatch::for_key(&key) else {
+                    continue;
+                };
+                let regex = translate_alternatives(pattern, false, alternatives).unwrap();
+                for haystack in haystacks {
+                    let haystack = haystack.as_bytes();
+                    assert_eq!(
+                        literals.iter().any(|literal| literal.is_match(haystack)),
+                        regex.is_match(haystack),
+                        "`{pattern}` on {:?}",
+                        String::from_utf8_lossy(haystack),
+                    );
+                }
+            }
+        }
+
+        assert!(LiteralMatch::for_key(&("a*b".into(), false, false)).is_none());
+        assert!(LiteralMatch::for_key(&("std::*".into(), true, false)).is_none());
+    }
+
+    #[test]
+    fn compiles_regexes_lazily() {
+        let mut cache = RegexCache::new(10).with_lazy_regexes(true);
+        let regex = cache.get_or_try_insert_lazy("f?o*", false, false).unwrap();
+        let shared = cache.get_or_try_insert_lazy("f?o*", false, false).unwrap();
+        assert!(Arc::ptr_eq(&regex, &shared));
+        assert!(regex.regex.get().is_none());
+
+        assert!(shared.is_match(b"foobar"));
+        assert!(regex.regex.get().is_some());
+        let compiled = cache.get_or_try_insert("f?o*", false).unwrap();
+        assert!(Arc::ptr_eq(&compiled, regex.get().unwrap()));
+
+        // Literal patterns are matched without compiling their regex at all.
+        let literal = cache.get_or_try_insert_lazy("foo*", false, false).unwrap();
+        assert!(literal.is_match(b"foobar"));
+        assert!(literal.regex.get().is_none());
+
+        let invalid = cache.get_or_try_insert_lazy("a[", false, false).unwrap();
+        assert!(!invalid.is_match(b"a["));
+        assert!(cache.get_or_try_insert("a[", false).is_err());
+
+        let mut cache = Cache::new(10).with_lazy_regexes(true);
+        let rule = cache.get_or_try_insert_rule("function:foo* -app").unwrap();
+        let frames = [crate::enhancers::Frame {
+            function: Some("foobar".into()),
+            ..Default::default()
+        }];
+        assert!(rule.matches_frame(&frames, 0));
+    }
+
+    #[test]
+    fn persists_rules() {
+        let path = std::env::temp_dir().join(format!("ophio-cache-{}", std::process::id()));
+        let input = "function:foo -app\n\
+                     [ family:native ] | path:**/bar.c +group\n\
+                     has_vars:yes -vars";
+
+        let mut cache = Cache::new(10);
+        let enhancements = Enhancements::parse(input, &mut cache).unwrap();
+        assert_eq!(cache.cached_rules(), 3);
+        assert_eq!(cache.cached_regexes(), 2);
+        assert_eq!(cache.save_to(&path).unwrap(), 2);
+
+        let mut loaded = Cache::new(10);
+        assert_eq!(loaded.load_from(&path).unwrap(), 2);
+        assert_eq!(loaded.load_from(&path).unwrap(), 0);
+        assert_eq!(loaded.cached_rules(), 2);
+        let rules = loaded.rules.0.as_ref().unwrap();
+        assert!(rules.contains("function:foo -app"));
+        assert!(!rules.contains("has_vars:yes -vars"));
+
+        let reparsed = Enhancements::parse(input, &mut loaded).unwrap();
+        assert!(enhancements.rules().eq(reparsed.rules()));
+
+        let outdated = PersistedCache {
+            version: "0.0.0+format.0".into(),
+            rules: vec![],
+        };
+        std::fs::write(&path, rmp_serde::to_vec(&outdated).unwrap()).unwrap();
+        assert_eq!(Cache::new(10).load_from(&path).unwrap(), 0);
+
+        std::fs::remove_file(&path).unwrap();
+        assert!(matches!(Cache::new(10).load_from(&path), Err(Error::Io(_))));
+    }
 }
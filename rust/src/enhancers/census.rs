@@ -0,0 +1,145 @@
+//! Statistics about the matchers and glob syntax used by enhancements.
+//!
+//! Before the semantics of a matcher or of some glob syntax can be changed, it is worth knowing
+//! how many configs rely on it. [`Enhancements::pattern_census`] counts this for one config, so
+//! that the counts can be collected across configs or used to warn their authors.
+
+use std::collections::BTreeMap;
+
+use serde::Serialize;
+
+use super::cache::glob_features;
+use super::{Enhancements, MatcherOffset};
+
+/// A glob feature a pattern can use.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum GlobFeature {
+    /// `*`, which matches anything except path separators in path patterns.
+    Wildcard,
+    /// `**`, which matches anything, including path separators.
+    RecursiveWildcard,
+    /// `?`, which matches a single character.
+    SingleCharacter,
+    /// A character class like `[a-z]`.
+    CharacterClass,
+    /// Brace expansion like `{a,b}`.
+    BraceExpansion,
+    /// Comma-separated alternatives in `function` patterns, like `malloc,calloc`.
+    Alternatives,
+    /// A backslash escape like `\*` in a pattern other than a path pattern.
+    Escape,
+    /// A literal pattern, starting with `=`.
+    Literal,
+}
+
+impl GlobFeature {
+    /// Returns the name of this feature, as it is serialized.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            GlobFeature::Wildcard => "wildcard",
+            GlobFeature::RecursiveWildcard => "recursive_wildcard",
+            GlobFeature::SingleCharacter => "single_character",
+            GlobFeature::CharacterClass => "character_class",
+            GlobFeature::BraceExpansion => "brace_expansion",
+            GlobFeature::Alternatives => "alternatives",
+            GlobFeature::Escape => "escape",
+            GlobFeature::Literal => "literal",
+        }
+    }
+}
+
+/// A summary of the matchers and glob features used by [`Enhancements`].
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct PatternCensus {
+    /// The number of rules.
+    pub rules: usize,
+    /// The number of matchers with every canonical key, e.g. `function` or `type`.
+    pub matchers: BTreeMap<String, usize>,
+    /// The number of matchers negated with `!`.
+    pub negated_matchers: usize,
+    /// The number of matchers checking the caller frame.
+    pub caller_matchers: usize,
+    /// The number of matchers checking the callee frame.
+    pub callee_matchers: usize,
+    /// The number of patterns using every glob feature. Patterns using a feature several
+    /// times count once.
+    pub glob_features: BTreeMap<GlobFeature, usize>,
+}
+
+impl Enhancements {
+    /// Counts the matchers and glob features used by the rules of this collection.
+    pub fn pattern_census(&self) -> PatternCensus {
+        let mut census = PatternCensus::default();
+        for rule in self.rules() {
+            census.rules += 1;
+            for matcher in rule.describe().matchers {
+                *census.matchers.entry(matcher.key).or_default() += 1;
+                census.negated_matchers += usize::from(matcher.negated);
+                match matcher.offset {
+                    Some(MatcherOffset::Caller) => census.caller_matchers += 1,
+                    Some(MatcherOffset::Callee) => census.callee_matchers += 1,
+                    None => {}
+                }
+            }
+            for (pattern, is_path, alternatives) in rule.globs() {
+                for feature in glob_features(pattern, is_path, alternatives) {
+                    *census.glob_features.entry(feature).or_default() += 1;
+                }
+            }
+        }
+        census
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::enhancers::Cache;
+
+    #[test]
+    fn counts_patterns() {
+        let enhancements = Enhancements::parse(
+            "path:**/vendor/** function:malloc,calloc -app\n\
+             [ !module:std::* ] | function:=operator[] +app\n\
+             error.type:Panic family:native function:\\*sym? category=x\n\
+             function:{foo,bar}[0-9] ^-group",
+            &mut Cache::default(),
+        )
+        .unwrap();
+        let census = enhancements.pattern_census();
+
+        let matchers = [
+            ("family", 1),
+            ("function", 4),
+            ("module", 1),
+            ("path", 1),
+            ("type", 1),
+        ];
+        let glob_features = [
+            (GlobFeature::Wildcard, 1),
+            (GlobFeature::RecursiveWildcard, 1),
+            (GlobFeature::SingleCharacter, 1),
+            (GlobFeature::CharacterClass, 1),
+            (GlobFeature::BraceExpansion, 1),
+            (GlobFeature::Alternatives, 1),
+            (GlobFeature::Escape, 1),
+            (GlobFeature::Literal, 1),
+        ];
+        assert_eq!(
+            census,
+            PatternCensus {
+                rules: 4,
+                matchers: matchers.map(|(k, n)| (k.to_owned(), n)).into(),
+                negated_matchers: 1,
+                caller_matchers: 1,
+                callee_matchers: 0,
+                glob_features: glob_features.into(),
+            }
+        );
+        assert_eq!(
+            serde_json::to_value(&census).unwrap()["glob_features"]["recursive_wildcard"],
+            1
+        );
+    }
+}
@@ -0,0 +1,238 @@
+//! Transmitting the modifications of frames as compact change-sets.
+//!
+//! Rules can be applied in one service, which sends only the resulting changes to another
+//! service holding the same frames. The change-set is a msgpack array
+//! `[version, [frame_count, changes]]`, where every change is an array
+//! `[frame_index, field_name, new_value]`. Only the layout of the version is fixed across
+//! versions.
+//!
+//! Readers ignore changes of fields they don't know, so new fields can be added without
+//! breaking older readers. Changes that older readers can't safely ignore require bumping
+//! [`CHANGES_VERSION`], and change-sets with a newer version are rejected.
+
+use std::borrow::Cow;
+
+use anyhow::{bail, Context};
+use serde::de::IgnoredAny;
+use serde::{Deserialize, Serialize};
+
+use super::{Enhancements, ExceptionData, FrameField, FrameLikeMut, StringField};
+use crate::{Error, Result};
+
+/// The version of the change-set format written by [`Enhancements::apply_and_encode_changes`].
+const CHANGES_VERSION: u32 = 1;
+
+/// The new value of a frame field, as it is encoded.
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(untagged)]
+enum EncodedValue<'a> {
+    Flag(bool),
+    #[serde(borrow)]
+    Str(Cow<'a, str>),
+    /// A value of a type that no known field has, which is only valid for unknown fields.
+    #[serde(skip_serializing)]
+    Other(IgnoredAny),
+}
+
+/// A change of the field `.1` of the frame at index `.0` to the value `.2`, where `None`
+/// unsets the field.
+type EncodedChange<'a> = (usize, Cow<'a, str>, Option<EncodedValue<'a>>);
+
+impl Enhancements {
+    /// Applies the modifier rules to `frames` like
+    /// [`apply_modifications_to_frames`](Self::apply_modifications_to_frames) and encodes the
+    /// resulting changes.
+    ///
+    /// Only the fields that differ after applying the rules are encoded. The result can be
+    /// applied to a copy of the original frames with [`apply_encoded_changes`].
+    pub fn apply_and_encode_changes<F: FrameLikeMut>(
+        &self,
+        frames: &mut [F],
+        exception_data: &ExceptionData,
+    ) -> Vec<u8> {
+        let original: Vec<_> = frames
+            .iter()
+            .map(|f| {
+                let category = f.get_field(FrameField::Category).map(StringField::new);
+                (f.in_app(), category, f.has_vars())
+            })
+            .collect();
+
+        self.apply_modifications_to_frames(frames, exception_data);
+
+        let mut changes: Vec<EncodedChange> = Vec::new();
+        for (idx, (frame, (in_app, category, has_vars))) in frames.iter().zip(original).enumerate()
+        {
+            if frame.in_app() != in_app {
+                let value = frame.in_app().map(EncodedValue::Flag);
+                changes.push((idx, "in_app".into(), value));
+            }
+            let new_category = frame.get_field(FrameField::Category);
+            if new_category != category.as_deref() {
+                let value = new_category.map(|c| EncodedValue::Str(c.into()));
+                changes.push((idx, "category".into(), value));
+            }
+            if frame.has_vars() != has_vars {
+                let value = frame.has_vars().map(EncodedValue::Flag);
+                changes.push((idx, "has_vars".into(), value));
+            }
+        }
+
+        // Encoding integers, strings and booleans into a `Vec` can't fail.
+        rmp_serde::to_vec(&(CHANGES_VERSION, (frames.len(), changes)))
+            .expect("change-sets are always encodable")
+    }
+}
+
+/// Applies the changes encoded by [`Enhancements::apply_and_encode_changes`] to `frames`.
+///
+/// `frames` must be the frames the changes were encoded for, before the rules were applied.
+/// Changes of unknown fields are ignored. Returns an error without modifying any frames if the
+/// change-set is malformed, has a newer version, or was encoded for a different number of
+/// frames.
+pub fn apply_encoded_changes<F: FrameLikeMut>(frames: &mut [F], changes: &[u8]) -> Result<()> {
+    decode_changes(frames, changes)
+        .context("invalid change-set")
+        .map_err(Error::Changes)
+}
+
+fn decode_changes<F: FrameLikeMut>(frames: &mut [F], input: &[u8]) -> anyhow::Result<()> {
+    let (version, _): (u32, IgnoredAny) = rmp_serde::from_slice(input)?;
+    if version > CHANGES_VERSION {
+        bail!("unsupported version {version}");
+    }
+
+    let (_, (frame_count, changes)): (u32, (usize, Vec<EncodedChange>)) =
+        rmp_serde::from_slice(input)?;
+    if frame_count != frames.len() {
+        bail!(
+            "encoded for {frame_count} frames, but applied to {}",
+            frames.len()
+        );
+    }
+
+    let mut valid = Vec::with_capacity(changes.len());
+    for (idx, field, value) in changes {
+        if idx >= frames.len() {
+            bail!("frame index {idx} out of bounds");
+        }
+        let change = match (&*field, value) {
+            ("in_app", None) => Change::InApp(None),
+            ("in_app", Some(EncodedValue::Flag(in_app))) => Change::InApp(Some(in_app)),
+            ("category", None) => Change::Category(None),
+            ("category", Some(EncodedValue::Str(category))) => {
+                Change::Category(Some(StringField::new(category)))
+            }
+            ("has_vars", None) => Change::HasVars(None),
+            ("has_vars", Some(EncodedValue::Flag(has_vars))) => Change::HasVars(Some(has_vars)),
+            ("in_app" | "category" | "has_vars", Some(_)) => {
+                bail!("invalid value for field `{field}`")
+            }
+            _ => continue,
+        };
+        valid.push((idx, change));
+    }
+
+    for (idx, change) in valid {
+        change.apply(&mut frames[idx]);
+    }
+
+    Ok(())
+}
+
+/// A decoded change of a known frame field.
+#[derive(Debug, Clone)]
+pub(crate) enum Change {
+    InApp(Option<bool>),
+    Category(Option<StringField>),
+    HasVars(Option<bool>),
+}
+
+impl Change {
+    /// Sets the changed field of `frame`.
+    pub(crate) fn apply<F: FrameLikeMut>(self, frame: &mut F) {
+        match self {
+            Change::InApp(in_app) => frame.set_in_app(in_app),
+            Change::Category(category) => frame.set_category(category),
+            Change::HasVars(has_vars) => frame.set_has_vars(has_vars),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+    use crate::enhancers::{Cache, Frame};
+
+    fn frames() -> Vec<Frame> {
+        vec![
+            Frame::from_test(&json!({"function": "foo"}), "native"),
+            Frame::from_test(&json!({"function": "bar", "in_app": true}), "native"),
+        ]
+    }
+
+    #[test]
+    fn applies_encoded_changes() {
+        let enhancements = Enhancements::parse(
+            "function:foo +app category=main\nfunction:bar -app",
+            &mut Cache::default(),
+        )
+        .unwrap();
+
+        let mut modified = frames();
+        let changes = enhancements.apply_and_encode_changes(&mut modified, &Default::default());
+
+        let mut frames = frames();
+        apply_encoded_changes(&mut frames, &changes).unwrap();
+        for (frame, modified) in frames.iter().zip(&modified) {
+            assert_eq!(frame.in_app, modified.in_app);
+            assert_eq!(frame.category, modified.category);
+        }
+        assert_eq!(frames[0].in_app, Some(true));
+        assert_eq!(frames[0].category.as_deref(), Some("main"));
+        assert_eq!(frames[1].in_app, Some(false));
+
+        let err = apply_encoded_changes(&mut frames[..1], &changes).unwrap_err();
+        assert_eq!(
+            format!("{:#}", anyhow::Error::from(err)),
+            "invalid change-set: encoded for 2 frames, but applied to 1"
+        );
+    }
+
+    #[test]
+    fn is_forward_compatible() {
+        // A change-set from a newer writer of the same version, with a change of an unknown
+        // field holding a value of an unknown type.
+        let changes = (
+            (0, "in_app", true),
+            (1, "frame_score", [1, 2, 3]),
+            (1, "category", "ui"),
+        );
+        let changes = rmp_serde::to_vec(&(CHANGES_VERSION, (2, changes))).unwrap();
+
+        let mut frames = frames();
+        apply_encoded_changes(&mut frames, &changes).unwrap();
+        assert_eq!(frames[0].in_app, Some(true));
+        assert_eq!(frames[1].category.as_deref(), Some("ui"));
+
+        // Newer versions may change everything but the version, and are rejected without
+        // modifying the frames.
+        let changes = rmp_serde::to_vec(&(CHANGES_VERSION + 1, "in_app")).unwrap();
+        let err = apply_encoded_changes(&mut frames, &changes).unwrap_err();
+        assert_eq!(
+            format!("{:#}", anyhow::Error::from(err)),
+            "invalid change-set: unsupported version 2"
+        );
+
+        let changes = ((0, "in_app", false), (1, "in_app", "yes"));
+        let changes = rmp_serde::to_vec(&(CHANGES_VERSION, (2, changes))).unwrap();
+        let err = apply_encoded_changes(&mut frames, &changes).unwrap_err();
+        assert_eq!(
+            format!("{:#}", anyhow::Error::from(err)),
+            "invalid change-set: invalid value for field `in_app`"
+        );
+        assert_eq!(frames[0].in_app, Some(true));
+    }
+}
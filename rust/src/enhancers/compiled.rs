@@ -0,0 +1,152 @@
+//! Export of enhancements together with their translated regexes.
+//!
+//! Translating glob patterns to regexes is a large part of the cost of parsing enhancements.
+//! The compiled form stores the regex source of every pattern next to the rules, so another
+//! process can load the rules without translating any patterns again.
+//!
+//! This module is only available with the `compiled` feature.
+
+use std::borrow::Cow;
+
+use anyhow::Context;
+use serde::{Deserialize, Serialize};
+
+use super::{Cache, Enhancements};
+use crate::{Error, Result};
+
+/// The version of the compiled format.
+///
+/// Regex sources are only valid for the version of this crate that translated them, so loading
+/// enhancements compiled by a different version fails.
+const COMPILED_VERSION: &str = concat!(env!("CARGO_PKG_VERSION"), "+compiled.1");
+
+/// The contents written by [`Enhancements::to_compiled`].
+#[derive(Debug, Deserialize, Serialize)]
+struct CompiledEnhancements<'a> {
+    #[serde(borrow)]
+    version: Cow<'a, str>,
+    /// The text of every rule, in order.
+    #[serde(borrow)]
+    rules: Vec<Cow<'a, str>>,
+    /// The pattern, whether it is a path pattern, whether it consists of alternatives, and
+    /// the translated regex source of every regex used by the rules.
+    #[serde(borrow)]
+    regexes: Vec<(Cow<'a, str>, bool, bool, Cow<'a, str>)>,
+}
+
+impl Enhancements {
+    /// Exports these enhancements in msgpack, together with the translated regexes of their
+    /// matchers.
+    ///
+    /// The result can be loaded with [`from_compiled`](Self::from_compiled) by the same version
+    /// of this crate, which skips translating glob patterns. Rules are stored as text, so options
+    /// the rules were parsed with that don't show in their text are not preserved.
+    pub fn to_compiled(&self) -> Result<Vec<u8>> {
+        let mut regexes = Vec::new();
+        for rule in self.rules() {
+            for matcher in &rule.0.frame_matchers {
+                if let Some(((pattern, is_path, alternatives), source)) = matcher.translated_regex()
+                {
+                    regexes.push((
+                        pattern.as_str().into(),
+                        *is_path,
+                        *alternatives,
+                        source.into(),
+                    ));
+                }
+            }
+            for matcher in &rule.0.exception_matchers {
+                let (pattern, source) = matcher.translated_regex();
+                regexes.push((pattern.into(), false, false, source.into()));
+            }
+        }
+        regexes.sort_unstable();
+        regexes.dedup();
+
+        let compiled = CompiledEnhancements {
+            version: COMPILED_VERSION.into(),
+            rules: self
+                .rules()
+                .map(|rule| format!("{rule:#}").into())
+                .collect(),
+            regexes,
+        };
+        rmp_serde::to_vec(&compiled)
+            .context("failed to encode compiled enhancements")
+            .map_err(Error::ConfigStructure)
+    }
+
+    /// Loads enhancements exported by [`to_compiled`](Self::to_compiled).
+    ///
+    /// The regexes are compiled from their stored sources instead of being translated from
+    /// glob patterns again. This fails if the enhancements were exported by a different version
+    /// of this crate.
+    pub fn from_compiled(input: &[u8]) -> Result<Self> {
+        let compiled: CompiledEnhancements = rmp_serde::from_slice(input)
+            .context("failed to decode compiled enhancements")
+            .map_err(Error::ConfigStructure)?;
+        if compiled.version != COMPILED_VERSION {
+            return Err(Error::ConfigStructure(anyhow::anyhow!(
+                "compiled enhancements have version `{}`, expected `{COMPILED_VERSION}`",
+                compiled.version
+            )));
+        }
+
+        // A cache that can hold every regex, so parsing the rules finds all of them.
+        let mut cache = Cache::new(compiled.regexes.len().max(compiled.rules.len()));
+        for (pattern, is_path, alternatives, source) in compiled.regexes {
+            cache
+                .regex
+                .insert_translated((pattern.into(), is_path, alternatives), &source)?;
+        }
+
+        let rules = compiled
+            .rules
+            .iter()
+            .map(|rule| cache.get_or_try_insert_rule(rule))
+            .collect::<Result<_>>()?;
+        Ok(Enhancements::new(rules))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+    use crate::enhancers::{ExceptionData, Frame};
+
+    #[test]
+    fn compiled_roundtrip() {
+        let enhancements = Enhancements::parse(
+            "path:**/vendor/** -app\n\
+             function:foo,bar +app\n\
+             error.type:ValueError family:native category=x\n\
+             function:[invalid -app",
+            &mut Cache::default(),
+        )
+        .unwrap();
+
+        let compiled = enhancements.to_compiled().unwrap();
+        let loaded = Enhancements::from_compiled(&compiled).unwrap();
+        assert_eq!(loaded.to_json(), enhancements.to_json());
+        assert_eq!(loaded.to_compiled().unwrap(), compiled);
+
+        // Patterns aren't translated again, so a changed regex source takes effect.
+        let mut changed: CompiledEnhancements = rmp_serde::from_slice(&compiled).unwrap();
+        for (pattern, _, _, source) in &mut changed.regexes {
+            if pattern == "foo,bar" {
+                *source = "^baz$".into();
+            }
+        }
+        let changed = Enhancements::from_compiled(&rmp_serde::to_vec(&changed).unwrap()).unwrap();
+        let mut frames = vec![Frame::from_test(&json!({"function": "baz"}), "native")];
+        changed.apply_modifications_to_frames(&mut frames, &ExceptionData::default());
+        assert_eq!(frames[0].in_app, Some(true));
+
+        let mut outdated: CompiledEnhancements = rmp_serde::from_slice(&compiled).unwrap();
+        outdated.version = "0.0.0+compiled.0".into();
+        let outdated = rmp_serde::to_vec(&outdated).unwrap();
+        assert!(Enhancements::from_compiled(&outdated).is_err());
+    }
+}
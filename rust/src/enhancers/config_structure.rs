@@ -1,66 +1,308 @@
-//! Definition of the compact msgpack format for enhancements, and methods for deserializing it.
+//! Definition of the compact msgpack format for enhancements, and methods for serializing
+//! and deserializing it.
+
+use std::borrow::Cow;
+use std::fmt;
+use std::num::NonZeroUsize;
 
 use anyhow::Context;
-use serde::Deserialize;
+use serde::de::{self, DeserializeSeed, SeqAccess, Visitor};
+use serde::{Deserialize, Deserializer, Serialize};
 use smol_str::SmolStr;
 
 use super::actions::{Action, FlagAction, FlagActionType, Range, VarAction};
+use super::cache::LITERAL_PREFIX;
+use super::description::{ActionDescription, MatcherDescription, MatcherOffset, VarValue};
 use super::matchers::{FrameOffset, Matcher};
+use super::rules::Rule;
 use super::RegexCache;
 
+/// Which implementations have to be able to decode an encoding.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub enum Encoding {
+    /// The encoding Sentry's Python implementation decodes.
+    ///
+    /// Rules using extensions of this crate can't be encoded: `trust` and `thread.*` matchers,
+    /// `app:unset`, literal patterns starting with `=`, `~` sibling ranges, and `priority`
+    /// actions.
+    #[default]
+    Sentry,
+    /// The encoding extended with keys and codes for the extensions of this crate, which only
+    /// this crate decodes.
+    Extended,
+}
+
 /// Compact representation of an [`Enhancements`](super::Enhancements) structure.
 ///
-/// Can be deserialized from msgpack.
-#[derive(Debug, Deserialize)]
+/// Can be serialized to and deserialized from msgpack. It is decoded with
+/// [`decode_enhancements`] though, so this is only constructed to encode enhancements.
+#[derive(Debug, Deserialize, Serialize)]
 pub struct EncodedEnhancements<'a>(
     pub usize,
     pub Vec<SmolStr>,
     #[serde(borrow)] pub Vec<EncodedRule<'a>>,
 );
 
-/// Compact representation of a [`Rule`](super::rules::Rule).
+/// Decodes the msgpack representation of an [`Enhancements`](super::Enhancements) structure
+/// into the names of its bases and its rules.
+///
+/// Unlike deserializing an [`EncodedEnhancements`], this hands every rule to `convert` as soon
+/// as it is decoded, with its matchers borrowed from `input`. So the encoded rules are never all
+/// in memory at the same time, which keeps the peak memory of decoding large configs low.
+pub(crate) fn decode_enhancements(
+    input: &[u8],
+    mut convert: impl FnMut(EncodedRule<'_>) -> anyhow::Result<Rule>,
+) -> anyhow::Result<(Vec<SmolStr>, Vec<Rule>)> {
+    let mut error = None;
+    let seed = EnhancementsSeed {
+        convert: &mut convert,
+        error: &mut error,
+    };
+    let mut deserializer = rmp_serde::Deserializer::from_read_ref(input);
+    let result = seed.deserialize(&mut deserializer);
+    // Errors of `convert` are smuggled past the deserializer, which only keeps their message.
+    match error {
+        Some(error) => Err(error),
+        None => Ok(result?),
+    }
+}
+
+/// Deserializes an [`EncodedEnhancements`], converting its rules one at a time.
+struct EnhancementsSeed<'a, F> {
+    convert: &'a mut F,
+    error: &'a mut Option<anyhow::Error>,
+}
+
+impl<'de, F> DeserializeSeed<'de> for EnhancementsSeed<'_, F>
+where
+    F: FnMut(EncodedRule<'de>) -> anyhow::Result<Rule>,
+{
+    type Value = (Vec<SmolStr>, Vec<Rule>);
+
+    fn deserialize<D: Deserializer<'de>>(self, deserializer: D) -> Result<Self::Value, D::Error> {
+        deserializer.deserialize_tuple(3, self)
+    }
+}
+
+impl<'de, F> Visitor<'de> for EnhancementsSeed<'_, F>
+where
+    F: FnMut(EncodedRule<'de>) -> anyhow::Result<Rule>,
+{
+    type Value = (Vec<SmolStr>, Vec<Rule>);
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter.write_str("an encoded enhancements structure")
+    }
+
+    fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<Self::Value, A::Error> {
+        let version: usize = seq
+            .next_element()?
+            .ok_or_else(|| de::Error::invalid_length(0, &self))?;
+        if version != 2 {
+            let error =
+                anyhow::anyhow!("Rust Enhancements only supports config_structure version `2`");
+            let message = error.to_string();
+            *self.error = Some(error);
+            return Err(de::Error::custom(message));
+        }
+        let bases: Vec<SmolStr> = seq
+            .next_element()?
+            .ok_or_else(|| de::Error::invalid_length(1, &self))?;
+
+        let rules = RulesSeed {
+            convert: &mut *self.convert,
+            error: &mut *self.error,
+        };
+        let Some(rules) = seq.next_element_seed(rules)? else {
+            return Err(de::Error::invalid_length(2, &self));
+        };
+        Ok((bases, rules))
+    }
+}
+
+/// Deserializes a sequence of [`EncodedRule`]s, converting every rule right away.
+struct RulesSeed<'a, F> {
+    convert: &'a mut F,
+    error: &'a mut Option<anyhow::Error>,
+}
+
+impl<'de, F> DeserializeSeed<'de> for RulesSeed<'_, F>
+where
+    F: FnMut(EncodedRule<'de>) -> anyhow::Result<Rule>,
+{
+    type Value = Vec<Rule>;
+
+    fn deserialize<D: Deserializer<'de>>(self, deserializer: D) -> Result<Self::Value, D::Error> {
+        deserializer.deserialize_seq(self)
+    }
+}
+
+impl<'de, F> Visitor<'de> for RulesSeed<'_, F>
+where
+    F: FnMut(EncodedRule<'de>) -> anyhow::Result<Rule>,
+{
+    type Value = Vec<Rule>;
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter.write_str("a sequence of encoded rules")
+    }
+
+    fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<Self::Value, A::Error> {
+        let mut rules = Vec::with_capacity(seq.size_hint().unwrap_or_default());
+        while let Some(encoded) = seq.next_element::<EncodedRule<'de>>()? {
+            match (self.convert)(encoded) {
+                Ok(rule) => rules.push(rule),
+                Err(error) => {
+                    let message = error.to_string();
+                    *self.error = Some(error);
+                    return Err(de::Error::custom(message));
+                }
+            }
+        }
+        Ok(rules)
+    }
+}
+
+/// Compact representation of a [`Rule`].
 ///
-/// Can be deserialized from msgpack.
-#[derive(Debug, Deserialize)]
+/// Can be serialized to and deserialized from msgpack.
+#[derive(Debug, Deserialize, Serialize)]
 pub struct EncodedRule<'a>(
     #[serde(borrow)] pub Vec<EncodedMatcher<'a>>,
     #[serde(borrow)] pub Vec<EncodedAction<'a>>,
 );
 
+impl EncodedRule<'static> {
+    /// Encodes a [`Rule`] the same way Sentry's Python implementation does.
+    ///
+    /// Matchers are encoded caller matchers first and callee matchers last. This fails for
+    /// `has_vars` matchers, `vars` actions, and `family` matchers with families other than
+    /// `native`, `javascript`, and `all`, which the encoding can't represent. With
+    /// [`Encoding::Sentry`], it also fails for the extensions of this crate.
+    pub fn from_rule(rule: &Rule, encoding: Encoding) -> anyhow::Result<Self> {
+        let description = rule.describe();
+        let matchers = description
+            .matchers
+            .iter()
+            .map(|matcher| EncodedMatcher::from_description(matcher, encoding))
+            .collect::<anyhow::Result<_>>()
+            .with_context(|| format!("at `{rule}`: unable to encode rule"))?;
+        let actions = description
+            .actions
+            .into_iter()
+            .map(|action| EncodedAction::from_description(action, encoding))
+            .collect::<anyhow::Result<_>>()
+            .with_context(|| format!("at `{rule}`: unable to encode rule"))?;
+
+        Ok(Self(matchers, actions))
+    }
+}
+
+impl EncodedRule<'_> {
+    /// Decodes this rule, using `regex_cache` to memoize the construction of regexes.
+    pub fn into_rule(self, regex_cache: &mut RegexCache) -> anyhow::Result<Rule> {
+        let matchers = self
+            .0
+            .into_iter()
+            .map(|encoded| encoded.into_matcher(regex_cache))
+            .collect::<anyhow::Result<_>>()?;
+        let actions = self
+            .1
+            .into_iter()
+            .map(EncodedAction::into_action)
+            .collect::<anyhow::Result<_>>()?;
+
+        Ok(Rule::new(matchers, actions))
+    }
+}
+
 /// Compact representation of a [`Matcher`].
 ///
-/// Can be deserialized from msgpack.
-#[derive(Debug, Deserialize)]
-pub struct EncodedMatcher<'a>(pub &'a str);
+/// Can be serialized to and deserialized from msgpack.
+#[derive(Debug, Deserialize, Serialize)]
+pub struct EncodedMatcher<'a>(#[serde(borrow)] pub Cow<'a, str>);
+
+impl EncodedMatcher<'_> {
+    /// Encodes the matcher described by `description`.
+    fn from_description(
+        description: &MatcherDescription,
+        encoding: Encoding,
+    ) -> anyhow::Result<Self> {
+        let key = match description.key.as_str() {
+            "path" => 'p',
+            "function" => 'f',
+            "module" => 'm',
+            "family" => 'F',
+            "package" => 'P',
+            "app" => 'a',
+            "type" => 't',
+            "value" => 'v',
+            "mechanism" => 'M',
+            "category" => 'c',
+            // Extensions of this crate, unknown to Sentry.
+            "trust" if encoding == Encoding::Extended => 'T',
+            "thread.name" if encoding == Encoding::Extended => 'n',
+            "thread.id" if encoding == Encoding::Extended => 'i',
+            "thread.main" if encoding == Encoding::Extended => 'q',
+            "thread.crashed" if encoding == Encoding::Extended => 'x',
+            key => anyhow::bail!("unable to encode matcher `{key}`"),
+        };
+        if encoding == Encoding::Sentry {
+            let pattern = &description.pattern;
+            if key == 'a' && pattern == "unset" {
+                anyhow::bail!("unable to encode `app:unset`");
+            }
+            if "pfmPtvMc".contains(key) && pattern.starts_with(LITERAL_PREFIX) {
+                anyhow::bail!("unable to encode literal pattern `{pattern}`");
+            }
+        }
+
+        let mut encoded = String::new();
+        match description.offset {
+            Some(MatcherOffset::Caller) => encoded.push('['),
+            Some(MatcherOffset::Callee) => encoded.push_str("|["),
+            None => {}
+        }
+        if description.negated {
+            encoded.push('!');
+        }
+        encoded.push(key);
+        if key == 'F' {
+            for family in description.pattern.split(',') {
+                match family {
+                    "native" => encoded.push('N'),
+                    "javascript" => encoded.push('J'),
+                    "all" => encoded.push('a'),
+                    "" => {}
+                    family => anyhow::bail!("unable to encode family `{family}`"),
+                }
+            }
+        } else {
+            encoded.push_str(&description.pattern);
+        }
+        match description.offset {
+            Some(MatcherOffset::Caller) => encoded.push_str("]|"),
+            Some(MatcherOffset::Callee) => encoded.push(']'),
+            None => {}
+        }
+
+        Ok(Self(Cow::Owned(encoded)))
+    }
 
-impl<'a> EncodedMatcher<'a> {
     /// Converts the encoded matcher to a [`Matcher`].
     ///
     /// The `cache` is used to memoize the computation of regexes.
     pub fn into_matcher(self, regex_cache: &mut RegexCache) -> anyhow::Result<Matcher> {
-        let mut def = self.0;
-        let mut frame_offset = FrameOffset::None;
-
-        if def.starts_with("|[") && def.ends_with(']') {
-            frame_offset = FrameOffset::Callee;
-            def = &def[2..def.len() - 1];
-        } else if def.starts_with('[') && def.ends_with("]|") {
-            frame_offset = FrameOffset::Caller;
-            def = &def[1..def.len() - 2];
-        }
-
-        let (def, negated) = if let Some(def) = def.strip_prefix('!') {
-            (def, true)
-        } else {
-            (def, false)
-        };
+        let (negated, key, arg, frame_offset) = self
+            .split()
+            .with_context(|| format!("unable to parse encoded Matcher: `{}`", self.0))?;
 
         let mut families = String::new();
-        let (key, arg) = match def.split_at(1) {
-            ("p", arg) => ("path", arg),
-            ("f", arg) => ("function", arg),
-            ("m", arg) => ("module", arg),
-            ("F", arg) => {
+        let key = match key {
+            'p' => "path",
+            'f' => "function",
+            'm' => "module",
+            'F' => {
                 use std::fmt::Write;
                 for f in arg.chars() {
                     match f {
@@ -70,27 +312,71 @@ impl<'a> EncodedMatcher<'a> {
                         _ => {}
                     }
                 }
-                ("family", families.get(1..).unwrap_or_default())
-            }
-            ("P", arg) => ("package", arg),
-            ("a", arg) => ("app", arg),
-            ("t", arg) => ("type", arg),
-            ("v", arg) => ("value", arg),
-            ("M", arg) => ("mechanism", arg),
-            ("c", arg) => ("category", arg),
+                "family"
+            }
+            'P' => "package",
+            'a' => "app",
+            't' => "type",
+            'v' => "value",
+            'M' => "mechanism",
+            'c' => "category",
+            'T' => "trust",
+            'n' => "thread.name",
+            'i' => "thread.id",
+            'q' => "thread.main",
+            'x' => "thread.crashed",
             _ => {
                 anyhow::bail!("unable to parse encoded Matcher: `{}`", self.0)
             }
         };
+        let arg = if key == "family" {
+            families.get(1..).unwrap_or_default()
+        } else {
+            arg
+        };
 
         Matcher::new(negated, key, arg, frame_offset, regex_cache)
     }
+
+    /// Splits the encoded matcher into its negation, key, argument, and frame offset.
+    ///
+    /// The encoded grammar is `[ "[" | "|[" ] [ "!" ] <key> <argument> [ "]|" | "]" ]`,
+    /// where `<key>` is a single character and `<argument>` is an unescaped pattern.
+    /// Since the argument may itself contain `[`, `]`, and `|`, the closing delimiter is
+    /// determined by the opening one and always taken from the very end of the input.
+    fn split(&self) -> anyhow::Result<(bool, char, &str, FrameOffset)> {
+        let input = &*self.0;
+
+        let (frame_offset, rest, closing) = if let Some(rest) = input.strip_prefix("|[") {
+            (FrameOffset::Callee, rest, "]")
+        } else if let Some(rest) = input.strip_prefix('[') {
+            (FrameOffset::Caller, rest, "]|")
+        } else {
+            (FrameOffset::None, input, "")
+        };
+
+        let rest = rest
+            .strip_suffix(closing)
+            .ok_or_else(|| anyhow::anyhow!("expected closing `{closing}`"))?;
+
+        let (negated, rest) = match rest.strip_prefix('!') {
+            Some(rest) => (true, rest),
+            None => (false, rest),
+        };
+
+        let mut chars = rest.chars();
+        let key = chars
+            .next()
+            .ok_or_else(|| anyhow::anyhow!("expected matcher key"))?;
+
+        Ok((negated, key, chars.as_str(), frame_offset))
+    }
 }
 
 /// The RHS of a [`VarAction`].
 ///
 /// This wraps a `bool`, `usize`, or string according to the variable on the action's LHS.
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Deserialize, Serialize)]
 #[serde(untagged)]
 pub enum VarActionValue {
     Int(usize),
@@ -100,8 +386,8 @@ pub enum VarActionValue {
 
 /// Compact representation of an [`Action`].
 ///
-/// Can be deserialized from msgpack.
-#[derive(Debug, Deserialize)]
+/// Can be serialized to and deserialized from msgpack.
+#[derive(Debug, Deserialize, Serialize)]
 #[serde(untagged)]
 pub enum EncodedAction<'a> {
     /// A flag action.
@@ -118,16 +404,19 @@ pub enum EncodedAction<'a> {
     ///
     /// The bits `b10, b9, b8` encode the flag value and the range:
     ///
-    ///| b₁₀b₉b₈ |   flag  |  range |
-    ///| ------- | ------  | ------ |
-    ///|   000   |  `true` | `none` |
-    ///|   001   |  `true` |  `up`  |
-    ///|   010   |  `true` | `down` |
-    ///|   011   | `false` | `None` |
-    ///|   100   | `false` |  `up`  |
-    ///|   101   | `false` | `down` |
+    ///| b₁₀b₉b₈ |   flag  |    range   |
+    ///| ------- | ------  | ---------- |
+    ///|   000   |  `true` |   `none`   |
+    ///|   001   |  `true` |    `up`    |
+    ///|   010   |  `true` |   `down`   |
+    ///|   011   | `false` |   `None`   |
+    ///|   100   | `false` |    `up`    |
+    ///|   101   | `false` |   `down`   |
+    ///|   110   |  `true` | `siblings` |
+    ///|   111   | `false` | `siblings` |
     ///
-    /// All other bits are unused.
+    /// The `siblings` range is an extension of this crate, unknown to Sentry. All other bits
+    /// are unused.
     FlagAction(usize),
 
     /// A [`VarAction`], comprising the name of the variable
@@ -136,6 +425,64 @@ pub enum EncodedAction<'a> {
     VarAction((&'a str, VarActionValue)),
 }
 
+// NOTE: we only support version 2 encoding here
+/// The flag types of flag actions, indexed by their encoding.
+const FLAG_ACTION_TYPES: &[&str] = &["group", "app"];
+/// The values and ranges of flag actions, indexed by their encoding.
+const FLAG_ACTION_FLAGS: &[(bool, Option<&str>)] = &[
+    (true, None),
+    (true, Some("up")),
+    (true, Some("down")),
+    (false, None),
+    (false, Some("up")),
+    (false, Some("down")),
+    (true, Some("siblings")),
+    (false, Some("siblings")),
+];
+const ACTION_BITSIZE: usize = 8;
+const ACTION_MASK: usize = 0xF;
+
+impl EncodedAction<'static> {
+    /// Encodes the action described by `description`.
+    fn from_description(
+        description: ActionDescription,
+        encoding: Encoding,
+    ) -> anyhow::Result<Self> {
+        Ok(match description {
+            ActionDescription::Flag {
+                range: Some("siblings"),
+                ..
+            } if encoding == Encoding::Sentry => {
+                anyhow::bail!("unable to encode sibling range `~`")
+            }
+            ActionDescription::Var {
+                name: "priority", ..
+            } if encoding == Encoding::Sentry => {
+                anyhow::bail!("unable to encode variable `priority`")
+            }
+            ActionDescription::Flag { flag, value, range } => {
+                let ty = FLAG_ACTION_TYPES
+                    .iter()
+                    .position(|ty| *ty == flag)
+                    .with_context(|| format!("unable to encode flag `{flag}`"))?;
+                let flags = FLAG_ACTION_FLAGS
+                    .iter()
+                    .position(|flags| *flags == (value, range))
+                    .expect("all flag values and ranges are encodable");
+                EncodedAction::FlagAction(flags << ACTION_BITSIZE | ty)
+            }
+            ActionDescription::Var { name, value } => {
+                let value = match value {
+                    VarValue::Int(value) => VarActionValue::Int(value),
+                    VarValue::Bool(value) => VarActionValue::Bool(value),
+                    VarValue::Str(value) => VarActionValue::Str(value.into()),
+                };
+                EncodedAction::VarAction((name, value))
+            }
+        })
+    }
+}
+
 impl<'a> EncodedAction<'a> {
     /// Converts the encoded action to an [`Action`].
     pub fn into_action(self) -> anyhow::Result<Action> {
@@ -150,10 +497,9 @@ impl<'a> EncodedAction<'a> {
                     (false, None),
                     (false, Some(Range::Up)),
                     (false, Some(Range::Down)),
+                    (true, Some(Range::Siblings)),
+                    (false, Some(Range::Siblings)),
                 ];
-                // NOTE: we only support version 2 encoding here
-                const ACTION_BITSIZE: usize = 8;
-                const ACTION_MASK: usize = 0xF;
 
                 let ty = ACTIONS
                     .get(flag & ACTION_MASK)
@@ -166,18 +512,283 @@ impl<'a> EncodedAction<'a> {
                 Action::Flag(FlagAction { flag, ty, range })
             }
             EncodedAction::VarAction(("min-frames", Int(value))) => {
-                Action::Var(VarAction::MinFrames(value))
+                Action::Var(VarAction::MinFrames(NonZeroUsize::new(value)))
             }
             EncodedAction::VarAction(("max-frames", Int(value))) => {
-                Action::Var(VarAction::MaxFrames(value))
+                Action::Var(VarAction::MaxFrames(NonZeroUsize::new(value)))
             }
             EncodedAction::VarAction(("invert-stacktrace", Bool(value))) => {
                 Action::Var(VarAction::InvertStacktrace(value))
             }
             EncodedAction::VarAction(("category", Str(value))) => {
-                Action::Var(VarAction::Category(value.clone()))
+                Action::Var(VarAction::Category(value.as_str().into()))
+            }
+            EncodedAction::VarAction(("priority", Int(value))) => {
+                Action::Var(VarAction::Priority(value))
             }
             _ => anyhow::bail!("Failed to convert encoded Action: `{:?}`", self),
         })
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[track_caller]
+    fn assert_roundtrip(encoded: &str, expected: &str) {
+        let matcher = EncodedMatcher(encoded.into())
+            .into_matcher(&mut Default::default())
+            .unwrap();
+        assert_eq!(matcher.to_string(), expected, "{encoded}");
+    }
+
+    #[test]
+    fn encoded_matchers_roundtrip() {
+        let keys = [
+            ("p", "path"),
+            ("f", "function"),
+            ("m", "module"),
+            ("P", "package"),
+            ("c", "category"),
+            ("T", "trust"),
+        ];
+
+        for (key, name) in keys {
+            for pattern in ["foo", "*", "[foo]", "*]foo", "a|b", "]|"] {
+                assert_roundtrip(&format!("{key}{pattern}"), &format!("{name}:{pattern}"));
+                assert_roundtrip(&format!("!{key}{pattern}"), &format!("!{name}:{pattern}"));
+                assert_roundtrip(
+                    &format!("[{key}{pattern}]|"),
+                    &format!("[{name}:{pattern}] |"),
+                );
+                assert_roundtrip(
+                    &format!("[!{key}{pattern}]|"),
+                    &format!("[!{name}:{pattern}] |"),
+                );
+                assert_roundtrip(
+                    &format!("|[{key}{pattern}]"),
+                    &format!("| [{name}:{pattern}]"),
+                );
+                assert_roundtrip(
+                    &format!("|[!{key}{pattern}]"),
+                    &format!("| [!{name}:{pattern}]"),
+                );
+            }
+        }
+
+        assert_roundtrip("FNJ", "family:native,javascript");
+        assert_roundtrip("[!Fa]|", "[!family:all] |");
+        assert_roundtrip("a1", "app:1");
+        assert_roundtrip("|[!a0]", "| [!app:0]");
+        assert_roundtrip("aunset", "app:unset");
+        assert_roundtrip("tValueError", "type:ValueError");
+        assert_roundtrip("!vfoo]", "!value:foo]");
+        assert_roundtrip("Mhandled", "mechanism:handled");
+        assert_roundtrip(r"fmalloc,calloc,a\,b", r"function:malloc,calloc,a\,b");
+    }
+
+    #[test]
+    fn decodes_rules_one_at_a_time() {
+        // `[2, ["base"], [[["ffoo"], [769]], [["fbar"], [769]]]]`
+        let input = b"\x93\x02\x91\xa4base\x92\x92\x91\xa4ffoo\x91\xcd\x03\x01\x92\x91\xa4fbar\x91\xcd\x03\x01";
+
+        let mut borrowed = 0;
+        let (bases, rules) = decode_enhancements(input, |encoded| {
+            borrowed += encoded
+                .0
+                .iter()
+                .filter(|m| matches!(m.0, Cow::Borrowed(_)))
+                .count();
+            encoded.into_rule(&mut Default::default())
+        })
+        .unwrap();
+        assert_eq!(bases, ["base"]);
+        assert_eq!(rules.len(), 2);
+        assert_eq!(rules[1].to_string(), "function:bar -app");
+        assert_eq!(borrowed, 2);
+
+        // Errors of the conversion stop decoding and are returned as they are.
+        let mut decoded = 0;
+        let err = decode_enhancements(input, |_| {
+            decoded += 1;
+            anyhow::bail!("no rules")
+        })
+        .unwrap_err();
+        assert_eq!(err.to_string(), "no rules");
+        assert_eq!(decoded, 1);
+
+        let err = decode_enhancements(b"\x93\x03\x90\x90", |_| unreachable!()).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "Rust Enhancements only supports config_structure version `2`"
+        );
+        assert!(decode_enhancements(b"\x92\x02\x90", |_| unreachable!()).is_err());
+    }
+
+    #[test]
+    fn negated_caller_callee_matching() {
+        use serde_json::json;
+
+        use crate::enhancers::Frame;
+
+        let frames = &[
+            Frame::from_test(&json!({"function": "x]foo"}), "native"),
+            Frame::from_test(&json!({"function": "bar"}), "native"),
+            Frame::from_test(&json!({"function": "baz"}), "native"),
+        ];
+
+        let matches = |encoded: &str, idx| match EncodedMatcher(encoded.into())
+            .into_matcher(&mut Default::default())
+            .unwrap()
+        {
+            Matcher::Frame(m) => m.matches_frame(frames, idx, Default::default()),
+            Matcher::Exception(_) => unreachable!(),
+        };
+
+        assert!(matches("[f*]foo]|", 1));
+        assert!(!matches("[!f*]foo]|", 1));
+        assert!(matches("[!f*]foo]|", 2));
+        assert!(matches("|[fbaz]", 1));
+        assert!(!matches("|[!fbaz]", 1));
+        assert!(matches("|[!fbaz]", 0));
+    }
+
+    #[test]
+    fn invalid_encoded_matchers() {
+        for encoded in [
+            "", "!", "[]|", "|[]", "[!]|", "[ffoo", "[ffoo]", "|[ffoo", "xfoo", "éfoo",
+        ] {
+            assert!(
+                EncodedMatcher(encoded.into())
+                    .into_matcher(&mut Default::default())
+                    .is_err(),
+                "{encoded}"
+            );
+        }
+    }
+
+    /// Decodes the msgpack blob Sentry's Python encoder produced for each default config and
+    /// checks that re-encoding it with [`EncodedRule::from_rule`] yields the same rules.
+    #[cfg(feature = "default-configs")]
+    #[test]
+    fn reencodes_default_configs() {
+        use crate::enhancers::{Cache, Enhancements, DEFAULT_CONFIG_VERSIONS};
+
+        for version in DEFAULT_CONFIG_VERSIONS {
+            let path = std::path::PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+                .join("../tests/fixtures")
+                .join(format!("{}.bin", version.replace(':', "@")));
+            let encoded = std::fs::read(&path).unwrap();
+
+            let mut cache = Cache::default();
+            let decoded = Enhancements::from_config_structure(&encoded, &mut cache).unwrap();
+            let EncodedEnhancements(enc_version, bases, _) =
+                rmp_serde::from_slice(&encoded).unwrap();
+            let rules = decoded
+                .rules()
+                .map(|rule| EncodedRule::from_rule(rule, Encoding::Sentry).unwrap())
+                .collect();
+            let reencoded =
+                rmp_serde::to_vec(&EncodedEnhancements(enc_version, bases, rules)).unwrap();
+            assert_eq!(reencoded, encoded, "{version}");
+
+            let redecoded = Enhancements::from_config_structure(&reencoded, &mut cache).unwrap();
+            assert!(redecoded.rules().eq(decoded.rules()), "{version}");
+        }
+    }
+
+    #[test]
+    fn encodes_rules() {
+        use crate::enhancers::grammar::parse_rule;
+
+        let encode = |input: &str| {
+            let rule = parse_rule(input, &mut Default::default()).unwrap();
+            let EncodedRule(matchers, actions) =
+                EncodedRule::from_rule(&rule, Encoding::Sentry).unwrap();
+            let matchers: Vec<_> = matchers.into_iter().map(|m| m.0.into_owned()).collect();
+            (matchers, rmp_serde::to_vec(&actions).unwrap())
+        };
+
+        let (matchers, _) = encode(
+            "[ !function:foo ] | error.type:Error path:**/*.c family:native,javascript | [ module:bar ] +app",
+        );
+        assert_eq!(
+            matchers,
+            ["[!ffoo]|", "tError", "p**/*.c", "FNJ", "|[mbar]"]
+        );
+
+        let (_, actions) =
+            encode(r#"function:foo ^-group category=bar category="a b" max-frames=3"#);
+        let expected: Vec<EncodedAction> = vec![
+            EncodedAction::FlagAction(4 << ACTION_BITSIZE),
+            EncodedAction::VarAction(("category", VarActionValue::Str("bar".into()))),
+            EncodedAction::VarAction(("category", VarActionValue::Str("a b".into()))),
+            EncodedAction::VarAction(("max-frames", VarActionValue::Int(3))),
+        ];
+        assert_eq!(actions, rmp_serde::to_vec(&expected).unwrap());
+
+        let rule = parse_rule("family:python +app", &mut Default::default()).unwrap();
+        assert!(EncodedRule::from_rule(&rule, Encoding::Sentry).is_err());
+    }
+
+    #[test]
+    fn encodes_extensions_only_when_extended() {
+        use crate::enhancers::grammar::parse_rule;
+
+        let roundtrip = |rule: &Rule, encoding| {
+            let encoded = EncodedRule::from_rule(rule, encoding)?;
+            let encoded = rmp_serde::to_vec(&encoded).unwrap();
+            let decoded: EncodedRule = rmp_serde::from_slice(&encoded).unwrap();
+            decoded.into_rule(&mut Default::default())
+        };
+
+        // Everything Sentry knows is encoded the same way in both encodings.
+        let rule = parse_rule(
+            "[ !function:foo ] | path:**/*.c family:native app:yes error.mechanism:handled \
+             | [ category:bar ] v-group ^+app category=baz min-frames=2 invert-stacktrace=1",
+            &mut Default::default(),
+        )
+        .unwrap();
+        for encoding in [Encoding::Sentry, Encoding::Extended] {
+            assert_eq!(roundtrip(&rule, encoding).unwrap(), rule);
+        }
+
+        let extensions = [
+            ("trust:scan +app", "unable to encode matcher `trust`"),
+            (
+                "thread.main:yes +app",
+                "unable to encode matcher `thread.main`",
+            ),
+            (
+                "thread.name:foo +app",
+                "unable to encode matcher `thread.name`",
+            ),
+            ("thread.id:1 +app", "unable to encode matcher `thread.id`"),
+            (
+                "thread.crashed:no +app",
+                "unable to encode matcher `thread.crashed`",
+            ),
+            ("app:unset +app", "unable to encode `app:unset`"),
+            (
+                "function:=-[Foo* +app",
+                "unable to encode literal pattern `=-[Foo*`",
+            ),
+            ("function:foo ~-group", "unable to encode sibling range `~`"),
+            (
+                "function:foo priority=3",
+                "unable to encode variable `priority`",
+            ),
+        ];
+        for (input, message) in extensions {
+            let rule = parse_rule(input, &mut Default::default()).unwrap();
+            let err = roundtrip(&rule, Encoding::Sentry).unwrap_err();
+            assert!(format!("{err:#}").ends_with(message), "{input}: {err:#}");
+            assert_eq!(
+                roundtrip(&rule, Encoding::Extended).unwrap(),
+                rule,
+                "{input}"
+            );
+        }
+    }
+}
@@ -0,0 +1,135 @@
+//! Built-in rules deciding which frames contribute to grouping by default.
+//!
+//! Before the enhancement rules of a grouping config are applied, Sentry doesn't let some
+//! frames contribute, e.g. native frames that weren't symbolicated. These defaults are kept
+//! here as data, one set per grouping config, and turned into regular rules that are applied
+//! before the rules of the config, so that everyone using this crate applies the same defaults.
+
+use super::{Cache, Enhancements};
+use crate::{Error, Result};
+
+/// A default of whether frames contribute.
+struct ContributesDefault {
+    /// The family of frames this default applies to.
+    family: &'static str,
+    /// The matchers selecting the frames within the family.
+    matchers: &'static str,
+    contributes: bool,
+}
+
+/// The defaults of every grouping config, keyed by its Sentry identifier.
+const DEFAULTS: &[(&str, &[ContributesDefault])] = &[(
+    "newstyle:2023-01-11",
+    &[
+        // Native frames that weren't symbolicated.
+        ContributesDefault {
+            family: "native",
+            matchers: "!function:*",
+            contributes: false,
+        },
+        // JavaScript frames without a function that point to a URL, which are usually
+        // frames of minified code without source maps.
+        ContributesDefault {
+            family: "javascript",
+            matchers: "!function:* path:http://**",
+            contributes: false,
+        },
+        ContributesDefault {
+            family: "javascript",
+            matchers: "!function:* path:https://**",
+            contributes: false,
+        },
+    ],
+)];
+
+/// The identifiers of all grouping configs with default contributes rules, e.g.
+/// `"newstyle:2023-01-11"`.
+pub const DEFAULT_CONTRIBUTES_VERSIONS: &[&str] = &["newstyle:2023-01-11"];
+
+impl Enhancements {
+    /// Returns the rules deciding which frames contribute by default in the grouping config
+    /// `version`.
+    ///
+    /// See [`DEFAULT_CONTRIBUTES_VERSIONS`] for the available identifiers.
+    pub fn default_contributes(version: &str, cache: &mut Cache) -> Result<Self> {
+        let Some((_, defaults)) = DEFAULTS.iter().find(|(name, _)| *name == version) else {
+            return Err(Error::UnknownDefaultConfig(version.into()));
+        };
+
+        let mut enhancements = Self::default();
+        for default in *defaults {
+            let flag = if default.contributes { '+' } else { '-' };
+            let rule = format!("family:{} {} {flag}group", default.family, default.matchers);
+            enhancements.extend([cache.get_or_try_insert_rule(&rule)?]);
+        }
+        Ok(enhancements)
+    }
+
+    /// Returns a copy of `self` with the [default contributes rules](Self::default_contributes)
+    /// of the grouping config `version` applied before its own rules.
+    pub fn with_default_contributes(&self, version: &str, cache: &mut Cache) -> Result<Self> {
+        Ok(Self::default_contributes(version, cache)?.with_additional_rules(self))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+    use crate::enhancers::{Component, Frame};
+
+    #[test]
+    fn applies_default_contributes_rules() {
+        assert_eq!(DEFAULTS.len(), DEFAULT_CONTRIBUTES_VERSIONS.len());
+
+        let mut cache = Cache::default();
+        let enhancements =
+            Enhancements::default_contributes("newstyle:2023-01-11", &mut cache).unwrap();
+
+        let frames = [
+            Frame::from_test(&json!({"function": "main"}), "native"),
+            Frame::from_test(&json!({"package": "/usr/lib/libc.so"}), "native"),
+            Frame::from_test(
+                &json!({"abs_path": "https://example.com/app.js"}),
+                "javascript",
+            ),
+            Frame::from_test(&json!({"abs_path": "/app/index.js"}), "javascript"),
+        ];
+        let mut components = vec![
+            Component {
+                contributes: Some(true),
+                ..Default::default()
+            };
+            frames.len()
+        ];
+        enhancements.assemble_stacktrace_component(&mut components, &frames, &Default::default());
+        let contributes: Vec<_> = components.iter().map(|c| c.contributes).collect();
+        assert_eq!(
+            contributes,
+            [Some(true), Some(false), Some(false), Some(true)]
+        );
+        assert_eq!(
+            components[1].primary_hint(),
+            Some("ignored by stack trace rule (family:native !function:* -group)")
+        );
+
+        // The rules of the config are applied after the defaults.
+        let frames = [Frame::from_test(&json!({}), "native")];
+        let enhancements = Enhancements::parse("family:native +group", &mut cache).unwrap();
+        let enhancements = enhancements
+            .with_default_contributes("newstyle:2023-01-11", &mut cache)
+            .unwrap();
+        let mut components = vec![Component {
+            contributes: Some(true),
+            ..Default::default()
+        }];
+        enhancements.assemble_stacktrace_component(&mut components, &frames, &Default::default());
+        assert_eq!(components[0].contributes, Some(true));
+
+        assert!(matches!(
+            Enhancements::default_contributes("newstyle:1970-01-01", &mut cache),
+            Err(Error::UnknownDefaultConfig(_))
+        ));
+    }
+}
@@ -0,0 +1,49 @@
+//! Copies of Sentry's default enhancements, embedded at compile time.
+//!
+//! This module is only available with the `default-configs` feature.
+
+use super::{Cache, Enhancements};
+use crate::{Error, Result};
+
+/// The embedded default configurations, keyed by their Sentry identifiers.
+const DEFAULT_CONFIGS: &[(&str, &str)] = &[(
+    "newstyle:2023-01-11",
+    include_str!("../../../tests/fixtures/newstyle@2023-01-11.txt"),
+)];
+
+/// The identifiers of all embedded default configurations, e.g. `"newstyle:2023-01-11"`.
+pub const DEFAULT_CONFIG_VERSIONS: &[&str] = &["newstyle:2023-01-11"];
+
+impl Enhancements {
+    /// Parses the embedded default configuration with the identifier `version`.
+    ///
+    /// See [`DEFAULT_CONFIG_VERSIONS`] for the available identifiers.
+    pub fn default_config(version: &str, cache: &mut Cache) -> Result<Self> {
+        let Some((_, input)) = DEFAULT_CONFIGS.iter().find(|(name, _)| *name == version) else {
+            return Err(Error::UnknownDefaultConfig(version.into()));
+        };
+
+        Self::parse(input, cache)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_embedded_configs() {
+        assert_eq!(DEFAULT_CONFIGS.len(), DEFAULT_CONFIG_VERSIONS.len());
+
+        let mut cache = Cache::default();
+        for version in DEFAULT_CONFIG_VERSIONS {
+            let enhancements = Enhancements::default_config(version, &mut cache).unwrap();
+            assert!(enhancements.rules().count() > 0);
+        }
+
+        assert!(matches!(
+            Enhancements::default_config("newstyle:1970-01-01", &mut cache),
+            Err(Error::UnknownDefaultConfig(_))
+        ));
+    }
+}
@@ -0,0 +1,135 @@
+//! Structured descriptions of rules, for tools that analyze enhancements.
+//!
+//! Descriptions are plain data and serialize to JSON via [`Enhancements::to_json`](super::Enhancements::to_json).
+
+use serde::Serialize;
+
+/// A description of a [`Rule`](super::Rule).
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct RuleDescription {
+    /// The rule in its text form.
+    pub text: String,
+    /// The rule's matchers, caller matchers first and callee matchers last.
+    pub matchers: Vec<MatcherDescription>,
+    /// The rule's actions, in order.
+    pub actions: Vec<ActionDescription>,
+}
+
+/// Whether a matcher checks frames, exceptions, or threads.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum MatcherKind {
+    /// The matcher checks a frame.
+    Frame,
+    /// The matcher checks the exception.
+    Exception,
+    /// The matcher checks the thread, like `thread.name`.
+    Thread,
+}
+
+/// Which frame other than the current one a frame matcher checks.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum MatcherOffset {
+    /// The caller frame, written as `[ <matcher> ] |`.
+    Caller,
+    /// The callee frame, written as `| [ <matcher> ]`.
+    Callee,
+}
+
+/// A description of a matcher.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct MatcherDescription {
+    /// Whether the matcher checks frames, exceptions, or threads.
+    pub kind: MatcherKind,
+    /// The canonical name of the matcher, e.g. `function` for both `function` and `stack.function`.
+    pub key: String,
+    /// The pattern as it was written in the rule.
+    pub pattern: String,
+    /// Whether the matcher is negated with `!`.
+    pub negated: bool,
+    /// Which adjacent frame the matcher checks, if any.
+    pub offset: Option<MatcherOffset>,
+}
+
+/// A description of an action.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(tag = "type", rename_all = "lowercase")]
+pub enum ActionDescription {
+    /// A flag action like `-app` or `^+group`.
+    Flag {
+        /// The flag being set, `app`, `group`, or `vars`.
+        flag: &'static str,
+        /// The value the flag is set to.
+        value: bool,
+        /// The frames the action applies to besides the matching one:
+        /// `up`, `down`, `siblings`, or `None`.
+        range: Option<&'static str>,
+    },
+    /// A variable action like `max-frames=3`.
+    Var {
+        /// The variable being set, e.g. `max-frames`.
+        name: &'static str,
+        /// The value the variable is set to.
+        value: VarValue,
+    },
+}
+
+/// The value of a variable action.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(untagged)]
+pub enum VarValue {
+    /// The value of `min-frames` or `max-frames`, where `0` means unset.
+    Int(usize),
+    /// The value of `invert-stacktrace`.
+    Bool(bool),
+    /// The value of `category`.
+    Str(String),
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use crate::enhancers::{Cache, Enhancements};
+
+    #[test]
+    fn exports_json() {
+        let enhancements = Enhancements::parse(
+            "[ !function:foo ] | error.type:Error stack.abs_path:**/*.c ^-app category=x\n\
+             family:native | [ module:bar ] max-frames=3 invert-stacktrace=1",
+            &mut Cache::default(),
+        )
+        .unwrap();
+
+        let exported: serde_json::Value = serde_json::from_str(&enhancements.to_json()).unwrap();
+        assert_eq!(
+            exported,
+            json!([
+                {
+                    "text": "type:Error [!function:foo] | path:**/*.c ^-app category=x",
+                    "matchers": [
+                        {"kind": "frame", "key": "function", "pattern": "foo", "negated": true, "offset": "caller"},
+                        {"kind": "exception", "key": "type", "pattern": "Error", "negated": false, "offset": null},
+                        {"kind": "frame", "key": "path", "pattern": "**/*.c", "negated": false, "offset": null},
+                    ],
+                    "actions": [
+                        {"type": "flag", "flag": "app", "value": false, "range": "up"},
+                        {"type": "var", "name": "category", "value": "x"},
+                    ],
+                },
+                {
+                    "text": "family:native | [module:bar] max-frames=3 invert-stacktrace=true",
+                    "matchers": [
+                        {"kind": "frame", "key": "family", "pattern": "native", "negated": false, "offset": null},
+                        {"kind": "frame", "key": "module", "pattern": "bar", "negated": false, "offset": "callee"},
+                    ],
+                    "actions": [
+                        {"type": "var", "name": "max-frames", "value": 3},
+                        {"type": "var", "name": "invert-stacktrace", "value": true},
+                    ],
+                },
+            ])
+        );
+    }
+}
@@ -0,0 +1,277 @@
+//! A lossless representation of an enhancements file.
+//!
+//! Unlike [`Enhancements::parse`], which discards everything but the rules,
+//! a [`Document`] also keeps comments, blank lines, and the exact formatting
+//! of every line. This makes it possible to programmatically edit a file and
+//! write it back out without disturbing the parts that were not touched.
+
+use std::fmt;
+
+use anyhow::Context;
+
+use super::{Cache, Enhancements, Rule};
+use crate::{Error, Result};
+
+/// The kind of a [`DocumentLine`].
+#[derive(Debug, Clone)]
+pub enum LineKind {
+    /// A line containing a rule.
+    Rule(Rule),
+    /// A line containing only a comment.
+    Comment,
+    /// A line containing only whitespace.
+    Blank,
+}
+
+/// A single line in a [`Document`].
+#[derive(Debug, Clone)]
+pub struct DocumentLine {
+    /// What kind of line this is.
+    kind: LineKind,
+    /// The verbatim text of the line, without the line ending.
+    text: String,
+    /// The line ending (`"\n"`, `"\r\n"`, or `""` for the last line).
+    line_ending: &'static str,
+}
+
+impl DocumentLine {
+    /// Returns the kind of this line.
+    pub fn kind(&self) -> &LineKind {
+        &self.kind
+    }
+
+    /// Returns the verbatim text of this line, without the line ending.
+    pub fn text(&self) -> &str {
+        &self.text
+    }
+
+    /// Returns the rule contained in this line, if any.
+    pub fn rule(&self) -> Option<&Rule> {
+        match &self.kind {
+            LineKind::Rule(rule) => Some(rule),
+            _ => None,
+        }
+    }
+}
+
+/// An enhancements file that preserves comments, blank lines and formatting.
+///
+/// Serializing an unchanged `Document` with [`Display`](fmt::Display) reproduces
+/// the original input byte for byte.
+#[derive(Debug, Clone, Default)]
+pub struct Document {
+    lines: Vec<DocumentLine>,
+}
+
+impl Document {
+    /// Parses a `Document` from a string.
+    pub fn parse(input: &str, cache: &mut Cache) -> Result<Self> {
+        let mut lines = vec![];
+
+        for (idx, raw_line) in input.split_inclusive('\n').enumerate() {
+            let (text, line_ending) = split_line_ending(raw_line);
+
+            let kind = parse_line(text, cache)
+                .with_context(|| format!("at line {}", idx + 1))
+                .map_err(Error::Parse)?;
+            lines.push(DocumentLine {
+                kind,
+                text: text.into(),
+                line_ending,
+            });
+        }
+
+        Ok(Self { lines })
+    }
+
+    /// Returns all lines of this document.
+    ///
+    /// The position of a line in the returned slice is its 0-based line number.
+    pub fn lines(&self) -> &[DocumentLine] {
+        &self.lines
+    }
+
+    /// Returns an iterator over all rules in this document, together with their 0-based line numbers.
+    pub fn rules(&self) -> impl Iterator<Item = (usize, &Rule)> {
+        self.lines
+            .iter()
+            .enumerate()
+            .filter_map(|(idx, line)| Some((idx, line.rule()?)))
+    }
+
+    /// Inserts a new line at the 0-based line number `idx`, shifting all following lines down.
+    ///
+    /// The line may contain a rule, a comment, or nothing at all.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `idx` is larger than the number of lines.
+    pub fn insert_line(&mut self, idx: usize, text: &str, cache: &mut Cache) -> Result<()> {
+        assert!(idx <= self.lines.len(), "line index out of bounds");
+        if text.contains('\n') {
+            return Err(Error::Parse(anyhow::anyhow!(
+                "a line must not contain line breaks"
+            )));
+        }
+
+        let kind = parse_line(text, cache)?;
+        let default_ending = self.default_line_ending();
+        let is_last = idx == self.lines.len();
+
+        // Inserting after a final line without line ending requires terminating that line first.
+        let line_ending = match self.lines.get_mut(idx.wrapping_sub(1)) {
+            Some(prev) if is_last && prev.line_ending.is_empty() => {
+                prev.line_ending = default_ending;
+                ""
+            }
+            _ => default_ending,
+        };
+
+        self.lines.insert(
+            idx,
+            DocumentLine {
+                kind,
+                text: text.into(),
+                line_ending,
+            },
+        );
+
+        Ok(())
+    }
+
+    /// Appends a new line to the end of this document.
+    pub fn push_line(&mut self, text: &str, cache: &mut Cache) -> Result<()> {
+        self.insert_line(self.lines.len(), text, cache)
+    }
+
+    /// Removes the line at the 0-based line number `idx` and returns it.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `idx` is out of bounds.
+    pub fn remove_line(&mut self, idx: usize) -> DocumentLine {
+        let removed = self.lines.remove(idx);
+
+        // Keep the file's final line ending (or lack thereof) intact.
+        if idx == self.lines.len() {
+            if let Some(last) = self.lines.last_mut() {
+                last.line_ending = removed.line_ending;
+            }
+        }
+
+        removed
+    }
+
+    /// Creates an [`Enhancements`] structure from the rules in this document.
+    pub fn to_enhancements(&self) -> Enhancements {
+        Enhancements::new(self.rules().map(|(_, rule)| rule.clone()).collect())
+    }
+
+    /// The line ending used for newly inserted lines.
+    ///
+    /// This is the line ending of the first line, defaulting to `"\n"`.
+    fn default_line_ending(&self) -> &'static str {
+        match self.lines.first() {
+            Some(line) if line.line_ending == "\r\n" => "\r\n",
+            _ => "\n",
+        }
+    }
+}
+
+impl fmt::Display for Document {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for line in &self.lines {
+            write!(f, "{}{}", line.text, line.line_ending)?;
+        }
+        Ok(())
+    }
+}
+
+/// Splits a line as returned by [`str::split_inclusive`] into its text and its line ending
+/// (`"\n"`, `"\r\n"`, or `""` for the last line).
+pub(crate) fn split_line_ending(raw_line: &str) -> (&str, &'static str) {
+    if let Some(text) = raw_line.strip_suffix("\r\n") {
+        (text, "\r\n")
+    } else if let Some(text) = raw_line.strip_suffix('\n') {
+        (text, "\n")
+    } else {
+        (raw_line, "")
+    }
+}
+
+/// Classifies a single line, parsing it if it contains a rule.
+fn parse_line(text: &str, cache: &mut Cache) -> Result<LineKind> {
+    let trimmed = text.trim();
+    if trimmed.is_empty() {
+        Ok(LineKind::Blank)
+    } else if trimmed.starts_with('#') {
+        Ok(LineKind::Comment)
+    } else {
+        Ok(LineKind::Rule(cache.get_or_try_insert_rule(trimmed)?))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn roundtrips_losslessly() {
+        let inputs = [
+            "",
+            "\n",
+            "function:foo +app",
+            "# comment\n\nfunction:foo +app   # trailing\n  \n",
+            "# windows\r\n\r\nfunction:foo -group\r\npath:**/bar -app",
+        ];
+
+        for input in inputs {
+            let document = Document::parse(input, &mut Cache::default()).unwrap();
+            assert_eq!(document.to_string(), input);
+        }
+    }
+
+    #[test]
+    fn tracks_rules_and_comments() {
+        let input = "# comment\n\nfunction:foo +app\n";
+        let document = Document::parse(input, &mut Cache::default()).unwrap();
+
+        assert!(matches!(document.lines()[0].kind(), LineKind::Comment));
+        assert!(matches!(document.lines()[1].kind(), LineKind::Blank));
+
+        let rules: Vec<_> = document
+            .rules()
+            .map(|(idx, rule)| (idx, rule.to_string()))
+            .collect();
+        assert_eq!(rules, [(2, "function:foo +app".to_owned())]);
+        assert_eq!(document.to_enhancements().rules().count(), 1);
+    }
+
+    #[test]
+    fn edits_lines() {
+        let mut cache = Cache::default();
+        let mut document = Document::parse("# comment\r\nfunction:foo +app", &mut cache).unwrap();
+
+        document.push_line("function:bar -app", &mut cache).unwrap();
+        assert_eq!(
+            document.to_string(),
+            "# comment\r\nfunction:foo +app\r\nfunction:bar -app"
+        );
+
+        document.insert_line(1, "", &mut cache).unwrap();
+        assert_eq!(
+            document.to_string(),
+            "# comment\r\n\r\nfunction:foo +app\r\nfunction:bar -app"
+        );
+
+        let removed = document.remove_line(3);
+        assert_eq!(removed.text(), "function:bar -app");
+        assert_eq!(document.to_string(), "# comment\r\n\r\nfunction:foo +app");
+
+        document.remove_line(0);
+        assert_eq!(document.to_string(), "\r\nfunction:foo +app");
+
+        assert!(document.insert_line(0, "function:foo", &mut cache).is_err());
+        assert!(document.insert_line(0, "a\nb", &mut cache).is_err());
+    }
+}
@@ -0,0 +1,262 @@
+//! Conversions between frames, exceptions, and Sentry's event JSON.
+//!
+//! Frames and exceptions are (de)serialized in the shape they have in Sentry events, so that
+//! they can be deserialized directly from an event's `stacktrace.frames` and `exception.values`.
+
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use serde_json::{Map, Value};
+
+use super::frame::{Frame, RawFrame, StringField};
+use super::{primary_hint, ExceptionData, Hint, ThreadData};
+
+/// A stack frame as it appears in Sentry's event JSON.
+#[derive(Debug, Default, Deserialize, Serialize)]
+#[serde(default)]
+pub(crate) struct EventFrame {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    function: Option<StringField>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    raw_function: Option<StringField>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    module: Option<StringField>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    package: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    abs_path: Option<String>,
+    #[serde(skip_serializing)]
+    filename: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    platform: Option<StringField>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    trust: Option<StringField>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    in_app: Option<bool>,
+    #[serde(skip_serializing_if = "EventFrameData::is_empty")]
+    data: EventFrameData,
+    #[serde(skip_serializing)]
+    vars: Option<Map<String, Value>>,
+}
+
+/// The `data` of a stack frame in Sentry's event JSON.
+#[derive(Debug, Default, Deserialize, Serialize)]
+#[serde(default)]
+struct EventFrameData {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    category: Option<StringField>,
+    /// The `in_app` flag before grouping, where `-1` stands for an unset flag.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    orig_in_app: Option<Value>,
+}
+
+impl EventFrameData {
+    fn is_empty(&self) -> bool {
+        self.category.is_none() && self.orig_in_app.is_none()
+    }
+}
+
+impl From<EventFrame> for Frame {
+    fn from(frame: EventFrame) -> Self {
+        let orig_in_app = frame.data.orig_in_app.map(|in_app| match in_app {
+            Value::Bool(in_app) => Some(in_app),
+            Value::Number(n) if n.as_i64() == Some(0) => Some(false),
+            Value::Number(n) if n.as_i64() == Some(1) => Some(true),
+            _ => None,
+        });
+
+        Self::from_raw_parts(RawFrame {
+            category: frame.data.category,
+            platform: frame.platform,
+            function: frame.function,
+            raw_function: frame.raw_function,
+            module: frame.module,
+            package: frame.package.as_deref(),
+            path: frame.abs_path.as_deref().or(frame.filename.as_deref()),
+            path_lowercase: None,
+            trust: frame.trust,
+            in_app: frame.in_app,
+            orig_in_app,
+            has_vars: frame.vars.map(|vars| !vars.is_empty()),
+        })
+    }
+}
+
+impl From<Frame> for EventFrame {
+    fn from(frame: Frame) -> Self {
+        let orig_in_app = frame.orig_in_app.map(|in_app| match in_app {
+            Some(in_app) => Value::Bool(in_app),
+            None => Value::from(-1),
+        });
+
+        Self {
+            function: frame.function,
+            raw_function: frame.raw_function,
+            module: frame.module,
+            package: frame.package.map(String::from),
+            abs_path: frame.path.map(String::from),
+            filename: None,
+            platform: frame.raw_family,
+            trust: frame.trust,
+            in_app: frame.in_app,
+            data: EventFrameData {
+                category: frame.category,
+                orig_in_app,
+            },
+            vars: None,
+        }
+    }
+}
+
+/// An exception as it appears in Sentry's event JSON.
+#[derive(Debug, Default, Deserialize, Serialize)]
+#[serde(default)]
+pub(crate) struct EventException {
+    #[serde(rename = "type", skip_serializing_if = "Option::is_none")]
+    ty: Option<StringField>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    value: Option<StringField>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    module: Option<StringField>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    mechanism: Option<EventMechanism>,
+    #[serde(skip_serializing_if = "ThreadData::is_empty")]
+    thread: ThreadData,
+}
+
+/// The mechanism of an exception in Sentry's event JSON.
+#[derive(Debug, Default, Deserialize, Serialize)]
+#[serde(default)]
+struct EventMechanism {
+    #[serde(rename = "type", skip_serializing_if = "Option::is_none")]
+    ty: Option<StringField>,
+}
+
+impl From<EventException> for ExceptionData {
+    fn from(exception: EventException) -> Self {
+        Self {
+            ty: exception.ty,
+            value: exception.value,
+            module: exception.module,
+            mechanism: exception.mechanism.and_then(|m| m.ty),
+            thread: exception.thread,
+        }
+    }
+}
+
+impl From<ExceptionData> for EventException {
+    fn from(exception: ExceptionData) -> Self {
+        Self {
+            ty: exception.ty,
+            value: exception.value,
+            module: exception.module,
+            mechanism: exception
+                .mechanism
+                .map(|ty| EventMechanism { ty: Some(ty) }),
+            thread: exception.thread,
+        }
+    }
+}
+
+/// Serializes the hints of a component as its primary hint, the `hint` of Sentry's components.
+pub(crate) fn serialize_hints<S: Serializer>(
+    hints: &[Hint],
+    serializer: S,
+) -> Result<S::Ok, S::Error> {
+    primary_hint(hints).serialize(serializer)
+}
+
+/// Deserializes the `hint` of Sentry's components as the only hint of a component.
+pub(crate) fn deserialize_hints<'de, D: Deserializer<'de>>(
+    deserializer: D,
+) -> Result<Vec<Hint>, D::Error> {
+    let hint = Option::<String>::deserialize(deserializer)?;
+    Ok(hint.map(Hint::new).into_iter().collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use crate::enhancers::{Component, Families, Frame};
+
+    use super::*;
+
+    #[test]
+    fn deserializes_event_frames() {
+        let frame: Frame = serde_json::from_value(json!({
+            "function": "foo",
+            "raw_function": "foo<int>",
+            "package": "C:\\Foo\\Bar.dll",
+            "filename": "foo.c",
+            "platform": "native",
+            "in_app": true,
+            "data": {"category": "ui", "orig_in_app": -1},
+            "vars": {"x": 1},
+            "lineno": 23,
+        }))
+        .unwrap();
+
+        assert_eq!(frame.function.as_deref(), Some("foo"));
+        assert_eq!(frame.raw_function.as_deref(), Some("foo<int>"));
+        assert_eq!(frame.package.as_deref(), Some("c:/foo/bar.dll"));
+        assert_eq!(frame.path.as_deref(), Some("foo.c"));
+        assert_eq!(frame.family, Families::NATIVE);
+        assert_eq!(frame.category.as_deref(), Some("ui"));
+        assert_eq!(frame.in_app, Some(true));
+        assert_eq!(frame.orig_in_app, Some(None));
+        assert_eq!(frame.has_vars, Some(true));
+
+        let serialized = serde_json::to_value(&frame).unwrap();
+        assert_eq!(
+            serialized,
+            json!({
+                "function": "foo",
+                "raw_function": "foo<int>",
+                "package": "c:/foo/bar.dll",
+                "abs_path": "foo.c",
+                "platform": "native",
+                "in_app": true,
+                "data": {"category": "ui", "orig_in_app": -1},
+            })
+        );
+
+        let frame: Frame = serde_json::from_value(json!({})).unwrap();
+        assert_eq!(frame.family, Families::OTHER);
+        assert_eq!(frame.has_vars, None);
+    }
+
+    #[test]
+    fn deserializes_event_exceptions() {
+        let event_exception = json!({
+            "type": "Error",
+            "value": "oh no",
+            "module": "app.errors",
+            "mechanism": {"type": "onerror", "handled": false},
+        });
+        let exception: ExceptionData = serde_json::from_value(event_exception).unwrap();
+        assert_eq!(exception.ty.as_deref(), Some("Error"));
+        assert_eq!(exception.module.as_deref(), Some("app.errors"));
+        assert_eq!(exception.mechanism.as_deref(), Some("onerror"));
+
+        assert_eq!(
+            serde_json::to_value(exception).unwrap(),
+            json!({
+                "type": "Error",
+                "value": "oh no",
+                "module": "app.errors",
+                "mechanism": {"type": "onerror"},
+            })
+        );
+    }
+
+    #[test]
+    fn serializes_components() {
+        let component: Component =
+            serde_json::from_value(json!({"contributes": false, "hint": "ignored"})).unwrap();
+        assert_eq!(component.contributes, Some(false));
+        assert!(!component.is_sentinel_frame);
+        assert_eq!(component.primary_hint(), Some("ignored"));
+
+        let serialized = serde_json::to_value(&component).unwrap();
+        assert_eq!(serialized["hint"], "ignored");
+    }
+}
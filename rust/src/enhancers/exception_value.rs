@@ -0,0 +1,76 @@
+//! Normalization of exception values.
+//!
+//! Exception values often contain memory addresses, ids, and other variable parts, which makes it
+//! hard to match them with `error.value` globs. Normalizing replaces these parts with placeholders.
+
+use std::borrow::Cow;
+use std::sync::OnceLock;
+
+use regex::{Captures, Regex};
+
+/// Matches the variable parts of an exception value, in order of precedence.
+fn variable_parts_re() -> &'static Regex {
+    static RE: OnceLock<Regex> = OnceLock::new();
+    RE.get_or_init(|| {
+        Regex::new(
+            r#"(?x)
+            (?P<str>"[^"]*"|\B'[^']*')
+            | (?P<uuid>\b[0-9a-fA-F]{8}-[0-9a-fA-F]{4}-[0-9a-fA-F]{4}-[0-9a-fA-F]{4}-[0-9a-fA-F]{12}\b)
+            | (?P<hex>\b0[xX][0-9a-fA-F]+\b)
+            | (?P<int>\b[0-9]+)
+            "#,
+        )
+        .unwrap()
+    })
+}
+
+/// Normalizes an exception value by replacing its variable parts with placeholders.
+///
+/// The following parts are replaced:
+/// * quoted strings with `<str>`, where single quotes directly after a word are
+///   considered apostrophes;
+/// * UUIDs with `<uuid>`;
+/// * hexadecimal numbers starting with `0x`, such as memory addresses, with `<hex>`;
+/// * numbers at the start of a word with `<int>`.
+///
+/// For example, `Object at 0x7ffe12 has no key "foo" (42 tries)` is normalized to
+/// `Object at <hex> has no key <str> (<int> tries)`.
+pub fn normalize_exception_value(value: &str) -> Cow<'_, str> {
+    variable_parts_re().replace_all(value, |captures: &Captures| {
+        let placeholder = ["str", "uuid", "hex", "int"]
+            .into_iter()
+            .find(|name| captures.name(name).is_some())
+            .unwrap_or_default();
+        format!("<{placeholder}>")
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn normalizes_variable_parts() {
+        let cases = [
+            (
+                r#"Object at 0x7ffe12 has no key "foo" (42 tries)"#,
+                "Object at <hex> has no key <str> (<int> tries)",
+            ),
+            (
+                "Request 123e4567-e89b-12d3-a456-426614174000 timed out after 30s",
+                "Request <uuid> timed out after <int>s",
+            ),
+            ("can't decode 'x' as utf8", "can't decode <str> as utf8"),
+            ("no variable parts", "no variable parts"),
+        ];
+
+        for (value, expected) in cases {
+            assert_eq!(normalize_exception_value(value), expected);
+        }
+
+        assert!(matches!(
+            normalize_exception_value("no variable parts"),
+            Cow::Borrowed(_)
+        ));
+    }
+}
@@ -2,42 +2,83 @@
 //!
 //! Since there are only 3 relevant family strings ("native", "javascript", and "other"),
 //! plus the wildcard "all" that matches any family, we can concisely represent them using one byte.
+//!
+//! Any other family name is represented by a single "unknown" bit. Matching such names
+//! exactly is left to the family matcher, which keeps the original names around.
 
 /// A bit field representing a list of allowed families.
 ///
 /// * `0b001` represents `"other"`
 /// * `0b010` represents `"native"`
 /// * `0b100` represents `"javascript"`
+/// * `0b1000` represents any other family
 /// * `u8::MAX` represents `"all"`
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub struct Families(u8);
 
 const BITFIELD_OTHER: u8 = 0b001;
 const BITFIELD_NATIVE: u8 = 0b010;
 const BITFIELD_JAVASCRIPT: u8 = 0b100;
+const BITFIELD_UNKNOWN: u8 = 0b1000;
 const BITFIELD_ALL: u8 = u8::MAX;
 
 impl Families {
+    /// No family at all.
+    pub(crate) const NONE: Self = Self(0);
+    /// The `"other"` family.
+    pub(crate) const OTHER: Self = Self(BITFIELD_OTHER);
+    /// The `"native"` family.
+    pub(crate) const NATIVE: Self = Self(BITFIELD_NATIVE);
+    /// The `"javascript"` family.
+    pub(crate) const JAVASCRIPT: Self = Self(BITFIELD_JAVASCRIPT);
+    /// Any family other than `"other"`, `"native"`, and `"javascript"`.
+    pub(crate) const UNKNOWN: Self = Self(BITFIELD_UNKNOWN);
+    /// The families that have a dedicated bit, i.e., all but [`UNKNOWN`](Self::UNKNOWN).
+    pub(crate) const KNOWN: Self = Self(BITFIELD_OTHER | BITFIELD_NATIVE | BITFIELD_JAVASCRIPT);
+    /// All families.
+    pub(crate) const ALL: Self = Self(BITFIELD_ALL);
+
     /// Creates a [`Families`] structure from a comma-separated list of families.
+    ///
+    /// Empty names are ignored, all other unrecognized names are folded into a single "unknown" bit.
     pub fn new(families: &str) -> Self {
         let mut bitfield = 0;
         for family in families.split(',') {
             bitfield |= match family {
+                "" => 0,
                 "other" => BITFIELD_OTHER,
                 "native" => BITFIELD_NATIVE,
                 "javascript" => BITFIELD_JAVASCRIPT,
                 "all" => BITFIELD_ALL,
-                _ => 0,
+                _ => BITFIELD_UNKNOWN,
             };
         }
         Self(bitfield)
     }
 
+    /// Returns the names in the comma-separated list `families` that are not
+    /// represented by a dedicated bit.
+    pub(crate) fn unknown_names(families: &str) -> impl Iterator<Item = &str> {
+        families
+            .split(',')
+            .filter(|family| !matches!(*family, "" | "other" | "native" | "javascript" | "all"))
+    }
+
     /// Checks whether `self` and `other` have at least one family in common, where
     /// `all` counts as all families.
     pub fn matches(&self, other: Families) -> bool {
         (self.0 & other.0) > 0
     }
+
+    /// Returns the families contained in either `self` or `other`.
+    pub fn union(self, other: Families) -> Self {
+        Self(self.0 | other.0)
+    }
+
+    /// Returns the families contained in both `self` and `other`.
+    pub fn intersection(self, other: Families) -> Self {
+        Self(self.0 & other.0)
+    }
 }
 
 impl Default for Families {
@@ -1,29 +1,151 @@
 //! Types for stack frames.
 
+use std::borrow::Borrow;
+use std::cell::RefCell;
 use std::fmt;
+use std::num::NonZeroUsize;
+use std::ops::Deref;
 
+use lru::LruCache;
 use smol_str::SmolStr;
 
 use super::families::Families;
+use crate::Lowercase;
 
-pub type StringField = SmolStr;
+/// An immutable string that is cheap to clone, used for the values of frame and exception fields.
+///
+/// Short strings are stored inline without allocating. This dereferences to [`str`] and can be
+/// created from `&str` or `String`.
+#[derive(Clone, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(transparent)
+)]
+pub struct StringField(SmolStr);
+
+impl StringField {
+    /// Creates a new `StringField` from the given string.
+    pub fn new(s: impl AsRef<str>) -> Self {
+        Self(SmolStr::new(s))
+    }
+
+    /// Returns the value as a string slice.
+    pub fn as_str(&self) -> &str {
+        self.0.as_str()
+    }
+}
+
+impl Deref for StringField {
+    type Target = str;
+
+    fn deref(&self) -> &str {
+        self.as_str()
+    }
+}
+
+impl AsRef<str> for StringField {
+    fn as_ref(&self) -> &str {
+        self.as_str()
+    }
+}
+
+impl Borrow<str> for StringField {
+    fn borrow(&self) -> &str {
+        self.as_str()
+    }
+}
+
+impl From<&str> for StringField {
+    fn from(s: &str) -> Self {
+        Self::new(s)
+    }
+}
+
+impl From<String> for StringField {
+    fn from(s: String) -> Self {
+        Self(SmolStr::from(s))
+    }
+}
+
+impl From<&String> for StringField {
+    fn from(s: &String) -> Self {
+        Self::new(s)
+    }
+}
+
+impl From<StringField> for String {
+    fn from(s: StringField) -> Self {
+        s.0.into()
+    }
+}
+
+impl PartialEq<str> for StringField {
+    fn eq(&self, other: &str) -> bool {
+        self.as_str() == other
+    }
+}
+
+impl PartialEq<&str> for StringField {
+    fn eq(&self, other: &&str) -> bool {
+        self.as_str() == *other
+    }
+}
+
+impl fmt::Debug for StringField {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Debug::fmt(self.as_str(), f)
+    }
+}
+
+impl fmt::Display for StringField {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
 
 /// Represents a stack frame for the purposes of grouping rules.
+///
+/// With the `serde` feature, frames (de)serialize in the shape of Sentry's event JSON: `path` is
+/// read from `abs_path` or `filename`, `family` from `platform`, and `category` and `orig_in_app`
+/// from `data`. `has_vars` is derived from whether `vars` is non-empty and is not serialized.
 #[derive(Debug, Clone, Default)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(from = "super::event::EventFrame", into = "super::event::EventFrame")
+)]
 pub struct Frame {
     /// The frame's category (e.g. `"telemetry"`, `"ui"`, &c.)
     pub category: Option<StringField>,
     /// The frame's family (`"native"`, `"javascript"`, or `"other"`), represented
     /// compactly as a bit field.
     pub family: Families,
+    /// The frame's family name as it was given.
+    ///
+    /// This is used to match `family` matchers against family names that
+    /// [`Families`] has no dedicated representation for.
+    pub raw_family: Option<StringField>,
     /// The frame's function name.
+    ///
+    /// This is the name rules match against, which may have been trimmed of
+    /// e.g. argument lists or template parameters.
     pub function: Option<StringField>,
+    /// The frame's function name before it was trimmed, if it differs from `function`.
+    ///
+    /// `function` matchers only check this if asked to via
+    /// [`ModifyOptions::match_raw_function`](super::ModifyOptions::match_raw_function)
+    /// or [`AssembleOptions::match_raw_function`](super::AssembleOptions::match_raw_function).
+    pub raw_function: Option<StringField>,
     /// The frame's module name.
     pub module: Option<StringField>,
     /// The frame's package name.
     pub package: Option<StringField>,
     /// The frame's path.
     pub path: Option<StringField>,
+    /// How the frame was found while unwinding a native stack (e.g. `"cfi"`, `"scan"`, or
+    /// `"context"`).
+    pub trust: Option<StringField>,
 
     /// The frame's `in_app` flag.
     ///
@@ -34,18 +156,26 @@ pub struct Frame {
 
     /// The original `in_app` flag which was set before any grouping code ran.
     pub orig_in_app: Option<Option<bool>>,
+
+    /// Whether the frame contains local variables.
+    ///
+    /// This is set to `Some(false)` by `-vars` actions, which means that the frame's
+    /// variables should be stripped.
+    pub has_vars: Option<bool>,
 }
 
 /// The name of a string-valued field in a frame.
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub enum FrameField {
     Category,
     Function,
     Module,
     Package,
     Path,
-    // NOTE: This is only used to have something to `Display` in the `Noop` matcher.
+    Trust,
+    // NOTE: These are only used to have something to `Display` in the `Noop` matcher.
     App,
+    HasVars,
 }
 
 impl fmt::Display for FrameField {
@@ -56,11 +186,148 @@ impl fmt::Display for FrameField {
             FrameField::Module => write!(f, "module"),
             FrameField::Package => write!(f, "package"),
             FrameField::Path => write!(f, "path"),
+            FrameField::Trust => write!(f, "trust"),
             FrameField::App => write!(f, "app"),
+            FrameField::HasVars => write!(f, "has_vars"),
         }
     }
 }
 
+/// Read access to the parts of a stack frame that rules match against.
+///
+/// This allows applying [`Enhancements`](super::Enhancements) to frame representations other
+/// than [`Frame`] without copying them first. String values of `package` and `path` are expected
+/// to be normalized with [`normalize_path`].
+pub trait FrameLike {
+    /// Gets the value of the string-valued `field`.
+    ///
+    /// This is never called with [`FrameField::App`] or [`FrameField::HasVars`].
+    fn get_field(&self, field: FrameField) -> Option<&str>;
+
+    /// The frame's family.
+    fn family(&self) -> Families;
+
+    /// The frame's family name as it was given.
+    ///
+    /// This is only used to match `family` matchers against family names that
+    /// [`Families`] has no dedicated representation for.
+    fn raw_family(&self) -> Option<&str> {
+        None
+    }
+
+    /// The frame's function name before it was trimmed, if it differs from the `function` field.
+    fn raw_function(&self) -> Option<&str> {
+        None
+    }
+
+    /// The frame's `in_app` flag.
+    fn in_app(&self) -> Option<bool>;
+
+    /// The original `in_app` flag which was set before any grouping code ran, if known.
+    fn orig_in_app(&self) -> Option<Option<bool>> {
+        None
+    }
+
+    /// Whether the frame contains local variables, if known.
+    fn has_vars(&self) -> Option<bool> {
+        None
+    }
+}
+
+/// Write access to the parts of a stack frame that rules modify.
+pub trait FrameLikeMut: FrameLike {
+    /// Sets the frame's `in_app` flag.
+    fn set_in_app(&mut self, in_app: Option<bool>);
+
+    /// Sets the frame's category.
+    fn set_category(&mut self, category: Option<StringField>);
+
+    /// Sets whether the frame contains local variables.
+    ///
+    /// `-vars` actions set this to `Some(false)`. Frame representations without local variables
+    /// can ignore it.
+    fn set_has_vars(&mut self, _has_vars: Option<bool>) {}
+}
+
+/// Normalizes a `package` or `path` value the way `path` and `package` matchers expect it.
+///
+/// Backslashes are replaced by forward slashes, and ASCII letters are lowercased. Other
+/// characters are kept as they are, since path matchers only ignore the case of ASCII letters
+/// too. Unicode lowercasing would turn e.g. `İ` into `i̇` and the Kelvin sign `K` into `k`, so
+/// that patterns containing such characters could no longer match.
+pub fn normalize_path(path: &str) -> StringField {
+    normalize_path_with(path, Lowercase::Ascii)
+}
+
+/// Normalizes a `package` or `path` value like [`normalize_path`], lowercasing it as
+/// configured by `lowercase`.
+pub fn normalize_path_with(path: &str, lowercase: Lowercase) -> StringField {
+    let path = path.replace('\\', "/");
+    StringField::from(lowercase.apply(&path).as_ref())
+}
+
+/// The number of normalized `package` and `path` values to keep per thread.
+const NORMALIZED_PATHS_CAPACITY: NonZeroUsize = NonZeroUsize::new(4096).unwrap();
+
+thread_local! {
+    /// Normalized `package` and `path` values, keyed by their raw value.
+    ///
+    /// These repeat heavily across events, so caching them saves normalizing and
+    /// allocating the same strings over and over.
+    static NORMALIZED_PATHS: RefCell<LruCache<Box<str>, StringField>> =
+        RefCell::new(LruCache::new(NORMALIZED_PATHS_CAPACITY));
+}
+
+/// Like [`normalize_path_with`], but reuses values that were recently normalized on this thread.
+///
+/// Only the default [`Lowercase::Ascii`] policy is cached, as the others are rarely used.
+fn normalize_path_cached(path: &str, lowercase: Lowercase) -> StringField {
+    if lowercase != Lowercase::Ascii {
+        return normalize_path_with(path, lowercase);
+    }
+    NORMALIZED_PATHS.with_borrow_mut(|cache| {
+        if let Some(normalized) = cache.get(path) {
+            return normalized.clone();
+        }
+        let normalized = normalize_path(path);
+        cache.put(path.into(), normalized.clone());
+        normalized
+    })
+}
+
+/// The parts of a stack frame as they are given in an event, before normalization.
+///
+/// This is turned into a [`Frame`] with [`Frame::from_raw_parts`]. `package` and `path` are
+/// borrowed, as only their normalized values end up in the frame.
+#[derive(Debug, Clone, Default)]
+pub struct RawFrame<'a> {
+    /// The frame's category.
+    pub category: Option<StringField>,
+    /// The frame's platform, which determines its family. Defaults to `"other"`.
+    pub platform: Option<StringField>,
+    /// The frame's function name.
+    pub function: Option<StringField>,
+    /// The frame's function name before it was trimmed.
+    pub raw_function: Option<StringField>,
+    /// The frame's module name.
+    pub module: Option<StringField>,
+    /// The frame's package name, as given.
+    pub package: Option<&'a str>,
+    /// The frame's path, as given.
+    pub path: Option<&'a str>,
+    /// How `package` and `path` are lowercased. Defaults to [`Lowercase::Ascii`], see
+    /// [`normalize_path`].
+    pub path_lowercase: Option<Lowercase>,
+    /// How the frame was found while unwinding a native stack.
+    pub trust: Option<StringField>,
+    /// The frame's `in_app` flag.
+    pub in_app: Option<bool>,
+    /// The original `in_app` flag which was set before any grouping code ran.
+    pub orig_in_app: Option<Option<bool>>,
+    /// Whether the frame contains local variables.
+    pub has_vars: Option<bool>,
+}
+
 impl Frame {
     /// Gets the value of `field` from `self`.
     pub fn get_field(&self, field: FrameField) -> Option<&StringField> {
@@ -70,46 +337,202 @@ impl Frame {
             FrameField::Module => self.module.as_ref(),
             FrameField::Package => self.package.as_ref(),
             FrameField::Path => self.path.as_ref(),
-            // NOTE: we never *access* the field via `get_field`.
-            FrameField::App => unreachable!(),
+            FrameField::Trust => self.trust.as_ref(),
+            // NOTE: we never *access* these fields via `get_field`.
+            FrameField::App | FrameField::HasVars => unreachable!(),
+        }
+    }
+
+    /// Creates a frame from the parts given in an event.
+    ///
+    /// `package` and `path` are normalized with [`normalize_path_with`] and the policy in
+    /// `path_lowercase`, so that Windows paths like `C:\Foo\Bar.dll` match the same rules as
+    /// `c:/foo/bar.dll`. Recently normalized values are cached per thread. The family is derived
+    /// from `platform`, which defaults to `"other"`.
+    pub fn from_raw_parts(raw: RawFrame<'_>) -> Self {
+        let platform = raw.platform.unwrap_or_else(|| StringField::new("other"));
+        let lowercase = raw.path_lowercase.unwrap_or(Lowercase::Ascii);
+
+        Self {
+            category: raw.category,
+            family: Families::new(&platform),
+            raw_family: Some(platform),
+            function: raw.function,
+            raw_function: raw.raw_function,
+            module: raw.module,
+            package: raw
+                .package
+                .map(|package| normalize_path_cached(package, lowercase)),
+            path: raw.path.map(|path| normalize_path_cached(path, lowercase)),
+            trust: raw.trust,
+            in_app: raw.in_app,
+            orig_in_app: raw.orig_in_app,
+            has_vars: raw.has_vars,
         }
     }
 
     /// Convenience constructor for use within tests.
     #[cfg(any(test, feature = "testing"))]
     pub fn from_test(raw_frame: &serde_json::Value, platform: &str) -> Self {
-        Self {
+        let get_str = |key: &str| raw_frame.get(key).and_then(|s| s.as_str());
+
+        Self::from_raw_parts(RawFrame {
             category: raw_frame
                 .pointer("/data/category")
                 .and_then(|s| s.as_str())
-                .map(SmolStr::new),
-            family: Families::new(
-                raw_frame
-                    .get("platform")
-                    .and_then(|s| s.as_str())
-                    .unwrap_or(platform),
-            ),
-
-            function: raw_frame
-                .get("function")
-                .and_then(|s| s.as_str())
-                .map(SmolStr::new),
-            module: raw_frame
-                .get("module")
-                .and_then(|s| s.as_str())
-                .map(SmolStr::new),
-            package: raw_frame
-                .get("package")
-                .and_then(|s| s.as_str())
-                .map(|s| SmolStr::new(s.replace('\\', "/").to_lowercase())),
-            path: raw_frame
-                .get("abs_path")
-                .or(raw_frame.get("filename"))
-                .and_then(|s| s.as_str())
-                .map(|s| SmolStr::new(s.replace('\\', "/").to_lowercase())),
-
+                .map(StringField::new),
+            platform: Some(StringField::new(get_str("platform").unwrap_or(platform))),
+            function: get_str("function").map(StringField::new),
+            raw_function: get_str("raw_function").map(StringField::new),
+            module: get_str("module").map(StringField::new),
+            package: get_str("package"),
+            path: get_str("abs_path").or(get_str("filename")),
+            path_lowercase: None,
+            trust: get_str("trust").map(StringField::new),
             in_app: raw_frame.get("in_app").and_then(|s| s.as_bool()),
             orig_in_app: None,
+            has_vars: raw_frame
+                .get("vars")
+                .map(|vars| vars.as_object().is_some_and(|vars| !vars.is_empty())),
+        })
+    }
+}
+
+impl FrameLike for Frame {
+    fn get_field(&self, field: FrameField) -> Option<&str> {
+        Frame::get_field(self, field).map(StringField::as_str)
+    }
+
+    fn family(&self) -> Families {
+        self.family
+    }
+
+    fn raw_family(&self) -> Option<&str> {
+        self.raw_family.as_deref()
+    }
+
+    fn raw_function(&self) -> Option<&str> {
+        self.raw_function.as_deref()
+    }
+
+    fn in_app(&self) -> Option<bool> {
+        self.in_app
+    }
+
+    fn orig_in_app(&self) -> Option<Option<bool>> {
+        self.orig_in_app
+    }
+
+    fn has_vars(&self) -> Option<bool> {
+        self.has_vars
+    }
+}
+
+impl FrameLikeMut for Frame {
+    fn set_in_app(&mut self, in_app: Option<bool>) {
+        self.in_app = in_app;
+    }
+
+    fn set_category(&mut self, category: Option<StringField>) {
+        self.category = category;
+    }
+
+    fn set_has_vars(&mut self, has_vars: Option<bool>) {
+        self.has_vars = has_vars;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashSet;
+
+    use super::*;
+    use crate::enhancers::{Cache, Enhancements};
+
+    #[test]
+    fn string_field_conversions() {
+        let field = StringField::from("foo");
+        assert_eq!(field, StringField::from(String::from("foo")));
+        assert_eq!(field, "foo");
+        assert_eq!(field.len(), 3);
+        assert_eq!(String::from(field.clone()), "foo");
+        assert_eq!(format!("{field} {field:?}"), r#"foo "foo""#);
+
+        let set: HashSet<StringField> = ["foo", "bar"].into_iter().map(Into::into).collect();
+        assert!(set.contains("foo"));
+
+        assert_eq!(normalize_path(r"C:\Foo\Bar.dll"), "c:/foo/bar.dll");
+    }
+
+    #[test]
+    fn normalizes_paths_ascii_only() {
+        // Characters whose Unicode lowercase form has a different length or is a different
+        // character: dotted capital I, Kelvin sign, Ohm sign, and capital sigma.
+        for path in ["İSTANBUL/İ", "\u{212A}ELVIN", "\u{2126}HM", "ΣΑΣ"] {
+            let normalized = normalize_path(path);
+            assert_eq!(normalized.len(), path.len(), "{path}");
+            assert_eq!(normalized.as_str(), path.to_ascii_lowercase(), "{path}");
         }
+        assert_eq!(normalize_path(r"C:\İ\Kelvin"), "c:/İ/kelvin");
+
+        assert_eq!(
+            normalize_path_with("İ\\\u{212A}", Lowercase::Unicode),
+            "i\u{307}/k"
+        );
+        assert_eq!(normalize_path_with(r"C:\Foo", Lowercase::None), "C:/Foo");
+
+        // Path matchers ignore the case of ASCII letters only, so they match the value as is.
+        let enhancements =
+            Enhancements::parse("path:**/İstanbul/* +app", &mut Cache::default()).unwrap();
+        let mut frames = [Frame::from_raw_parts(RawFrame {
+            path: Some(r"C:\İSTANBUL\Main.cs"),
+            ..Default::default()
+        })];
+        enhancements.apply_modifications_to_frames(&mut frames, &Default::default());
+        assert_eq!(frames[0].in_app, Some(true));
+    }
+
+    #[test]
+    fn frame_from_raw_parts() {
+        let frame = Frame::from_raw_parts(RawFrame {
+            function: Some("Main".into()),
+            package: Some(r"C:\Program Files\App\App.EXE"),
+            path: Some(r"src\Main.cs"),
+            ..Default::default()
+        });
+
+        assert_eq!(frame.function.as_deref(), Some("Main"));
+        assert_eq!(
+            frame.package.as_deref(),
+            Some("c:/program files/app/app.exe")
+        );
+        assert_eq!(frame.path.as_deref(), Some("src/main.cs"));
+        assert_eq!(frame.raw_family.as_deref(), Some("other"));
+        assert_eq!(frame.family, Families::new("other"));
+
+        // Cached values are the same as freshly normalized ones.
+        let again = Frame::from_raw_parts(RawFrame {
+            platform: Some("native".into()),
+            path: Some(r"SRC\main.cs"),
+            ..Default::default()
+        });
+        assert_eq!(again.path, frame.path);
+        assert_eq!(again.family, Families::new("native"));
+
+        // Other lowercasing policies don't reuse the values cached for the default one.
+        let frame = |path_lowercase| {
+            Frame::from_raw_parts(RawFrame {
+                path: Some(r"SRC\İ.cs"),
+                path_lowercase,
+                ..Default::default()
+            })
+            .path
+        };
+        assert_eq!(frame(None).as_deref(), Some("src/İ.cs"));
+        assert_eq!(frame(Some(Lowercase::None)).as_deref(), Some("SRC/İ.cs"));
+        assert_eq!(
+            frame(Some(Lowercase::Unicode)).as_deref(),
+            Some("src/i\u{307}.cs")
+        );
     }
 }
@@ -0,0 +1,368 @@
+//! Trimming and normalization of function names.
+//!
+//! This is a port of Sentry's `trim_function_name` logic, which strips argument lists,
+//! template arguments, C++ trailers and compiler-specific lambda names from native function
+//! names so that they are stable across compilers and builds.
+
+use std::borrow::Cow;
+use std::sync::OnceLock;
+
+use regex::Regex;
+
+use super::families::Families;
+
+/// Matches Windows `__stdcall`/`__fastcall` decoration, e.g. `@foo@12`.
+fn windecl_re() -> &'static Regex {
+    static RE: OnceLock<Regex> = OnceLock::new();
+    RE.get_or_init(|| Regex::new(r"^@?(.*?)@[0-9]+$").unwrap())
+}
+
+/// Matches the hash Rust appends to legacy-mangled symbols.
+fn rust_hash_re() -> &'static Regex {
+    static RE: OnceLock<Regex> = OnceLock::new();
+    RE.get_or_init(|| Regex::new(r"::h[a-z0-9]{16}$").unwrap())
+}
+
+/// Matches trailing C++ qualifiers and GCC clone suffixes.
+fn cpp_trailer_re() -> &'static Regex {
+    static RE: OnceLock<Regex> = OnceLock::new();
+    RE.get_or_init(|| Regex::new(r"(\bconst\b|&|\[clone [^\]]*\])$").unwrap())
+}
+
+/// Matches the `T as ` prefix of Rust blanket impls.
+fn rust_blanket_re() -> &'static Regex {
+    static RE: OnceLock<Regex> = OnceLock::new();
+    RE.get_or_init(|| Regex::new(r"^([A-Z] as )").unwrap())
+}
+
+/// Matches the lambda names produced by gcc, msvc, and clang respectively.
+fn lambda_re() -> &'static Regex {
+    static RE: OnceLock<Regex> = OnceLock::new();
+    RE.get_or_init(|| {
+        Regex::new(r"\{lambda\(.*?\)#\d+\}|\blambda_[a-f0-9]{32}\b|\$_\d+\b").unwrap()
+    })
+}
+
+/// Matches the msvc representation of an anonymous namespace.
+fn anon_namespace_re() -> &'static Regex {
+    static RE: OnceLock<Regex> = OnceLock::new();
+    RE.get_or_init(|| Regex::new(r"\?A0x[a-f0-9]{8}::").unwrap())
+}
+
+/// Trims the function name `function` of a frame with the given `family`.
+///
+/// Only native function names are trimmed, all others are returned unchanged.
+pub fn trim(function: &str, family: Families) -> Cow<'_, str> {
+    if !family.matches(Families::new("native")) {
+        return Cow::Borrowed(function);
+    }
+
+    let trimmed = trim_native(function);
+    if trimmed == function {
+        Cow::Borrowed(function)
+    } else {
+        Cow::Owned(trimmed)
+    }
+}
+
+/// Trims a native function name.
+fn trim_native(function: &str) -> String {
+    if function == "<redacted>" || function == "<unknown>" {
+        return function.into();
+    }
+
+    let original_function = function;
+    let mut function = function.trim();
+
+    // Ensure we don't operate on objc functions
+    if function.starts_with('[') || function.starts_with("+[") || function.starts_with("-[") {
+        return function.into();
+    }
+
+    // Chop off C++ trailers
+    while let Some(m) = cpp_trailer_re().find(function) {
+        function = function[..m.start()].trim_end();
+    }
+
+    let function = anon_namespace_re().replace_all(function, "`anonymous namespace'::");
+
+    // `operator<<` and friends mess with our bracket balancing, so we replace them
+    // with characters that are neither used by `split_func_tokens` nor by
+    // `replace_enclosed_string`.
+    let function = function
+        .replace("operator<<", "operator⟨⟨")
+        .replace("operator<", "operator⟨")
+        .replace("operator()", "operator◯")
+        .replace("operator->", "operator⟿")
+        .replace(" -> ", " ⟿ ")
+        .replace("`anonymous namespace'", "〔anonymousnamespace〕");
+
+    // Normalize C++ lambdas, as different compilers use wildly different names for them.
+    let function = lambda_re().replace_all(&function, "lambda");
+
+    // Remove the arguments if there are any.
+    let function = replace_enclosed_string(&function, '(', ')', |value, _| {
+        let value = value.trim();
+        if value == "anonymous namespace" || value == "operator" {
+            format!("({value})")
+        } else {
+            String::new()
+        }
+    });
+
+    // Resolve generic types, with a special case for Rust trait impls like `<Foo as Bar>::baz`.
+    let function = replace_enclosed_string(&function, '<', '>', process_generics);
+
+    let mut tokens = split_func_tokens(&function);
+
+    // MSVC demangles generic operator functions with a space between the
+    // function name and the generics, so we merge those two tokens again.
+    if tokens.len() > 1 && tokens.last().is_some_and(|t| t == "<T>") {
+        tokens.pop();
+        if let Some(last) = tokens.last_mut() {
+            last.push_str(" <T>");
+        }
+    }
+
+    // Find the token which is the function name. Since we chopped off C++ trailers
+    // there are only two cases we care about: the token left of the `->` return marker
+    // (used for instance in Swift), or otherwise the last token.
+    let func_token = match tokens.iter().position(|t| t == "⟿") {
+        Some(idx) => idx.checked_sub(1).and_then(|idx| tokens.get(idx)),
+        None => tokens.last(),
+    };
+
+    let function = match func_token {
+        Some(token) => token
+            .replace('⟨', "<")
+            .replace('◯', "()")
+            .replace('⟿', "->")
+            .replace("〔anonymousnamespace〕", "`anonymous namespace'"),
+        // This really should never happen
+        None => original_function.into(),
+    };
+
+    let function = rust_hash_re().replace(&function, "");
+    windecl_re().replace(&function, "$1").into_owned()
+}
+
+/// Replaces generic arguments with `<T>`, keeping lambdas and leading Rust trait impls intact.
+fn process_generics(value: &str, start: usize) -> String {
+    if value == "lambda" || lambda_re().is_match(value) {
+        return format!("<{value}>");
+    }
+
+    if start > 0 {
+        return "<T>".into();
+    }
+
+    // Prefer the trait for blanket impls
+    let value = rust_blanket_re().replace(value, "");
+    let value = replace_enclosed_string(&value, '<', '>', process_generics);
+    value.split(" as ").next().unwrap_or_default().into()
+}
+
+/// Replaces every outermost `start`…`end` enclosed substring of `s` with the output of `replacement`.
+///
+/// `replacement` is called with the enclosed string (excluding the delimiters) and the byte offset of
+/// the opening delimiter.
+///
+/// Like Sentry's version, this doesn't guard against unbalanced delimiters: an unmatched `end`
+/// drops everything up to the next `start`, so both implementations trim such names the same way.
+fn replace_enclosed_string(
+    s: &str,
+    start: char,
+    end: char,
+    mut replacement: impl FnMut(&str, usize) -> String,
+) -> String {
+    if !s.contains(start) {
+        return s.into();
+    }
+
+    let mut depth = 0isize;
+    let mut result = String::with_capacity(s.len());
+    let mut pair_start = 0;
+
+    for (idx, c) in s.char_indices() {
+        if c == start {
+            if depth == 0 {
+                pair_start = idx;
+            }
+            depth += 1;
+        } else if c == end {
+            depth -= 1;
+            if depth == 0 {
+                let inner = &s[pair_start + start.len_utf8()..idx];
+                result.push_str(&replacement(inner, pair_start));
+            }
+        } else if depth == 0 {
+            result.push(c);
+        }
+    }
+
+    result
+}
+
+/// Splits a function signature into whitespace-separated tokens, keeping bracketed
+/// groups intact.
+fn split_func_tokens(s: &str) -> Vec<String> {
+    fn closing(c: char) -> Option<char> {
+        match c {
+            '(' => Some(')'),
+            '{' => Some('}'),
+            '[' => Some(']'),
+            '<' => Some('>'),
+            _ => None,
+        }
+    }
+
+    let mut tokens = Vec::new();
+    let mut buf = String::new();
+    let mut stack = Vec::new();
+    let mut end = 0;
+
+    for (idx, c) in s.char_indices() {
+        let next = idx + c.len_utf8();
+        if let Some(close) = closing(c) {
+            stack.push(close);
+        } else if stack.last() == Some(&c) {
+            stack.pop();
+            if stack.is_empty() {
+                buf.push_str(&s[end..next]);
+                end = next;
+            }
+        } else if stack.is_empty() {
+            if c.is_whitespace() {
+                if !buf.is_empty() {
+                    tokens.push(std::mem::take(&mut buf));
+                }
+            } else {
+                buf.push_str(&s[end..next]);
+            }
+            end = next;
+        }
+    }
+
+    if !buf.is_empty() {
+        tokens.push(buf);
+    }
+
+    tokens
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[track_caller]
+    fn assert_trim(input: &str, expected: &str) {
+        assert_eq!(trim(input, Families::new("native")), expected, "{input}");
+    }
+
+    #[test]
+    fn trims_native_function_names() {
+        assert_trim("", "");
+        assert_trim("foo", "foo");
+        assert_trim("foo()", "foo");
+        assert_trim("foo(bar)", "foo");
+        assert_trim("foo(bar)::baz", "foo::baz");
+        assert_trim("foo(bar)::baz(meh)", "foo::baz");
+        assert_trim("foo(bar) const", "foo");
+        assert_trim("foo(bar) const &&", "foo");
+        assert_trim("foo(bar) const&&", "foo");
+        assert_trim("foo<int>(bar)", "foo<T>");
+        assert_trim("void foo(bar)", "foo");
+        assert_trim("void foo<int>(bar)", "foo<T>");
+        assert_trim("Foo::operator()(bar)", "Foo::operator()");
+        assert_trim("Foo::operator<(bar)", "Foo::operator<");
+        assert_trim(
+            "std::function<void (int)>::operator()",
+            "std::function<T>::operator()",
+        );
+        assert_trim(
+            "std::basic_ostream<char, std::char_traits<char> >& std::operator<< <std::char_traits<char> >(std::basic_ostream<char, std::char_traits<char> >&, char)",
+            "std::operator<< <T>",
+        );
+        assert_trim(
+            "foo()::`anonymous namespace'::bar()",
+            "foo::`anonymous namespace'::bar",
+        );
+        assert_trim("?A0x1234abcd::bar", "`anonymous namespace'::bar");
+        assert_trim(
+            "trigger_crash_a(int*) [clone .constprop.0]",
+            "trigger_crash_a",
+        );
+        assert_trim(
+            "ShellCorona::screenInvariants() const [clone .cold]",
+            "ShellCorona::screenInvariants",
+        );
+    }
+
+    #[test]
+    fn trims_unbalanced_function_names() {
+        assert_trim("foo) bar(baz)", "foobaz");
+        assert_trim("foo(bar", "foo");
+        assert_trim("foo<bar", "foo");
+    }
+
+    #[test]
+    fn normalizes_lambdas() {
+        assert_trim("main::$_0", "main::lambda");
+        assert_trim("main::$_42", "main::lambda");
+        assert_trim("main::{lambda(int)#1}", "main::lambda");
+        assert_trim("main::{lambda()#42}", "main::lambda");
+        assert_trim(
+            "foo()::{lambda(bar)#1}::operator()",
+            "foo::lambda::operator()",
+        );
+        assert_trim("lambda_7156c3ceaa11256748687ab67e3ef4cd", "lambda");
+        assert_trim(
+            "<lambda_7156c3ceaa11256748687ab67e3ef4cd>::operator()",
+            "<lambda>::operator()",
+        );
+    }
+
+    #[test]
+    fn trims_swift_function_names() {
+        assert_trim(
+            "Swift.String.init(stringInterpolation: Swift.DefaultStringInterpolation) -> Swift.String",
+            "Swift.String.init",
+        );
+        assert_trim(
+            "@objc ViewController.causeCrash(Any) -> ()",
+            "ViewController.causeCrash",
+        );
+    }
+
+    #[test]
+    fn trims_rust_function_names() {
+        assert_trim(
+            "core::panicking::panic::h1234567890abcdef",
+            "core::panicking::panic",
+        );
+        assert_trim(
+            "<core::str::Utf8Error as core::fmt::Debug>::fmt",
+            "core::str::Utf8Error::fmt",
+        );
+        assert_trim("<T as core::any::Any>::type_id", "core::any::Any::type_id");
+    }
+
+    #[test]
+    fn keeps_special_function_names() {
+        assert_trim("<redacted>", "<redacted>");
+        assert_trim("<unknown>", "<unknown>");
+        assert_trim("-[NSObject foo:bar:]", "-[NSObject foo:bar:]");
+        assert_trim("+[NSObject foo:bar:]", "+[NSObject foo:bar:]");
+        assert_trim("@_foo@12", "_foo");
+    }
+
+    #[test]
+    fn ignores_non_native_frames() {
+        let function = "foo(bar)";
+        assert_eq!(trim(function, Families::new("javascript")), "foo(bar)");
+        assert!(matches!(
+            trim(function, Families::new("other")),
+            Cow::Borrowed(_)
+        ));
+    }
+}
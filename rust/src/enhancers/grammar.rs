@@ -4,36 +4,26 @@
 //! <https://github.com/getsentry/sentry/blob/e5c5e56d176d96081ce4b25424e6ec7d3ba17cff/src/sentry/grouping/enhancer/__init__.py#L42-L79>
 
 // TODO:
-// - quoted identifiers/arguments should properly support escapes, etc
+// - quoted matcher arguments should properly support escapes, etc
 
 use std::borrow::Cow;
+use std::num::{IntErrorKind, NonZeroUsize};
 
-use anyhow::{anyhow, Context};
+use anyhow::Context;
 
 use super::actions::{Action, FlagAction, FlagActionType, Range, VarAction};
 use super::matchers::{FrameOffset, Matcher};
+use super::parse_error::{SyntaxContext, SyntaxError};
 use super::rules::Rule;
+use super::ParseErrorKind::*;
 use super::RegexCache;
 
-/// Possible prefixes of a matcher definition.
-/// Matchers always start with one of these,
-/// and actions never do. This means that if
-/// the rest of the input starts with one these,
-/// there is another matcher to parse, and if it doesn't,
-/// there isn't.
-const MATCHER_LOOKAHEAD: [&str; 11] = [
-    "!",
-    "a",
-    "category:",
-    "e",
-    "f",
-    "me",
-    "mo",
-    "p",
-    "s",
-    "t",
-    "va",
-];
+/// Returns early with a [`SyntaxError`] of the given kind at `$rest`, like [`anyhow::bail!`].
+macro_rules! bail_at {
+    ($kind:expr, $rest:expr, $($message:tt)+) => {
+        return Err(SyntaxError::new($kind, $rest, format!($($message)+)).into())
+    };
+}
 
 /// Strips the prefix `pat` from `input` and returns the rest.
 ///
@@ -41,7 +31,7 @@ const MATCHER_LOOKAHEAD: [&str; 11] = [
 fn expect<'a>(input: &'a str, pat: &str) -> anyhow::Result<&'a str> {
     input
         .strip_prefix(pat)
-        .ok_or_else(|| anyhow!("at `{input}`: expected `{pat}`"))
+        .ok_or_else(|| SyntaxError::new(Syntax, input, format!("expected `{pat}`")).into())
 }
 
 /// Parses a string into a bool.
@@ -60,7 +50,7 @@ fn bool(input: &str) -> anyhow::Result<bool> {
 /// Parses an "identifier" and returns it together with the rest of the input.
 ///
 /// An "identifier" is defined by the regex `[a-zA-Z0-9_.-]+`.
-fn ident(input: &str) -> anyhow::Result<(&str, &str)> {
+pub(crate) fn ident(input: &str) -> anyhow::Result<(&str, &str)> {
     let Some(end) =
         input.find(|c: char| !(c.is_ascii_alphanumeric() || matches!(c, '_' | '.' | '-')))
     else {
@@ -68,7 +58,7 @@ fn ident(input: &str) -> anyhow::Result<(&str, &str)> {
     };
 
     if end == 0 {
-        anyhow::bail!("at `{input}`: invalid identifier");
+        bail_at!(Syntax, input, "invalid identifier");
     }
 
     Ok(input.split_at(end))
@@ -81,11 +71,13 @@ fn ident(input: &str) -> anyhow::Result<(&str, &str)> {
 /// non-`"` characters enclosed in `""`.
 ///
 /// Escaped characters in the argument are unescaped.
-fn argument(input: &str) -> anyhow::Result<(Cow<str>, &str)> {
+///
+/// An unquoted argument must not be empty. The empty argument has to be written as `""`.
+fn argument(input: &str) -> anyhow::Result<(Cow<'_, str>, &str)> {
     let (result, rest) = if let Some(rest) = input.strip_prefix('"') {
         let end = rest
             .find('"')
-            .ok_or_else(|| anyhow!("at `{input}`: unclosed `\"`"))?;
+            .ok_or_else(|| SyntaxError::new(Syntax, input, "unclosed `\"`"))?;
         let result = &rest[..end];
         let rest = &rest[end + 1..];
         (result, rest)
@@ -96,6 +88,14 @@ fn argument(input: &str) -> anyhow::Result<(Cow<str>, &str)> {
         }
     };
 
+    if result.is_empty() && !input.starts_with('"') {
+        bail_at!(
+            Syntax,
+            input,
+            "expected argument, use `\"\"` to match empty values"
+        );
+    }
+
     // TODO: support even more escapes
     let unescaped = if result.contains("\\\\") {
         result.replace("\\\\", "\\").into()
@@ -106,34 +106,76 @@ fn argument(input: &str) -> anyhow::Result<(Cow<str>, &str)> {
     Ok((unescaped, rest))
 }
 
+/// Parses the right-hand side of a [`VarAction`] and returns it together with the rest of the
+/// input.
+///
+/// The value is either an [identifier](ident) or any sequence of characters enclosed in `""`,
+/// in which `\"` and `\\` stand for `"` and `\`.
+fn var_value(input: &str) -> anyhow::Result<(Cow<'_, str>, &str)> {
+    let Some(quoted) = input.strip_prefix('"') else {
+        let (value, rest) = ident(input)?;
+        return Ok((value.into(), rest));
+    };
+
+    let mut value = String::new();
+    let mut chars = quoted.char_indices();
+    while let Some((i, c)) = chars.next() {
+        match c {
+            '"' => return Ok((value.into(), &quoted[i + 1..])),
+            '\\' => match chars.next() {
+                Some((_, c @ ('"' | '\\'))) => value.push(c),
+                Some(_) => bail_at!(Syntax, &quoted[i..], "invalid escape sequence"),
+                None => break,
+            },
+            c => value.push(c),
+        }
+    }
+
+    bail_at!(Syntax, input, "unclosed `\"`")
+}
+
+/// Parses the value of the frame count variable `name`.
+///
+/// The value must be a non-negative number that fits into a `usize`. `0`
+/// means "unset" and is returned as `None`.
+fn frame_count(name: &str, input: &str) -> anyhow::Result<Option<NonZeroUsize>> {
+    number(name, input).map(NonZeroUsize::new)
+}
+
+/// Parses the value of the numeric variable `name`.
+///
+/// The value must be a non-negative number that fits into a `usize`.
+fn number(name: &str, input: &str) -> anyhow::Result<usize> {
+    match input.parse::<usize>() {
+        Ok(n) => Ok(n),
+        Err(e) if *e.kind() == IntErrorKind::PosOverflow => {
+            anyhow::bail!("at `{input}`: value of `{name}` is too large")
+        }
+        Err(_) if input.starts_with('-') => {
+            anyhow::bail!("at `{input}`: value of `{name}` must not be negative")
+        }
+        Err(_) => anyhow::bail!("at `{input}`: value of `{name}` must be a number"),
+    }
+}
+
 /// Parses a [`VarAction`] and returns it together with the rest of the input.
 fn var_action(input: &str) -> anyhow::Result<(VarAction, &str)> {
     let input = input.trim_start();
 
-    let (lhs, after_lhs) =
-        ident(input).with_context(|| format!("at `{input}`: expected variable name"))?;
+    let (lhs, after_lhs) = ident(input).context_at(Syntax, input, "expected variable name")?;
 
     let after_lhs = after_lhs.trim_start();
 
     let after_eq = expect(after_lhs, "=")?.trim_start();
 
     let (rhs, rest) =
-        ident(after_eq).with_context(|| format!("at `{after_eq}`: expected value for variable"))?;
+        var_value(after_eq).context_at(Syntax, after_eq, "expected value for variable")?;
+    let rhs = &*rhs;
 
     let a = match lhs {
-        "max-frames" => {
-            let n = rhs
-                .parse()
-                .with_context(|| format!("at `{rhs}`: failed to parse rhs of `max-frames`"))?;
-            VarAction::MaxFrames(n)
-        }
+        "max-frames" => VarAction::MaxFrames(frame_count(lhs, rhs)?),
 
-        "min-frames" => {
-            let n = rhs
-                .parse()
-                .with_context(|| format!("at `{rhs}`: failed to parse rhs of `min-frames`"))?;
-            VarAction::MinFrames(n)
-        }
+        "min-frames" => VarAction::MinFrames(frame_count(lhs, rhs)?),
 
         "invert-stacktrace" => {
             let b = bool(rhs).with_context(|| {
@@ -144,7 +186,9 @@ fn var_action(input: &str) -> anyhow::Result<(VarAction, &str)> {
 
         "category" => VarAction::Category(rhs.into()),
 
-        _ => anyhow::bail!("at `{input}`: invalid variable name `{lhs}`"),
+        "priority" => VarAction::Priority(number(lhs, rhs)?),
+
+        _ => bail_at!(Syntax, input, "invalid variable name `{lhs}`"),
     };
 
     Ok((a, rest))
@@ -158,6 +202,8 @@ fn flag_action(input: &str) -> anyhow::Result<(FlagAction, &str)> {
         (Some(Range::Up), rest)
     } else if let Some(rest) = input.strip_prefix('v') {
         (Some(Range::Down), rest)
+    } else if let Some(rest) = input.strip_prefix('~') {
+        (Some(Range::Siblings), rest)
     } else {
         (None, input)
     };
@@ -167,29 +213,29 @@ fn flag_action(input: &str) -> anyhow::Result<(FlagAction, &str)> {
     } else if let Some(rest) = after_range.strip_prefix('-') {
         (false, rest)
     } else {
-        anyhow::bail!("at `{input}`: expected flag value");
+        bail_at!(Syntax, input, "expected flag value");
     };
 
-    let (name, rest) =
-        ident(after_flag).with_context(|| format!("at `{after_flag}`: expected flag name"))?;
+    let (name, rest) = ident(after_flag).context_at(Syntax, after_flag, "expected flag name")?;
 
     let ty = match name {
         "app" => FlagActionType::App,
         "group" => FlagActionType::Group,
-        _ => anyhow::bail!("at `{after_flag}`: invalid flag name `{name}`"),
+        "vars" if flag => bail_at!(Syntax, input, "the `vars` flag can only be unset"),
+        "vars" => FlagActionType::Vars,
+        _ => bail_at!(Syntax, after_flag, "invalid flag name `{name}`"),
     };
 
     Ok((FlagAction { flag, ty, range }, rest))
 }
 
-/// Parses a sequence of [`Actions`](Action) and returns it.
+/// Parses a sequence of [`Actions`](Action) and returns it together with the trailing comment, if any.
 ///
 /// The sequence must contain at least one action.
 ///
 /// Since actions are the last part of a rule definition and can only
-/// be followed by whitespace or a comment, there is no point in returning the
-/// rest of the input.
-fn actions(input: &str) -> anyhow::Result<Vec<Action>> {
+/// be followed by whitespace or a comment, the comment is all that can remain of the input.
+fn actions(input: &str) -> anyhow::Result<(Vec<Action>, Option<&str>)> {
     let mut input = input.trim_start();
 
     let mut result = Vec::new();
@@ -197,15 +243,18 @@ fn actions(input: &str) -> anyhow::Result<Vec<Action>> {
     // we're done with actions if there's either nothing or just a comment remaining.
     while !input.is_empty() && !input.starts_with('#') {
         // flag actions always start with one of these characters, and var actions never do.
-        if input.starts_with(['v', '^', '+', '-']) {
-            let (action, after_action) = flag_action(input)
-                .with_context(|| format!("at `{input}`: failed to parse flag action"))?;
+        if input.starts_with(['v', '^', '~', '+', '-']) {
+            let (action, after_action) = flag_action(input).context_at(
+                InvalidAction,
+                input,
+                "failed to parse flag action",
+            )?;
 
             result.push(Action::Flag(action));
             input = after_action.trim_start();
         } else {
-            let (action, after_action) = var_action(input)
-                .with_context(|| format!("at `{input}`: failed to parse var action"))?;
+            let (action, after_action) =
+                var_action(input).context_at(InvalidAction, input, "failed to parse var action")?;
 
             result.push(Action::Var(action));
             input = after_action.trim_start();
@@ -213,18 +262,27 @@ fn actions(input: &str) -> anyhow::Result<Vec<Action>> {
     }
 
     if result.is_empty() {
-        anyhow::bail!("expected at least one action");
+        bail_at!(MissingAction, input, "expected at least one action");
     }
 
-    Ok(result)
+    let comment = (!input.is_empty()).then(|| input.trim_end());
+    Ok((result, comment))
 }
 
-/// Parses a [`Matcher`] and returns it together with the rest of the input.
-fn matcher<'a>(
-    input: &'a str,
-    frame_offset: FrameOffset,
-    regex_cache: &mut RegexCache,
-) -> anyhow::Result<(Matcher, &'a str)> {
+/// A matcher as it is written, before its name and argument are checked.
+struct MatcherToken<'a> {
+    negated: bool,
+    name: &'a str,
+    argument: Cow<'a, str>,
+}
+
+/// Splits a matcher off the start of `input` and returns it together with the rest of the input.
+///
+/// A matcher is written as `[ "!" ] <identifier> ":" <argument>`. Returns `None` if `input`
+/// doesn't start with an identifier directly followed by `:`, which is how actions are told
+/// apart from matchers. In that case nothing is consumed. Once a `!` or `<identifier>:` has been
+/// read, the input has to be a matcher and malformed input is an error.
+fn matcher_token(input: &str) -> anyhow::Result<Option<(MatcherToken<'_>, &str)>> {
     let input = input.trim_start();
 
     let (negated, before_name) = if let Some(rest) = input.strip_prefix('!') {
@@ -233,27 +291,73 @@ fn matcher<'a>(
         (false, input)
     };
 
-    let (name, after_name) = ident(before_name)
-        .with_context(|| format!("at `{before_name}`: failed to parse matcher name"))?;
+    let before_arg = match ident(before_name) {
+        Ok((name, after_name)) => after_name.strip_prefix(':').map(|rest| (name, rest)),
+        Err(_) => None,
+    };
+    let Some((name, before_arg)) = before_arg else {
+        if negated {
+            bail_at!(Syntax, before_name, "failed to parse matcher name");
+        }
+        return Ok(None);
+    };
 
-    let before_arg = expect(after_name, ":")?;
+    let (argument, rest) =
+        argument(before_arg).context_at(Syntax, before_arg, "failed to parse matcher argument")?;
 
-    let (arg, rest) = argument(before_arg)
-        .with_context(|| format!("at `{before_arg}`: failed to parse matcher argument"))?;
+    let token = MatcherToken {
+        negated,
+        name,
+        argument,
+    };
+    Ok(Some((token, rest)))
+}
 
-    let m = Matcher::new(negated, name, &arg, frame_offset, regex_cache)?;
-    Ok((m, rest))
+/// Skips a matcher at the start of `input` without constructing it and returns the rest of the input.
+///
+/// Returns `None` if `input` doesn't start with a matcher, like [`matcher_token`].
+pub(crate) fn skip_matcher(input: &str) -> anyhow::Result<Option<&str>> {
+    Ok(matcher_token(input)?.map(|(_, rest)| rest))
 }
 
-/// Parses the caller matcher in a rule and returns it together with the rest of the input.
+/// Parses a [`Matcher`] and returns it together with its name as written and the rest of the input.
+fn matcher<'a>(
+    input: &'a str,
+    frame_offset: FrameOffset,
+    regex_cache: &mut RegexCache,
+) -> anyhow::Result<(Matcher, &'a str, &'a str)> {
+    let input = input.trim_start();
+    let (token, rest) =
+        matcher_token(input)?.ok_or_else(|| SyntaxError::new(Syntax, input, "expected matcher"))?;
+
+    let m = build_matcher(&token, frame_offset, regex_cache)?;
+    Ok((m, token.name, rest))
+}
+
+/// Constructs the [`Matcher`] written as `token`.
+fn build_matcher(
+    token: &MatcherToken<'_>,
+    frame_offset: FrameOffset,
+    regex_cache: &mut RegexCache,
+) -> anyhow::Result<Matcher> {
+    Matcher::new(
+        token.negated,
+        token.name,
+        &token.argument,
+        frame_offset,
+        regex_cache,
+    )
+}
+
+/// Parses the caller matcher in a rule and returns it together with its name and the rest of the input.
 ///
 /// A caller matcher is defined as `[ <matcher> ] |`.
 /// NB: This function assumes that the leading `[` has already been consumed!
 fn caller_matcher<'a>(
     input: &'a str,
     regex_cache: &mut RegexCache,
-) -> anyhow::Result<(Matcher, &'a str)> {
-    let (matcher, rest) = matcher(input, FrameOffset::Caller, regex_cache)?;
+) -> anyhow::Result<(Matcher, &'a str, &'a str)> {
+    let (matcher, name, rest) = matcher(input, FrameOffset::Caller, regex_cache)?;
 
     let rest = rest.trim_start();
     let rest = expect(rest, "]")?;
@@ -261,35 +365,38 @@ fn caller_matcher<'a>(
     let rest = rest.trim_start();
     let rest = expect(rest, "|")?;
 
-    Ok((matcher, rest))
+    Ok((matcher, name, rest))
 }
 
-/// Parses the callee matcher in a rule and returns it together with the rest of the input.
+/// Parses the callee matcher in a rule and returns it together with its name and the rest of the input.
 ///
 /// A callee matcher is defined as `| [ <matcher> ] `.
 /// NB: This function assumes that the leading `|` has already been consumed!
 fn callee_matcher<'a>(
     input: &'a str,
     regex_cache: &mut RegexCache,
-) -> anyhow::Result<(Matcher, &'a str)> {
+) -> anyhow::Result<(Matcher, &'a str, &'a str)> {
     let rest = input.trim_start();
     let rest = expect(rest, "[")?;
 
-    let (matcher, rest) = matcher(rest, FrameOffset::Callee, regex_cache)?;
+    let (matcher, name, rest) = matcher(rest, FrameOffset::Callee, regex_cache)?;
 
     let rest = rest.trim_start();
     let rest = expect(rest, "]")?;
 
-    Ok((matcher, rest))
+    Ok((matcher, name, rest))
 }
 
 /// Parses a sequence of [`Matchers`](Matcher) and returns it
 /// together with the rest of the input.
 ///
+/// The names of the matchers, as written, are appended to `names`.
+///
 /// The sequence must contain at least one matcher.
 fn matchers<'a>(
     input: &'a str,
     regex_cache: &mut RegexCache,
+    names: &mut Vec<&'a str>,
 ) -> anyhow::Result<(Vec<Matcher>, &'a str)> {
     let mut input = input.trim_start();
 
@@ -297,10 +404,14 @@ fn matchers<'a>(
 
     // A `[` at the start means we have a caller matcher
     if let Some(rest) = input.strip_prefix('[') {
-        let (caller_matcher, rest) = caller_matcher(rest, regex_cache)
-            .with_context(|| format!("at `{input}`: failed to parse caller matcher"))?;
+        let (caller_matcher, name, rest) = caller_matcher(rest, regex_cache).context_at(
+            InvalidMatcher,
+            input,
+            "failed to parse caller matcher",
+        )?;
 
         result.push(caller_matcher);
+        names.push(name);
 
         input = rest.trim_start()
     }
@@ -308,27 +419,35 @@ fn matchers<'a>(
     // Keep track of whether we've parsed at least one matcher
     let mut parsed = false;
 
-    while MATCHER_LOOKAHEAD
-        .iter()
-        .any(|prefix| input.starts_with(prefix))
+    // Matchers continue until something that isn't a matcher, i.e. the actions, follows.
+    while let Some((token, rest)) =
+        matcher_token(input).context_at(InvalidMatcher, input, "failed to parse matcher")?
     {
-        let (m, rest) = matcher(input, FrameOffset::None, regex_cache)
-            .with_context(|| format!("at `{input}`: failed to parse matcher"))?;
+        let m = build_matcher(&token, FrameOffset::None, regex_cache).context_at(
+            InvalidMatcher,
+            input,
+            "failed to parse matcher",
+        )?;
         result.push(m);
+        names.push(token.name);
         input = rest.trim_start();
         parsed = true;
     }
 
     if !parsed {
-        anyhow::bail!("at `{input}`: expected at least one matcher");
+        bail_at!(MissingMatcher, input, "expected at least one matcher");
     }
 
     // A `|` after the main list of matchers means we have a callee matcher.
     if let Some(rest) = input.strip_prefix('|') {
-        let (callee_matcher, rest) = callee_matcher(rest, regex_cache)
-            .with_context(|| format!("at `{input}`: failed to parse callee matcher"))?;
+        let (callee_matcher, name, rest) = callee_matcher(rest, regex_cache).context_at(
+            InvalidMatcher,
+            input,
+            "failed to parse callee matcher",
+        )?;
 
         result.push(callee_matcher);
+        names.push(name);
         input = rest;
     }
 
@@ -339,12 +458,34 @@ fn matchers<'a>(
 ///
 /// `regex_cache` is used to memoize the construction of regexes.
 pub fn parse_rule(input: &str, regex_cache: &mut RegexCache) -> anyhow::Result<Rule> {
-    let (matchers, after_matchers) = matchers(input, regex_cache)
+    parse_rule_with_syntax(input, regex_cache).map(|(rule, _)| rule)
+}
+
+/// The parts of a rule's text that don't end up in the parsed [`Rule`].
+#[derive(Debug, Default)]
+pub(crate) struct RuleSyntax<'a> {
+    /// The names of the matchers as they were written, in order of appearance.
+    pub(crate) matcher_names: Vec<&'a str>,
+    /// The comment at the end of the rule, including the leading `#`.
+    pub(crate) comment: Option<&'a str>,
+}
+
+/// Parses a [`Rule`] like [`parse_rule`], additionally returning the [`RuleSyntax`] it was written in.
+pub(crate) fn parse_rule_with_syntax<'a>(
+    input: &'a str,
+    regex_cache: &mut RegexCache,
+) -> anyhow::Result<(Rule, RuleSyntax<'a>)> {
+    let mut matcher_names = Vec::new();
+    let (matchers, after_matchers) = matchers(input, regex_cache, &mut matcher_names)
         .with_context(|| format!("at `{input}`: failed to parse matchers"))?;
-    let actions = actions(after_matchers)
+    let (actions, comment) = actions(after_matchers)
         .with_context(|| format!("at `{after_matchers}`: failed to parse actions"))?;
 
-    Ok(Rule::new(matchers, actions))
+    let syntax = RuleSyntax {
+        matcher_names,
+        comment,
+    };
+    Ok((Rule::new(matchers, actions), syntax))
 }
 
 #[cfg(test)]
@@ -370,12 +511,26 @@ mod tests {
         let matcher = matcher.into_matcher(&mut Default::default()).unwrap();
         match matcher {
             Matcher::Frame(frame) => {
-                assert!(!frame.matches_frame(frames, 0));
+                assert!(!frame.matches_frame(frames, 0, Default::default()));
             }
             Matcher::Exception(_) => unreachable!(),
         }
 
         let _rule = parse_rule("stack.module:[foo:bar/* -app", &mut Default::default()).unwrap();
+
+        // Literal patterns match the selector without escaping.
+        let rule = parse_rule("stack.function:=-[* -app", &mut RegexCache::default()).unwrap();
+        assert!(rule.matches_frame(frames, 0));
+        assert_eq!(format!("{rule:#}"), "function:=-[* -app");
+
+        let matcher: EncodedMatcher = serde_json::from_str(r#""f=-[*""#).unwrap();
+        let matcher = matcher.into_matcher(&mut Default::default()).unwrap();
+        match matcher {
+            Matcher::Frame(frame) => {
+                assert!(frame.matches_frame(frames, 0, Default::default()));
+            }
+            Matcher::Exception(_) => unreachable!(),
+        }
     }
 
     #[test]
@@ -395,4 +550,196 @@ mod tests {
         assert!(!rule.matches_frame(frames, 1));
         assert!(!rule.matches_frame(frames, 2));
     }
+
+    #[test]
+    fn all_matcher_names() {
+        let matchers = [
+            ("module", "foo"),
+            ("stack.module", "foo"),
+            ("function", "foo"),
+            ("stack.function", "foo"),
+            ("category", "foo"),
+            ("path", "foo"),
+            ("stack.abs_path", "foo"),
+            ("package", "foo"),
+            ("stack.package", "foo"),
+            ("trust", "scan"),
+            ("stack.trust", "scan"),
+            ("family", "native"),
+            ("app", "yes"),
+            ("app", "unset"),
+            ("has_vars", "yes"),
+            ("stack.has_vars", "yes"),
+            ("type", "foo"),
+            ("error.type", "foo"),
+            ("value", "foo"),
+            ("error.value", "foo"),
+            ("mechanism", "foo"),
+            ("error.mechanism", "foo"),
+            ("thread.name", "foo"),
+            ("thread.id", "1"),
+            ("thread.main", "yes"),
+            ("thread.crashed", "no"),
+        ];
+
+        for (name, arg) in matchers {
+            for negation in ["", "!"] {
+                let input = format!("{negation}{name}:{arg} app:yes category=x -app");
+                let (rule, syntax) = parse_rule_with_syntax(&input, &mut Default::default())
+                    .unwrap_or_else(|e| panic!("{input}: {e:#}"));
+                assert_eq!(syntax.matcher_names, [name, "app"], "{input}");
+                assert_eq!(rule.0.actions.len(), 2, "{input}");
+
+                let reparsed = parse_rule(&format!("{rule:#}"), &mut Default::default()).unwrap();
+                assert_eq!(reparsed, rule, "{input}");
+            }
+        }
+
+        // Unknown matchers are reported as such instead of being mistaken for actions.
+        let err = parse_rule("thread.state:yes -app", &mut Default::default()).unwrap_err();
+        assert_eq!(
+            err.root_cause().to_string(),
+            "Unknown matcher `thread.state`"
+        );
+        let err = parse_rule("!+app", &mut Default::default()).unwrap_err();
+        assert!(format!("{err:#}").contains("failed to parse matcher name"));
+    }
+
+    #[test]
+    fn empty_matcher_argument() {
+        for input in [
+            "function: -app",
+            "function:",
+            "[ function: ] | module:foo -app",
+        ] {
+            let err = parse_rule(input, &mut Default::default()).unwrap_err();
+            assert!(
+                format!("{err:#}").contains("use `\"\"` to match empty values"),
+                "{input}: {err:#}"
+            );
+        }
+
+        let err = parse_rule("family:, -app", &mut Default::default()).unwrap_err();
+        assert_eq!(
+            err.root_cause().to_string(),
+            "`family` matcher needs at least one family"
+        );
+
+        let frames = &[
+            Frame::from_test(&json!({}), "native"),
+            Frame::from_test(&json!({"function": ""}), "native"),
+            Frame::from_test(&json!({"function": "foo"}), "native"),
+        ];
+        for input in [r#"function:"" -app"#, r#"!function:"" -app"#] {
+            let rule = parse_rule(input, &mut Default::default()).unwrap();
+            let negated = input.starts_with('!');
+            assert_eq!(rule.matches_frame(frames, 0), negated, "{input}");
+            assert_eq!(rule.matches_frame(frames, 1), !negated, "{input}");
+            assert_eq!(rule.matches_frame(frames, 2), negated, "{input}");
+            assert_eq!(format!("{rule:#}"), input);
+        }
+
+        let matcher: EncodedMatcher = serde_json::from_str(r#""f""#).unwrap();
+        let Matcher::Frame(matcher) = matcher.into_matcher(&mut Default::default()).unwrap() else {
+            unreachable!()
+        };
+        assert!(!matcher.matches_frame(frames, 0, Default::default()));
+        assert!(matcher.matches_frame(frames, 1, Default::default()));
+    }
+
+    #[test]
+    fn quoted_var_values() {
+        let rule = parse_rule(
+            r#"function:foo category="Lcom.foo bar" category="a\"b\\c" category="""#,
+            &mut Default::default(),
+        )
+        .unwrap();
+        assert_eq!(
+            rule.0.actions,
+            [
+                Action::Var(VarAction::Category("Lcom.foo bar".into())),
+                Action::Var(VarAction::Category(r#"a"b\c"#.into())),
+                Action::Var(VarAction::Category("".into())),
+            ]
+        );
+        assert_eq!(
+            rule.to_string(),
+            r#"function:foo category="Lcom.foo bar" category="a\"b\\c" category="""#
+        );
+
+        let rule = parse_rule(
+            r#"function:foo category="ui" max-frames="3""#,
+            &mut Default::default(),
+        )
+        .unwrap();
+        assert_eq!(rule.to_string(), "function:foo category=ui max-frames=3");
+
+        let err = |input| {
+            parse_rule(input, &mut Default::default())
+                .unwrap_err()
+                .root_cause()
+                .to_string()
+        };
+        assert_eq!(
+            err(r#"function:foo category="foo bar"#),
+            r#"at `"foo bar`: unclosed `"`"#
+        );
+        assert_eq!(
+            err(r#"function:foo category="foo\n""#),
+            r#"at `\n"`: invalid escape sequence"#
+        );
+    }
+
+    #[test]
+    fn frame_count_actions() {
+        let rule = parse_rule(
+            "function:foo max-frames=0 min-frames=3",
+            &mut Default::default(),
+        )
+        .unwrap();
+        assert_eq!(
+            rule.0.actions,
+            [
+                Action::Var(VarAction::MaxFrames(None)),
+                Action::Var(VarAction::MinFrames(NonZeroUsize::new(3))),
+            ]
+        );
+        assert_eq!(rule.to_string(), "function:foo max-frames=0 min-frames=3");
+
+        let err = |input| {
+            parse_rule(input, &mut Default::default())
+                .unwrap_err()
+                .root_cause()
+                .to_string()
+        };
+        assert_eq!(
+            err("function:foo max-frames=-1"),
+            "at `-1`: value of `max-frames` must not be negative"
+        );
+        assert_eq!(
+            err("function:foo min-frames=99999999999999999999999"),
+            "at `99999999999999999999999`: value of `min-frames` is too large"
+        );
+        assert_eq!(
+            err("function:foo min-frames=many"),
+            "at `many`: value of `min-frames` must be a number"
+        );
+    }
+
+    #[test]
+    fn priority_action() {
+        let rule = parse_rule("function:foo +app priority=10", &mut Default::default()).unwrap();
+        assert_eq!(rule.priority(), 10);
+        assert_eq!(rule.to_string(), "function:foo +app priority=10");
+        assert!(rule.has_modifier_action());
+
+        let rule = parse_rule("function:foo +app", &mut Default::default()).unwrap();
+        assert_eq!(rule.priority(), 0);
+
+        let err = parse_rule("function:foo +app priority=-1", &mut Default::default())
+            .unwrap_err()
+            .root_cause()
+            .to_string();
+        assert_eq!(err, "at `-1`: value of `priority` must not be negative");
+    }
 }
@@ -0,0 +1,183 @@
+//! Heuristics for the default `in_app` flag of frames.
+//!
+//! Before any enhancement rules are applied, Sentry derives a default `in_app` flag for frames
+//! that the SDK didn't mark, based on where the frame's code lives. These heuristics mirror the
+//! ones applied by Sentry for native, JavaScript, and Java frames.
+
+use super::families::Families;
+use super::frame::{FrameField, FrameLike};
+
+/// Platforms whose frames are treated like native frames, in addition to the `native` family.
+const NATIVE_PLATFORMS: &[&str] = &["c", "cocoa", "objc", "swift"];
+
+/// Package prefixes of system libraries on Linux, Android, and Apple platforms.
+const SYSTEM_PACKAGE_PREFIXES: &[&str] = &[
+    "/lib/",
+    "/usr/lib/",
+    "linux-gate.so",
+    "/apex/com.android.",
+    "/system/",
+    "/library/",
+];
+
+/// Package prefixes of app bundles on iOS devices.
+const APP_BUNDLE_PREFIXES: &[&str] = &[
+    "/var/containers/bundle/application/",
+    "/private/var/containers/bundle/application/",
+];
+
+/// Module prefixes of the Java and Android standard libraries.
+const JAVA_SYSTEM_MODULE_PREFIXES: &[&str] = &[
+    "java.",
+    "javax.",
+    "jdk.",
+    "sun.",
+    "com.sun.",
+    "kotlin.",
+    "kotlinx.",
+    "android.",
+    "androidx.",
+    "dalvik.",
+    "com.android.internal.",
+];
+
+/// Derives the default `in_app` flag of `frame` from its package, path, or module.
+///
+/// * Native frames are not in-app if their package is a system library, and in-app if it is in
+///   an app bundle.
+/// * JavaScript frames are not in-app if their path is in `node_modules`.
+/// * Java frames are not in-app if their module is part of the Java or Android standard library.
+///
+/// Returns `None` if the heuristics don't apply to the frame. The frame's current `in_app` flag
+/// is not taken into account. `package` and `path` are expected to be normalized with
+/// [`normalize_path`](super::normalize_path).
+pub fn default_in_app<F: FrameLike + ?Sized>(frame: &F) -> Option<bool> {
+    let family = frame.family();
+    let platform = frame.raw_family().unwrap_or_default();
+
+    if family.matches(Families::NATIVE) || NATIVE_PLATFORMS.contains(&platform) {
+        frame.get_field(FrameField::Package).and_then(native_in_app)
+    } else if family.matches(Families::JAVASCRIPT) || platform == "node" {
+        frame
+            .get_field(FrameField::Path)
+            .and_then(javascript_in_app)
+    } else if platform == "java" {
+        let module = frame.get_field(FrameField::Module)?;
+        JAVA_SYSTEM_MODULE_PREFIXES
+            .iter()
+            .any(|prefix| module.starts_with(prefix))
+            .then_some(false)
+    } else {
+        None
+    }
+}
+
+/// Derives the `in_app` flag of a native frame from its normalized `package`.
+fn native_in_app(package: &str) -> Option<bool> {
+    let is_app_bundle = APP_BUNDLE_PREFIXES
+        .iter()
+        .any(|prefix| package.starts_with(prefix))
+        || (package.contains("/developer/coresimulator/devices/")
+            && package.contains("/containers/bundle/application/"))
+        || package.contains(".app/contents/");
+    if is_app_bundle {
+        return Some(true);
+    }
+
+    let is_system = SYSTEM_PACKAGE_PREFIXES
+        .iter()
+        .any(|prefix| package.starts_with(prefix))
+        || is_windows_system_path(package);
+    is_system.then_some(false)
+}
+
+/// Checks whether `package` is in the Windows directory of any drive, like `c:/windows/`.
+fn is_windows_system_path(package: &str) -> bool {
+    match package.as_bytes() {
+        [drive, b':', b'/', rest @ ..] => {
+            drive.is_ascii_alphabetic() && rest.starts_with(b"windows")
+        }
+        _ => false,
+    }
+}
+
+/// Derives the `in_app` flag of a JavaScript frame from its normalized `path`.
+fn javascript_in_app(path: &str) -> Option<bool> {
+    let is_dependency = path.contains("/node_modules/") || path.starts_with("webpack:///~/");
+    is_dependency.then_some(false)
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+    use crate::enhancers::{Cache, Enhancements, Frame, ModifyOptions};
+
+    #[test]
+    fn derives_default_in_app() {
+        let cases = [
+            (
+                json!({"package": "/usr/lib/libc.so.6"}),
+                "native",
+                Some(false),
+            ),
+            (
+                json!({"package": r"C:\Windows\System32\ntdll.dll"}),
+                "native",
+                Some(false),
+            ),
+            (
+                json!({"package": "/System/Library/Frameworks/UIKit.framework/UIKit"}),
+                "cocoa",
+                Some(false),
+            ),
+            (
+                json!({"package": "/private/var/containers/Bundle/Application/ABC/App.app/App"}),
+                "cocoa",
+                Some(true),
+            ),
+            (json!({"package": "/opt/app/bin/server"}), "native", None),
+            (json!({"function": "main"}), "native", None),
+            (
+                json!({"abs_path": "/srv/app/node_modules/react/index.js"}),
+                "javascript",
+                Some(false),
+            ),
+            (json!({"abs_path": "/srv/app/src/index.js"}), "node", None),
+            (json!({"module": "java.lang.Thread"}), "java", Some(false)),
+            (json!({"module": "com.example.Main"}), "java", None),
+            (json!({"module": "java.lang.Thread"}), "python", None),
+        ];
+
+        for (frame, platform, expected) in cases {
+            let frame = Frame::from_test(&frame, platform);
+            assert_eq!(default_in_app(&frame), expected, "{frame:?}");
+        }
+
+        let enhancements =
+            Enhancements::parse("function:main +app", &mut Cache::default()).unwrap();
+        let mut frames = vec![
+            Frame::from_test(&json!({"package": "/usr/lib/libc.so.6"}), "native"),
+            Frame::from_test(
+                &json!({"package": "/usr/lib/libfoo.so", "in_app": true}),
+                "native",
+            ),
+            Frame::from_test(
+                &json!({"package": "/usr/lib/libc.so.6", "function": "main"}),
+                "native",
+            ),
+        ];
+        let options = ModifyOptions {
+            derive_default_in_app: true,
+            ..Default::default()
+        };
+        enhancements.apply_modifications_to_frames_with_options(
+            &mut frames,
+            &Default::default(),
+            &options,
+        );
+        let in_app: Vec<_> = frames.iter().map(|f| f.in_app).collect();
+        assert_eq!(in_app, [Some(false), Some(true), Some(true)]);
+    }
+}
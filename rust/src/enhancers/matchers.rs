@@ -7,15 +7,26 @@
 //! See <https://docs.sentry.io/product/data-management-settings/event-grouping/stack-trace-rules/#matchers> for an explanation of how
 //! the various matchers work.
 
+use std::collections::{BTreeSet, HashMap};
 use std::fmt;
+use std::hash::{Hash, Hasher};
 use std::sync::Arc;
 
 use regex::bytes::Regex;
 use smol_str::SmolStr;
 
+#[cfg(feature = "compiled")]
+use super::cache::RegexKey;
+use super::cache::{describe_glob_error, LazyRegex};
+use super::description::{MatcherDescription, MatcherKind, MatcherOffset};
 use super::families::Families;
-use super::frame::{Frame, FrameField};
-use super::{ExceptionData, RegexCache};
+use super::frame::{FrameField, FrameLike};
+use super::rules::Rule;
+use super::warnings::WarningKind;
+use super::{ExceptionData, MissingValue, MissingValuePolicy, RegexCache, ThreadData};
+
+/// The value [`MissingValue::Unknown`] substitutes for missing values.
+const UNKNOWN_VALUE: &str = "<unknown>";
 
 /// Enum that wraps a frame or exception matcher.
 ///
@@ -39,6 +50,7 @@ impl Matcher {
             frame_offset,
             inner,
             raw_pattern: SmolStr::new(raw_pattern),
+            missing: MissingValuePolicy::default().frames,
         })
     }
 
@@ -48,11 +60,15 @@ impl Matcher {
     /// * `negated`: Whether the matcher should be negated.
     /// * `matcher_type`: The matcher's type, e.g. `module` or `mechanism`.
     /// * `raw_pattern`: The raw pattern values are matched against. This argument's format depends
-    ///   on the matcher type: for `app`, it is a pseudo-boolean; for `family`, a comma-separated list
-    ///   of families; for all others, a glob pattern.
+    ///   on the matcher type: for `app` and `has_vars`, it is a pseudo-boolean, or `unset` for `app`; for `family`, a comma-separated list
+    ///   of families; for `function`, a comma-separated list of glob patterns; for all others, a glob pattern.
     /// * frame_offset: Determines whether this matcher should match a frame by checking the frame itself
     ///   or one of its adjacent frames. This only applies to frame matchers, not exception matchers.
     /// * `regex_cache`: A cache for regexes.
+    ///
+    /// An empty `raw_pattern` is valid for field and exception matchers, which then only match
+    /// values that are present and empty. `app` and `has_vars` treat it like any other unrecognized value,
+    /// and `family` rejects it since it couldn't match any frame.
     pub(crate) fn new(
         negated: bool,
         matcher_type: &str,
@@ -65,7 +81,13 @@ impl Matcher {
             "stack.module" | "module" => Ok(Self::new_frame(
                 negated,
                 frame_offset,
-                FrameMatcherInner::new_field(FrameField::Module, false, raw_pattern, regex_cache)?,
+                FrameMatcherInner::new_field(
+                    FrameField::Module,
+                    false,
+                    false,
+                    raw_pattern,
+                    regex_cache,
+                )?,
                 raw_pattern,
             )),
             "stack.function" | "function" => Ok(Self::new_frame(
@@ -74,6 +96,7 @@ impl Matcher {
                 FrameMatcherInner::new_field(
                     FrameField::Function,
                     false,
+                    true,
                     raw_pattern,
                     regex_cache,
                 )?,
@@ -85,6 +108,19 @@ impl Matcher {
                 FrameMatcherInner::new_field(
                     FrameField::Category,
                     false,
+                    false,
+                    raw_pattern,
+                    regex_cache,
+                )?,
+                raw_pattern,
+            )),
+            "stack.trust" | "trust" => Ok(Self::new_frame(
+                negated,
+                frame_offset,
+                FrameMatcherInner::new_field(
+                    FrameField::Trust,
+                    false,
+                    false,
                     raw_pattern,
                     regex_cache,
                 )?,
@@ -95,17 +131,32 @@ impl Matcher {
             "stack.abs_path" | "path" => Ok(Self::new_frame(
                 negated,
                 frame_offset,
-                FrameMatcherInner::new_field(FrameField::Path, true, raw_pattern, regex_cache)?,
+                FrameMatcherInner::new_field(
+                    FrameField::Path,
+                    true,
+                    false,
+                    raw_pattern,
+                    regex_cache,
+                )?,
                 raw_pattern,
             )),
             "stack.package" | "package" => Ok(Self::new_frame(
                 negated,
                 frame_offset,
-                FrameMatcherInner::new_field(FrameField::Package, true, raw_pattern, regex_cache)?,
+                FrameMatcherInner::new_field(
+                    FrameField::Package,
+                    true,
+                    false,
+                    raw_pattern,
+                    regex_cache,
+                )?,
                 raw_pattern,
             )),
 
             // Family matcher
+            "family" if raw_pattern.split(',').all(str::is_empty) => {
+                anyhow::bail!("`family` matcher needs at least one family")
+            }
             "family" => Ok(Self::new_frame(
                 negated,
                 frame_offset,
@@ -121,6 +172,14 @@ impl Matcher {
                 raw_pattern,
             )),
 
+            // HasVars matcher
+            "stack.has_vars" | "has_vars" => Ok(Self::new_frame(
+                negated,
+                frame_offset,
+                FrameMatcherInner::new_has_vars(raw_pattern),
+                raw_pattern,
+            )),
+
             // Exception matchers
             "error.type" | "type" => Ok(Self::Exception(ExceptionMatcher::new_type(
                 negated,
@@ -138,13 +197,39 @@ impl Matcher {
                 ExceptionMatcher::new_mechanism(negated, raw_pattern, regex_cache)?,
             )),
 
+            // Thread matchers
+            "thread.name" => Ok(Self::Exception(ExceptionMatcher::new_thread(
+                negated,
+                ExceptionMatcherType::ThreadName,
+                raw_pattern,
+                regex_cache,
+            )?)),
+            "thread.id" => Ok(Self::Exception(ExceptionMatcher::new_thread(
+                negated,
+                ExceptionMatcherType::ThreadId,
+                raw_pattern,
+                regex_cache,
+            )?)),
+            "thread.main" => Ok(Self::Exception(ExceptionMatcher::new_thread(
+                negated,
+                ExceptionMatcherType::ThreadMain,
+                raw_pattern,
+                regex_cache,
+            )?)),
+            "thread.crashed" => Ok(Self::Exception(ExceptionMatcher::new_thread(
+                negated,
+                ExceptionMatcherType::ThreadCrashed,
+                raw_pattern,
+                regex_cache,
+            )?)),
+
             matcher_type => anyhow::bail!("Unknown matcher `{matcher_type}`"),
         }
     }
 }
 
 /// Denotes whether a frame matcher applies to the current frame or one of the adjacent frames.
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub(crate) enum FrameOffset {
     /// The caller frame, i.e., the one before the current frame.
     Caller,
@@ -167,30 +252,471 @@ pub struct FrameMatcher {
     inner: FrameMatcherInner,
     /// The string pattern this matcher was constructed from. This is used for the `Display` impl.
     raw_pattern: SmolStr,
+    /// How this matcher treats frames without the field it checks.
+    missing: MissingValue,
 }
 
 impl FrameMatcher {
+    /// Returns the key and translated source of this matcher's regex, if it has a valid one.
+    #[cfg(feature = "compiled")]
+    pub(crate) fn translated_regex(&self) -> Option<(&RegexKey, &str)> {
+        match &self.inner {
+            FrameMatcherInner::Field { pattern, .. } => pattern.translated(),
+            _ => None,
+        }
+    }
+
+    /// Returns the glob pattern of this matcher, whether it is a path pattern, and whether it
+    /// consists of comma-separated alternatives, if this matcher checks a string-valued field.
+    pub(crate) fn glob(&self) -> Option<(&str, bool, bool)> {
+        let (FrameMatcherInner::Field { field, .. } | FrameMatcherInner::Noop { field, .. }) =
+            &self.inner
+        else {
+            return None;
+        };
+        let (is_path, alternatives) = match field {
+            FrameField::Path | FrameField::Package => (true, false),
+            FrameField::Function => (false, true),
+            FrameField::Category | FrameField::Module | FrameField::Trust => (false, false),
+            FrameField::App | FrameField::HasVars => return None,
+        };
+        Some((&self.raw_pattern, is_path, alternatives))
+    }
+
+    /// Returns the kind and description of the problem that keeps this matcher from ever
+    /// matching, if there is one.
+    ///
+    /// This compiles the regex of this matcher if it is lazy, to find out whether it is valid.
+    pub(crate) fn warning(&self) -> Option<(WarningKind, String)> {
+        match &self.inner {
+            FrameMatcherInner::Field { pattern, .. } => pattern
+                .error()
+                .map(|err| (WarningKind::InvalidPattern, err.to_string())),
+            FrameMatcherInner::Noop {
+                reason: NoopReason::InvalidPattern(err),
+                ..
+            } => Some((WarningKind::InvalidPattern, err.to_string())),
+            FrameMatcherInner::Noop {
+                reason: NoopReason::InvalidFlag,
+                field,
+            } => {
+                let unset = if *field == FrameField::App {
+                    ", `no`, or `unset`"
+                } else {
+                    ", or `no`"
+                };
+                Some((
+                    WarningKind::InvalidFlag,
+                    format!(
+                        "`{}` is not one of `1`, `true`, `yes`, `0`, `false`{unset}, \
+                        so `{field}` never matches",
+                        self.raw_pattern
+                    ),
+                ))
+            }
+            _ => None,
+        }
+    }
+
+    /// Returns how this matcher treats frames without the field it checks.
+    pub(crate) fn missing_value(&self) -> MissingValue {
+        self.missing
+    }
+
+    /// Sets how this matcher treats frames without the field it checks.
+    pub(crate) fn set_missing_value(&mut self, missing: MissingValue) {
+        self.missing = missing;
+    }
+
+    /// Returns whether this `app` matcher treats an unset `in_app` flag like `false`, or `None`
+    /// if this isn't an `app` matcher.
+    pub(crate) fn unset_in_app_is_false(&self) -> Option<bool> {
+        match self.inner {
+            FrameMatcherInner::InApp { unset_is_false, .. } => Some(unset_is_false),
+            _ => None,
+        }
+    }
+
+    /// Sets whether this matcher treats an unset `in_app` flag like `false`, if it is an `app`
+    /// matcher.
+    pub(crate) fn set_unset_in_app_is_false(&mut self, value: bool) {
+        if let FrameMatcherInner::InApp { unset_is_false, .. } = &mut self.inner {
+            *unset_is_false = value;
+        }
+    }
+
+    /// Returns whether the outcome `matches` of the inner matcher makes this matcher match.
+    ///
+    /// `None` means the frame doesn't have the field the inner matcher checks.
+    fn outcome(&self, matches: Option<bool>) -> bool {
+        match matches {
+            Some(matches) => self.negated ^ matches,
+            None => match self.missing {
+                MissingValue::NoMatch => self.negated,
+                MissingValue::Unknown => self.negated ^ self.inner.matches_value(UNKNOWN_VALUE),
+                MissingValue::NeverMatch => false,
+            },
+        }
+    }
+
+    /// Returns the frame this matcher applies to.
+    pub(crate) fn frame_offset(&self) -> FrameOffset {
+        self.frame_offset
+    }
+
+    /// Returns a structured description of this matcher.
+    pub(crate) fn describe(&self) -> MatcherDescription {
+        MatcherDescription {
+            kind: MatcherKind::Frame,
+            key: self.inner.to_string(),
+            pattern: self.raw_pattern.to_string(),
+            negated: self.negated,
+            offset: match self.frame_offset {
+                FrameOffset::Caller => Some(MatcherOffset::Caller),
+                FrameOffset::Callee => Some(MatcherOffset::Callee),
+                FrameOffset::None => None,
+            },
+        }
+    }
+
+    /// Returns the families a frame must belong to in order to pass this matcher,
+    /// or `None` if this matcher doesn't restrict families.
+    pub fn required_families(&self) -> Option<Families> {
+        match (&self.inner, self.negated) {
+            (FrameMatcherInner::Family { families, .. }, false) => Some(*families),
+            _ => None,
+        }
+    }
+
     /// Tests whether the `i`th frame in `frames` matches.
     ///
     /// Fundamentally this calles `self.inner.matches_frame`. If `self.negated` is true,
     /// that method's result will be flipped. `self.frame_offset` controls whether
     /// `inner.matches_frame` is called on `frames[i]` or one of the adjacent frames.
-    pub fn matches_frame(&self, frames: &[Frame], idx: usize) -> bool {
+    /// `options` are passed on to `inner.matches_frame`.
+    pub(crate) fn matches_frame<F: FrameLike>(
+        &self,
+        frames: &[F],
+        idx: usize,
+        options: MatchOptions,
+    ) -> bool {
+        let Some((_, frame)) = self.target_frame(frames, idx) else {
+            return false;
+        };
+
+        self.outcome(self.inner.matches_frame(frame, options))
+    }
+
+    /// Tests whether the `i`th frame in `frames` matches, like [`matches_frame`](Self::matches_frame),
+    /// looking up and storing the result of matching a field against a regex in `memo`.
+    pub(crate) fn matches_frame_memoized<F: FrameLike>(
+        &self,
+        frames: &[F],
+        idx: usize,
+        memo: &mut MatchMemo,
+    ) -> bool {
+        let Some((idx, frame)) = self.target_frame(frames, idx) else {
+            return false;
+        };
+
+        let shared_id = match &self.inner {
+            FrameMatcherInner::Field { pattern, .. } => memo.shared.id(pattern),
+            _ => None,
+        };
+        let options = memo.options;
+        let matches = match shared_id {
+            Some(id) => {
+                memo.get_or_insert_with(id, idx, || self.inner.matches_frame(frame, options))
+            }
+            None => self.inner.matches_frame(frame, options),
+        };
+
+        self.outcome(matches)
+    }
+
+    /// Returns the index and the frame this matcher checks when testing `frames[idx]`.
+    fn target_frame<'a, F: FrameLike>(
+        &self,
+        frames: &'a [F],
+        idx: usize,
+    ) -> Option<(usize, &'a F)> {
         let idx = match self.frame_offset {
             FrameOffset::Caller => idx.checked_sub(1),
             FrameOffset::Callee => idx.checked_add(1),
             FrameOffset::None => Some(idx),
-        };
+        }?;
 
-        let Some(idx) = idx else {
-            return false;
+        Some((idx, frames.get(idx)?))
+    }
+
+    /// Returns true if the outcome of this matcher depends on the given frame field.
+    pub(crate) fn reads_field(&self, field: FrameField) -> bool {
+        match &self.inner {
+            FrameMatcherInner::Field { field: f, .. } => *f == field,
+            FrameMatcherInner::InApp { .. } => field == FrameField::App,
+            FrameMatcherInner::HasVars { .. } => field == FrameField::HasVars,
+            FrameMatcherInner::Family { .. } | FrameMatcherInner::Noop { .. } => false,
+        }
+    }
+}
+
+/// Ids for the field matchers that occur in more than one rule.
+///
+/// Matchers checking the same field against the same pattern get the same id, regardless
+/// of whether they share their regex through the [`RegexCache`].
+#[derive(Debug, Clone, Default)]
+pub(crate) struct SharedMatchers {
+    /// The addresses of the shared matchers' regexes and their ids, sorted by address.
+    ids: Vec<(usize, usize)>,
+    /// The addresses of the rules containing at least one shared matcher, sorted.
+    rules: Vec<usize>,
+    /// The number of distinct ids.
+    len: usize,
+}
+
+impl SharedMatchers {
+    /// Assigns ids to the field matchers that occur in more than one of `rules`.
+    pub(crate) fn new(rules: &[Rule]) -> Self {
+        let mut occurrences: HashMap<(FrameField, bool, &str), Vec<usize>> = HashMap::new();
+        for matcher in rules.iter().flat_map(|rule| &rule.0.frame_matchers) {
+            if let FrameMatcherInner::Field {
+                field,
+                path_like,
+                pattern,
+            } = &matcher.inner
+            {
+                occurrences
+                    .entry((*field, *path_like, pattern.pattern()))
+                    .or_default()
+                    .push(Arc::as_ptr(pattern) as usize);
+            }
+        }
+
+        let mut ids = Vec::new();
+        let mut len = 0;
+        for addresses in occurrences.into_values().filter(|a| a.len() > 1) {
+            ids.extend(addresses.into_iter().map(|address| (address, len)));
+            len += 1;
+        }
+        ids.sort_unstable();
+        ids.dedup();
+
+        let mut shared = Self {
+            ids,
+            rules: Vec::new(),
+            len,
         };
+        shared.rules = rules
+            .iter()
+            .filter(|rule| {
+                rule.0
+                    .frame_matchers
+                    .iter()
+                    .any(|matcher| match &matcher.inner {
+                        FrameMatcherInner::Field { pattern, .. } => shared.id(pattern).is_some(),
+                        _ => false,
+                    })
+            })
+            .map(|rule| Arc::as_ptr(&rule.0) as usize)
+            .collect();
+        shared.rules.sort_unstable();
+        shared.rules.dedup();
+
+        shared
+    }
+
+    /// Returns true if `rule` contains at least one shared matcher.
+    pub(crate) fn contains_rule(&self, rule: &Rule) -> bool {
+        !self.rules.is_empty()
+            && self
+                .rules
+                .binary_search(&(Arc::as_ptr(&rule.0) as usize))
+                .is_ok()
+    }
+
+    /// Returns the id of the matcher using the regex `pattern`, if it is shared.
+    fn id(&self, pattern: &Arc<LazyRegex>) -> Option<usize> {
+        if self.ids.is_empty() {
+            return None;
+        }
+
+        let address = Arc::as_ptr(pattern) as usize;
+        let pos = self.ids.binary_search_by_key(&address, |(a, _)| *a).ok()?;
+        Some(self.ids[pos].1)
+    }
+}
+
+/// Options that change how frame matchers check a frame.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub(crate) struct MatchOptions {
+    /// Whether `function` matchers also check a frame's [raw function](FrameLike::raw_function).
+    pub(crate) match_raw_function: bool,
+}
+
+/// Memoizes the results of [shared](SharedMatchers) field matchers across rules.
+///
+/// This is only valid as long as the frames aren't modified.
+#[derive(Debug)]
+pub(crate) struct MatchMemo<'a> {
+    shared: &'a SharedMatchers,
+    /// The options all results are computed with.
+    options: MatchOptions,
+    /// The number of frames.
+    frames: usize,
+    /// The result for every combination of shared matcher id and frame index, if already computed.
+    ///
+    /// This is only allocated once the first result is stored.
+    results: Vec<Option<Option<bool>>>,
+}
 
-        let Some(frame) = frames.get(idx) else {
+impl<'a> MatchMemo<'a> {
+    /// Creates an empty memo for the given shared matchers, number of frames, and options.
+    pub(crate) fn new(shared: &'a SharedMatchers, frames: usize, options: MatchOptions) -> Self {
+        Self {
+            shared,
+            options,
+            frames,
+            results: Vec::new(),
+        }
+    }
+
+    /// Stores the results in `results` instead of a new allocation.
+    pub(crate) fn with_results(mut self, mut results: Vec<Option<Option<bool>>>) -> Self {
+        results.clear();
+        self.results = results;
+        self
+    }
+
+    /// Returns the allocation of the results, e.g. to pass it to [`with_results`](Self::with_results)
+    /// of another memo.
+    pub(crate) fn into_results(self) -> Vec<Option<Option<bool>>> {
+        self.results
+    }
+
+    /// Returns the result of the shared matcher `id` on the frame at `idx`,
+    /// computing it with `f` if it isn't known yet.
+    fn get_or_insert_with(
+        &mut self,
+        id: usize,
+        idx: usize,
+        f: impl FnOnce() -> Option<bool>,
+    ) -> Option<bool> {
+        if self.results.is_empty() {
+            self.results.resize(self.shared.len * self.frames, None);
+        }
+        let slot = &mut self.results[id * self.frames + idx];
+        *slot.get_or_insert_with(f)
+    }
+}
+
+/// Frame matchers are equal if they match the same frames in the same way.
+///
+/// This disregards differences in the pattern that don't affect matching, like the order of families
+/// in a `family` matcher, or the pattern of a matcher that never matches.
+impl PartialEq for FrameMatcher {
+    fn eq(&self, other: &Self) -> bool {
+        if self.negated != other.negated
+            || self.frame_offset != other.frame_offset
+            || self.missing != other.missing
+        {
             return false;
-        };
+        }
 
-        self.negated ^ self.inner.matches_frame(frame)
+        match (&self.inner, &other.inner) {
+            (
+                FrameMatcherInner::Field {
+                    field: a_field,
+                    path_like: a_path_like,
+                    ..
+                },
+                FrameMatcherInner::Field {
+                    field: b_field,
+                    path_like: b_path_like,
+                    ..
+                },
+            ) => {
+                a_field == b_field
+                    && a_path_like == b_path_like
+                    && self.raw_pattern == other.raw_pattern
+            }
+            (
+                FrameMatcherInner::Family {
+                    families: a_families,
+                    unknown: a_unknown,
+                },
+                FrameMatcherInner::Family {
+                    families: b_families,
+                    unknown: b_unknown,
+                },
+            ) => {
+                a_families == b_families
+                    && a_unknown.iter().all(|f| b_unknown.contains(f))
+                    && b_unknown.iter().all(|f| a_unknown.contains(f))
+            }
+            (
+                FrameMatcherInner::InApp {
+                    expected: a,
+                    unset_is_false: a_unset,
+                },
+                FrameMatcherInner::InApp {
+                    expected: b,
+                    unset_is_false: b_unset,
+                },
+            ) => a == b && a_unset == b_unset,
+            (
+                FrameMatcherInner::HasVars { expected: a },
+                FrameMatcherInner::HasVars { expected: b },
+            ) => a == b,
+            (
+                FrameMatcherInner::Noop { field: a, .. },
+                FrameMatcherInner::Noop { field: b, .. },
+            ) => a == b,
+            _ => false,
+        }
+    }
+}
+
+impl Eq for FrameMatcher {}
+
+impl Hash for FrameMatcher {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.negated.hash(state);
+        self.frame_offset.hash(state);
+        self.missing.hash(state);
+        std::mem::discriminant(&self.inner).hash(state);
+
+        match &self.inner {
+            FrameMatcherInner::Field {
+                field, path_like, ..
+            } => {
+                field.hash(state);
+                path_like.hash(state);
+                self.raw_pattern.hash(state);
+            }
+            // The unknown family names are compared as a set, so they are hashed as one.
+            FrameMatcherInner::Family { families, unknown } => {
+                families.hash(state);
+                unknown.iter().collect::<BTreeSet<_>>().hash(state);
+            }
+            FrameMatcherInner::InApp {
+                expected,
+                unset_is_false,
+            } => {
+                expected.hash(state);
+                unset_is_false.hash(state);
+            }
+            FrameMatcherInner::HasVars { expected } => expected.hash(state),
+            FrameMatcherInner::Noop { field, .. } => field.hash(state),
+        }
+    }
+}
+
+impl fmt::Display for Matcher {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Matcher::Frame(m) => m.fmt(f),
+            Matcher::Exception(m) => m.fmt(f),
+        }
     }
 }
 
@@ -201,11 +727,15 @@ impl fmt::Display for FrameMatcher {
             frame_offset,
             inner,
             raw_pattern,
+            missing: _,
         } = self;
 
+        // The parser needs whitespace between the pattern and the closing `]`.
+        let padding = if f.alternate() { " " } else { "" };
+
         match frame_offset {
-            FrameOffset::Caller => write!(f, "[")?,
-            FrameOffset::Callee => write!(f, "| [")?,
+            FrameOffset::Caller => write!(f, "[{padding}")?,
+            FrameOffset::Callee => write!(f, "| [{padding}")?,
             FrameOffset::None => {}
         }
 
@@ -213,11 +743,12 @@ impl fmt::Display for FrameMatcher {
             write!(f, "!")?;
         }
 
-        write!(f, "{inner}:{raw_pattern}")?;
+        write!(f, "{inner}:")?;
+        fmt_pattern(raw_pattern, f)?;
 
         match frame_offset {
-            FrameOffset::Caller => write!(f, "] |")?,
-            FrameOffset::Callee => write!(f, "]")?,
+            FrameOffset::Caller => write!(f, "{padding}] |")?,
+            FrameOffset::Callee => write!(f, "{padding}]")?,
             FrameOffset::None => {}
         }
 
@@ -240,103 +771,209 @@ enum FrameMatcherInner {
         /// to slashes in both the pattern and the value, among other things.
         path_like: bool,
         /// The regex pattern to check the frame field against.
-        pattern: Arc<Regex>,
+        ///
+        /// This may only be compiled on first use, see [`Cache::with_lazy_regexes`](super::Cache::with_lazy_regexes).
+        pattern: Arc<LazyRegex>,
     },
     /// Checks whether a frame's `family` field is one of the allowed families.
-    Family { families: Families },
+    Family {
+        /// The allowed families.
+        families: Families,
+        /// The allowed family names that have no dedicated representation in [`Families`].
+        ///
+        /// Frames with such a family only match if their name is contained verbatim.
+        unknown: Vec<SmolStr>,
+    },
     /// Checks whether a frame's in_app field is equal to an expected value.
-    InApp { expected: bool },
-    /// A matcher that will never match.
+    InApp {
+        /// The expected value, `None` for `app:unset`.
+        expected: Option<bool>,
+        /// Whether frames with an unset `in_app` field match `app:no`, instead of being treated
+        /// like frames without the field, see [`MissingValuePolicy::unset_in_app_is_false`].
+        unset_is_false: bool,
+    },
+    /// Checks whether a frame's has_vars field is equal to an expected value.
+    HasVars { expected: bool },
+    /// A matcher that will never match, because its pattern is invalid.
+    ///
+    /// Negated, it matches every frame that has the field. See [`FrameMatcher::warning`].
     Noop {
         /// The field to check.
         field: FrameField,
+        /// Why the matcher can't match.
+        reason: NoopReason,
     },
 }
 
+/// Why a [`FrameMatcherInner::Noop`] matcher can't match.
+#[derive(Debug, Clone)]
+pub(crate) enum NoopReason {
+    /// The glob pattern can't be compiled, with the error explaining why.
+    InvalidPattern(SmolStr),
+    /// The pattern of an `app` or `has_vars` matcher isn't a boolean.
+    InvalidFlag,
+}
+
 impl FrameMatcherInner {
     /// Creates a matcher that checks a frame field.
+    ///
+    /// If `alternatives` is true, `pattern` may consist of several comma-separated
+    /// patterns, see [`RegexCache::get_or_try_insert_alternatives`].
     fn new_field(
         field: FrameField,
         path_like: bool,
+        alternatives: bool,
         pattern: &str,
         regex_cache: &mut RegexCache,
     ) -> anyhow::Result<Self> {
-        let Ok(pattern) = regex_cache.get_or_try_insert(pattern, path_like) else {
+        match regex_cache.get_or_try_insert_lazy(pattern, path_like, alternatives) {
+            Ok(pattern) => Ok(Self::Field {
+                field,
+                path_like,
+                pattern,
+            }),
             // TODO: we should be returning real errors in a `strict` parsing mode
-            return Ok(Self::Noop { field });
-        };
-
-        Ok(Self::Field {
-            field,
-            path_like,
-            pattern,
-        })
+            Err(err) => Ok(Self::Noop {
+                field,
+                reason: NoopReason::InvalidPattern(
+                    describe_glob_error(pattern, err).to_string().into(),
+                ),
+            }),
+        }
     }
 
     /// Creates a matcher that checks a frame's family.
     fn new_family(families: &str) -> Self {
         Self::Family {
             families: Families::new(families),
+            unknown: Families::unknown_names(families)
+                .map(SmolStr::new)
+                .collect(),
         }
     }
 
     /// Creates a matcher that checks a frame's `in_app` field.
     fn new_in_app(expected: &str) -> anyhow::Result<Self> {
-        match expected {
-            "1" | "true" | "yes" => Ok(Self::InApp { expected: true }),
-            "0" | "false" | "no" => Ok(Self::InApp { expected: false }),
-            _ => Ok(Self::Noop {
-                field: FrameField::App,
-            }),
+        let expected = match expected {
+            "1" | "true" | "yes" => Some(true),
+            "0" | "false" | "no" => Some(false),
+            "unset" => None,
             // TODO: we should be returning real errors in a `strict` parsing mode
             // _ => Err(anyhow::anyhow!("Invalid value for `app`: `{expected}`")),
+            _ => {
+                return Ok(Self::Noop {
+                    field: FrameField::App,
+                    reason: NoopReason::InvalidFlag,
+                })
+            }
+        };
+        Ok(Self::InApp {
+            expected,
+            unset_is_false: false,
+        })
+    }
+
+    /// Creates a matcher that checks a frame's `has_vars` field.
+    fn new_has_vars(expected: &str) -> Self {
+        match expected {
+            "1" | "true" | "yes" => Self::HasVars { expected: true },
+            "0" | "false" | "no" => Self::HasVars { expected: false },
+            _ => Self::Noop {
+                field: FrameField::HasVars,
+                reason: NoopReason::InvalidFlag,
+            },
         }
     }
 
     /// Checks whether a frame matches.
-    fn matches_frame(&self, frame: &Frame) -> bool {
-        match self {
+    ///
+    /// Returns `None` if the frame doesn't have the string-valued field this matcher checks.
+    fn matches_frame(&self, frame: &impl FrameLike, options: MatchOptions) -> Option<bool> {
+        let matches = match self {
             FrameMatcherInner::Field {
-                field,
-                path_like,
-                pattern,
-            } => {
-                let Some(value) = frame.get_field(*field) else {
-                    return false;
-                };
-
-                if pattern.is_match(value.as_bytes()) {
-                    return true;
-                }
-
-                if *path_like && !value.starts_with('/') {
-                    // TODO: avoid
-                    let value = format!("/{value}");
-                    return pattern.is_match(value.as_bytes());
+                field: FrameField::Function,
+                ..
+            } if options.match_raw_function => {
+                let function = frame.get_field(FrameField::Function);
+                let raw_function = frame.raw_function();
+                if function.is_none() && raw_function.is_none() {
+                    return None;
                 }
+                function.is_some_and(|f| self.matches_value(f))
+                    || raw_function.is_some_and(|f| self.matches_value(f))
+            }
+            FrameMatcherInner::Field { field, .. } => self.matches_value(frame.get_field(*field)?),
+            FrameMatcherInner::Family { families, unknown } => {
+                let family = frame.family();
+                families.matches(family.intersection(Families::KNOWN))
+                    || family.matches(Families::UNKNOWN)
+                        && (*families == Families::ALL
+                            || frame
+                                .raw_family()
+                                .is_some_and(|name| unknown.iter().any(|u| u == name)))
+            }
+            FrameMatcherInner::InApp {
+                expected,
+                unset_is_false,
+            } => match (frame.in_app(), expected) {
+                (None, Some(false)) if *unset_is_false => true,
+                // Unset is a state of its own, which only `app:unset` checks for.
+                (None, Some(_)) => return None,
+                (in_app, expected) => in_app == *expected,
+            },
+            // Like an unset `in_app` flag, an unknown one is neither `true` nor `false`.
+            FrameMatcherInner::HasVars { expected } => frame.has_vars()? == *expected,
+            FrameMatcherInner::Noop {
+                field: FrameField::App | FrameField::HasVars,
+                ..
+            } => false,
+            FrameMatcherInner::Noop { field, .. } => {
+                frame.get_field(*field)?;
                 false
             }
-            FrameMatcherInner::Family { families } => families.matches(frame.family),
-            FrameMatcherInner::InApp { expected } => frame.in_app.unwrap_or_default() == *expected,
-            FrameMatcherInner::Noop { .. } => false,
+        };
+        Some(matches)
+    }
+
+    /// Checks whether the value of the field this matcher checks matches.
+    ///
+    /// This is always false for matchers that don't check a string-valued field.
+    fn matches_value(&self, value: &str) -> bool {
+        let FrameMatcherInner::Field {
+            path_like, pattern, ..
+        } = self
+        else {
+            return false;
+        };
+
+        if pattern.is_match(value.as_bytes()) {
+            return true;
+        }
+
+        if *path_like && !value.starts_with('/') {
+            // TODO: avoid
+            let value = format!("/{value}");
+            return pattern.is_match(value.as_bytes());
         }
+        false
     }
 }
 
 impl fmt::Display for FrameMatcherInner {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
-            FrameMatcherInner::Field { field, .. } | FrameMatcherInner::Noop { field } => {
+            FrameMatcherInner::Field { field, .. } | FrameMatcherInner::Noop { field, .. } => {
                 write!(f, "{field}")
             }
             FrameMatcherInner::Family { .. } => write!(f, "family"),
             FrameMatcherInner::InApp { .. } => write!(f, "app"),
+            FrameMatcherInner::HasVars { .. } => write!(f, "has_vars"),
         }
     }
 }
 
 /// Which field an exception matcher checks.
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 enum ExceptionMatcherType {
     /// Checks the `type` field.
     Type,
@@ -344,6 +981,29 @@ enum ExceptionMatcherType {
     Value,
     /// Checks the `mechanism.type` field.
     Mechanism,
+    /// Checks the name of the thread.
+    ThreadName,
+    /// Checks the id of the thread.
+    ThreadId,
+    /// Checks whether the thread is the main thread.
+    ThreadMain,
+    /// Checks whether the thread crashed.
+    ThreadCrashed,
+}
+
+impl ExceptionMatcherType {
+    /// Returns true if this type checks a boolean instead of a string.
+    fn is_flag(self) -> bool {
+        matches!(self, Self::ThreadMain | Self::ThreadCrashed)
+    }
+
+    /// Returns true if this type checks the thread instead of the exception itself.
+    fn is_thread(self) -> bool {
+        matches!(
+            self,
+            Self::ThreadName | Self::ThreadId | Self::ThreadMain | Self::ThreadCrashed
+        )
+    }
 }
 
 impl fmt::Display for ExceptionMatcherType {
@@ -352,6 +1012,10 @@ impl fmt::Display for ExceptionMatcherType {
             ExceptionMatcherType::Type => write!(f, "type"),
             ExceptionMatcherType::Value => write!(f, "value"),
             ExceptionMatcherType::Mechanism => write!(f, "mechanism"),
+            ExceptionMatcherType::ThreadName => write!(f, "thread.name"),
+            ExceptionMatcherType::ThreadId => write!(f, "thread.id"),
+            ExceptionMatcherType::ThreadMain => write!(f, "thread.main"),
+            ExceptionMatcherType::ThreadCrashed => write!(f, "thread.crashed"),
         }
     }
 }
@@ -366,23 +1030,68 @@ pub struct ExceptionMatcher {
     pattern: Arc<Regex>,
     /// The field to check.
     ty: ExceptionMatcherType,
+    /// The value a boolean field must have, parsed from the pattern of `thread.main` and
+    /// `thread.crashed` matchers.
+    expected: Option<bool>,
     /// The string pattern this matcher was constructed from. This is used for the `Display` impl.
     raw_pattern: SmolStr,
+    /// How this matcher treats exceptions without the field it checks.
+    missing: MissingValue,
 }
 
 impl ExceptionMatcher {
+    /// Returns the raw pattern and translated source of this matcher's regex.
+    #[cfg(feature = "compiled")]
+    pub(crate) fn translated_regex(&self) -> (&str, &str) {
+        (&self.raw_pattern, self.pattern.as_str())
+    }
+
+    /// Returns the glob pattern of this matcher.
+    pub(crate) fn pattern(&self) -> &str {
+        &self.raw_pattern
+    }
+
+    /// Returns how this matcher treats exceptions without the field it checks.
+    pub(crate) fn missing_value(&self) -> MissingValue {
+        self.missing
+    }
+
+    /// Sets how this matcher treats exceptions without the field it checks.
+    pub(crate) fn set_missing_value(&mut self, missing: MissingValue) {
+        self.missing = missing;
+    }
+
+    /// Returns a structured description of this matcher.
+    pub(crate) fn describe(&self) -> MatcherDescription {
+        MatcherDescription {
+            kind: if self.ty.is_thread() {
+                MatcherKind::Thread
+            } else {
+                MatcherKind::Exception
+            },
+            key: self.ty.to_string(),
+            pattern: self.raw_pattern.to_string(),
+            negated: self.negated,
+            offset: None,
+        }
+    }
+
     /// Creates a matcher that checks an exception's `type` field.
     fn new_type(
         negated: bool,
         raw_pattern: &str,
         regex_cache: &mut RegexCache,
     ) -> anyhow::Result<Self> {
-        let pattern = regex_cache.get_or_try_insert(raw_pattern, false)?;
+        let pattern = regex_cache
+            .get_or_try_insert(raw_pattern, false)
+            .map_err(|err| describe_glob_error(raw_pattern, err))?;
         Ok(Self {
             negated,
             pattern,
             ty: ExceptionMatcherType::Type,
+            expected: None,
             raw_pattern: SmolStr::new(raw_pattern),
+            missing: MissingValuePolicy::default().exceptions,
         })
     }
 
@@ -392,12 +1101,16 @@ impl ExceptionMatcher {
         raw_pattern: &str,
         regex_cache: &mut RegexCache,
     ) -> anyhow::Result<Self> {
-        let pattern = regex_cache.get_or_try_insert(raw_pattern, false)?;
+        let pattern = regex_cache
+            .get_or_try_insert(raw_pattern, false)
+            .map_err(|err| describe_glob_error(raw_pattern, err))?;
         Ok(Self {
             negated,
             pattern,
             ty: ExceptionMatcherType::Value,
+            expected: None,
             raw_pattern: SmolStr::new(raw_pattern),
+            missing: MissingValuePolicy::default().exceptions,
         })
     }
 
@@ -407,25 +1120,120 @@ impl ExceptionMatcher {
         raw_pattern: &str,
         regex_cache: &mut RegexCache,
     ) -> anyhow::Result<Self> {
-        let pattern = regex_cache.get_or_try_insert(raw_pattern, false)?;
+        let pattern = regex_cache
+            .get_or_try_insert(raw_pattern, false)
+            .map_err(|err| describe_glob_error(raw_pattern, err))?;
         Ok(Self {
             negated,
             pattern,
             ty: ExceptionMatcherType::Mechanism,
+            expected: None,
+            raw_pattern: SmolStr::new(raw_pattern),
+            missing: MissingValuePolicy::default().exceptions,
+        })
+    }
+
+    /// Creates a matcher that checks a field of the thread.
+    ///
+    /// The patterns of `thread.main` and `thread.crashed` matchers must be booleans.
+    fn new_thread(
+        negated: bool,
+        ty: ExceptionMatcherType,
+        raw_pattern: &str,
+        regex_cache: &mut RegexCache,
+    ) -> anyhow::Result<Self> {
+        let expected = match raw_pattern {
+            _ if !ty.is_flag() => None,
+            "1" | "true" | "yes" => Some(true),
+            "0" | "false" | "no" => Some(false),
+            _ => anyhow::bail!(
+                "invalid boolean value `{raw_pattern}` for `{ty}`, \
+                expected one of `1`, `true`, `yes`, `0`, `false`, or `no`"
+            ),
+        };
+        let pattern = regex_cache
+            .get_or_try_insert(raw_pattern, false)
+            .map_err(|err| describe_glob_error(raw_pattern, err))?;
+        Ok(Self {
+            negated,
+            pattern,
+            ty,
+            expected,
             raw_pattern: SmolStr::new(raw_pattern),
+            missing: MissingValuePolicy::default().exceptions,
         })
     }
 
     /// Checks whether an exception matches.
+    ///
+    /// `error.type` matchers match if either the bare type or the type qualified with the
+    /// exception's module matches.
     pub fn matches_exception(&self, exception_data: &ExceptionData) -> bool {
+        let thread = &exception_data.thread;
         let value = match self.ty {
             ExceptionMatcherType::Type => &exception_data.ty,
             ExceptionMatcherType::Value => &exception_data.value,
             ExceptionMatcherType::Mechanism => &exception_data.mechanism,
+            ExceptionMatcherType::ThreadName => &thread.name,
+            ExceptionMatcherType::ThreadId => &thread.id,
+            ExceptionMatcherType::ThreadMain | ExceptionMatcherType::ThreadCrashed => {
+                return self.matches_flag(thread);
+            }
         };
 
-        let value = value.as_deref().unwrap_or("<unknown>").as_bytes();
-        self.negated ^ self.pattern.is_match(value)
+        match (value, self.missing) {
+            (Some(ty), _) if self.ty == ExceptionMatcherType::Type => {
+                let qualified = || {
+                    exception_data.module.as_ref().is_some_and(|module| {
+                        let qualified = format!("{module}.{ty}");
+                        self.pattern.is_match(qualified.as_bytes())
+                    })
+                };
+                self.negated ^ (self.pattern.is_match(ty.as_bytes()) || qualified())
+            }
+            (Some(value), _) => self.negated ^ self.pattern.is_match(value.as_bytes()),
+            (None, MissingValue::NoMatch) => self.negated,
+            (None, MissingValue::Unknown) => {
+                self.negated ^ self.pattern.is_match(UNKNOWN_VALUE.as_bytes())
+            }
+            (None, MissingValue::NeverMatch) => false,
+        }
+    }
+
+    /// Checks whether the boolean field of the thread this matcher checks has the expected
+    /// value.
+    fn matches_flag(&self, thread: &ThreadData) -> bool {
+        let value = match self.ty {
+            ExceptionMatcherType::ThreadMain => thread.main,
+            _ => thread.crashed,
+        };
+        match (value, self.missing) {
+            (Some(value), _) => self.negated ^ (Some(value) == self.expected),
+            (None, MissingValue::NoMatch | MissingValue::Unknown) => self.negated,
+            (None, MissingValue::NeverMatch) => false,
+        }
+    }
+}
+
+/// Exception matchers are equal if they check the same field against the same pattern
+/// and treat missing values the same way.
+impl PartialEq for ExceptionMatcher {
+    fn eq(&self, other: &Self) -> bool {
+        self.negated == other.negated
+            && self.ty == other.ty
+            && self.raw_pattern == other.raw_pattern
+            && self.missing == other.missing
+    }
+}
+
+impl Eq for ExceptionMatcher {}
+
+impl Hash for ExceptionMatcher {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.negated.hash(state);
+        self.ty.hash(state);
+        self.raw_pattern.hash(state);
+        self.missing.hash(state);
     }
 }
 
@@ -442,7 +1250,25 @@ impl fmt::Display for ExceptionMatcher {
             write!(f, "!")?;
         }
 
-        write!(f, "{ty}:{raw_pattern}")
+        write!(f, "{ty}:")?;
+        fmt_pattern(raw_pattern, f)
+    }
+}
+
+/// Writes a matcher's pattern.
+///
+/// In the alternate form (`{:#}`), the pattern is escaped and quoted as needed
+/// so that the [grammar](super::grammar) parses it back into the same pattern.
+fn fmt_pattern(pattern: &str, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    if !f.alternate() {
+        return f.write_str(pattern);
+    }
+
+    let escaped = pattern.replace('\\', "\\\\");
+    if pattern.is_empty() || pattern.contains(|c: char| c.is_ascii_whitespace()) {
+        write!(f, "\"{escaped}\"")
+    } else {
+        f.write_str(&escaped)
     }
 }
 
@@ -450,9 +1276,7 @@ impl fmt::Display for ExceptionMatcher {
 mod tests {
     use serde_json::json;
 
-    use crate::enhancers::Enhancements;
-
-    use super::*;
+    use crate::enhancers::{Enhancements, Frame};
 
     fn create_matcher(input: &str) -> impl Fn(Frame) -> bool {
         let enhancements = Enhancements::parse(input, &mut Default::default()).unwrap();
@@ -464,6 +1288,81 @@ mod tests {
         }
     }
 
+    /// Glob patterns and values, and whether Sentry's Python implementation matches them.
+    const PYTHON_GLOBS: &[(&str, &str, bool)] = &[
+        ("module:foo*", "foobar", true),
+        ("module:foo*", "barfoo", false),
+        ("module:f?o", "foo", true),
+        ("module:f?o", "fo", false),
+        ("module:*/foo", "a/b/foo", true),
+        ("module:Foo", "foo", false),
+        ("module:[abc]x", "bx", true),
+        ("module:[abc]x", "dx", false),
+        ("module:[a-c]x", "bx", true),
+        ("module:[!a]x", "bx", true),
+        ("module:[!a]x", "ax", false),
+        ("module:[[]x", "[x", true),
+        ("module:[{]x", "{x", true),
+        (r"module:a\*", "a*", true),
+        (r"module:a\*", "ab", false),
+        ("path:*.js", "/app/foo.js", false),
+        ("path:**/foo.js", "app/foo.js", true),
+        ("path:**/*.JS", "/app/foo.js", true),
+        ("path:src/*.py", "src/a/b.py", false),
+        ("path:src/[ab].py", "src/b.py", true),
+        (r"path:C:\app\*", "C:/app/foo", true),
+    ];
+
+    /// Glob patterns and values on which this implementation deliberately differs from
+    /// Sentry's Python implementation, and whether this implementation matches them.
+    const DIFFERENT_GLOBS: &[(&str, &str, bool)] = &[
+        // Python doesn't support brace expansion and matches braces literally.
+        ("module:{foo,bar}x", "foox", true),
+        ("module:{foo,bar}x", "{foo,bar}x", false),
+        // Python matches `[` of unclosed character classes literally.
+        ("module:-[*", "-[foo", false),
+    ];
+
+    #[test]
+    fn glob_compatibility() {
+        for &(matcher, value, expected) in PYTHON_GLOBS.iter().chain(DIFFERENT_GLOBS) {
+            let field = if matcher.starts_with("path:") {
+                "abs_path"
+            } else {
+                "module"
+            };
+            let matches = create_matcher(&format!("{matcher} +app"));
+            let frame = Frame::from_test(&json!({ field: value }), "other");
+            assert_eq!(matches(frame), expected, "`{matcher}` on `{value}`");
+        }
+    }
+
+    #[test]
+    fn literal_patterns() {
+        let frame = |function: &str| {
+            Frame::from_test(
+                &json!({ "function": function, "abs_path": r"C:\App\[id].js" }),
+                "native",
+            )
+        };
+
+        let matcher = create_matcher("function:=-[UIApplication* +app");
+        assert!(matcher(frame(
+            "-[UIApplication sendAction:to:from:forEvent:]"
+        )));
+        assert!(!matcher(frame("+[UIApplication sharedApplication]")));
+        assert!(!matcher(frame("-UIApplication")));
+
+        // Only a trailing `*` is special, and commas don't separate alternatives.
+        let matcher = create_matcher(r#"function:"=+[NSObject a,b*]" +app"#);
+        assert!(matcher(frame("+[NSObject a,b*]")));
+        assert!(!matcher(frame("+[NSObject a]")));
+
+        // Path patterns are normalized like glob patterns.
+        let matcher = create_matcher(r"path:=c:\app\[id]* +app");
+        assert!(matcher(frame("")));
+    }
+
     #[test]
     fn path_matching() {
         let matcher = create_matcher("path:**/test.js              +app");
@@ -523,6 +1422,59 @@ mod tests {
         )));
     }
 
+    #[test]
+    fn unknown_family_matching() {
+        let ruby_matcher = create_matcher("family:ruby,native function:foo -app");
+        let all_matcher = create_matcher("family:all function:foo -app");
+        let not_ruby_matcher = create_matcher("!family:ruby function:foo -app");
+
+        let frame = |family| Frame::from_test(&json!({"function": "foo"}), family);
+
+        assert!(ruby_matcher(frame("ruby")));
+        assert!(ruby_matcher(frame("native")));
+        assert!(!ruby_matcher(frame("elixir")));
+        assert!(!ruby_matcher(frame("javascript")));
+
+        assert!(all_matcher(frame("ruby")));
+        assert!(all_matcher(frame("javascript")));
+
+        assert!(!not_ruby_matcher(frame("ruby")));
+        assert!(not_ruby_matcher(frame("elixir")));
+        assert!(not_ruby_matcher(frame("native")));
+    }
+
+    #[test]
+    fn function_alternatives_matching() {
+        let matcher = create_matcher(r"function:malloc*,calloc,foo<a\,b> -app");
+        let frame = |function: &str| Frame::from_test(&json!({ "function": function }), "native");
+
+        assert!(matcher(frame("malloc_zone")));
+        assert!(matcher(frame("calloc")));
+        assert!(matcher(frame("foo<a,b>")));
+        assert!(!matcher(frame("realloc")));
+
+        // Other matchers treat commas literally.
+        let matcher = create_matcher("module:a,b -app");
+        assert!(matcher(Frame::from_test(
+            &json!({"module": "a,b"}),
+            "native"
+        )));
+    }
+
+    #[test]
+    fn trust_matching() {
+        let matcher = create_matcher("family:native stack.trust:scan -group");
+        let frame = |trust: Option<&str>| Frame::from_test(&json!({ "trust": trust }), "native");
+
+        assert!(matcher(frame(Some("scan"))));
+        assert!(!matcher(frame(Some("cfi"))));
+        assert!(!matcher(frame(None)));
+
+        let matcher = create_matcher("!trust:scan -group");
+        assert!(matcher(frame(Some("context"))));
+        assert!(!matcher(frame(Some("scan"))));
+    }
+
     #[test]
     fn app_matching() {
         let yes_matcher = create_matcher("family:javascript path:**/test.js app:yes       +app");
@@ -0,0 +1,190 @@
+//! Rewriting of enhancement rules written in legacy syntax.
+//!
+//! The grammar still accepts a number of deprecated matcher names, like `stack.abs_path`
+//! for `path`, and is lenient about whitespace and quoting. [`migrate`] rewrites every rule
+//! into its canonical form and reports what it changed, while keeping comments, blank lines,
+//! and line endings intact.
+
+use std::fmt;
+
+use anyhow::Context;
+
+use super::document::split_line_ending;
+use super::grammar::parse_rule_with_syntax;
+use super::Cache;
+use crate::{Error, Result};
+
+/// Deprecated matcher names and the names replacing them.
+const DEPRECATED_MATCHERS: [(&str, &str); 8] = [
+    ("stack.abs_path", "path"),
+    ("stack.function", "function"),
+    ("stack.module", "module"),
+    ("stack.package", "package"),
+    ("stack.has_vars", "has_vars"),
+    ("error.type", "type"),
+    ("error.value", "value"),
+    ("error.mechanism", "mechanism"),
+];
+
+/// The kind of change [`migrate`] made to a line.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TransformationKind {
+    /// A deprecated matcher name was replaced by its modern equivalent.
+    RenamedMatcher {
+        /// The deprecated name, e.g. `stack.abs_path`.
+        from: &'static str,
+        /// The modern name, e.g. `path`.
+        to: &'static str,
+    },
+    /// The rule was rewritten in its canonical form.
+    ///
+    /// This covers changes in whitespace, quoting, and the order of matchers.
+    Reformatted,
+}
+
+impl fmt::Display for TransformationKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TransformationKind::RenamedMatcher { from, to } => {
+                write!(f, "renamed matcher `{from}` to `{to}`")
+            }
+            TransformationKind::Reformatted => write!(f, "reformatted rule"),
+        }
+    }
+}
+
+/// A change [`migrate`] made to a single line.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Transformation {
+    /// The 0-based number of the changed line.
+    pub line: usize,
+    /// What was changed.
+    pub kind: TransformationKind,
+}
+
+/// The result of [`migrate`].
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Migration {
+    /// The migrated rules.
+    pub text: String,
+    /// All changes that were made, in the order of the lines they were made to.
+    pub transformations: Vec<Transformation>,
+}
+
+/// Rewrites enhancement rules into canonical modern syntax.
+///
+/// Every rule is replaced by its canonical text form, with deprecated matcher names replaced.
+/// Trailing comments are kept. Lines that only contain a comment or whitespace are not touched.
+/// Migrating already migrated rules doesn't change them any further.
+pub fn migrate(input: &str, cache: &mut Cache) -> Result<Migration> {
+    let mut migration = Migration::default();
+
+    for (idx, raw_line) in input.split_inclusive('\n').enumerate() {
+        let (line, line_ending) = split_line_ending(raw_line);
+        let trimmed = line.trim();
+        if trimmed.is_empty() || trimmed.starts_with('#') {
+            migration.text.push_str(raw_line);
+            continue;
+        }
+
+        let (rule, syntax) = parse_rule_with_syntax(trimmed, &mut cache.regex)
+            .with_context(|| format!("at line {}", idx + 1))
+            .map_err(Error::Parse)?;
+
+        let mut migrated = format!("{rule:#}");
+        if let Some(comment) = syntax.comment {
+            migrated.push(' ');
+            migrated.push_str(comment);
+        }
+
+        // The line as it would look if only the deprecated matchers were renamed.
+        let mut renamed = line.to_owned();
+        for name in syntax.matcher_names {
+            if let Some(&(from, to)) = DEPRECATED_MATCHERS.iter().find(|(from, _)| *from == name) {
+                renamed = renamed.replacen(&format!("{from}:"), &format!("{to}:"), 1);
+                migration.transformations.push(Transformation {
+                    line: idx,
+                    kind: TransformationKind::RenamedMatcher { from, to },
+                });
+            }
+        }
+        if renamed != migrated {
+            migration.transformations.push(Transformation {
+                line: idx,
+                kind: TransformationKind::Reformatted,
+            });
+        }
+
+        migration.text.push_str(&migrated);
+        migration.text.push_str(line_ending);
+    }
+
+    Ok(migration)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::enhancers::Enhancements;
+
+    #[test]
+    fn migrates_rules() {
+        let input = "# comment\r\n\
+                     \r\n\
+                     stack.function:foo   error.type:Bar -app # trailing\r\n\
+                     [ stack.module:foo ]|function:bar +group\r\n\
+                     path:**/foo.js -app";
+        let migration = migrate(input, &mut Cache::default()).unwrap();
+
+        assert_eq!(
+            migration.text,
+            "# comment\r\n\
+             \r\n\
+             type:Bar function:foo -app # trailing\r\n\
+             [ module:foo ] | function:bar +group\r\n\
+             path:**/foo.js -app"
+        );
+
+        let transformations: Vec<_> = migration
+            .transformations
+            .iter()
+            .map(|t| (t.line, t.kind.to_string()))
+            .collect();
+        assert_eq!(
+            transformations,
+            [
+                (2, "renamed matcher `stack.function` to `function`".into()),
+                (2, "renamed matcher `error.type` to `type`".into()),
+                (2, "reformatted rule".into()),
+                (3, "renamed matcher `stack.module` to `module`".into()),
+                (3, "reformatted rule".into()),
+            ]
+        );
+
+        let remigrated = migrate(&migration.text, &mut Cache::default()).unwrap();
+        assert_eq!(remigrated.text, migration.text);
+        assert!(remigrated.transformations.is_empty());
+    }
+
+    #[test]
+    fn migrates_default_enhancers() {
+        let input = std::fs::read_to_string("../tests/fixtures/newstyle@2023-01-11.txt").unwrap();
+        let mut cache = Cache::default();
+        let migration = migrate(&input, &mut cache).unwrap();
+        assert!(!migration.text.contains("stack.abs_path:"));
+
+        let original = Enhancements::parse(&input, &mut cache).unwrap();
+        let migrated = Enhancements::parse(&migration.text, &mut cache).unwrap();
+        assert!(original.rules().eq(migrated.rules()));
+    }
+
+    #[test]
+    fn reports_line_of_invalid_rule() {
+        let err = migrate(
+            "function:foo +app\nfunction:bar +nope",
+            &mut Cache::default(),
+        )
+        .unwrap_err();
+        assert!(format!("{err:#}").starts_with("at line 2"));
+    }
+}
@@ -4,60 +4,360 @@
 //! modify certain stack frames and change grouping metadata.
 //!
 //! `Enhancements` can be parsed from a human-readable string representation with [`parse`](Enhancements::parse)
-//! or from a compact msgpack representation with [`from_config_structure`](Enhancements::from_config_structure).
+//! or from a compact msgpack representation with [`from_config_structure`](Enhancements::from_config_structure),
+//! which [`to_config_structure`](Enhancements::to_config_structure) produces.
 //!
 //! They are applied to stacktraces with [`apply_modifications_to_frames`](Enhancements::apply_modifications_to_frames).
 
+use std::collections::HashMap;
 use std::fmt::Write;
+use std::num::NonZeroUsize;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
 
+use anyhow::Context;
 use smol_str::SmolStr;
 
+use crate::{Error, Result};
+
 mod actions;
+mod bounded;
+mod builder;
 mod cache;
+mod census;
+mod changes;
+#[cfg(feature = "compiled")]
+mod compiled;
 mod config_structure;
+mod contributes;
+#[cfg(feature = "default-configs")]
+mod defaults;
+mod description;
+mod document;
+#[cfg(feature = "serde")]
+mod event;
+mod exception_value;
 mod families;
 mod frame;
+pub mod function_name;
 mod grammar;
+mod in_app;
 mod matchers;
+mod migrate;
+mod options;
+mod otel;
+mod parse_error;
+mod partition;
+mod patterns;
+mod recorder;
+mod registry;
+mod result_cache;
 mod rules;
+mod shadow;
+mod shared;
+mod threads;
+mod variants;
+#[cfg(test)]
+mod vectors;
+mod warnings;
 
+use actions::FramePriorities;
+pub use actions::{FlagActionType, HintKind, Range, VarKind};
+pub use builder::RuleBuilder;
 pub use cache::*;
-use config_structure::{EncodedAction, EncodedEnhancements, EncodedMatcher};
+pub use census::{GlobFeature, PatternCensus};
+pub use changes::apply_encoded_changes;
+pub use config_structure::Encoding;
+pub use contributes::DEFAULT_CONTRIBUTES_VERSIONS;
+#[cfg(feature = "default-configs")]
+pub use defaults::DEFAULT_CONFIG_VERSIONS;
+pub use description::{
+    ActionDescription, MatcherDescription, MatcherKind, MatcherOffset, RuleDescription, VarValue,
+};
+pub use document::{Document, DocumentLine, LineKind};
+pub use exception_value::normalize_exception_value;
 pub use families::Families;
-pub use frame::{Frame, StringField};
-pub use rules::Rule;
+pub use frame::{
+    normalize_path, normalize_path_with, Frame, FrameField, FrameLike, FrameLikeMut, RawFrame,
+    StringField,
+};
+pub use in_app::default_in_app;
+use matchers::{MatchMemo, MatchOptions, SharedMatchers};
+pub use migrate::{migrate, Migration, Transformation, TransformationKind};
+pub use options::{
+    AssembleOptions, GlobOptions, MissingValue, MissingValuePolicy, ModifyOptions, ParseOptions,
+};
+pub use otel::OtelFrame;
+pub use parse_error::{ParseError, ParseErrorKind};
+use partition::PartitionedRules;
+use patterns::Patterns;
+use recorder::Recorder;
+pub use registry::EnhancementsRegistry;
+pub use result_cache::{ResultCache, ResultCacheStats};
+pub use rules::{Rule, RuleId};
+pub use shadow::ShadowDifference;
+pub use shared::SharedRegistry;
+pub use threads::ThreadData;
+pub use variants::{StacktraceVariant, Variants, NON_APP_FRAME_HINT};
+pub use warnings::{ParseWarning, WarningKind};
+
+/// The lowercasing policies [`normalize_path_with`] and [`RawFrame::path_lowercase`] accept.
+pub use crate::Lowercase;
 
 /// Exception data to match against rules.
+///
+/// With the `serde` feature, exception data (de)serializes in the shape of an exception in
+/// Sentry's event JSON, with the mechanism's `type` nested in `mechanism`.
 #[derive(Debug, Clone, Default)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(from = "event::EventException", into = "event::EventException")
+)]
 pub struct ExceptionData {
     /// The exception's type, i.e. name.
-    pub ty: Option<SmolStr>,
+    pub ty: Option<StringField>,
+    /// The module or package the exception's type is defined in, e.g. `java.lang` for a
+    /// `NullPointerException`.
+    ///
+    /// If it is set, `error.type` matchers match both the bare type and the type qualified
+    /// with the module, e.g. `java.lang.NullPointerException`.
+    pub module: Option<StringField>,
     /// The exception's value, i.e. human-readable description.
-    pub value: Option<SmolStr>,
+    pub value: Option<StringField>,
     /// The exception's mechanism.
-    pub mechanism: Option<SmolStr>,
+    pub mechanism: Option<StringField>,
+    /// The thread whose stack trace is being processed, checked by `thread.*` matchers.
+    pub thread: ThreadData,
+}
+
+impl ExceptionData {
+    /// Returns a copy of this exception data with the value [normalized](normalize_exception_value).
+    fn with_normalized_value(&self) -> Self {
+        Self {
+            value: self
+                .value
+                .as_ref()
+                .map(|value| StringField::new(normalize_exception_value(value))),
+            ..self.clone()
+        }
+    }
 }
 
 /// The result of the `assemble_stacktrace_component` fn.
+#[derive(Debug, Clone, Default)]
 pub struct AssembleResult {
     pub contributes: bool,
     pub hint: Option<String>,
     pub invert_stacktrace: bool,
+    /// Counts of how the rules were evaluated.
+    ///
+    /// This is `None` unless [`AssembleOptions::collect_stats`] is set.
+    pub stats: Option<ApplyStats>,
+    /// The normalized exception value the rules were matched against.
+    ///
+    /// This is `None` unless [`AssembleOptions::normalize_exception_value`] is set.
+    pub normalized_exception_value: Option<StringField>,
+    /// The first rule that wasn't applied because the time budget was exhausted.
+    ///
+    /// This is `None` unless the [`AssembleOptions::time_budget`] was exhausted.
+    pub budget_exhausted_at: Option<RuleId>,
 }
 
-/// A collection of [Rules](Rule) that modify the stacktrace and update grouping information.
+/// The result of the `apply_all` fn.
+pub struct ApplyAllResult {
+    /// The attributes of the `stacktrace` grouping component.
+    pub assemble: AssembleResult,
+    /// The stacktrace-level variables, as set by the rules.
+    pub stacktrace_state: StacktraceState,
+}
+
+/// The result of the `apply_modifications_to_frames_with_options` fn.
+#[derive(Debug, Clone, Default)]
+pub struct ModifyResult {
+    /// For every frame, the number of rules that flipped its `in_app` flag
+    /// from one definite value to the other.
+    ///
+    /// This is empty unless [`ModifyOptions::track_in_app_flips`] is set.
+    pub in_app_flips: Vec<usize>,
+    /// The rules that changed the `in_app` flag of at least one frame, in the order
+    /// they were applied.
+    ///
+    /// This is empty unless [`ModifyOptions::summarize_in_app_changes`] is set.
+    pub in_app_changes: Vec<InAppChanges>,
+    /// For every frame, the rule that last changed its `in_app` flag, if any.
+    ///
+    /// The rules can be looked up with [`Enhancements::rule`].
+    /// This is empty unless [`ModifyOptions::track_in_app_last_changed`] is set.
+    pub in_app_last_changed: Vec<Option<RuleId>>,
+    /// The original values of all frame fields that were modified, in order of the frames.
+    ///
+    /// This is empty unless [`ModifyOptions::record_undo_log`] is set.
+    pub undo_log: Vec<UndoEntry>,
+    /// The time spent on every rule that was considered, most expensive first.
+    ///
+    /// This is empty unless [`ModifyOptions::profile_rules`] is set.
+    pub rule_profiles: Vec<RuleProfile>,
+    /// Counts of how the rules were evaluated.
+    ///
+    /// This is `None` unless [`ModifyOptions::collect_stats`] is set.
+    pub stats: Option<ApplyStats>,
+    /// The normalized exception value the rules were matched against.
+    ///
+    /// This is `None` unless [`ModifyOptions::normalize_exception_value`] is set.
+    pub normalized_exception_value: Option<StringField>,
+    /// The first rule that wasn't applied because the time budget was exhausted.
+    ///
+    /// This is `None` unless the [`ModifyOptions::time_budget`] was exhausted.
+    pub budget_exhausted_at: Option<RuleId>,
+    /// Whether any rule matched at least one frame.
+    pub matched: bool,
+    /// Whether any frame field was set to a different value.
+    ///
+    /// This includes setting the default `in_app` flags of frames, and a field still counts as
+    /// changed if a later rule set it back to its original value. If this is `false`, the
+    /// frames are exactly as they were before, so they don't need to be written back.
+    pub changed: bool,
+}
+
+/// Counts of how many rules were skipped or evaluated while applying [`Enhancements`].
+///
+/// Every rule is counted exactly once, so the fields add up to the number of rules
+/// relevant to the operation. Rules that weren't applied because the time budget was
+/// exhausted are not counted.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ApplyStats {
+    /// The number of rules skipped because they can't match the families of the frames.
+    pub skipped_by_families: usize,
+    /// The number of rules skipped because their exception matchers didn't match.
+    pub skipped_by_exception: usize,
+    /// The number of rules whose frame matchers were evaluated against the frames.
+    pub evaluated: usize,
+    /// Whether rules were only applied to the first and last frames because there were more
+    /// frames than the `frame_limit` of the options allows.
+    pub truncated: bool,
+}
+
+impl ApplyStats {
+    /// Creates stats for the given number of rules, of which only `candidates` were considered
+    /// after filtering by families, being applied to the frames in `frame_range`.
+    fn new(rules: usize, candidates: usize, frame_range: FrameRange) -> Self {
+        Self {
+            skipped_by_families: rules - candidates,
+            truncated: frame_range.is_truncated(),
+            ..Default::default()
+        }
+    }
+
+    /// Records whether a rule was skipped because of its exception matchers.
+    fn record(&mut self, matches_exception: bool) {
+        if matches_exception {
+            self.evaluated += 1;
+        } else {
+            self.skipped_by_exception += 1;
+        }
+    }
+}
+
+/// The time a rule took to be matched against and applied to a stacktrace.
+#[derive(Debug, Clone)]
+pub struct RuleProfile {
+    /// The profiled rule.
+    pub rule: Rule,
+    /// The time spent matching the rule and applying its actions.
+    pub elapsed: Duration,
+    /// The number of frames the rule matched.
+    pub matched_frames: usize,
+}
+
+/// The original value of a frame field that was modified by
+/// [`apply_modifications_to_frames_with_options`](Enhancements::apply_modifications_to_frames_with_options).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UndoEntry {
+    /// The index of the modified frame.
+    pub frame: usize,
+    /// The modified field and its original value.
+    pub old_value: FieldValue,
+}
+
+/// The value of a frame field that rules can modify.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum FieldValue {
+    /// The value of [`Frame::in_app`].
+    InApp(Option<bool>),
+    /// The value of [`Frame::category`].
+    Category(Option<StringField>),
+    /// The value of [`Frame::has_vars`].
+    HasVars(Option<bool>),
+}
+
+/// Restores the original values of the frame fields recorded in `undo_log`.
+///
+/// `frames` must be the frames the undo log was recorded for. Entries referring
+/// to frames that don't exist are ignored.
+pub fn revert<F: FrameLikeMut>(frames: &mut [F], undo_log: &[UndoEntry]) {
+    for entry in undo_log.iter().rev() {
+        let Some(frame) = frames.get_mut(entry.frame) else {
+            continue;
+        };
+
+        match &entry.old_value {
+            FieldValue::InApp(in_app) => frame.set_in_app(*in_app),
+            FieldValue::Category(category) => frame.set_category(category.clone()),
+            FieldValue::HasVars(has_vars) => frame.set_has_vars(*has_vars),
+        }
+    }
+}
+
+/// The number of frames whose `in_app` flag was changed by a rule.
+#[derive(Debug, Clone)]
+pub struct InAppChanges {
+    /// The rule that changed the frames.
+    pub rule: Rule,
+    /// The number of frames whose `in_app` flag differs after applying the rule.
+    pub frames: usize,
+}
+
+/// Buffers that are reused across calls to [`apply_with_scratch`](Enhancements::apply_with_scratch).
+///
+/// A `Scratch` holds no state between calls, so a single one can be used with any
+/// number of `Enhancements` and stacktraces.
 #[derive(Debug, Default)]
+pub struct Scratch {
+    /// The indices of the frames matching the current rule.
+    matching_frames: Vec<usize>,
+    /// The priorities of the rules that last modified each frame.
+    priorities: FramePriorities,
+}
+
+/// A collection of [Rules](Rule) that modify the stacktrace and update grouping information.
+///
+/// The rules are partitioned by the frame families they can match as they are added, and
+/// applying them only visits the partition of the frames' family. Rules are applied through
+/// `&self`, so an `Arc<Enhancements>` is a handle that any number of threads can apply at once
+/// without copying the rules or their partitions. Cloning copies the lists of rules, but the
+/// rules themselves are shared between the clones.
+#[derive(Debug, Clone, Default)]
 pub struct Enhancements {
     /// The list of all rules in this collection.
     pub(crate) all_rules: Vec<Rule>,
     /// The list of "modifier rules" in this collection.
     ///
     /// Modifier rules are those rules that may modify a stacktrace.
-    modifier_rules: Vec<Rule>,
+    modifier_rules: PartitionedRules,
     /// The list of "updater rules" in this collection.
     ///
     /// Updater rules are those rules that may update grouping metadata.
-    updater_rules: Vec<Rule>,
+    updater_rules: PartitionedRules,
+    /// The list of rules in this collection that set a frame's category.
+    ///
+    /// These are a subset of the modifier rules.
+    category_rules: PartitionedRules,
+    /// The field matchers that occur in more than one updater rule.
+    ///
+    /// Their results are memoized while assembling a stacktrace component if
+    /// [`AssembleOptions::memoize_shared_matchers`] is set.
+    shared_matchers: SharedMatchers,
 }
 
 impl Enhancements {
@@ -69,83 +369,423 @@ impl Enhancements {
             .cloned()
             .collect();
 
-        let updater_rules = all_rules
+        let updater_rules: PartitionedRules = all_rules
             .iter()
             .filter(|r| r.has_updater_action())
             .cloned()
             .collect();
 
+        let category_rules = all_rules
+            .iter()
+            .filter(|r| r.has_category_action())
+            .cloned()
+            .collect();
+
+        let shared_matchers = SharedMatchers::new(&updater_rules.all);
+
         Enhancements {
             all_rules,
             modifier_rules,
             updater_rules,
+            category_rules,
+            shared_matchers,
         }
     }
 
     /// Parses an `Enhancements` structure from a string (in the form of a list of rules).
-    pub fn parse(input: &str, cache: &mut Cache) -> anyhow::Result<Self> {
+    ///
+    /// Lines of the form `@define <name> <matchers>` define named patterns that can be used
+    /// as `@<name>` in place of matchers in the rules.
+    pub fn parse(input: &str, cache: &mut Cache) -> Result<Self> {
+        Self::parse_with_options(input, cache, &ParseOptions::default())
+    }
+
+    /// Parses an `Enhancements` structure from a string (in the form of a list of rules),
+    /// validating every rule against the given [`ParseOptions`].
+    ///
+    /// If a line can't be parsed, the returned [`Error::Parse`] wraps a [`ParseError`]
+    /// locating the problem in the line.
+    pub fn parse_with_options(
+        input: &str,
+        cache: &mut Cache,
+        options: &ParseOptions,
+    ) -> Result<Self> {
+        Self::parse_lines(input, cache, options, &mut |err| Err(err.into()))
+    }
+
+    /// Parses an `Enhancements` structure from a string like [`parse`](Self::parse), skipping
+    /// lines that can't be parsed.
+    ///
+    /// Returns the rules of all other lines, together with the errors of the skipped lines in
+    /// the order of the lines. A line defining a pattern that is skipped leaves the pattern
+    /// undefined, so the rules using it are skipped as well.
+    pub fn parse_lenient(input: &str, cache: &mut Cache) -> (Self, Vec<ParseError>) {
+        Self::parse_lenient_with_options(input, cache, &ParseOptions::default())
+    }
+
+    /// Parses an `Enhancements` structure from a string like
+    /// [`parse_with_options`](Self::parse_with_options), skipping lines that can't be parsed
+    /// like [`parse_lenient`](Self::parse_lenient).
+    pub fn parse_lenient_with_options(
+        input: &str,
+        cache: &mut Cache,
+        options: &ParseOptions,
+    ) -> (Self, Vec<ParseError>) {
+        let mut errors = Vec::new();
+        let enhancements = Self::parse_lines(input, cache, options, &mut |err| {
+            errors.push(err);
+            Ok(())
+        })
+        .expect("lenient parsing reports all errors as `ParseError`s");
+        // Pattern definitions are parsed before the rules.
+        errors.sort_by_key(|err| err.line);
+        (enhancements, errors)
+    }
+
+    /// Parses the rules in the lines of `input`, passing the error of every line that can't
+    /// be parsed to `on_error`.
+    ///
+    /// Parsing stops at the first error `on_error` returns; lines it returns `Ok` for are
+    /// skipped.
+    fn parse_lines(
+        input: &str,
+        cache: &mut Cache,
+        options: &ParseOptions,
+        on_error: &mut dyn FnMut(ParseError) -> Result<()>,
+    ) -> Result<Self> {
         let mut all_rules = vec![];
 
-        for line in input.lines() {
-            let line = line.trim();
-            if line.is_empty() || line.starts_with('#') {
+        // Patterns may be used before they are defined, so all definitions are collected first.
+        let mut patterns = Patterns::default();
+        for (idx, raw_line) in input.lines().enumerate() {
+            let line = raw_line.trim();
+            if !Patterns::is_definition(line) {
+                continue;
+            }
+            let defined = patterns
+                .define(line)
+                .with_context(|| format!("at `{line}`: invalid pattern definition"));
+            if let Err(err) = defined {
+                let err = ParseError::locate(err, idx + 1, raw_line, line);
+                on_error(err.with_kind(ParseErrorKind::InvalidPattern))?;
+            }
+        }
+
+        for (idx, raw_line) in input.lines().enumerate() {
+            let line = raw_line.trim();
+            if line.is_empty() || line.starts_with('#') || Patterns::is_definition(line) {
                 continue;
             }
-            let rule = cache.get_or_try_insert_rule(line)?;
-            all_rules.push(rule);
+            match Self::parse_line(raw_line, idx + 1, &patterns, cache, options) {
+                Ok(rule) => all_rules.push(rule),
+                Err(err) => on_error(err)?,
+            }
         }
 
         Ok(Enhancements::new(all_rules))
     }
 
-    /// Parses an `Enhancements` structure from the msgpack representation.
-    pub fn from_config_structure(input: &[u8], cache: &mut Cache) -> anyhow::Result<Self> {
-        let EncodedEnhancements(version, _bases, rules) = rmp_serde::from_slice(input)?;
+    /// Parses the rule in `raw_line`, the line with the number `line_number`, expanding the
+    /// `patterns` it uses.
+    fn parse_line(
+        raw_line: &str,
+        line_number: usize,
+        patterns: &Patterns,
+        cache: &mut Cache,
+        options: &ParseOptions,
+    ) -> Result<Rule, ParseError> {
+        let line = raw_line.trim();
+        let expanded = patterns
+            .expand(line)
+            .with_context(|| format!("at `{line}`: failed to expand patterns"))
+            .map_err(|err| {
+                ParseError::locate(err, line_number, raw_line, line)
+                    .with_kind(ParseErrorKind::InvalidPattern)
+            })?;
+        let rule = cache
+            .get_or_try_insert_rule(&expanded)
+            .map_err(|err| {
+                let err = match err {
+                    Error::Parse(err) => err,
+                    err => err.into(),
+                };
+                ParseError::locate(err, line_number, raw_line, &expanded)
+            })?
+            .with_missing_values(options.missing_values);
+        rule.validate(options)
+            .with_context(|| format!("at `{expanded}`: invalid rule"))
+            .map_err(|err| {
+                ParseError::locate(err, line_number, raw_line, &expanded)
+                    .with_kind(ParseErrorKind::InvalidRule)
+            })?;
+        Ok(rule)
+    }
 
-        anyhow::ensure!(
-            version == 2,
-            "Rust Enhancements only supports config_structure version `2`"
-        );
+    /// Parses an `Enhancements` structure from the msgpack representation.
+    pub fn from_config_structure(input: &[u8], cache: &mut Cache) -> Result<Self> {
+        Self::from_config_structure_with_options(input, cache, &ParseOptions::default())
+    }
 
-        let all_rules: Vec<_> = rules
-            .into_iter()
-            .map(|r| {
-                let matchers =
-                    r.0.into_iter()
-                        .map(|encoded| EncodedMatcher::into_matcher(encoded, &mut cache.regex))
-                        .collect::<anyhow::Result<_>>()?;
-                let actions =
-                    r.1.into_iter()
-                        .map(EncodedAction::into_action)
-                        .collect::<anyhow::Result<_>>()?;
-
-                Ok(Rule::new(matchers, actions))
-            })
-            .collect::<anyhow::Result<_>>()?;
+    /// Parses an `Enhancements` structure from the msgpack representation,
+    /// validating every rule against the given [`ParseOptions`].
+    pub fn from_config_structure_with_options(
+        input: &[u8],
+        cache: &mut Cache,
+        options: &ParseOptions,
+    ) -> Result<Self> {
+        let all_rules =
+            decode_config_structure(input, cache, options).map_err(Error::ConfigStructure)?;
+        Ok(Enhancements::new(all_rules))
+    }
 
+    /// Parses an `Enhancements` structure from the msgpack representation, including the rules
+    /// of its bases.
+    ///
+    /// `resolve` returns the msgpack representation of the base with the given name, or `None`
+    /// if there is no such base. The rules of the bases come before the rules of the structure
+    /// naming them, in the order the bases are named, and bases of bases are resolved
+    /// recursively. A base that is named by several structures contributes its rules once for
+    /// every time it is named, like in Sentry.
+    ///
+    /// Returns an error if a base can't be resolved or decoded, or if a base names itself,
+    /// directly or through other bases.
+    pub fn from_config_structure_with_bases(
+        input: &[u8],
+        cache: &mut Cache,
+        options: &ParseOptions,
+        mut resolve: impl FnMut(&str) -> Option<Vec<u8>>,
+    ) -> Result<Self> {
+        let mut all_rules = Vec::new();
+        decode_config_structure_with_bases(
+            input,
+            cache,
+            options,
+            &mut resolve,
+            &mut Vec::new(),
+            &mut all_rules,
+        )
+        .map_err(Error::ConfigStructure)?;
         Ok(Enhancements::new(all_rules))
     }
 
+    /// Encodes these enhancements in the version 2 msgpack representation, which
+    /// [`from_config_structure`](Self::from_config_structure) and Sentry decode.
+    ///
+    /// The encoding names no bases, since the rules of the bases are already part of these
+    /// enhancements. Fails if a rule can't be represented, which is the case for `has_vars`
+    /// matchers, `vars` actions, `family` matchers with families other than `native`,
+    /// `javascript`, and `all`, and the extensions of this crate Sentry doesn't know (see
+    /// [`Encoding::Sentry`]).
+    pub fn to_config_structure(&self) -> Result<Vec<u8>> {
+        self.to_config_structure_with_encoding(Encoding::Sentry)
+    }
+
+    /// Encodes these enhancements like [`to_config_structure`](Self::to_config_structure),
+    /// in the given [`Encoding`].
+    ///
+    /// With [`Encoding::Extended`], the extensions of this crate are encoded too, but only
+    /// [`from_config_structure`](Self::from_config_structure) decodes the result.
+    pub fn to_config_structure_with_encoding(&self, encoding: Encoding) -> Result<Vec<u8>> {
+        let rules = self
+            .rules()
+            .map(|rule| config_structure::EncodedRule::from_rule(rule, encoding))
+            .collect::<anyhow::Result<_>>()
+            .map_err(Error::ConfigStructure)?;
+        let encoded = config_structure::EncodedEnhancements(2, Vec::new(), rules);
+        rmp_serde::to_vec(&encoded)
+            .context("failed to encode enhancements")
+            .map_err(Error::ConfigStructure)
+    }
+
     /// Matches `frames` and `exception_data` against all rules in this collection
     /// and applies the corresponding modifications if a frame matches a rule.
-    pub fn apply_modifications_to_frames(
+    pub fn apply_modifications_to_frames<F: FrameLikeMut>(
+        &self,
+        frames: &mut [F],
+        exception_data: &ExceptionData,
+    ) {
+        self.apply_modifications_to_frames_with_options(
+            frames,
+            exception_data,
+            &ModifyOptions::default(),
+        );
+    }
+
+    /// Applies modifications to `frames` like
+    /// [`apply_modifications_to_frames`](Self::apply_modifications_to_frames),
+    /// additionally collecting the information requested by the given [`ModifyOptions`].
+    pub fn apply_modifications_to_frames_with_options<F: FrameLikeMut>(
+        &self,
+        frames: &mut [F],
+        exception_data: &ExceptionData,
+        options: &ModifyOptions,
+    ) -> ModifyResult {
+        self.apply_modifications(
+            frames,
+            exception_data,
+            options,
+            &mut Scratch::default(),
+            None,
+            Recorder::new(options),
+        )
+    }
+
+    /// Applies modifications to `frames` like
+    /// [`apply_modifications_to_frames`](Self::apply_modifications_to_frames),
+    /// reusing the buffers in `scratch` instead of allocating new ones.
+    pub fn apply_with_scratch<F: FrameLikeMut>(
+        &self,
+        frames: &mut [F],
+        exception_data: &ExceptionData,
+        scratch: &mut Scratch,
+    ) {
+        self.apply_modifications(
+            frames,
+            exception_data,
+            &ModifyOptions::default(),
+            scratch,
+            None,
+            Recorder::default(),
+        );
+    }
+
+    /// Applies modifications to `frames`, using the buffers in `scratch`.
+    ///
+    /// The information `options` asks for besides the modifications is collected by `recorder`.
+    /// If `recorded` is given, the frames matched by rules that also update components
+    /// are recorded, as long as they can be reused for updating components.
+    fn apply_modifications<F: FrameLikeMut>(
+        &self,
+        frames: &mut [F],
+        exception_data: &ExceptionData,
+        options: &ModifyOptions,
+        scratch: &mut Scratch,
+        mut recorded: Option<&mut RecordedMatches>,
+        mut recorder: Recorder,
+    ) -> ModifyResult {
+        let mut result = ModifyResult::default();
+        let normalized;
+        let exception_data = if options.normalize_exception_value {
+            normalized = exception_data.with_normalized_value();
+            result.normalized_exception_value = normalized.value.clone();
+            &normalized
+        } else {
+            exception_data
+        };
+        let match_options = MatchOptions {
+            match_raw_function: options.match_raw_function,
+        };
+        let Scratch {
+            matching_frames,
+            priorities,
+        } = scratch;
+        matching_frames.clear();
+
+        let frame_range = FrameRange::new(frames.len(), options.frame_limit);
+        let rules = self.modifier_rules.for_frames(frames);
+        recorder.start(
+            frames,
+            self.modifier_rules.all.len(),
+            rules.len(),
+            frame_range,
+        );
+
+        if options.derive_default_in_app {
+            for frame in frames.iter_mut() {
+                if frame.in_app().is_none() {
+                    let in_app = default_in_app(frame);
+                    result.changed |= in_app.is_some();
+                    frame.set_in_app(in_app);
+                }
+            }
+        }
+
+        let reusable = match recorded {
+            Some(_) => reusable_matches(rules),
+            None => Vec::new(),
+        };
+        let use_priorities = rules.iter().any(|rule| rule.priority() > 0);
+        if use_priorities {
+            priorities.reset(frames.len());
+        }
+
+        for (rule_idx, rule) in rules.iter().enumerate() {
+            if recorder.start_rule(self, rule).is_break() {
+                break;
+            }
+
+            let matches_exception = rule.matches_exception(exception_data);
+            recorder.matched_exception(matches_exception);
+            if !matches_exception {
+                recorder.finish_rule(rule, frames, 0);
+                continue;
+            }
+
+            // first, for each frame check if the rule matches
+            matching_frames.extend(
+                frame_range
+                    .indices()
+                    .filter(|idx| rule.matches_frame_with_options(frames, *idx, match_options)),
+            );
+            let matched_frames = matching_frames.len();
+            result.matched |= matched_frames > 0;
+
+            if let Some(recorded) = recorded.as_deref_mut() {
+                if reusable[rule_idx] {
+                    recorded.record(rule, matching_frames);
+                }
+            }
+            recorder.before_actions(frames, matching_frames);
+
+            // then in a second pass, apply the actions to all matching frames
+            for idx in matching_frames.drain(..) {
+                result.changed |= if use_priorities {
+                    rule.apply_modifications_with_priorities(frames, idx, priorities)
+                } else {
+                    rule.apply_modifications_to_frame(frames, idx)
+                };
+            }
+
+            recorder.finish_rule(rule, frames, matched_frames);
+        }
+
+        recorder.finish(frames, &mut result);
+        result
+    }
+
+    /// Matches `frames` and `exception_data` against all rules in this collection
+    /// that set a frame's category, and applies only the `category` actions.
+    ///
+    /// This is considerably cheaper than [`apply_modifications_to_frames`](Self::apply_modifications_to_frames)
+    /// for callers that are only interested in frame categories. Note that since
+    /// `in_app` is not modified, rules matching on `app:` see the original `in_app` values.
+    pub fn apply_categories<F: FrameLikeMut>(
         &self,
-        frames: &mut [Frame],
+        frames: &mut [F],
         exception_data: &ExceptionData,
     ) {
         let mut matching_frames = Vec::with_capacity(frames.len());
-        for rule in &self.modifier_rules {
+        let rules = self.category_rules.for_frames(frames);
+        let mut priorities = FramePriorities::default();
+        let use_priorities = rules.iter().any(|rule| rule.priority() > 0);
+        if use_priorities {
+            priorities.reset(frames.len());
+        }
+
+        for rule in rules {
             if !rule.matches_exception(exception_data) {
                 continue;
             }
 
-            // first, for each frame check if the rule matches
             matching_frames
                 .extend((0..frames.len()).filter(|idx| rule.matches_frame(frames, *idx)));
 
-            // then in a second pass, apply the actions to all matching frames
             for idx in matching_frames.drain(..) {
-                rule.apply_modifications_to_frame(frames, idx);
+                if use_priorities {
+                    rule.apply_categories_with_priorities(frames, idx, &mut priorities);
+                } else {
+                    rule.apply_categories_to_frame(frames, idx);
+                }
             }
         }
     }
@@ -154,42 +794,366 @@ impl Enhancements {
     /// `frame` [`Component`]s and [`Frame`]s.
     ///
     /// It also updates the [`Component`]s `contributes`, `hint` and other attributes.
-    pub fn assemble_stacktrace_component(
+    /// Components without a `contributes` flag but with [`values`](Component::values)
+    /// contribute if they have any values.
+    pub fn assemble_stacktrace_component<F: FrameLike>(
+        &self,
+        components: &mut [Component],
+        frames: &[F],
+        exception_data: &ExceptionData,
+    ) -> AssembleResult {
+        self.assemble_stacktrace_component_with_options(
+            components,
+            frames,
+            exception_data,
+            &AssembleOptions::default(),
+        )
+    }
+
+    /// Assembles a `stacktrace` grouping component like
+    /// [`assemble_stacktrace_component`](Self::assemble_stacktrace_component),
+    /// using the given [`AssembleOptions`] as defaults for the stacktrace state.
+    pub fn assemble_stacktrace_component_with_options<F: FrameLike>(
         &self,
         components: &mut [Component],
-        frames: &[Frame],
+        frames: &[F],
+        exception_data: &ExceptionData,
+        options: &AssembleOptions,
+    ) -> AssembleResult {
+        let mut memo_results = Vec::new();
+        self.assemble(
+            components,
+            frames,
+            exception_data,
+            options,
+            None,
+            &mut memo_results,
+        )
+        .assemble
+    }
+
+    /// Updates the components of `buffer` like
+    /// [`assemble_stacktrace_component_with_options`](Self::assemble_stacktrace_component_with_options).
+    ///
+    /// The components are the ones last [reset](ComponentBuffer::reset), and are updated in
+    /// place. Reusing one buffer for many stack traces, like those of all threads of an event,
+    /// saves allocating the components and the scratch space of matching for each of them.
+    pub fn assemble_stacktrace_component_in<F: FrameLike>(
+        &self,
+        buffer: &mut ComponentBuffer,
+        frames: &[F],
         exception_data: &ExceptionData,
+        options: &AssembleOptions,
     ) -> AssembleResult {
-        let mut stacktrace_state = StacktraceState::default();
+        let ComponentBuffer {
+            components,
+            len,
+            memo_results,
+        } = buffer;
+        self.assemble(
+            &mut components[..*len],
+            frames,
+            exception_data,
+            options,
+            None,
+            memo_results,
+        )
+        .assemble
+    }
+
+    /// Applies modifications to `frames` and updates `components` in a single pass over the rules.
+    ///
+    /// This has the same effect as calling [`apply_modifications_to_frames`](Self::apply_modifications_to_frames)
+    /// followed by [`assemble_stacktrace_component`](Self::assemble_stacktrace_component), but rules
+    /// that do both only match the frames once.
+    pub fn apply_all<F: FrameLikeMut>(
+        &self,
+        frames: &mut [F],
+        components: &mut [Component],
+        exception_data: &ExceptionData,
+    ) -> ApplyAllResult {
+        self.apply_all_with_options(frames, components, exception_data, &Default::default())
+    }
+
+    /// Applies modifications and updates components like [`apply_all`](Self::apply_all),
+    /// using the given [`AssembleOptions`] as defaults for the stacktrace state.
+    ///
+    /// The `match_raw_function` and `frame_limit` options also apply to the modifications.
+    pub fn apply_all_with_options<F: FrameLikeMut>(
+        &self,
+        frames: &mut [F],
+        components: &mut [Component],
+        exception_data: &ExceptionData,
+        options: &AssembleOptions,
+    ) -> ApplyAllResult {
+        let started = Instant::now();
+        let modify_options = ModifyOptions {
+            match_raw_function: options.match_raw_function,
+            frame_limit: options.frame_limit,
+            normalize_exception_value: options.normalize_exception_value,
+            time_budget: options.time_budget,
+            ..Default::default()
+        };
+        let mut recorded = RecordedMatches::default();
+        let modify_result = self.apply_modifications(
+            frames,
+            exception_data,
+            &modify_options,
+            &mut Scratch::default(),
+            Some(&mut recorded),
+            Recorder::new(&modify_options),
+        );
+
+        // Both passes share the time budget.
+        let assemble_options = AssembleOptions {
+            time_budget: options
+                .time_budget
+                .map(|budget| budget.saturating_sub(started.elapsed())),
+            ..options.clone()
+        };
+        let mut result = self.assemble(
+            components,
+            frames,
+            exception_data,
+            &assemble_options,
+            Some(&mut recorded),
+            &mut Vec::new(),
+        );
+        if modify_result.budget_exhausted_at.is_some() {
+            result.assemble.budget_exhausted_at = modify_result.budget_exhausted_at;
+        }
+        result
+    }
+
+    /// Updates `components` according to the rules and computes the resulting [`StacktraceState`].
+    ///
+    /// Rules whose matches were recorded in `recorded` are not matched against the frames again.
+    fn assemble<F: FrameLike>(
+        &self,
+        components: &mut [Component],
+        frames: &[F],
+        exception_data: &ExceptionData,
+        options: &AssembleOptions,
+        mut recorded: Option<&mut RecordedMatches>,
+        memo_results: &mut Vec<Option<Option<bool>>>,
+    ) -> ApplyAllResult {
+        let normalized;
+        let exception_data = if options.normalize_exception_value {
+            normalized = exception_data.with_normalized_value();
+            &normalized
+        } else {
+            exception_data
+        };
+        let mut stacktrace_state = StacktraceState::from_options(options);
+        let match_options = MatchOptions {
+            match_raw_function: options.match_raw_function,
+        };
+        let mut memo = MatchMemo::new(&self.shared_matchers, frames.len(), match_options)
+            .with_results(std::mem::take(memo_results));
+
+        for component in components.iter_mut() {
+            component.init_contributes();
+        }
+
+        let deadline = deadline(options.time_budget);
+        let mut budget_exhausted_at = None;
+        // Like in `apply_modifications`, the id of every rule is searched from the previous one.
+        let mut next_rule_id = 0;
+        let frame_range = FrameRange::new(frames.len(), options.frame_limit);
+        // The memo would be sized for all frames, which is what truncation tries to avoid.
+        let memoize = options.memoize_shared_matchers && !frame_range.is_truncated();
+        let rules = self.updater_rules.for_frames(frames);
+        let mut stats = options
+            .collect_stats
+            .then(|| ApplyStats::new(self.updater_rules.all.len(), rules.len(), frame_range));
 
         // Apply direct frame actions and update the stack state alongside
-        for rule in &self.updater_rules {
-            if !rule.matches_exception(exception_data) {
+        for rule in rules {
+            let rule_id = deadline
+                .is_some()
+                .then(|| self.find_rule_id(rule, &mut next_rule_id))
+                .flatten();
+            if deadline.is_some_and(|deadline| now() >= deadline) {
+                budget_exhausted_at = rule_id;
+                break;
+            }
+            if let Some(matched) = recorded.as_deref_mut().and_then(|r| r.take(rule)) {
+                if let Some(stats) = &mut stats {
+                    stats.record(true);
+                }
+                for &idx in matched {
+                    rule.update_frame_components_contributions(components, frames, idx);
+                    rule.modify_stacktrace_state(&mut stacktrace_state);
+                }
                 continue;
             }
 
-            for idx in 0..frames.len() {
-                if rule.matches_frame(frames, idx) {
+            let matches_exception = rule.matches_exception(exception_data);
+            if let Some(stats) = &mut stats {
+                stats.record(matches_exception);
+            }
+            if !matches_exception {
+                continue;
+            }
+
+            let memoize = memoize && self.shared_matchers.contains_rule(rule);
+            for idx in frame_range.indices() {
+                let matches = if memoize {
+                    rule.matches_frame_memoized(frames, idx, &mut memo)
+                } else {
+                    rule.matches_frame_with_options(frames, idx, match_options)
+                };
+                if matches {
                     rule.update_frame_components_contributions(components, frames, idx);
                     rule.modify_stacktrace_state(&mut stacktrace_state);
                 }
             }
         }
 
+        if options.trim_to_sentinel {
+            update_components_for_sentinel(components);
+        }
+
         // Use the stack state to update frame contributions again to trim
         // down to `max-frames`.
-        update_components_for_max_frames(components, stacktrace_state.max_frames);
+        // This only counts the frames that are left after trimming to the sentinel.
+        update_components_for_max_frames(components, stacktrace_state.max_frames.clone());
 
         // `min-frames` is handled on the other hand for
         // the entire stacktrace.
         let (contributes, hint) =
-            update_components_for_min_frames(components, stacktrace_state.min_frames);
+            update_components_for_min_frames(components, stacktrace_state.min_frames.clone());
+        *memo_results = memo.into_results();
 
-        AssembleResult {
-            contributes,
-            hint,
-            invert_stacktrace: stacktrace_state.invert_stacktrace.value,
+        ApplyAllResult {
+            assemble: AssembleResult {
+                contributes,
+                hint,
+                invert_stacktrace: stacktrace_state.invert_stacktrace.value,
+                stats,
+                normalized_exception_value: options
+                    .normalize_exception_value
+                    .then(|| exception_data.value.clone())
+                    .flatten(),
+                budget_exhausted_at,
+            },
+            stacktrace_state,
+        }
+    }
+
+    /// Computes only the [`StacktraceState`] for the given `frames` and `exception_data`,
+    /// without updating any components.
+    ///
+    /// This yields the same state [`assemble_stacktrace_component_with_options`](Self::assemble_stacktrace_component_with_options)
+    /// computes internally, using the given [`AssembleOptions`] as defaults.
+    pub fn stacktrace_state<F: FrameLike>(
+        &self,
+        frames: &[F],
+        exception_data: &ExceptionData,
+        options: &AssembleOptions,
+    ) -> StacktraceState {
+        let normalized;
+        let exception_data = if options.normalize_exception_value {
+            normalized = exception_data.with_normalized_value();
+            &normalized
+        } else {
+            exception_data
+        };
+        let mut stacktrace_state = StacktraceState::from_options(options);
+        let match_options = MatchOptions {
+            match_raw_function: options.match_raw_function,
+        };
+        let mut memo = MatchMemo::new(&self.shared_matchers, frames.len(), match_options);
+        let frame_range = FrameRange::new(frames.len(), options.frame_limit);
+        let memoize = options.memoize_shared_matchers && !frame_range.is_truncated();
+
+        for rule in self.updater_rules.for_frames(frames) {
+            if !rule.matches_exception(exception_data) {
+                continue;
+            }
+
+            let memoize = memoize && self.shared_matchers.contains_rule(rule);
+            if frame_range.indices().any(|idx| {
+                if memoize {
+                    rule.matches_frame_memoized(frames, idx, &mut memo)
+                } else {
+                    rule.matches_frame_with_options(frames, idx, match_options)
+                }
+            }) {
+                rule.modify_stacktrace_state(&mut stacktrace_state);
+            }
+        }
+
+        stacktrace_state
+    }
+
+    /// Drops all rules that don't update grouping metadata.
+    ///
+    /// This is useful for processes that only ever call
+    /// [`assemble_stacktrace_component`](Self::assemble_stacktrace_component).
+    /// Afterwards, [`apply_modifications_to_frames`](Self::apply_modifications_to_frames)
+    /// is a no-op.
+    pub fn retain_updater_only(&mut self) {
+        self.all_rules.retain(|r| r.has_updater_action());
+        self.modifier_rules = Default::default();
+        self.category_rules = Default::default();
+    }
+
+    /// Drops all rules that don't modify frames.
+    ///
+    /// This is useful for processes that only ever call
+    /// [`apply_modifications_to_frames`](Self::apply_modifications_to_frames).
+    /// Afterwards, [`assemble_stacktrace_component`](Self::assemble_stacktrace_component)
+    /// will not update any components.
+    pub fn retain_modifier_only(&mut self) {
+        self.all_rules.retain(|r| r.has_modifier_action());
+        self.updater_rules = Default::default();
+        self.shared_matchers = Default::default();
+    }
+
+    /// Removes duplicate rules, keeping the last occurrence of each.
+    ///
+    /// An earlier occurrence is only removed if none of the rules between it and
+    /// the next kept occurrence observe its effects, e.g. by matching on `app:` when
+    /// it sets the `app` flag. This ensures that the result of applying the
+    /// enhancements doesn't change.
+    pub fn dedup(&mut self) {
+        let rules = &self.all_rules;
+        let mut keep = vec![true; rules.len()];
+        // The interior mutability of `Rule` lies in its compiled regexes, which don't affect hashing.
+        #[allow(clippy::mutable_key_type)]
+        let mut next_kept: HashMap<&Rule, usize> = HashMap::new();
+
+        for (idx, rule) in rules.iter().enumerate().rev() {
+            if let Some(&next) = next_kept.get(rule) {
+                let observed = (idx + 1..next)
+                    .filter(|&between| keep[between])
+                    .any(|between| rules[between].observes_effects_of(rule));
+                if !observed {
+                    keep[idx] = false;
+                    continue;
+                }
+            }
+            next_kept.insert(rule, idx);
+        }
+
+        if keep.iter().all(|keep| *keep) {
+            return;
         }
+
+        self.modifier_rules.retain_positions(rules, &keep);
+        self.updater_rules.retain_positions(rules, &keep);
+        self.category_rules.retain_positions(rules, &keep);
+        self.shared_matchers = SharedMatchers::new(&self.updater_rules.all);
+
+        let mut keep = keep.into_iter();
+        self.all_rules.retain(|_| keep.next().unwrap_or(true));
+    }
+
+    /// Returns a JSON array with the [description](Rule::describe) of every rule in this collection.
+    pub fn to_json(&self) -> String {
+        let rules: Vec<_> = self.rules().map(Rule::describe).collect();
+        serde_json::to_string(&rules).expect("rule descriptions are valid JSON")
     }
 
     /// Returns an iterator over all rules in this collection.
@@ -197,14 +1161,83 @@ impl Enhancements {
         self.all_rules.iter()
     }
 
+    /// Returns all rules that set the variable `kind`, in their original order.
+    ///
+    /// For example, this finds the rules overriding the `max-frames` limit.
+    pub fn rules_setting(&self, kind: VarKind) -> impl Iterator<Item = &Rule> {
+        self.all_rules
+            .iter()
+            .filter(move |rule| rule.sets_var(kind))
+    }
+
+    /// Returns the ids of all rules that match the frame at `frames[idx]` and `exception_data`,
+    /// in their original order.
+    ///
+    /// Both modifier and updater rules are checked. The frames are matched as they are given,
+    /// without applying any rules first, so e.g. `app` matchers don't see the `in_app` flags
+    /// earlier rules would set. This is meant for finding out which rules are responsible for
+    /// how a frame is treated.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `idx` is out of bounds.
+    pub fn matching_rules<F: FrameLike>(
+        &self,
+        frames: &[F],
+        idx: usize,
+        exception_data: &ExceptionData,
+    ) -> Vec<RuleId> {
+        assert!(idx < frames.len(), "frame index out of bounds");
+        self.all_rules
+            .iter()
+            .enumerate()
+            .filter(|(_, rule)| {
+                rule.matches_exception(exception_data) && rule.matches_frame(frames, idx)
+            })
+            .filter_map(|(idx, _)| RuleId::new(idx))
+            .collect()
+    }
+
+    /// Returns the rule with the given id.
+    ///
+    /// Ids are positions in [`rules`](Self::rules), so they are only meaningful for the
+    /// collection that produced them, and only until its rules are changed.
+    pub fn rule(&self, id: RuleId) -> Option<&Rule> {
+        self.all_rules.get(id.index())
+    }
+
+    /// Returns the id of `rule`, searching the list of all rules from position `from` onward.
+    ///
+    /// `from` is advanced past the rule, so that the ids of rules in their original order
+    /// can be found in a single pass.
+    fn find_rule_id(&self, rule: &Rule, from: &mut usize) -> Option<RuleId> {
+        let offset = self.all_rules[*from..]
+            .iter()
+            .position(|candidate| Arc::ptr_eq(&candidate.0, &rule.0))?;
+        let idx = *from + offset;
+        *from = idx + 1;
+        RuleId::new(idx)
+    }
+
     /// Adds all rules contained in `other` to `self`.
     pub fn extend_from(&mut self, other: &Enhancements) {
         self.extend(other.rules().cloned())
     }
+
+    /// Returns a copy of `self` with all rules contained in `other` added to it.
+    ///
+    /// The rules are shared with `self` and `other` rather than copied.
+    pub fn with_additional_rules(&self, other: &Enhancements) -> Self {
+        let mut enhancements = self.clone();
+        enhancements.extend_from(other);
+        enhancements
+    }
 }
 
 impl Extend<Rule> for Enhancements {
     fn extend<T: IntoIterator<Item = Rule>>(&mut self, iter: T) {
+        let updater_rules = self.updater_rules.all.len();
+
         for rule in iter.into_iter() {
             if rule.has_modifier_action() {
                 self.modifier_rules.push(rule.clone());
@@ -214,15 +1247,166 @@ impl Extend<Rule> for Enhancements {
                 self.updater_rules.push(rule.clone());
             }
 
+            if rule.has_category_action() {
+                self.category_rules.push(rule.clone());
+            }
+
             self.all_rules.push(rule);
         }
+
+        if self.updater_rules.all.len() != updater_rules {
+            self.shared_matchers = SharedMatchers::new(&self.updater_rules.all);
+        }
     }
 }
 
 #[derive(Debug, Clone, Default)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(default)
+)]
 pub struct Component {
     pub contributes: Option<bool>,
-    pub hint: Option<String>,
+    /// The hints explaining the `contributes` flag, in the order they were added.
+    ///
+    /// Each hint is present only once. See [`primary_hint`](Self::primary_hint) for the hint
+    /// that takes precedence. Only the primary hint is (de)serialized, as `"hint"`.
+    #[cfg_attr(
+        feature = "serde",
+        serde(
+            rename = "hint",
+            serialize_with = "event::serialize_hints",
+            deserialize_with = "event::deserialize_hints"
+        )
+    )]
+    pub hints: Vec<Hint>,
+    /// Whether the frame is a sentinel frame, see [`AssembleOptions::trim_to_sentinel`].
+    pub is_sentinel_frame: bool,
+    /// Whether the frame is a prefix frame, see [`AssembleOptions::trim_to_sentinel`].
+    pub is_prefix_frame: bool,
+    /// The id of the grouping component, e.g. `"frame"`.
+    #[cfg_attr(feature = "serde", serde(skip_serializing_if = "Option::is_none"))]
+    pub id: Option<String>,
+    /// The grouping values of the component's contributing children, if known.
+    ///
+    /// Components whose `contributes` flag is unset contribute if and only if they have
+    /// contributing values.
+    #[cfg_attr(feature = "serde", serde(skip_serializing_if = "Option::is_none"))]
+    pub values: Option<Vec<String>>,
+}
+
+/// Components that can be reused for assembling many stack traces.
+///
+/// See [`Enhancements::assemble_stacktrace_component_in`].
+#[derive(Debug, Default)]
+pub struct ComponentBuffer {
+    /// The components of the current stack trace, followed by spare ones from earlier ones.
+    components: Vec<Component>,
+    /// The number of components of the current stack trace.
+    len: usize,
+    /// The cleared scratch space of [`MatchMemo`].
+    memo_results: Vec<Option<Option<bool>>>,
+}
+
+impl ComponentBuffer {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Returns `len` components in their default state for the next stack trace.
+    ///
+    /// The allocations of the hints of earlier components are reused.
+    pub fn reset(&mut self, len: usize) -> &mut [Component] {
+        if self.components.len() < len {
+            self.components.resize_with(len, Default::default);
+        }
+        self.len = len;
+
+        let components = &mut self.components[..len];
+        for component in components.iter_mut() {
+            let mut hints = std::mem::take(&mut component.hints);
+            hints.clear();
+            *component = Component {
+                hints,
+                ..Default::default()
+            };
+        }
+        components
+    }
+
+    /// Returns the components of the current stack trace.
+    pub fn components(&self) -> &[Component] {
+        &self.components[..self.len]
+    }
+
+    /// Returns the components of the current stack trace mutably.
+    pub fn components_mut(&mut self) -> &mut [Component] {
+        &mut self.components[..self.len]
+    }
+}
+
+impl Component {
+    /// Sets the `contributes` flag from the contributing values, unless it is already set.
+    fn init_contributes(&mut self) {
+        if self.contributes.is_none() {
+            self.contributes = self.values.as_ref().map(|values| !values.is_empty());
+        }
+    }
+
+    /// Adds a hint to this component.
+    ///
+    /// If the component already has the same hint, it is moved to the end instead.
+    /// Returns whether the hint is now the [primary hint](Self::primary_hint), which is not
+    /// the case if the component already has a hint of a higher [`HintKind`].
+    pub fn add_hint(&mut self, kind: HintKind, text: String) -> bool {
+        let hint = Hint {
+            kind: Some(kind),
+            text,
+        };
+        self.hints.retain(|existing| *existing != hint);
+        self.hints.push(hint);
+
+        self.hints
+            .iter()
+            .all(|existing| existing.kind <= Some(kind))
+    }
+
+    /// The hint that takes precedence, i.e. the last one of the highest [`HintKind`].
+    ///
+    /// This is the hint that older versions kept as the only hint of the component.
+    pub fn primary_hint(&self) -> Option<&str> {
+        primary_hint(&self.hints)
+    }
+}
+
+fn primary_hint(hints: &[Hint]) -> Option<&str> {
+    // `max_by_key` returns the last of several maximal elements.
+    hints
+        .iter()
+        .max_by_key(|hint| hint.kind)
+        .map(|hint| hint.text.as_str())
+}
+
+/// A hint on a [`Component`], explaining why it does or doesn't contribute.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Hint {
+    /// The kind of the hint, or `None` if it wasn't added by the enhancements, e.g. because it
+    /// was part of the input.
+    ///
+    /// Hints without a kind have the lowest precedence.
+    pub kind: Option<HintKind>,
+    pub text: String,
+}
+
+impl Hint {
+    /// Creates a hint without a kind, e.g. one that was part of the input.
+    pub fn new(text: impl Into<String>) -> Self {
+        Self {
+            kind: None,
+            text: text.into(),
+        }
+    }
 }
 
 #[derive(Debug, Clone, Default)]
@@ -233,23 +1417,180 @@ pub struct StacktraceVariable<T> {
 
 #[derive(Debug, Clone, Default)]
 pub struct StacktraceState {
-    pub max_frames: StacktraceVariable<usize>,
-    pub min_frames: StacktraceVariable<usize>,
+    pub max_frames: StacktraceVariable<Option<NonZeroUsize>>,
+    pub min_frames: StacktraceVariable<Option<NonZeroUsize>>,
     pub invert_stacktrace: StacktraceVariable<bool>,
 }
 
+impl StacktraceState {
+    /// Creates a `StacktraceState` holding the default values from `options`.
+    pub fn from_options(options: &AssembleOptions) -> Self {
+        Self {
+            max_frames: StacktraceVariable {
+                value: options.max_frames,
+                setter: None,
+            },
+            min_frames: StacktraceVariable {
+                value: options.min_frames,
+                setter: None,
+            },
+            invert_stacktrace: StacktraceVariable {
+                value: options.invert_stacktrace,
+                setter: None,
+            },
+        }
+    }
+}
+
+/// The frames matched by rules while applying modifications, to be reused when updating components.
+#[derive(Debug, Default)]
+struct RecordedMatches {
+    /// The rules whose matches were recorded, in the order they were applied, together
+    /// with the range of their matches in `frames`.
+    rules: Vec<(Rule, std::ops::Range<usize>)>,
+    /// The indices of the matched frames.
+    frames: Vec<usize>,
+    /// The position in `rules` of the next rule to be taken.
+    next: usize,
+}
+
+impl RecordedMatches {
+    /// Records that `rule` matched the frames at the indices in `matches`.
+    fn record(&mut self, rule: &Rule, matches: &[usize]) {
+        let start = self.frames.len();
+        self.frames.extend_from_slice(matches);
+        self.rules.push((rule.clone(), start..self.frames.len()));
+    }
+
+    /// Returns the frames matched by `rule` if it is the next recorded rule.
+    ///
+    /// Since components are updated in the same order modifications are applied in,
+    /// every recorded rule is taken in turn.
+    fn take(&mut self, rule: &Rule) -> Option<&[usize]> {
+        let (recorded, range) = self.rules.get(self.next)?;
+        if !Arc::ptr_eq(&recorded.0, &rule.0) {
+            return None;
+        }
+        self.next += 1;
+        Some(&self.frames[range.clone()])
+    }
+}
+
+/// Returns, for each of the `rules`, whether the frames it matches while applying
+/// modifications can be reused for updating components.
+///
+/// Components are updated after all modifications have been applied, so this is only
+/// the case for rules that update components and match on no field modified by
+/// themselves or any later rule.
+fn reusable_matches(rules: &[Rule]) -> Vec<bool> {
+    let mut modified: Vec<FrameField> = Vec::new();
+    let mut reusable: Vec<_> = rules
+        .iter()
+        .rev()
+        .map(|rule| {
+            for field in rule.modified_fields() {
+                if !modified.contains(&field) {
+                    modified.push(field);
+                }
+            }
+            rule.has_updater_action() && !modified.iter().any(|field| rule.reads_field(*field))
+        })
+        .collect();
+    reusable.reverse();
+    reusable
+}
+
+/// Returns the instant at which a `time_budget` starting now is exhausted.
+///
+/// Budgets too large to be represented are treated as unlimited.
+fn deadline(time_budget: Option<Duration>) -> Option<Instant> {
+    time_budget.and_then(|budget| now().checked_add(budget))
+}
+
+/// Returns the current instant, against which time budgets are checked.
+///
+/// In tests that set the fake clock, the clock advances by a second on every reading
+/// instead, so that budgets run out at a predictable rule.
+fn now() -> Instant {
+    #[cfg(test)]
+    if let Some(now) = tests::FAKE_CLOCK.get() {
+        tests::FAKE_CLOCK.set(Some(now + Duration::from_secs(1)));
+        return now;
+    }
+    Instant::now()
+}
+
+/// The indices of the frames rules are applied to.
+///
+/// If there are more than twice as many frames as the limit, only the first and last `limit`
+/// frames are included.
+#[derive(Debug, Clone, Copy)]
+struct FrameRange {
+    len: usize,
+    head: usize,
+    tail_start: usize,
+}
+
+impl FrameRange {
+    fn new(len: usize, limit: Option<NonZeroUsize>) -> Self {
+        match limit {
+            Some(limit) if len > 2 * limit.get() => Self {
+                len,
+                head: limit.get(),
+                tail_start: len - limit.get(),
+            },
+            _ => Self {
+                len,
+                head: len,
+                tail_start: len,
+            },
+        }
+    }
+
+    fn is_truncated(&self) -> bool {
+        self.head < self.tail_start
+    }
+
+    fn indices(&self) -> impl Iterator<Item = usize> {
+        (0..self.head).chain(self.tail_start..self.len)
+    }
+}
+
+/// Ignores all frames above the last sentinel frame, except for the chain of prefix frames
+/// directly above it.
+fn update_components_for_sentinel(components: &mut [Component]) {
+    let Some(sentinel) = components.iter().rposition(|c| c.is_sentinel_frame) else {
+        return;
+    };
+
+    let above = &mut components[sentinel + 1..];
+    let prefix_chain = above.iter().take_while(|c| c.is_prefix_frame).count();
+
+    for component in &mut above[prefix_chain..] {
+        if !component.contributes.unwrap_or_default() {
+            continue;
+        }
+
+        component.contributes = Some(false);
+        component.add_hint(
+            HintKind::Sentinel,
+            "ignored because it is above the sentinel frame".into(),
+        );
+    }
+}
+
 fn update_components_for_max_frames(
     components: &mut [Component],
-    max_frames: StacktraceVariable<usize>,
+    max_frames: StacktraceVariable<Option<NonZeroUsize>>,
 ) {
     let StacktraceVariable {
         value: max_frames,
         setter,
     } = max_frames;
 
-    if max_frames == 0 {
+    let Some(max_frames) = max_frames.map(NonZeroUsize::get) else {
         return;
-    }
+    };
 
     let mut ignored = 0;
 
@@ -279,13 +1620,13 @@ fn update_components_for_max_frames(
         }
 
         component.contributes = Some(false);
-        component.hint = Some(hint);
+        component.add_hint(HintKind::MaxFrames, hint);
     }
 }
 
 fn update_components_for_min_frames(
     components: &[Component],
-    min_frames: StacktraceVariable<usize>,
+    min_frames: StacktraceVariable<Option<NonZeroUsize>>,
 ) -> (bool, Option<String>) {
     let total_contributes: usize = components
         .iter()
@@ -300,9 +1641,9 @@ fn update_components_for_min_frames(
         setter,
     } = min_frames;
 
-    if min_frames == 0 {
+    let Some(min_frames) = min_frames.map(NonZeroUsize::get) else {
         return (contributes, hint);
-    }
+    };
 
     if total_contributes > 0 && total_contributes < min_frames {
         let mut hint_str = format!("discarded because stack trace only contains {total_contributes} frame{} which is under the configured threshold", if total_contributes == 1 { "" } else {"s"});
@@ -318,18 +1659,1754 @@ fn update_components_for_min_frames(
     (contributes, hint)
 }
 
+/// Decodes and validates the rules of the msgpack representation of an [`Enhancements`] structure.
+fn decode_config_structure(
+    input: &[u8],
+    cache: &mut Cache,
+    options: &ParseOptions,
+) -> anyhow::Result<Vec<Rule>> {
+    decode_config_structure_parts(input, cache, options).map(|(_bases, rules)| rules)
+}
+
+/// Decodes the rules of the msgpack representation of an [`Enhancements`] structure like
+/// [`decode_config_structure`], after recursively decoding the rules of its bases.
+///
+/// `stack` contains the names of the bases currently being decoded.
+fn decode_config_structure_with_bases(
+    input: &[u8],
+    cache: &mut Cache,
+    options: &ParseOptions,
+    resolve: &mut dyn FnMut(&str) -> Option<Vec<u8>>,
+    stack: &mut Vec<SmolStr>,
+    all_rules: &mut Vec<Rule>,
+) -> anyhow::Result<()> {
+    let (bases, rules) = decode_config_structure_parts(input, cache, options)?;
+
+    for base in bases {
+        if stack.contains(&base) {
+            anyhow::bail!("base `{base}` names itself");
+        }
+        let encoded =
+            resolve(&base).with_context(|| format!("unknown base enhancements `{base}`"))?;
+
+        stack.push(base);
+        let result =
+            decode_config_structure_with_bases(&encoded, cache, options, resolve, stack, all_rules);
+        let base = stack.pop().unwrap();
+        result.with_context(|| format!("in base `{base}`"))?;
+    }
+
+    all_rules.extend(rules);
+    Ok(())
+}
+
+/// Decodes and validates the names of the bases and the rules of the msgpack representation of
+/// an [`Enhancements`] structure.
+fn decode_config_structure_parts(
+    input: &[u8],
+    cache: &mut Cache,
+    options: &ParseOptions,
+) -> anyhow::Result<(Vec<SmolStr>, Vec<Rule>)> {
+    config_structure::decode_enhancements(input, |encoded| {
+        let rule = encoded
+            .into_rule(&mut cache.regex)?
+            .with_missing_values(options.missing_values);
+        rule.validate(options)
+            .with_context(|| format!("at `{rule}`: invalid rule"))?;
+        Ok(rule)
+    })
+}
+
 #[cfg(test)]
 mod tests {
+    use std::cell::Cell;
+
+    use serde_json::json;
+
     use super::*;
 
+    thread_local! {
+        /// The current instant of the clock time budgets are checked against, if it is faked.
+        pub(super) static FAKE_CLOCK: Cell<Option<Instant>> = const { Cell::new(None) };
+    }
+
+    #[test]
+    fn resolves_bases() {
+        let mut cache = Cache::default();
+        let encode = |bases: &[&str], input: &str| {
+            let enhancements = Enhancements::parse(input, &mut Cache::default()).unwrap();
+            let rules = enhancements
+                .rules()
+                .map(|rule| {
+                    config_structure::EncodedRule::from_rule(rule, Encoding::Sentry).unwrap()
+                })
+                .collect();
+            let bases = bases.iter().map(|&base| base.into()).collect();
+            rmp_serde::to_vec(&config_structure::EncodedEnhancements(2, bases, rules)).unwrap()
+        };
+        let configs = HashMap::from([
+            ("common", encode(&[], "function:common -app")),
+            ("native", encode(&["common"], "family:native -group")),
+            ("loop", encode(&["cycle"], "function:a -app")),
+            ("cycle", encode(&["loop"], "function:b -app")),
+            ("broken", b"not msgpack".to_vec()),
+        ]);
+        let resolve = |name: &str| configs.get(name).cloned();
+
+        let input = encode(&["native", "common"], "function:custom +app");
+        let enhancements = Enhancements::from_config_structure_with_bases(
+            &input,
+            &mut cache,
+            &Default::default(),
+            resolve,
+        )
+        .unwrap();
+        let rules: Vec<_> = enhancements.rules().map(|r| r.to_string()).collect();
+        assert_eq!(
+            rules,
+            [
+                "function:common -app",
+                "family:native -group",
+                "function:common -app",
+                "function:custom +app"
+            ]
+        );
+
+        // Without resolving, the bases are ignored.
+        let enhancements = Enhancements::from_config_structure(&input, &mut cache).unwrap();
+        assert_eq!(enhancements.rules().count(), 1);
+
+        let mut error = |bases: &[&str]| {
+            let input = encode(bases, "");
+            let err = Enhancements::from_config_structure_with_bases(
+                &input,
+                &mut cache,
+                &Default::default(),
+                resolve,
+            )
+            .unwrap_err();
+            format!("{:#}", anyhow::Error::from(err))
+        };
+        assert_eq!(
+            error(&["loop"]),
+            "in base `loop`: in base `cycle`: base `loop` names itself"
+        );
+        assert_eq!(error(&["unknown"]), "unknown base enhancements `unknown`");
+        assert!(error(&["broken"]).starts_with("in base `broken`: "));
+    }
+
     #[test]
     fn parses_default_enhancers() {
         let enhancers =
             std::fs::read_to_string("../tests/fixtures/newstyle@2023-01-11.txt").unwrap();
         let enhancements = Enhancements::parse(&enhancers, &mut Cache::default()).unwrap();
         dbg!(enhancements.all_rules.len());
-        dbg!(enhancements.modifier_rules.len());
-        dbg!(enhancements.updater_rules.len());
+        dbg!(enhancements.modifier_rules.all.len());
+        dbg!(enhancements.updater_rules.all.len());
+    }
+
+    #[test]
+    fn rules_roundtrip_through_text() {
+        let mut cache = Cache::default();
+        let enhancers =
+            std::fs::read_to_string("../tests/fixtures/newstyle@2023-01-11.txt").unwrap();
+        let encoded = std::fs::read("../tests/fixtures/newstyle@2023-01-11.bin").unwrap();
+
+        for enhancements in [
+            Enhancements::parse(&enhancers, &mut cache).unwrap(),
+            Enhancements::from_config_structure(&encoded, &mut cache).unwrap(),
+        ] {
+            let text: Vec<_> = enhancements.rules().map(|r| format!("{r:#}")).collect();
+            let reparsed = Enhancements::parse(&text.join("\n"), &mut Cache::new(0)).unwrap();
+            assert!(enhancements.rules().eq(reparsed.rules()));
+        }
+    }
+
+    #[test]
+    fn rules_roundtrip_through_config_structure() {
+        let mut cache = Cache::default();
+        let encoded = std::fs::read("../tests/fixtures/newstyle@2023-01-11.bin").unwrap();
+        let enhancements = Enhancements::from_config_structure(&encoded, &mut cache).unwrap();
+
+        let reencoded = enhancements.to_config_structure().unwrap();
+        let decoded = Enhancements::from_config_structure(&reencoded, &mut cache).unwrap();
+        assert!(enhancements.rules().eq(decoded.rules()));
+        assert_eq!(decoded.to_config_structure().unwrap(), reencoded);
+
+        let enhancements = Enhancements::parse("has_vars:yes -app", &mut cache).unwrap();
+        let err = enhancements.to_config_structure().unwrap_err();
+        assert!(matches!(err, Error::ConfigStructure(_)));
+        assert!(format!("{err:#}").contains("unable to encode matcher `has_vars`"));
+    }
+
+    #[test]
+    fn parses_pattern_definitions() {
+        let mut cache = Cache::default();
+        let input = "@define std family:native function:std::*,core::*\n\
+                     @std -app\n\
+                     @std function:*::panicking::* -group\n\
+                     @define unused function:unused";
+        let enhancements = Enhancements::parse(input, &mut cache).unwrap();
+
+        let rules: Vec<_> = enhancements.rules().map(|r| r.to_string()).collect();
+        assert_eq!(
+            rules,
+            [
+                "family:native function:std::*,core::* -app",
+                "family:native function:std::*,core::* function:*::panicking::* -group",
+            ]
+        );
+
+        let err =
+            Enhancements::parse("@define std function:std::*\n@sdt -app", &mut cache).unwrap_err();
+        assert_eq!(
+            anyhow::Error::from(err).root_cause().to_string(),
+            "at `@sdt -app`: unknown pattern `@sdt`"
+        );
+    }
+
+    #[test]
+    fn validates_categories() {
+        let options = ParseOptions::default().with_allowed_categories(["telemetry", "ui"]);
+        let mut cache = Cache::default();
+
+        Enhancements::parse_with_options("function:foo category=ui", &mut cache, &options).unwrap();
+
+        let err =
+            Enhancements::parse_with_options("function:foo category=iu", &mut cache, &options)
+                .unwrap_err();
+        let err = anyhow::Error::from(err);
+        assert_eq!(err.root_cause().to_string(), "unknown category `iu`");
+
+        // Without an allow-list, any category is accepted.
+        Enhancements::parse("function:foo category=iu", &mut cache).unwrap();
+    }
+
+    #[test]
+    fn parses_leniently() {
+        let input = "function:foo +app\n\
+                     function:bar +nope\n\
+                     @define p module:a/*\n\
+                     @define 1/ module:b/*\n\
+                     \n\
+                     @p -group category=iu\n\
+                     @q -app\n\
+                     function:baz category=ui";
+        let options = ParseOptions::default().with_allowed_categories(["ui"]);
+        let mut cache = Cache::default();
+
+        let (enhancements, errors) =
+            Enhancements::parse_lenient_with_options(input, &mut cache, &options);
+        let rules: Vec<_> = enhancements.rules().map(|r| r.to_string()).collect();
+        assert_eq!(rules, ["function:foo +app", "function:baz category=ui"]);
+        let errors: Vec<_> = errors.iter().map(|e| (e.line, e.kind)).collect();
+        assert_eq!(
+            errors,
+            [
+                (2, ParseErrorKind::InvalidAction),
+                (4, ParseErrorKind::InvalidPattern),
+                (6, ParseErrorKind::InvalidRule),
+                (7, ParseErrorKind::InvalidPattern),
+            ]
+        );
+
+        // Strict parsing fails with the first of these errors.
+        let err = Enhancements::parse_with_options(input, &mut cache, &options).unwrap_err();
+        assert_eq!(err.parse_error().unwrap().line, 4);
+
+        let (enhancements, errors) = Enhancements::parse_lenient("function:foo +app", &mut cache);
+        assert_eq!((enhancements.rules().count(), errors.len()), (1, 0));
+    }
+
+    #[test]
+    fn validates_glob_syntax() {
+        let mut cache = Cache::default();
+        let parse = |input: &str, glob: GlobOptions, cache: &mut Cache| {
+            let options = ParseOptions {
+                glob,
+                ..Default::default()
+            };
+            Enhancements::parse_with_options(input, cache, &options)
+                .map_err(|err| anyhow::Error::from(err).root_cause().to_string())
+        };
+
+        let no_braces = GlobOptions {
+            braces: false,
+            ..Default::default()
+        };
+        parse("path:**/{foo,bar}/** -app", Default::default(), &mut cache).unwrap();
+        assert_eq!(
+            parse("path:**/{foo,bar}/** -app", no_braces, &mut cache).unwrap_err(),
+            "`**/{foo,bar}/**` uses brace expansion, which is disabled"
+        );
+        parse(r"module:[{]foo\{ -app", no_braces, &mut cache).unwrap();
+        // Every alternative is checked on its own.
+        assert_eq!(
+            parse("function:bar,{foo -app", no_braces, &mut cache).unwrap_err(),
+            "`{foo` uses brace expansion, which is disabled"
+        );
+
+        let no_classes = GlobOptions {
+            classes: false,
+            ..Default::default()
+        };
+        assert_eq!(
+            parse("type:[A-Z]*Error -app", no_classes, &mut cache).unwrap_err(),
+            "`[A-Z]*Error` uses a character class, which is disabled"
+        );
+        parse(r"type:\[Error\] -app", no_classes, &mut cache).unwrap();
+
+        // Invalid frame matcher patterns are only rejected on request.
+        let strict = GlobOptions {
+            reject_invalid: true,
+            ..Default::default()
+        };
+        parse("function:-[* -app", Default::default(), &mut cache).unwrap();
+        assert_eq!(
+            parse("function:-[* -app", strict, &mut cache).unwrap_err(),
+            "unclosed character class in `-[*`, write `[[]` to match `[` literally \
+             or `=-[*` to match the whole pattern literally"
+        );
+        parse("function:=-[* -app", strict, &mut cache).unwrap();
+        assert_eq!(
+            parse("package:{a,{b,c}} -app", strict, &mut cache).unwrap_err(),
+            "nested braces in `{a,{b,c}}` are not supported"
+        );
+        assert_eq!(
+            parse("type:[z-a] -app", Default::default(), &mut cache).unwrap_err(),
+            "invalid range `z-a` in a character class of `[z-a]`"
+        );
+    }
+
+    #[test]
+    fn tracks_in_app_flips() {
+        let enhancements = Enhancements::parse(
+            "function:foo -app\nfunction:* +app\nfunction:foo -app\nfunction:bar +app",
+            &mut Default::default(),
+        )
+        .unwrap();
+
+        let mut frames = vec![
+            Frame::from_test(&json!({"function": "foo"}), "native"),
+            Frame::from_test(&json!({"function": "bar"}), "native"),
+            Frame::from_test(&json!({"function": "baz", "in_app": true}), "native"),
+        ];
+
+        let options = ModifyOptions {
+            track_in_app_flips: true,
+            ..Default::default()
+        };
+        let result = enhancements.apply_modifications_to_frames_with_options(
+            &mut frames,
+            &Default::default(),
+            &options,
+        );
+
+        assert_eq!(result.in_app_flips, [2, 0, 0]);
+        assert_eq!(frames[0].in_app, Some(false));
+
+        let result = enhancements.apply_modifications_to_frames_with_options(
+            &mut frames,
+            &Default::default(),
+            &Default::default(),
+        );
+        assert!(result.in_app_flips.is_empty());
+    }
+
+    #[test]
+    fn reports_changes() {
+        let enhancements = Enhancements::parse(
+            "function:foo +app category=foo\nfunction:bar ^-app",
+            &mut Cache::default(),
+        )
+        .unwrap();
+        let apply = |frames: &mut [Frame]| {
+            let result = enhancements.apply_modifications_to_frames_with_options(
+                frames,
+                &Default::default(),
+                &Default::default(),
+            );
+            (result.matched, result.changed)
+        };
+
+        let mut frames = vec![
+            Frame::from_test(&json!({"function": "foo"}), "native"),
+            Frame::from_test(&json!({"function": "baz"}), "native"),
+        ];
+        assert_eq!(apply(&mut frames), (true, true));
+        // Applying the rules again matches the same frames, but doesn't change them.
+        assert_eq!(apply(&mut frames), (true, false));
+
+        let mut frames = vec![Frame::from_test(&json!({"function": "baz"}), "native")];
+        assert_eq!(apply(&mut frames), (false, false));
+
+        // Range actions change frames other than the matching one.
+        let mut frames = vec![
+            Frame::from_test(&json!({"function": "bar", "in_app": false}), "native"),
+            Frame::from_test(&json!({"function": "baz", "in_app": true}), "native"),
+        ];
+        assert_eq!(apply(&mut frames), (true, true));
+        assert_eq!(frames[1].in_app, Some(false));
+    }
+
+    #[test]
+    fn applies_with_scratch() {
+        let enhancements = Enhancements::parse(
+            "function:foo -app\nfunction:bar ^+app\n[ function:foo ] | function:baz category=x",
+            &mut Cache::default(),
+        )
+        .unwrap();
+        let stacks = [
+            json!([{"function": "foo"}, {"function": "bar"}, {"function": "baz"}]),
+            json!([{"function": "baz"}, {"function": "foo"}]),
+            json!([]),
+        ];
+
+        let mut scratch = Scratch::default();
+        for stack in &stacks {
+            let frames: Vec<_> = stack
+                .as_array()
+                .unwrap()
+                .iter()
+                .map(|frame| Frame::from_test(frame, "native"))
+                .collect();
+
+            let mut expected = frames.clone();
+            enhancements.apply_modifications_to_frames(&mut expected, &ExceptionData::default());
+
+            let mut actual = frames;
+            enhancements.apply_with_scratch(&mut actual, &ExceptionData::default(), &mut scratch);
+
+            let summary = |frames: &[Frame]| -> Vec<_> {
+                frames
+                    .iter()
+                    .map(|f| (f.in_app, f.category.clone()))
+                    .collect()
+            };
+            assert_eq!(summary(&actual), summary(&expected));
+        }
+    }
+
+    #[test]
+    fn applies_to_frame_like() {
+        struct ExternalFrame {
+            function: &'static str,
+            in_app: Option<bool>,
+            category: Option<StringField>,
+        }
+
+        impl FrameLike for ExternalFrame {
+            fn get_field(&self, field: FrameField) -> Option<&str> {
+                match field {
+                    FrameField::Function => Some(self.function),
+                    FrameField::Category => self.category.as_deref(),
+                    _ => None,
+                }
+            }
+
+            fn family(&self) -> Families {
+                Families::NATIVE
+            }
+
+            fn in_app(&self) -> Option<bool> {
+                self.in_app
+            }
+        }
+
+        impl FrameLikeMut for ExternalFrame {
+            fn set_in_app(&mut self, in_app: Option<bool>) {
+                self.in_app = in_app;
+            }
+
+            fn set_category(&mut self, category: Option<StringField>) {
+                self.category = category;
+            }
+        }
+
+        let enhancements = Enhancements::parse(
+            "family:native function:foo category=foo\n\
+             category:foo ^-app\n\
+             app:yes -group",
+            &mut Cache::default(),
+        )
+        .unwrap();
+
+        let mut frames: Vec<_> = ["bar", "foo", "baz"]
+            .into_iter()
+            .map(|function| ExternalFrame {
+                function,
+                in_app: Some(true),
+                category: None,
+            })
+            .collect();
+        enhancements.apply_modifications_to_frames(&mut frames, &ExceptionData::default());
+
+        let categories: Vec<_> = frames.iter().map(|f| f.category.as_deref()).collect();
+        assert_eq!(categories, [None, Some("foo"), None]);
+        let in_app: Vec<_> = frames.iter().map(|f| f.in_app).collect();
+        assert_eq!(in_app, [Some(true), Some(true), Some(false)]);
+
+        let mut components = vec![Component::default(); frames.len()];
+        enhancements.assemble_stacktrace_component(
+            &mut components,
+            &frames,
+            &ExceptionData::default(),
+        );
+        let contributes: Vec<_> = components.iter().map(|c| c.contributes).collect();
+        assert_eq!(contributes, [Some(false), Some(false), None]);
+    }
+
+    #[test]
+    fn profiles_rules() {
+        let enhancements = Enhancements::parse(
+            "function:foo -app\n\
+             error.type:Nope function:foo +app\n\
+             function:* category=all\n\
+             function:bar +group",
+            &mut Cache::default(),
+        )
+        .unwrap();
+        let mut frames = [
+            Frame::from_test(&json!({"function": "foo"}), "native"),
+            Frame::from_test(&json!({"function": "bar"}), "native"),
+        ];
+
+        let options = ModifyOptions {
+            profile_rules: true,
+            ..Default::default()
+        };
+        let result = enhancements.apply_modifications_to_frames_with_options(
+            &mut frames,
+            &ExceptionData::default(),
+            &options,
+        );
+
+        let mut profiles: Vec<_> = result
+            .rule_profiles
+            .iter()
+            .map(|p| (p.rule.to_string(), p.matched_frames))
+            .collect();
+        profiles.sort();
+        assert_eq!(
+            profiles,
+            [
+                ("function:* category=all".into(), 2),
+                ("function:foo -app".into(), 1),
+                ("type:Nope function:foo +app".into(), 0),
+            ]
+        );
+        assert!(result
+            .rule_profiles
+            .windows(2)
+            .all(|w| w[0].elapsed >= w[1].elapsed));
+
+        let result = enhancements.apply_modifications_to_frames_with_options(
+            &mut frames,
+            &ExceptionData::default(),
+            &ModifyOptions::default(),
+        );
+        assert!(result.rule_profiles.is_empty());
+    }
+
+    #[test]
+    fn memoizes_shared_matchers() {
+        let enhancements = Enhancements::parse(
+            "category:foo -group\n\
+             function:a category:foo +group max-frames=3\n\
+             !category:foo function:b +group\n\
+             function:c +group",
+            &mut Cache::default(),
+        )
+        .unwrap();
+        let rules = &enhancements.updater_rules.all;
+        assert!(rules[..3]
+            .iter()
+            .all(|r| enhancements.shared_matchers.contains_rule(r)));
+        assert!(!enhancements.shared_matchers.contains_rule(&rules[3]));
+
+        let frames: Vec<_> = [("a", "foo"), ("b", "bar"), ("b", "foo"), ("c", "foo")]
+            .into_iter()
+            .map(|(function, category)| Frame {
+                function: Some(function.into()),
+                category: Some(category.into()),
+                ..Default::default()
+            })
+            .collect();
+        for memoize_shared_matchers in [false, true] {
+            let options = AssembleOptions {
+                memoize_shared_matchers,
+                ..Default::default()
+            };
+            let mut components = vec![Component::default(); frames.len()];
+            let result = enhancements.assemble_stacktrace_component_with_options(
+                &mut components,
+                &frames,
+                &ExceptionData::default(),
+                &options,
+            );
+
+            let contributes: Vec<_> = components.iter().map(|c| c.contributes).collect();
+            assert_eq!(
+                contributes,
+                [Some(true), Some(true), Some(false), Some(true)]
+            );
+            assert!(result.contributes);
+            assert_eq!(
+                enhancements
+                    .stacktrace_state(&frames, &ExceptionData::default(), &options)
+                    .max_frames
+                    .value,
+                NonZeroUsize::new(3)
+            );
+        }
+    }
+
+    #[test]
+    fn strips_vars() {
+        let enhancements = Enhancements::parse(
+            "stack.has_vars:yes function:secret* -vars\nhas_vars:no function:* +app",
+            &mut Cache::default(),
+        )
+        .unwrap();
+        assert_eq!(enhancements.updater_rules.all.len(), 1);
+
+        let mut frames: Vec<_> = [
+            ("secret_key", Some(true)),
+            ("secret_key", None),
+            ("other", Some(true)),
+        ]
+        .into_iter()
+        .map(|(function, has_vars)| Frame {
+            function: Some(function.into()),
+            has_vars,
+            ..Default::default()
+        })
+        .collect();
+
+        let options = ModifyOptions {
+            record_undo_log: true,
+            ..Default::default()
+        };
+        let result = enhancements.apply_modifications_to_frames_with_options(
+            &mut frames,
+            &ExceptionData::default(),
+            &options,
+        );
+        let summary: Vec<_> = frames.iter().map(|f| (f.has_vars, f.in_app)).collect();
+        assert_eq!(
+            summary,
+            [(Some(false), Some(true)), (None, None), (Some(true), None)]
+        );
+
+        revert(&mut frames, &result.undo_log);
+        assert_eq!(frames[0].has_vars, Some(true));
+
+        let err = Enhancements::parse("function:foo +vars", &mut Cache::default()).unwrap_err();
+        assert!(format!("{err:#}").contains("the `vars` flag can only be unset"));
+    }
+
+    #[test]
+    fn collects_stats() {
+        let enhancements = Enhancements::parse(
+            "family:javascript function:foo -app\n\
+             type:Error function:foo -app max-frames=1\n\
+             function:foo +app\n\
+             category:bar category=baz",
+            &mut Cache::default(),
+        )
+        .unwrap();
+        let mut frames = vec![Frame {
+            family: Families::NATIVE,
+            function: Some("foo".into()),
+            ..Default::default()
+        }];
+        let exception_data = ExceptionData {
+            ty: Some("Panic".into()),
+            ..Default::default()
+        };
+
+        let options = ModifyOptions {
+            collect_stats: true,
+            ..Default::default()
+        };
+        let result = enhancements.apply_modifications_to_frames_with_options(
+            &mut frames,
+            &exception_data,
+            &options,
+        );
+        let expected = ApplyStats {
+            skipped_by_families: 1,
+            skipped_by_exception: 1,
+            evaluated: 2,
+            truncated: false,
+        };
+        assert_eq!(result.stats, Some(expected));
+
+        let result = enhancements.apply_modifications_to_frames_with_options(
+            &mut frames,
+            &exception_data,
+            &Default::default(),
+        );
+        assert_eq!(result.stats, None);
+
+        let mut components = vec![Component::default()];
+        let options = AssembleOptions {
+            collect_stats: true,
+            ..Default::default()
+        };
+        let result = enhancements.assemble_stacktrace_component_with_options(
+            &mut components,
+            &frames,
+            &exception_data,
+            &options,
+        );
+        let expected = ApplyStats {
+            skipped_by_families: 1,
+            skipped_by_exception: 1,
+            evaluated: 1,
+            truncated: false,
+        };
+        assert_eq!(result.stats, Some(expected));
+    }
+
+    #[test]
+    fn apply_all_matches_separate_passes() {
+        let enhancements = Enhancements::parse(
+            "app:no function:foo +app\n\
+             category:x function:baz +app\n\
+             function:b* -app max-frames=2\n\
+             app:yes ^-group\n\
+             function:* category=x",
+            &mut Cache::default(),
+        )
+        .unwrap();
+        let frame = |function: &str| Frame {
+            function: Some(function.into()),
+            in_app: Some(false),
+            ..Default::default()
+        };
+        let frames = vec![frame("foo"), frame("bar"), frame("baz"), frame("qux")];
+        let components = vec![
+            Component {
+                contributes: Some(true),
+                ..Default::default()
+            };
+            frames.len()
+        ];
+
+        let mut expected_frames = frames.clone();
+        let mut expected_components = components.clone();
+        enhancements.apply_modifications_to_frames(&mut expected_frames, &Default::default());
+        let expected = enhancements.assemble_stacktrace_component(
+            &mut expected_components,
+            &expected_frames,
+            &Default::default(),
+        );
+
+        let mut frames = frames;
+        let mut components = components;
+        let result = enhancements.apply_all(&mut frames, &mut components, &Default::default());
+
+        let modified = |frames: &[Frame]| -> Vec<_> {
+            frames
+                .iter()
+                .map(|f| (f.in_app, f.category.clone()))
+                .collect()
+        };
+        assert_eq!(modified(&frames), modified(&expected_frames));
+        let updated = |components: &[Component]| -> Vec<_> {
+            components
+                .iter()
+                .map(|c| (c.contributes, c.primary_hint().map(str::to_owned)))
+                .collect()
+        };
+        assert_eq!(updated(&components), updated(&expected_components));
+        assert_eq!(result.assemble.contributes, expected.contributes);
+        assert_eq!(result.assemble.hint, expected.hint);
+        assert_eq!(
+            result.stacktrace_state.max_frames.value,
+            NonZeroUsize::new(2)
+        );
+    }
+
+    #[test]
+    fn tracks_in_app_last_changed() {
+        let enhancements = Enhancements::parse(
+            "function:foo +app\n\
+             function:bar max-frames=3\n\
+             function:b* +app\n\
+             function:baz -app",
+            &mut Cache::default(),
+        )
+        .unwrap();
+        let frame = |function: &str| Frame {
+            function: Some(function.into()),
+            in_app: Some(false),
+            ..Default::default()
+        };
+        let mut frames = vec![frame("foo"), frame("bar"), frame("baz"), frame("qux")];
+
+        let options = ModifyOptions {
+            track_in_app_last_changed: true,
+            ..Default::default()
+        };
+        let result = enhancements.apply_modifications_to_frames_with_options(
+            &mut frames,
+            &Default::default(),
+            &options,
+        );
+        let last_changed: Vec<_> = result
+            .in_app_last_changed
+            .iter()
+            .map(|id| id.map(RuleId::index))
+            .collect();
+        assert_eq!(last_changed, [Some(0), Some(2), Some(3), None]);
+
+        let rule = enhancements.rule(result.in_app_last_changed[1].unwrap());
+        assert_eq!(rule.unwrap().to_string(), "function:b* +app");
+    }
+
+    #[test]
+    fn normalizes_exception_value() {
+        let enhancements =
+            Enhancements::parse("error.value:\"*<hex>*\" -app", &mut Cache::default()).unwrap();
+        let mut frames = vec![Frame {
+            in_app: Some(true),
+            ..Default::default()
+        }];
+        let exception_data = ExceptionData {
+            value: Some("Segfault at 0xdeadbeef".into()),
+            ..Default::default()
+        };
+
+        let options = ModifyOptions {
+            normalize_exception_value: true,
+            ..Default::default()
+        };
+        let result = enhancements.apply_modifications_to_frames_with_options(
+            &mut frames,
+            &exception_data,
+            &options,
+        );
+        assert_eq!(frames[0].in_app, Some(false));
+        assert_eq!(
+            result.normalized_exception_value.as_deref(),
+            Some("Segfault at <hex>")
+        );
+    }
+
+    #[test]
+    fn finds_rules_setting_variables() {
+        let enhancements = Enhancements::parse(
+            "function:foo max-frames=3\n\
+             function:bar +app category=bar\n\
+             function:baz min-frames=1 max-frames=5",
+            &mut Cache::default(),
+        )
+        .unwrap();
+
+        let rules = |kind| -> Vec<_> {
+            enhancements
+                .rules_setting(kind)
+                .map(|rule| rule.to_string())
+                .collect()
+        };
+        assert_eq!(
+            rules(VarKind::MaxFrames),
+            [
+                "function:foo max-frames=3",
+                "function:baz min-frames=1 max-frames=5"
+            ]
+        );
+        assert_eq!(rules(VarKind::Category), ["function:bar +app category=bar"]);
+        assert!(rules(VarKind::InvertStacktrace).is_empty());
+
+        for kind in VarKind::ALL {
+            assert_eq!(VarKind::from_name(kind.name()), Some(kind));
+        }
+    }
+
+    #[test]
+    fn respects_priorities() {
+        let enhancements = Enhancements::parse(
+            "function:foo +app category=foo priority=10\n\
+             function:* -app category=other\n\
+             function:bar +app priority=5\n\
+             function:bar -app priority=5",
+            &mut Cache::default(),
+        )
+        .unwrap();
+        let frame = |function: &str| Frame {
+            function: Some(function.into()),
+            ..Default::default()
+        };
+        let mut frames = vec![frame("foo"), frame("bar"), frame("baz")];
+
+        enhancements.apply_modifications_to_frames(&mut frames, &Default::default());
+        let modified: Vec<_> = frames
+            .iter()
+            .map(|f| (f.in_app, f.category.as_deref()))
+            .collect();
+        assert_eq!(
+            modified,
+            [
+                (Some(true), Some("foo")),
+                (Some(false), Some("other")),
+                (Some(false), Some("other")),
+            ]
+        );
+
+        let mut frames = vec![frame("foo"), frame("bar")];
+        enhancements.apply_categories(&mut frames, &Default::default());
+        let categories: Vec<_> = frames.iter().map(|f| f.category.as_deref()).collect();
+        assert_eq!(categories, [Some("foo"), Some("other")]);
+    }
+
+    #[test]
+    fn contributes_from_values() {
+        let enhancements =
+            Enhancements::parse("function:bar -group", &mut Cache::default()).unwrap();
+        let frames: Vec<_> = ["foo", "foo", "bar", "foo"]
+            .into_iter()
+            .map(|function| Frame {
+                function: Some(function.into()),
+                ..Default::default()
+            })
+            .collect();
+        let component = |values: Option<&[&str]>| Component {
+            id: Some("frame".into()),
+            values: values.map(|values| values.iter().map(|v| v.to_string()).collect()),
+            ..Default::default()
+        };
+        let mut components = [
+            component(Some(&["foo"])),
+            component(Some(&[])),
+            component(Some(&["bar"])),
+            component(None),
+        ];
+
+        let result = enhancements.assemble_stacktrace_component(
+            &mut components,
+            &frames,
+            &Default::default(),
+        );
+        assert!(result.contributes);
+        let contributes: Vec<_> = components.iter().map(|c| c.contributes).collect();
+        assert_eq!(contributes, [Some(true), Some(false), Some(false), None]);
+        assert_eq!(components[0].id.as_deref(), Some("frame"));
+    }
+
+    #[test]
+    fn respects_time_budget() {
+        let enhancements = Enhancements::parse(
+            "function:foo +app\nfunction:foo -group",
+            &mut Cache::default(),
+        )
+        .unwrap();
+        let frame = Frame {
+            function: Some("foo".into()),
+            ..Default::default()
+        };
+
+        // An exhausted budget stops before the first rule.
+        let mut frames = vec![frame.clone()];
+        let options = ModifyOptions {
+            time_budget: Some(Duration::ZERO),
+            ..Default::default()
+        };
+        let result = enhancements.apply_modifications_to_frames_with_options(
+            &mut frames,
+            &Default::default(),
+            &options,
+        );
+        assert_eq!(result.budget_exhausted_at, RuleId::new(0));
+        assert_eq!(frames[0].in_app, None);
+
+        let mut components = vec![Component::default()];
+        let result = enhancements.apply_all_with_options(
+            &mut frames,
+            &mut components,
+            &Default::default(),
+            &AssembleOptions {
+                time_budget: Some(Duration::ZERO),
+                ..Default::default()
+            },
+        );
+        assert_eq!(result.assemble.budget_exhausted_at, RuleId::new(0));
+        assert_eq!(components[0].contributes, None);
+
+        let result = enhancements.assemble_stacktrace_component_with_options(
+            &mut components,
+            &frames,
+            &Default::default(),
+            &AssembleOptions {
+                time_budget: Some(Duration::ZERO),
+                ..Default::default()
+            },
+        );
+        assert_eq!(result.budget_exhausted_at, RuleId::new(0));
+
+        // A generous budget applies all rules.
+        let result = enhancements.apply_all_with_options(
+            &mut frames,
+            &mut components,
+            &Default::default(),
+            &AssembleOptions {
+                time_budget: Some(Duration::MAX),
+                ..Default::default()
+            },
+        );
+        assert_eq!(result.assemble.budget_exhausted_at, None);
+        assert_eq!(frames[0].in_app, Some(true));
+        assert_eq!(components[0].contributes, Some(false));
+    }
+
+    #[test]
+    fn reports_duplicated_rule_exhausting_budget() {
+        // The first and third line share their rule, which the budget of two rules runs out on.
+        let enhancements = Enhancements::parse(
+            "function:foo +app -group\nfunction:bar +app -group\nfunction:foo +app -group",
+            &mut Cache::new(10),
+        )
+        .unwrap();
+        let rules: Vec<_> = enhancements.rules().collect();
+        assert!(Arc::ptr_eq(&rules[0].0, &rules[2].0));
+
+        FAKE_CLOCK.set(Some(Instant::now()));
+        let mut frames = vec![Frame::from_test(&json!({"function": "foo"}), "native")];
+        let result = enhancements.apply_modifications_to_frames_with_options(
+            &mut frames,
+            &Default::default(),
+            &ModifyOptions {
+                time_budget: Some(Duration::from_secs(3)),
+                ..Default::default()
+            },
+        );
+        assert_eq!(result.budget_exhausted_at, RuleId::new(2));
+
+        let result = enhancements.assemble_stacktrace_component_with_options(
+            &mut [Component::default()],
+            &frames,
+            &Default::default(),
+            &AssembleOptions {
+                time_budget: Some(Duration::from_secs(3)),
+                ..Default::default()
+            },
+        );
+        assert_eq!(result.budget_exhausted_at, RuleId::new(2));
+        FAKE_CLOCK.set(None);
+
+        // The same goes for the rule that last changed a frame.
+        let enhancements = Enhancements::parse(
+            "function:foo +app\nfunction:foo -app\nfunction:foo +app",
+            &mut Cache::default(),
+        )
+        .unwrap();
+        let mut frames = vec![Frame::from_test(
+            &json!({"function": "foo", "in_app": true}),
+            "native",
+        )];
+        let result = enhancements.apply_modifications_to_frames_with_options(
+            &mut frames,
+            &Default::default(),
+            &ModifyOptions {
+                track_in_app_last_changed: true,
+                ..Default::default()
+            },
+        );
+        assert_eq!(result.in_app_last_changed, [RuleId::new(2)]);
+    }
+
+    #[test]
+    fn limits_frames() {
+        let enhancements =
+            Enhancements::parse("function:foo -app -group", &mut Cache::default()).unwrap();
+        let mut frames: Vec<_> = (0..5)
+            .map(|_| Frame {
+                function: Some("foo".into()),
+                in_app: Some(true),
+                ..Default::default()
+            })
+            .collect();
+
+        let options = ModifyOptions {
+            collect_stats: true,
+            frame_limit: NonZeroUsize::new(2),
+            ..Default::default()
+        };
+        let result = enhancements.apply_modifications_to_frames_with_options(
+            &mut frames,
+            &Default::default(),
+            &options,
+        );
+        assert!(result.stats.unwrap().truncated);
+        let in_app: Vec<_> = frames.iter().map(|f| f.in_app).collect();
+        assert_eq!(
+            in_app,
+            [
+                Some(false),
+                Some(false),
+                Some(true),
+                Some(false),
+                Some(false)
+            ]
+        );
+
+        let mut components = vec![Component::default(); 5];
+        let options = AssembleOptions {
+            collect_stats: true,
+            frame_limit: NonZeroUsize::new(2),
+            ..Default::default()
+        };
+        let result = enhancements.assemble_stacktrace_component_with_options(
+            &mut components,
+            &frames,
+            &Default::default(),
+            &options,
+        );
+        assert!(result.stats.unwrap().truncated);
+        let contributes: Vec<_> = components.iter().map(|c| c.contributes).collect();
+        assert_eq!(
+            contributes,
+            [Some(false), Some(false), None, Some(false), Some(false)]
+        );
+
+        // Stacktraces that fit within the limit are not truncated.
+        let result = enhancements.assemble_stacktrace_component_with_options(
+            &mut components[..4],
+            &frames[..4],
+            &Default::default(),
+            &options,
+        );
+        assert!(!result.stats.unwrap().truncated);
+    }
+
+    #[test]
+    fn matches_raw_function() {
+        let enhancements = Enhancements::parse(
+            "function:foo<int> -app\n!function:bar* +group",
+            &mut Cache::default(),
+        )
+        .unwrap();
+        let make_frames = || -> Vec<_> {
+            [
+                ("foo", Some("foo<int>")),
+                ("foo", None),
+                ("baz", Some("bar<int>")),
+            ]
+            .into_iter()
+            .map(|(function, raw_function)| Frame {
+                function: Some(function.into()),
+                raw_function: raw_function.map(Into::into),
+                in_app: Some(true),
+                ..Default::default()
+            })
+            .collect()
+        };
+
+        let mut frames = make_frames();
+        enhancements.apply_modifications_to_frames(&mut frames, &ExceptionData::default());
+        let in_app: Vec<_> = frames.iter().map(|f| f.in_app).collect();
+        assert_eq!(in_app, [Some(true), Some(true), Some(true)]);
+
+        let mut frames = make_frames();
+        let options = ModifyOptions {
+            match_raw_function: true,
+            ..Default::default()
+        };
+        enhancements.apply_modifications_to_frames_with_options(
+            &mut frames,
+            &ExceptionData::default(),
+            &options,
+        );
+        let in_app: Vec<_> = frames.iter().map(|f| f.in_app).collect();
+        assert_eq!(in_app, [Some(false), Some(true), Some(true)]);
+        assert_eq!(frames[0].function.as_deref(), Some("foo"));
+        assert_eq!(frames[0].raw_function.as_deref(), Some("foo<int>"));
+
+        let mut components = vec![Component::default(); frames.len()];
+        let options = AssembleOptions {
+            match_raw_function: true,
+            ..Default::default()
+        };
+        enhancements.assemble_stacktrace_component_with_options(
+            &mut components,
+            &frames,
+            &ExceptionData::default(),
+            &options,
+        );
+        let contributes: Vec<_> = components.iter().map(|c| c.contributes).collect();
+        assert_eq!(contributes, [Some(true), Some(true), None]);
+    }
+
+    #[test]
+    fn missing_value_policy() {
+        use MissingValue::*;
+
+        let frames = [
+            Frame::default(),
+            Frame {
+                module: Some("foo".into()),
+                has_vars: Some(false),
+                ..Default::default()
+            },
+        ];
+        let matches = |input: &str, frames_policy, exceptions_policy| {
+            let options = ParseOptions {
+                missing_values: MissingValuePolicy {
+                    frames: frames_policy,
+                    exceptions: exceptions_policy,
+                    ..Default::default()
+                },
+                ..Default::default()
+            };
+            let enhancements =
+                Enhancements::parse_with_options(input, &mut Cache::default(), &options).unwrap();
+            let rule = enhancements.rules().next().unwrap();
+            let frames: Vec<_> = (0..frames.len())
+                .map(|idx| rule.matches_frame(&frames, idx))
+                .collect();
+            (frames, rule.matches_exception(&ExceptionData::default()))
+        };
+
+        assert_eq!(
+            matches("!module:bar -app", NoMatch, Unknown).0,
+            [true, true]
+        );
+        assert_eq!(
+            matches("!module:bar -app", NeverMatch, Unknown).0,
+            [false, true]
+        );
+        assert_eq!(matches("module:* -app", NoMatch, Unknown).0, [false, true]);
+        assert_eq!(matches("module:* -app", Unknown, Unknown).0, [true, true]);
+
+        assert_eq!(
+            matches("has_vars:no -app", NoMatch, Unknown).0,
+            [false, true]
+        );
+        assert_eq!(
+            matches("!has_vars:no -app", NoMatch, Unknown).0,
+            [true, false]
+        );
+        assert_eq!(
+            matches("!has_vars:no -app", NeverMatch, Unknown).0,
+            [false, false]
+        );
+        assert_eq!(
+            matches("has_vars:no -app", Unknown, Unknown).0,
+            [false, true]
+        );
+
+        assert!(matches("!type:foo -app", NoMatch, Unknown).1);
+        assert!(matches("type:* -app", NoMatch, Unknown).1);
+        assert!(matches("!type:foo -app", NoMatch, NoMatch).1);
+        assert!(!matches("type:* -app", NoMatch, NoMatch).1);
+        assert!(!matches("!type:foo -app", NoMatch, NeverMatch).1);
+
+        let mut cache = Cache::default();
+        let default = Enhancements::parse("!type:foo -app", &mut cache).unwrap();
+        let options = ParseOptions {
+            missing_values: MissingValuePolicy {
+                frames: NoMatch,
+                exceptions: NeverMatch,
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        let never =
+            Enhancements::parse_with_options("!type:foo -app", &mut cache, &options).unwrap();
+        assert_ne!(default.rules().next(), never.rules().next());
+    }
+
+    #[test]
+    fn reverts_modifications() {
+        let enhancements = Enhancements::parse(
+            "function:foo category=foo -app\nfunction:bar ^+app\nfunction:baz category=baz",
+            &mut Cache::default(),
+        )
+        .unwrap();
+        let original: Vec<_> = [
+            ("foo", None, None),
+            ("bar", Some(false), Some("x")),
+            ("baz", Some(true), Some("baz")),
+        ]
+        .into_iter()
+        .map(|(function, in_app, category)| Frame {
+            function: Some(function.into()),
+            in_app,
+            category: category.map(Into::into),
+            ..Default::default()
+        })
+        .collect();
+        let summary = |frames: &[Frame]| -> Vec<_> {
+            frames
+                .iter()
+                .map(|f| (f.in_app, f.category.clone()))
+                .collect()
+        };
+
+        let mut frames = original.clone();
+        let options = ModifyOptions {
+            record_undo_log: true,
+            ..Default::default()
+        };
+        let result = enhancements.apply_modifications_to_frames_with_options(
+            &mut frames,
+            &ExceptionData::default(),
+            &options,
+        );
+        assert_eq!(
+            result.undo_log,
+            [
+                UndoEntry {
+                    frame: 0,
+                    old_value: FieldValue::InApp(None)
+                },
+                UndoEntry {
+                    frame: 0,
+                    old_value: FieldValue::Category(None)
+                },
+            ]
+        );
+
+        revert(&mut frames, &result.undo_log);
+        assert_eq!(summary(&frames), summary(&original));
+    }
+
+    #[test]
+    fn adds_rules_to_a_copy() {
+        let mut cache = Cache::default();
+        let base = Enhancements::parse("function:foo -app", &mut cache).unwrap();
+        let custom = Enhancements::parse("function:foo +app -group", &mut cache).unwrap();
+
+        let combined = base.with_additional_rules(&custom);
+        assert_eq!(base.all_rules.len(), 1);
+        assert_eq!(combined.all_rules.len(), 2);
+        assert_eq!(combined.modifier_rules.all.len(), 2);
+        assert_eq!(combined.updater_rules.all.len(), 2);
+        assert!(Arc::ptr_eq(&base.all_rules[0].0, &combined.all_rules[0].0));
+
+        let mut frames = vec![Frame::from_test(&json!({"function": "foo"}), "native")];
+        combined.apply_modifications_to_frames(&mut frames, &ExceptionData::default());
+        assert_eq!(frames[0].in_app, Some(true));
+    }
+
+    #[test]
+    fn finds_matching_rules() {
+        let enhancements = Enhancements::parse(
+            "function:foo -app\n\
+             [ function:main ] | function:foo +app\n\
+             type:Panic function:foo -group\n\
+             function:bar max-frames=3\n\
+             function:foo category=x",
+            &mut Cache::default(),
+        )
+        .unwrap();
+
+        let frames = [
+            Frame::from_test(&json!({"function": "main"}), "native"),
+            Frame::from_test(&json!({"function": "foo"}), "native"),
+        ];
+        let ids = |exception_data: &ExceptionData| -> Vec<_> {
+            enhancements
+                .matching_rules(&frames, 1, exception_data)
+                .into_iter()
+                .map(RuleId::index)
+                .collect()
+        };
+
+        assert_eq!(ids(&ExceptionData::default()), [0, 1, 4]);
+        let panic = ExceptionData {
+            ty: Some("Panic".into()),
+            ..Default::default()
+        };
+        assert_eq!(ids(&panic), [0, 1, 2, 4]);
+        assert!(enhancements.matching_rules(&frames, 0, &panic).is_empty());
+    }
+
+    #[test]
+    fn dedups_rules() {
+        let mut cache = Cache::default();
+        let rules = |enhancements: &Enhancements| -> Vec<String> {
+            enhancements.rules().map(|r| r.to_string()).collect()
+        };
+
+        let mut enhancements = Enhancements::parse(
+            "function:foo -app\nfunction:bar +app\nfunction:foo -app\nfunction:bar +app -group",
+            &mut cache,
+        )
+        .unwrap();
+        enhancements.dedup();
+        assert_eq!(
+            rules(&enhancements),
+            [
+                "function:bar +app",
+                "function:foo -app",
+                "function:bar +app -group"
+            ]
+        );
+        assert_eq!(enhancements.modifier_rules.all.len(), 3);
+        assert_eq!(enhancements.updater_rules.all.len(), 3);
+
+        // Duplicates whose effects are observed by rules in between are kept.
+        let input = "function:foo +app\n\
+                     app:yes -group\n\
+                     function:foo +app\n\
+                     function:foo category=bar\n\
+                     category:bar -app\n\
+                     function:foo category=bar";
+        let mut enhancements = Enhancements::parse(input, &mut cache).unwrap();
+        enhancements.dedup();
+        assert_eq!(rules(&enhancements).len(), 6);
+
+        // Partitions that were dropped stay dropped.
+        let mut enhancements =
+            Enhancements::parse("function:foo -app\nfunction:foo -app", &mut cache).unwrap();
+        enhancements.retain_updater_only();
+        enhancements.dedup();
+        assert_eq!(rules(&enhancements), ["function:foo -app"]);
+        assert!(enhancements.modifier_rules.all.is_empty());
+    }
+
+    #[test]
+    fn computes_stacktrace_state() {
+        let enhancements = Enhancements::parse(
+            "function:foo max-frames=3\nfunction:bar min-frames=2 invert-stacktrace=1\nfunction:baz max-frames=5",
+            &mut Cache::default(),
+        )
+        .unwrap();
+
+        let frames = [
+            Frame::from_test(&json!({"function": "foo"}), "native"),
+            Frame::from_test(&json!({"function": "bar"}), "native"),
+        ];
+        let options = AssembleOptions {
+            max_frames: NonZeroUsize::new(10),
+            ..Default::default()
+        };
+        let state = enhancements.stacktrace_state(&frames, &Default::default(), &options);
+
+        assert_eq!(state.max_frames.value, NonZeroUsize::new(3));
+        assert_eq!(
+            state.max_frames.setter.unwrap().to_string(),
+            "function:foo max-frames=3"
+        );
+        assert_eq!(state.min_frames.value, NonZeroUsize::new(2));
+        assert!(state.invert_stacktrace.value);
+        assert_eq!(
+            state.invert_stacktrace.setter.unwrap().to_string(),
+            "function:bar min-frames=2 invert-stacktrace=true"
+        );
+
+        let state = enhancements.stacktrace_state::<Frame>(&[], &Default::default(), &options);
+        assert_eq!(state.max_frames.value, NonZeroUsize::new(10));
+        assert!(state.max_frames.setter.is_none());
+    }
+
+    #[test]
+    fn summarizes_in_app_changes() {
+        let enhancements = Enhancements::parse(
+            "function:foo -app\nfunction:* +app\nfunction:bar +app\nfunction:nope -app",
+            &mut Default::default(),
+        )
+        .unwrap();
+
+        let mut frames = vec![
+            Frame::from_test(&json!({"function": "foo", "in_app": true}), "native"),
+            Frame::from_test(&json!({"function": "bar"}), "native"),
+            Frame::from_test(&json!({"function": "baz", "in_app": true}), "native"),
+        ];
+
+        let options = ModifyOptions {
+            summarize_in_app_changes: true,
+            ..Default::default()
+        };
+        let result = enhancements.apply_modifications_to_frames_with_options(
+            &mut frames,
+            &Default::default(),
+            &options,
+        );
+
+        let summary: Vec<_> = result
+            .in_app_changes
+            .iter()
+            .map(|c| (c.rule.to_string(), c.frames))
+            .collect();
+        assert_eq!(
+            summary,
+            [
+                ("function:foo -app".to_owned(), 1),
+                ("function:* +app".to_owned(), 2)
+            ]
+        );
+        assert!(result.in_app_flips.is_empty());
+    }
+
+    #[test]
+    fn retains_phase_specific_rules() {
+        let input = "function:foo category=foo\nfunction:bar +group\nfunction:baz -app";
+        let mut cache = Cache::default();
+
+        let mut enhancements = Enhancements::parse(input, &mut cache).unwrap();
+        enhancements.retain_updater_only();
+        assert_eq!(enhancements.rules().count(), 2);
+        assert!(enhancements.modifier_rules.all.is_empty());
+        assert_eq!(enhancements.updater_rules.all.len(), 2);
+
+        let mut enhancements = Enhancements::parse(input, &mut cache).unwrap();
+        enhancements.retain_modifier_only();
+        assert_eq!(enhancements.rules().count(), 2);
+        assert_eq!(enhancements.modifier_rules.all.len(), 2);
+        assert!(enhancements.updater_rules.all.is_empty());
+    }
+
+    #[test]
+    fn reuses_component_buffers() {
+        let enhancements = Enhancements::parse(
+            "function:foo -group\nfunction:bar max-frames=1",
+            &mut Cache::default(),
+        )
+        .unwrap();
+        let frames = |functions: &[&str]| -> Vec<_> {
+            functions
+                .iter()
+                .map(|&function| Frame {
+                    function: Some(function.into()),
+                    ..Default::default()
+                })
+                .collect()
+        };
+
+        let mut buffer = ComponentBuffer::new();
+        for functions in [&["foo", "bar", "baz"][..], &["baz", "foo"], &["bar", "baz"]] {
+            let frames = frames(functions);
+            for component in buffer.reset(frames.len()) {
+                component.contributes = Some(true);
+            }
+            let result = enhancements.assemble_stacktrace_component_in(
+                &mut buffer,
+                &frames,
+                &Default::default(),
+                &Default::default(),
+            );
+
+            let mut components = vec![
+                Component {
+                    contributes: Some(true),
+                    ..Default::default()
+                };
+                frames.len()
+            ];
+            let expected = enhancements.assemble_stacktrace_component(
+                &mut components,
+                &frames,
+                &Default::default(),
+            );
+            assert_eq!(result.contributes, expected.contributes);
+            assert_eq!(buffer.components().len(), components.len());
+            for (component, expected) in buffer.components().iter().zip(&components) {
+                assert_eq!(component.contributes, expected.contributes);
+                assert_eq!(component.hints, expected.hints);
+            }
+        }
+    }
+
+    #[test]
+    fn assembles_with_options() {
+        let enhancements =
+            Enhancements::parse("function:foo max-frames=3", &mut Cache::default()).unwrap();
+        let frames: Vec<_> = ["foo", "bar", "baz", "qux"]
+            .into_iter()
+            .map(|function| Frame {
+                function: Some(function.into()),
+                ..Default::default()
+            })
+            .collect();
+        let components = || {
+            vec![
+                Component {
+                    contributes: Some(true),
+                    ..Default::default()
+                };
+                4
+            ]
+        };
+
+        let options = AssembleOptions {
+            max_frames: NonZeroUsize::new(2),
+            min_frames: NonZeroUsize::new(5),
+            invert_stacktrace: true,
+            ..Default::default()
+        };
+
+        // A rule overrides the default from the options.
+        let mut c = components();
+        let result = enhancements.assemble_stacktrace_component_with_options(
+            &mut c,
+            &frames,
+            &Default::default(),
+            &options,
+        );
+        assert_eq!(c.iter().filter(|c| c.contributes == Some(true)).count(), 3);
+        assert!(!result.contributes);
+        assert_eq!(
+            result.hint.as_deref(),
+            Some("discarded because stack trace only contains 3 frames which is under the configured threshold")
+        );
+        assert!(result.invert_stacktrace);
+
+        // Without a matching rule, the defaults apply.
+        let mut c = components();
+        let result = Enhancements::default().assemble_stacktrace_component_with_options(
+            &mut c,
+            &frames,
+            &Default::default(),
+            &options,
+        );
+        assert_eq!(c.iter().filter(|c| c.contributes == Some(true)).count(), 2);
+        assert_eq!(
+            c[0].primary_hint(),
+            Some("ignored because only 2 frames are considered")
+        );
+        assert!(!result.contributes);
+    }
+
+    #[test]
+    fn trims_to_sentinel() {
+        let enhancements = Enhancements::parse("function:e -group", &mut Cache::default()).unwrap();
+        let functions = ["a", "b", "c", "d", "e", "f"];
+        let frames: Vec<_> = functions
+            .into_iter()
+            .map(|function| Frame {
+                function: Some(function.into()),
+                ..Default::default()
+            })
+            .collect();
+        let components = || -> Vec<_> {
+            functions
+                .into_iter()
+                .map(|function| Component {
+                    contributes: Some(true),
+                    is_sentinel_frame: matches!(function, "a" | "b"),
+                    is_prefix_frame: matches!(function, "c" | "d" | "f"),
+                    ..Default::default()
+                })
+                .collect()
+        };
+        let options = AssembleOptions {
+            max_frames: NonZeroUsize::new(2),
+            trim_to_sentinel: true,
+            ..Default::default()
+        };
+
+        let mut c = components();
+        enhancements.assemble_stacktrace_component_with_options(
+            &mut c,
+            &frames,
+            &Default::default(),
+            &options,
+        );
+        let contributes: Vec<_> = c.iter().map(|c| c.contributes.unwrap()).collect();
+        assert_eq!(contributes, [false, false, true, true, false, false]);
+        assert_eq!(
+            c[0].primary_hint(),
+            Some("ignored because only 2 frames are considered")
+        );
+        assert_eq!(
+            c[4].primary_hint(),
+            Some("ignored by stack trace rule (function:e -group)")
+        );
+        assert_eq!(
+            c[5].primary_hint(),
+            Some("ignored because it is above the sentinel frame")
+        );
+
+        // Without the option, sentinel frames have no effect.
+        let mut c = components();
+        enhancements.assemble_stacktrace_component(&mut c, &frames, &Default::default());
+        let contributes: Vec<_> = c.iter().map(|c| c.contributes.unwrap()).collect();
+        assert_eq!(contributes, [true, true, true, true, false, true]);
+    }
+
+    #[test]
+    fn hint_precedence() {
+        let enhancements = Enhancements::parse(
+            "function:foo -group\nfunction:foo -app",
+            &mut Cache::default(),
+        )
+        .unwrap();
+        let frames = [Frame {
+            function: Some("foo".into()),
+            in_app: Some(false),
+            orig_in_app: Some(Some(true)),
+            ..Default::default()
+        }];
+
+        let mut components = [Component {
+            contributes: Some(true),
+            ..Default::default()
+        }];
+        enhancements.assemble_stacktrace_component(&mut components, &frames, &Default::default());
+
+        // The later `-app` rule does not take precedence over the `-group` hint.
+        assert_eq!(
+            components[0].primary_hint(),
+            Some("ignored by stack trace rule (function:foo -group)")
+        );
+        assert_eq!(components[0].hints.len(), 2);
+        assert_eq!(components[0].hints[1].kind, Some(HintKind::InApp));
+
+        let mut component = Component::default();
+        assert!(component.add_hint(HintKind::InApp, "first".into()));
+        assert!(component.add_hint(HintKind::InApp, "second".into()));
+        assert!(component.add_hint(HintKind::MaxFrames, "third".into()));
+        assert!(!component.add_hint(HintKind::Group, "fourth".into()));
+        assert_eq!(component.primary_hint(), Some("third"));
+    }
+
+    #[test]
+    fn deduplicates_hints() {
+        let mut component = Component {
+            hints: vec![Hint::new("given")],
+            ..Default::default()
+        };
+        assert_eq!(component.primary_hint(), Some("given"));
+
+        assert!(component.add_hint(HintKind::InApp, "first".into()));
+        assert!(component.add_hint(HintKind::InApp, "second".into()));
+        assert!(component.add_hint(HintKind::InApp, "first".into()));
+        assert_eq!(component.primary_hint(), Some("first"));
+
+        let texts: Vec<_> = component.hints.iter().map(|h| h.text.as_str()).collect();
+        assert_eq!(texts, ["given", "second", "first"]);
+    }
+
+    #[test]
+    fn applies_categories_only() {
+        let enhancements = Enhancements::parse(
+            "function:foo category=foo -app\nfunction:bar -app\napp:yes category=app",
+            &mut Cache::default(),
+        )
+        .unwrap();
+        let mut frames = [
+            Frame {
+                function: Some("foo".into()),
+                in_app: Some(true),
+                ..Default::default()
+            },
+            Frame {
+                function: Some("bar".into()),
+                in_app: Some(true),
+                ..Default::default()
+            },
+        ];
+
+        enhancements.apply_categories(&mut frames, &Default::default());
+
+        assert_eq!(frames[0].category.as_deref(), Some("app"));
+        assert_eq!(frames[0].in_app, Some(true));
+        assert_eq!(frames[1].category.as_deref(), Some("app"));
+        assert_eq!(frames[1].in_app, Some(true));
     }
 
     #[test]
@@ -0,0 +1,242 @@
+//! Options that control how [`Enhancements`](super::Enhancements) are constructed and applied.
+
+use std::collections::HashSet;
+use std::num::NonZeroUsize;
+use std::time::Duration;
+
+use super::StringField;
+
+/// Options for parsing [`Enhancements`](super::Enhancements).
+#[derive(Debug, Clone, Default)]
+pub struct ParseOptions {
+    /// The categories that `category=` actions are allowed to set.
+    ///
+    /// If this is `None`, any category is accepted. Otherwise, rules
+    /// setting a category not contained in this list are rejected.
+    pub allowed_categories: Option<HashSet<StringField>>,
+    /// How matchers treat values that are missing from frames and exceptions.
+    pub missing_values: MissingValuePolicy,
+    /// Which glob syntax the patterns of matchers may use.
+    pub glob: GlobOptions,
+}
+
+impl ParseOptions {
+    /// Restricts the categories `category=` actions are allowed to set to `categories`.
+    pub fn with_allowed_categories<I, S>(mut self, categories: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<StringField>,
+    {
+        self.allowed_categories = Some(categories.into_iter().map(Into::into).collect());
+        self
+    }
+
+    /// Checks whether `category` may be set by a `category=` action.
+    pub fn is_category_allowed(&self, category: &str) -> bool {
+        self.allowed_categories
+            .as_ref()
+            .is_none_or(|allowed| allowed.contains(category))
+    }
+}
+
+/// Which glob syntax the patterns of matchers may use.
+///
+/// Patterns support `*`, which matches anything except `/` in `path` and `package` patterns,
+/// `**`, which matches anything, and `?`, which matches a single character. On top of that:
+///
+/// * Character classes like `[abc]`, `[a-z]` and `[!abc]` match a single character that is
+///   (or isn't) in the class. Sentry's Python implementation supports these as well.
+/// * Brace expansion like `{foo,bar}` matches any of the comma-separated alternatives. Sentry's
+///   Python implementation doesn't support this and matches braces literally.
+///
+/// Both are enabled by default. If one is disabled, rules whose patterns use it are rejected
+/// instead of being interpreted differently, so that a config never silently changes meaning.
+/// A literal `[` or `{` can be written as `[[]` or `[{]`, which means the same with and without
+/// brace expansion, and in Python.
+///
+/// Patterns that are invalid, like `-[*` with its unclosed character class, never match in
+/// frame matchers, while Python matches the `[` literally. With
+/// [`reject_invalid`](Self::reject_invalid), such patterns are rejected instead.
+///
+/// Patterns starting with `=` are literal: they match their argument exactly, or every value
+/// starting with it if it ends with `*`. They are never split into alternatives, and none of
+/// these options apply to them. For example, `function:=-[UIApplication*` matches the methods
+/// of `UIApplication`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct GlobOptions {
+    /// Whether patterns may use brace expansion, like `{foo,bar}`.
+    pub braces: bool,
+    /// Whether patterns may use character classes, like `[a-z]`.
+    pub classes: bool,
+    /// Whether rules with invalid frame matcher patterns are rejected.
+    ///
+    /// Invalid exception matcher patterns are always rejected.
+    pub reject_invalid: bool,
+}
+
+impl Default for GlobOptions {
+    fn default() -> Self {
+        Self {
+            braces: true,
+            classes: true,
+            reject_invalid: false,
+        }
+    }
+}
+
+/// How a matcher treats a frame or exception that doesn't have the value it checks.
+///
+/// This applies to matchers checking string values, i.e. all exception matchers and the
+/// `category`, `function`, `module`, `package`, and `path` frame matchers, to `app:yes` and
+/// `app:no` matchers on frames whose `in_app` flag is unset, and to `has_vars` matchers on
+/// frames whose `has_vars` flag is unknown. `family` matchers always match against a frame's
+/// family.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum MissingValue {
+    /// The matcher doesn't match, so its negation does.
+    ///
+    /// For example, `!module:foo` matches frames without a module.
+    NoMatch,
+    /// The matcher matches its pattern against the placeholder `<unknown>`.
+    ///
+    /// For example, `type:*` and `!type:foo` both match exceptions without a type.
+    Unknown,
+    /// Neither the matcher nor its negation match.
+    ///
+    /// For example, neither `module:*` nor `!module:foo` match frames without a module.
+    NeverMatch,
+}
+
+/// How frame and exception matchers treat missing values.
+///
+/// The default is the behavior of Sentry's Python implementation: frame matchers use
+/// [`MissingValue::NoMatch`] and exception matchers use [`MissingValue::Unknown`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct MissingValuePolicy {
+    /// How frame matchers treat missing frame fields.
+    pub frames: MissingValue,
+    /// How exception matchers treat missing exception fields.
+    pub exceptions: MissingValue,
+    /// Whether `app:no` matches frames whose `in_app` flag is unset, like it did before `app`
+    /// matchers distinguished unset from `false`.
+    ///
+    /// This is meant for migrating rules written for the old behavior. By default, an unset
+    /// flag is only matched by `app:unset`, and treated as a missing value by `app:yes` and
+    /// `app:no`, so e.g. `!app:no` matches it.
+    pub unset_in_app_is_false: bool,
+}
+
+impl Default for MissingValuePolicy {
+    fn default() -> Self {
+        Self {
+            frames: MissingValue::NoMatch,
+            exceptions: MissingValue::Unknown,
+            unset_in_app_is_false: false,
+        }
+    }
+}
+
+/// Options for [`apply_modifications_to_frames`](super::Enhancements::apply_modifications_to_frames).
+///
+/// The default values preserve the behavior of not passing any options.
+#[derive(Debug, Clone, Default)]
+pub struct ModifyOptions {
+    /// Whether to count, per frame, how many rules flipped its `in_app` flag.
+    ///
+    /// This is meant for finding configurations in which rules fight each other.
+    pub track_in_app_flips: bool,
+    /// Whether to summarize which rules changed the `in_app` flag of how many frames.
+    ///
+    /// This is meant for showing SDK teams which rules override the `in_app` decisions of clients.
+    pub summarize_in_app_changes: bool,
+    /// Whether to record, per frame, the id of the rule that last changed its `in_app` flag.
+    ///
+    /// This is meant for attributing the final `in_app` value of a frame to a rule.
+    pub track_in_app_last_changed: bool,
+    /// Whether to record the original values of all modified frame fields.
+    ///
+    /// The resulting undo log can be passed to [`revert`](super::revert) to restore the frames.
+    pub record_undo_log: bool,
+    /// Whether to measure the time spent matching and applying each rule.
+    ///
+    /// This is meant for finding pathological rules that are worth rewriting.
+    pub profile_rules: bool,
+    /// Whether `function` matchers also check a frame's [raw function](super::FrameLike::raw_function).
+    ///
+    /// If this is set, a `function` matcher matches if either the function or the raw function
+    /// matches its pattern.
+    pub match_raw_function: bool,
+    /// Whether to count how many rules were skipped or evaluated.
+    ///
+    /// This is meant for finding out how effective skipping rules is on real stacktraces.
+    pub collect_stats: bool,
+    /// The maximum number of frames rules are applied to at the start and end of the stacktrace.
+    ///
+    /// If there are more than twice as many frames, rules are only matched against the first
+    /// and last `frame_limit` frames, and the [stats](super::ModifyResult::stats) are marked as
+    /// truncated. This guards against spending too much time on huge stacktraces, like the ones
+    /// produced by infinite recursion. `None` means that all frames are considered.
+    pub frame_limit: Option<NonZeroUsize>,
+    /// Whether to [normalize](super::normalize_exception_value) the exception value before
+    /// matching rules against it.
+    ///
+    /// This allows `error.value` matchers to ignore addresses, numbers, and other variable
+    /// parts of exception values. The normalized value is returned in the result.
+    pub normalize_exception_value: bool,
+    /// The maximum time to spend applying rules.
+    ///
+    /// Once the budget is exhausted, no further rules are applied and the id of the first rule
+    /// that was skipped is returned in the result. This guards against pathological
+    /// configurations stalling event processing. `None` means that there is no time limit.
+    pub time_budget: Option<Duration>,
+    /// Whether to set the `in_app` flag of frames that don't have one to its
+    /// [default](super::default_in_app) before applying any rules.
+    ///
+    /// This replaces deriving the defaults in a separate pass before applying the rules.
+    pub derive_default_in_app: bool,
+}
+
+/// Options for [`assemble_stacktrace_component`](super::Enhancements::assemble_stacktrace_component).
+///
+/// The default values preserve the behavior of not passing any options.
+#[derive(Debug, Clone, Default)]
+pub struct AssembleOptions {
+    /// The maximum number of contributing frames, unless a rule sets `max-frames`.
+    ///
+    /// `None` means that the number of frames is not limited.
+    pub max_frames: Option<NonZeroUsize>,
+    /// The minimum number of contributing frames, unless a rule sets `min-frames`.
+    ///
+    /// `None` means that there is no minimum.
+    pub min_frames: Option<NonZeroUsize>,
+    /// Whether the stacktrace should be inverted, unless a rule sets `invert-stacktrace`.
+    pub invert_stacktrace: bool,
+    /// Whether to ignore the frames above the last [sentinel frame](super::Component::is_sentinel_frame).
+    ///
+    /// Frames are ordered from the outermost caller to the innermost callee, so "above" means
+    /// closer to the crash. The [prefix frames](super::Component::is_prefix_frame) directly above
+    /// the sentinel frame are kept, as they are considered part of it.
+    ///
+    /// Trimming happens after all rules are applied and before the `max-frames` limit is enforced.
+    pub trim_to_sentinel: bool,
+    /// Whether `function` matchers also check a frame's [raw function](super::FrameLike::raw_function),
+    /// like [`ModifyOptions::match_raw_function`].
+    pub match_raw_function: bool,
+    /// Whether to count how many rules were skipped or evaluated, like [`ModifyOptions::collect_stats`].
+    pub collect_stats: bool,
+    /// The maximum number of frames rules are applied to at the start and end of the stacktrace,
+    /// like [`ModifyOptions::frame_limit`].
+    pub frame_limit: Option<NonZeroUsize>,
+    /// Whether to normalize the exception value before matching rules against it,
+    /// like [`ModifyOptions::normalize_exception_value`].
+    pub normalize_exception_value: bool,
+    /// The maximum time to spend applying rules, like [`ModifyOptions::time_budget`].
+    ///
+    /// Components are still trimmed according to the stacktrace state reached so far.
+    pub time_budget: Option<Duration>,
+    /// Whether to match field matchers that occur in several rules only once per frame.
+    ///
+    /// This only pays off for configurations in which many rules share matchers. Otherwise,
+    /// looking up the shared matchers costs more than it saves.
+    pub memoize_shared_matchers: bool,
+}
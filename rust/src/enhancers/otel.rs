@@ -0,0 +1,127 @@
+//! Stack frames described by OpenTelemetry's semantic conventions.
+//!
+//! OpenTelemetry exception stacktraces describe frames with `code.*` attributes rather than
+//! Sentry's frame fields. [`OtelFrame`] collects these attributes and converts into a [`Frame`],
+//! so that rules can be applied to OpenTelemetry data directly.
+
+use super::frame::{Frame, RawFrame, StringField};
+
+/// The attribute naming a frame's function.
+const FUNCTION: &str = "code.function.name";
+/// The deprecated name of [`FUNCTION`].
+const FUNCTION_DEPRECATED: &str = "code.function";
+/// The attribute naming a frame's namespace, i.e. its module or class.
+const NAMESPACE: &str = "code.namespace";
+/// The attribute holding the path of a frame's source file.
+const FILE_PATH: &str = "code.file.path";
+/// The deprecated name of [`FILE_PATH`].
+const FILE_PATH_DEPRECATED: &str = "code.filepath";
+
+/// A stack frame described by OpenTelemetry `code.*` attributes.
+///
+/// Converting it into a [`Frame`] maps the function to `function`, the namespace to `module`,
+/// and the file path to `path`. The family is derived from the language.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct OtelFrame<'a> {
+    /// The `code.function.name` attribute, or the deprecated `code.function`.
+    pub function: Option<&'a str>,
+    /// The `code.namespace` attribute.
+    pub namespace: Option<&'a str>,
+    /// The `code.file.path` attribute, or the deprecated `code.filepath`.
+    pub file_path: Option<&'a str>,
+    /// The language of the SDK that recorded the frame, as in the `telemetry.sdk.language`
+    /// resource attribute, e.g. `"python"` or `"nodejs"`.
+    pub language: Option<&'a str>,
+}
+
+impl<'a> OtelFrame<'a> {
+    /// Collects the frame attributes from the `(key, value)` pairs in `attributes`.
+    ///
+    /// Unrelated attributes are ignored. If an attribute is given under both its current and
+    /// its deprecated name, the current name takes precedence.
+    pub fn from_attributes<I>(attributes: I, language: Option<&'a str>) -> Self
+    where
+        I: IntoIterator<Item = (&'a str, &'a str)>,
+    {
+        let mut frame = Self {
+            language,
+            ..Default::default()
+        };
+        let (mut function, mut file_path) = (None, None);
+        for (key, value) in attributes {
+            match key {
+                FUNCTION => frame.function = Some(value),
+                FUNCTION_DEPRECATED => function = Some(value),
+                NAMESPACE => frame.namespace = Some(value),
+                FILE_PATH => frame.file_path = Some(value),
+                FILE_PATH_DEPRECATED => file_path = Some(value),
+                _ => {}
+            }
+        }
+        frame.function = frame.function.or(function);
+        frame.file_path = frame.file_path.or(file_path);
+        frame
+    }
+
+    /// Returns the family of frames recorded by SDKs of `language`.
+    fn family(language: &str) -> &'static str {
+        match language {
+            "nodejs" | "webjs" => "javascript",
+            "cpp" | "rust" | "swift" => "native",
+            _ => "other",
+        }
+    }
+}
+
+impl From<OtelFrame<'_>> for Frame {
+    fn from(frame: OtelFrame<'_>) -> Self {
+        let family = frame.language.map_or("other", OtelFrame::family);
+        Frame::from_raw_parts(RawFrame {
+            platform: Some(StringField::new(family)),
+            function: frame.function.map(StringField::new),
+            module: frame.namespace.map(StringField::new),
+            path: frame.file_path,
+            ..Default::default()
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::enhancers::{Cache, Enhancements, FrameLike};
+
+    #[test]
+    fn converts_otel_frames() {
+        let attributes = [
+            ("code.function", "handle"),
+            ("code.function.name", "handle_request"),
+            ("code.namespace", "app.views"),
+            ("code.filepath", "/srv/node_modules/app/views.js"),
+            ("code.lineno", "42"),
+        ];
+        let otel = OtelFrame::from_attributes(attributes, Some("nodejs"));
+        assert_eq!(
+            otel,
+            OtelFrame {
+                function: Some("handle_request"),
+                namespace: Some("app.views"),
+                file_path: Some("/srv/node_modules/app/views.js"),
+                language: Some("nodejs"),
+            }
+        );
+
+        let mut frames = [Frame::from(otel), Frame::from(OtelFrame::default())];
+        assert_eq!(frames[0].function.as_deref(), Some("handle_request"));
+        assert_eq!(frames[1].raw_family(), Some("other"));
+
+        let enhancements = Enhancements::parse(
+            "family:javascript path:**/node_modules/** module:app.* -app",
+            &mut Cache::default(),
+        )
+        .unwrap();
+        enhancements.apply_modifications_to_frames(&mut frames, &Default::default());
+        assert_eq!(frames[0].in_app, Some(false));
+        assert_eq!(frames[1].in_app, None);
+    }
+}
@@ -0,0 +1,304 @@
+//! Locating the part of a rule that failed to parse.
+//!
+//! The parser reports errors as chains of messages of the form ``at `<rest>`: <message>``,
+//! where `<rest>` is the text of the rule starting at the problem. The parser creates these
+//! messages as [`SyntaxError`]s, so that the position in the line and what kind of part is
+//! broken can be recovered from the chain and reported as a [`ParseError`].
+
+use std::fmt;
+use std::ops::Range;
+
+use serde::Serialize;
+
+use super::grammar::skip_matcher;
+
+/// What is wrong with a line a [`ParseError`] is about.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ParseErrorKind {
+    /// The line is malformed in a way not covered by the other kinds.
+    Syntax,
+    /// The rule doesn't start with a matcher.
+    MissingMatcher,
+    /// A matcher is malformed, unknown, or has an invalid argument.
+    InvalidMatcher,
+    /// The matchers of the rule aren't followed by an action.
+    MissingAction,
+    /// An action is malformed or sets an unknown flag or variable.
+    InvalidAction,
+    /// A pattern definition or a reference to a pattern is invalid.
+    InvalidPattern,
+    /// The rule is well-formed, but rejected by the [`ParseOptions`](super::ParseOptions).
+    InvalidRule,
+}
+
+impl ParseErrorKind {
+    /// Returns the name of this kind, as it is serialized.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ParseErrorKind::Syntax => "syntax",
+            ParseErrorKind::MissingMatcher => "missing_matcher",
+            ParseErrorKind::InvalidMatcher => "invalid_matcher",
+            ParseErrorKind::MissingAction => "missing_action",
+            ParseErrorKind::InvalidAction => "invalid_action",
+            ParseErrorKind::InvalidPattern => "invalid_pattern",
+            ParseErrorKind::InvalidRule => "invalid_rule",
+        }
+    }
+}
+
+/// An error in a line of rules, with the position of the offending token.
+///
+/// [`Enhancements::parse`](super::Enhancements::parse) reports errors of single lines this way,
+/// wrapped in [`Error::Parse`](crate::Error::Parse) and accessible with
+/// [`Error::parse_error`](crate::Error::parse_error). A `ParseError` displays as the message of
+/// the error it locates and reports the same sources, so the chain of messages is the same as
+/// without it.
+///
+/// ```
+/// use rust_ophio::enhancers::{Cache, Enhancements, ParseErrorKind};
+///
+/// let input = "function:foo +app\nfunction:bar +nope";
+/// let err = Enhancements::parse(input, &mut Cache::default()).unwrap_err();
+/// let parse_error = err.parse_error().unwrap();
+/// assert_eq!(parse_error.line, 2);
+/// assert_eq!(parse_error.columns, 14..18);
+/// assert_eq!(parse_error.token, "nope");
+/// assert_eq!(parse_error.kind, ParseErrorKind::InvalidAction);
+/// ```
+#[derive(Debug)]
+pub struct ParseError {
+    /// The number of the line, starting at 1.
+    pub line: usize,
+    /// The columns of the offending token in the line, counted in characters starting at 0.
+    ///
+    /// If the token can't be located more precisely, this spans the whole line, without
+    /// leading and trailing whitespace.
+    pub columns: Range<usize>,
+    /// The offending token, e.g. a matcher or an action.
+    pub token: String,
+    /// What is wrong with the line.
+    pub kind: ParseErrorKind,
+    error: anyhow::Error,
+}
+
+impl ParseError {
+    /// Locates `error` in `line`, the line with the number `line_number`.
+    ///
+    /// `parsed` is the text that was parsed for the (trimmed) line. The error is located at the
+    /// innermost [`SyntaxError`] whose position is in `parsed`, and its kind is the kind of the
+    /// outermost one. If `parsed` isn't the line itself, e.g. because patterns were expanded,
+    /// the error is located at the whole line.
+    pub(crate) fn locate(
+        error: anyhow::Error,
+        line_number: usize,
+        line: &str,
+        parsed: &str,
+    ) -> Self {
+        let trimmed = line.trim();
+        let start = line.len() - line.trim_start().len();
+
+        let syntax_errors: Vec<&SyntaxError> = error
+            .chain()
+            .filter_map(|cause| cause.downcast_ref::<SyntaxError>())
+            .collect();
+        let kind = syntax_errors
+            .first()
+            .map_or(ParseErrorKind::Syntax, |error| error.kind);
+        let rest = syntax_errors
+            .iter()
+            .rev()
+            .map(|error| error.rest.as_str())
+            .find(|rest| parsed == trimmed && trimmed.ends_with(rest));
+
+        let (start, token) = match rest {
+            Some(rest) => (start + trimmed.len() - rest.len(), token(rest)),
+            None => (start, trimmed),
+        };
+        let column = line[..start].chars().count();
+        Self {
+            line: line_number,
+            columns: column..column + token.chars().count(),
+            token: token.into(),
+            kind,
+            error,
+        }
+    }
+
+    /// Overrides the kind of this error.
+    pub(crate) fn with_kind(mut self, kind: ParseErrorKind) -> Self {
+        self.kind = kind;
+        self
+    }
+}
+
+/// Returns the token at the start of `rest`: the whole matcher if `rest` starts with one, or
+/// everything up to the next whitespace.
+fn token(rest: &str) -> &str {
+    let end = match skip_matcher(rest) {
+        Ok(Some(after)) => rest.len() - after.len(),
+        _ => rest.find(char::is_whitespace).unwrap_or(rest.len()),
+    };
+    &rest[..end]
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        // Only the outermost message, the rest of the chain are the sources.
+        write!(f, "{}", self.error)
+    }
+}
+
+impl std::error::Error for ParseError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        self.error.source()
+    }
+}
+
+/// An error at a position in the text of a rule.
+///
+/// This displays as ``at `<rest>`: <message>``, or just the message if `rest` is empty.
+#[derive(Debug)]
+pub(crate) struct SyntaxError {
+    kind: ParseErrorKind,
+    rest: String,
+    message: String,
+    source: Option<anyhow::Error>,
+}
+
+impl SyntaxError {
+    /// Creates an error about the start of `rest`, the rest of the input.
+    pub(crate) fn new(kind: ParseErrorKind, rest: &str, message: impl Into<String>) -> Self {
+        Self {
+            kind,
+            rest: rest.into(),
+            message: message.into(),
+            source: None,
+        }
+    }
+
+    /// Sets the error that caused this one.
+    pub(crate) fn with_source(mut self, source: anyhow::Error) -> Self {
+        self.source = Some(source);
+        self
+    }
+}
+
+impl fmt::Display for SyntaxError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if !self.rest.is_empty() {
+            write!(f, "at `{}`: ", self.rest)?;
+        }
+        f.write_str(&self.message)
+    }
+}
+
+impl std::error::Error for SyntaxError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        let source: &(dyn std::error::Error + 'static) = self.source.as_ref()?.as_ref();
+        Some(source)
+    }
+}
+
+/// Extends results with [`SyntaxError`]s as context, like [`anyhow::Context`].
+pub(crate) trait SyntaxContext<T> {
+    /// Wraps the error in a [`SyntaxError`] about the start of `rest`.
+    fn context_at(self, kind: ParseErrorKind, rest: &str, message: &str) -> anyhow::Result<T>;
+}
+
+impl<T> SyntaxContext<T> for anyhow::Result<T> {
+    fn context_at(self, kind: ParseErrorKind, rest: &str, message: &str) -> anyhow::Result<T> {
+        self.map_err(|e| SyntaxError::new(kind, rest, message).with_source(e).into())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::enhancers::{Cache, Enhancements, ParseOptions};
+    use crate::Error;
+
+    use super::*;
+
+    fn parse_error(input: &str) -> (usize, Range<usize>, String, ParseErrorKind) {
+        let options = ParseOptions::default().with_allowed_categories(["ui"]);
+        let err =
+            Enhancements::parse_with_options(input, &mut Cache::default(), &options).unwrap_err();
+        let e = err
+            .parse_error()
+            .unwrap_or_else(|| panic!("{input}: {err:#}"));
+        (e.line, e.columns.clone(), e.token.clone(), e.kind)
+    }
+
+    #[test]
+    fn locates_errors() {
+        use ParseErrorKind::*;
+
+        let cases = [
+            ("+app", (1, 0..4, "+app", MissingMatcher)),
+            ("function:foo", (1, 12..12, "", MissingAction)),
+            (
+                "# ok\n  thread.state:x -app",
+                (2, 2..16, "thread.state:x", InvalidMatcher),
+            ),
+            (
+                r#"function:"foo -app"#,
+                (1, 9..13, r#""foo"#, InvalidMatcher),
+            ),
+            ("[ function:foo | -app", (1, 15..16, "|", InvalidMatcher)),
+            (
+                "function:foo | [ bar ] -app",
+                (1, 17..20, "bar", InvalidMatcher),
+            ),
+            ("function:ä -äpp", (1, 12..15, "äpp", InvalidAction)),
+            (
+                "function:foo max-frames=x",
+                (1, 13..25, "max-frames=x", InvalidAction),
+            ),
+            (
+                "function:foo category=iu",
+                (1, 0..24, "function:foo category=iu", InvalidRule),
+            ),
+            (
+                "@define a/b function:foo",
+                (1, 8..11, "a/b", InvalidPattern),
+            ),
+            (
+                "@define p function:foo\n@q -app",
+                (2, 0..2, "@q", InvalidPattern),
+            ),
+            // Errors in rules using patterns are located at the whole line.
+            (
+                "@define p function:foo\n@p +nope",
+                (2, 0..8, "@p +nope", InvalidAction),
+            ),
+            (
+                "@define p function:foo\n@p category=iu",
+                (2, 0..14, "@p category=iu", InvalidRule),
+            ),
+        ];
+        for (input, (line, columns, token, kind)) in cases {
+            assert_eq!(
+                parse_error(input),
+                (line, columns, token.into(), kind),
+                "{input}"
+            );
+        }
+    }
+
+    #[test]
+    fn keeps_messages() {
+        let err = Enhancements::parse("function:foo +nope", &mut Cache::default()).unwrap_err();
+        assert!(matches!(err, Error::Parse(_)));
+        assert_eq!(
+            format!("{err:#}"),
+            "at `+nope`: failed to parse actions: \
+             at `+nope`: failed to parse flag action: \
+             at `nope`: invalid flag name `nope`"
+        );
+        assert_eq!(err.parse_error().unwrap().to_string(), err.to_string());
+
+        let err = Enhancements::parse("function:foo # no actions", &mut Cache::default());
+        assert!(format!("{:#}", err.unwrap_err())
+            .ends_with("at `# no actions`: expected at least one action"));
+    }
+}
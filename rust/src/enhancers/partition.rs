@@ -0,0 +1,220 @@
+//! Partitioning of rules by the frame families they can match.
+//!
+//! Most rules in the default configurations are restricted to a single family
+//! via a `family:` matcher. Since the frames of a typical stacktrace all belong to
+//! the same family, we can skip all rules that can't possibly match any of them.
+
+use std::hash::{DefaultHasher, Hash, Hasher};
+use std::sync::{Arc, OnceLock};
+
+use super::families::Families;
+use super::frame::FrameLike;
+use super::rules::Rule;
+
+/// A list of rules, additionally partitioned by the frame families they can match.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct PartitionedRules {
+    /// All rules, in their original order.
+    pub(crate) all: Vec<Rule>,
+    /// The rules that can match `"other"` frames.
+    other: Vec<Rule>,
+    /// The rules that can match `"native"` frames.
+    native: Vec<Rule>,
+    /// The rules that can match `"javascript"` frames.
+    javascript: Vec<Rule>,
+    /// A hash of all rules, computed when it is first needed.
+    fingerprint: OnceLock<u64>,
+}
+
+impl PartitionedRules {
+    /// Appends a rule to the list and all partitions it can match.
+    pub(crate) fn push(&mut self, rule: Rule) {
+        let families = rule.families();
+
+        if families.matches(Families::OTHER) {
+            self.other.push(rule.clone());
+        }
+        if families.matches(Families::NATIVE) {
+            self.native.push(rule.clone());
+        }
+        if families.matches(Families::JAVASCRIPT) {
+            self.javascript.push(rule.clone());
+        }
+
+        self.all.push(rule);
+        self.fingerprint = OnceLock::new();
+    }
+
+    /// Returns a hash of all rules and their order.
+    ///
+    /// Lists of equal rules have the same fingerprint, even if the rules were parsed separately.
+    pub(crate) fn fingerprint(&self) -> u64 {
+        *self.fingerprint.get_or_init(|| {
+            let mut hasher = DefaultHasher::new();
+            self.all.hash(&mut hasher);
+            hasher.finish()
+        })
+    }
+
+    /// Retains only the rules at the positions in `rules` for which `keep` is true.
+    ///
+    /// The rules in `self` must be a subsequence of `rules`, which is the case for all
+    /// partitions of an [`Enhancements`](super::Enhancements) structure with respect to its list of all rules.
+    pub(crate) fn retain_positions(&mut self, rules: &[Rule], keep: &[bool]) {
+        let mut positions = rules.iter().zip(keep);
+        *self = std::mem::take(&mut self.all)
+            .into_iter()
+            .filter(|rule| {
+                positions
+                    .find(|(candidate, _)| Arc::ptr_eq(&candidate.0, &rule.0))
+                    .is_none_or(|(_, keep)| *keep)
+            })
+            .collect();
+    }
+
+    /// Returns all rules that can possibly match any of the given `frames`, in their original order.
+    ///
+    /// If all frames belong to a single family, this is the corresponding partition,
+    /// otherwise it's the list of all rules.
+    pub(crate) fn for_frames<F: FrameLike>(&self, frames: &[F]) -> &[Rule] {
+        let families = frames
+            .iter()
+            .fold(Families::NONE, |acc, frame| acc.union(frame.family()));
+
+        if families == Families::OTHER {
+            &self.other
+        } else if families == Families::NATIVE {
+            &self.native
+        } else if families == Families::JAVASCRIPT {
+            &self.javascript
+        } else {
+            &self.all
+        }
+    }
+}
+
+impl FromIterator<Rule> for PartitionedRules {
+    fn from_iter<T: IntoIterator<Item = Rule>>(iter: T) -> Self {
+        let mut rules = Self::default();
+        for rule in iter {
+            rules.push(rule);
+        }
+        rules
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use crate::enhancers::{Cache, Enhancements, Frame, ModifyOptions};
+
+    use super::*;
+
+    #[test]
+    fn partitions_by_family() {
+        let enhancements = Enhancements::parse(
+            "family:native function:a -app\n\
+             family:javascript,native function:b -app\n\
+             !family:native function:c -app\n\
+             function:d -app",
+            &mut Cache::default(),
+        )
+        .unwrap();
+        let rules: PartitionedRules = enhancements.rules().cloned().collect();
+
+        let names = |frames: &[Frame]| -> Vec<String> {
+            rules
+                .for_frames(frames)
+                .iter()
+                .map(|r| r.to_string())
+                .collect()
+        };
+
+        let native = Frame::from_test(&json!({}), "native");
+        let javascript = Frame::from_test(&json!({}), "javascript");
+        let other = Frame::from_test(&json!({}), "other");
+
+        assert_eq!(
+            names(std::slice::from_ref(&native)),
+            [
+                "family:native function:a -app",
+                "family:javascript,native function:b -app",
+                "!family:native function:c -app",
+                "function:d -app",
+            ]
+        );
+        assert_eq!(
+            names(std::slice::from_ref(&javascript)),
+            [
+                "family:javascript,native function:b -app",
+                "!family:native function:c -app",
+                "function:d -app",
+            ]
+        );
+        assert_eq!(
+            names(&[other.clone(), other]),
+            ["!family:native function:c -app", "function:d -app"]
+        );
+        assert_eq!(names(&[native, javascript]).len(), 4);
+    }
+
+    #[test]
+    fn applies_family_partitions() {
+        let enhancements = Enhancements::parse(
+            "family:javascript function:foo +app\nfamily:native function:foo -app",
+            &mut Cache::default(),
+        )
+        .unwrap();
+
+        let mut frames = vec![
+            Frame::from_test(&json!({"function": "foo"}), "native"),
+            Frame::from_test(&json!({"function": "foo"}), "javascript"),
+        ];
+        enhancements.apply_modifications_to_frames(&mut frames, &Default::default());
+        assert_eq!(frames[0].in_app, Some(false));
+        assert_eq!(frames[1].in_app, Some(true));
+
+        let mut frames = vec![Frame::from_test(&json!({"function": "foo"}), "native")];
+        enhancements.apply_modifications_to_frames(&mut frames, &Default::default());
+        assert_eq!(frames[0].in_app, Some(false));
+    }
+
+    #[test]
+    fn shares_partitions_between_handles() {
+        let enhancements = Arc::new(
+            Enhancements::parse(
+                "family:javascript function:foo +app
+                 family:native function:foo -app
+                 family:native function:bar -app",
+                &mut Cache::default(),
+            )
+            .unwrap(),
+        );
+        let options = ModifyOptions {
+            collect_stats: true,
+            ..Default::default()
+        };
+
+        let handles: Vec<_> = [("native", Some(false), 1), ("javascript", Some(true), 2)]
+            .into_iter()
+            .map(|(family, in_app, skipped)| {
+                let enhancements = Arc::clone(&enhancements);
+                let options = options.clone();
+                std::thread::spawn(move || {
+                    let mut frames = vec![Frame::from_test(&json!({"function": "foo"}), family)];
+                    let result = enhancements.apply_modifications_to_frames_with_options(
+                        &mut frames,
+                        &Default::default(),
+                        &options,
+                    );
+                    assert_eq!(frames[0].in_app, in_app, "{family}");
+                    assert_eq!(result.stats.unwrap().skipped_by_families, skipped);
+                })
+            })
+            .collect();
+        for handle in handles {
+            handle.join().unwrap();
+        }
+    }
+}
@@ -0,0 +1,262 @@
+//! Named patterns that can be defined once and used in the matchers of many rules.
+//!
+//! A pattern is defined on a line of its own as `@define <name> <matchers>` and used by writing
+//! `@<name>` in place of matchers in a rule:
+//!
+//! ```text
+//! @define rust-std family:native function:std::*,core::*,alloc::*
+//! @rust-std -app
+//! @rust-std function:*::panicking::* -group
+//! ```
+//!
+//! References are replaced with the matchers of the pattern before a rule is parsed, so the
+//! rules above are the same as if they had been written out in full. Patterns may refer to
+//! other patterns, regardless of the order in which they are defined.
+
+use std::borrow::Cow;
+use std::collections::HashMap;
+
+use anyhow::{anyhow, bail, Context};
+
+use super::grammar::{ident, skip_matcher};
+use super::parse_error::{SyntaxContext, SyntaxError};
+use super::ParseErrorKind::InvalidPattern;
+
+/// The keyword starting a pattern definition.
+const DEFINE: &str = "@define";
+
+/// The maximum depth to which patterns may refer to other patterns.
+const MAX_DEPTH: usize = 8;
+
+/// The maximum length in bytes of a rule with all pattern references replaced.
+///
+/// Patterns referring to a pattern several times multiply its size at every level, so this
+/// keeps a few short definitions from expanding to an enormous rule.
+const MAX_EXPANDED_LEN: usize = 64 * 1024;
+
+/// The patterns defined in a list of rules.
+#[derive(Debug, Default)]
+pub(crate) struct Patterns<'a> {
+    /// The matchers of each pattern as they are written, keyed by the pattern's name.
+    definitions: HashMap<&'a str, &'a str>,
+}
+
+impl<'a> Patterns<'a> {
+    /// Returns whether the (trimmed) `line` is a pattern definition.
+    pub(crate) fn is_definition(line: &str) -> bool {
+        line.strip_prefix(DEFINE)
+            .is_some_and(|rest| rest.is_empty() || rest.starts_with(char::is_whitespace))
+    }
+
+    /// Adds the pattern defined in the (trimmed) `line`.
+    ///
+    /// `line` must be a definition according to [`is_definition`](Self::is_definition).
+    pub(crate) fn define(&mut self, line: &'a str) -> anyhow::Result<()> {
+        let input = line
+            .strip_prefix(DEFINE)
+            .ok_or_else(|| anyhow!("at `{line}`: expected `{DEFINE}`"))?
+            .trim_start();
+
+        let (name, matchers) =
+            ident(input).context_at(InvalidPattern, input, "invalid pattern name")?;
+        if name.is_empty() {
+            return Err(SyntaxError::new(InvalidPattern, line, "expected pattern name").into());
+        }
+        if !matchers.is_empty() && !matchers.starts_with(char::is_whitespace) {
+            return Err(SyntaxError::new(InvalidPattern, input, "invalid pattern name").into());
+        }
+
+        let matchers = matchers.trim();
+        if matchers.is_empty() {
+            bail!("pattern `@{name}` has no matchers");
+        }
+        if self.definitions.insert(name, matchers).is_some() {
+            bail!("pattern `@{name}` is defined more than once");
+        }
+
+        Ok(())
+    }
+
+    /// Replaces the pattern references in the (trimmed) `rule` with the matchers of the patterns.
+    ///
+    /// References may appear anywhere in the main list of matchers, but not in caller or callee
+    /// matchers.
+    pub(crate) fn expand<'r>(&self, rule: &'r str) -> anyhow::Result<Cow<'r, str>> {
+        if !rule.contains('@') {
+            return Ok(Cow::Borrowed(rule));
+        }
+
+        let mut expanded = String::new();
+        let mut input = rule;
+
+        // A caller matcher is copied as it is. If it is malformed, so is the rule, and parsing
+        // it reports the error.
+        if let Some(rest) = rule.strip_prefix('[') {
+            let after_caller = skip_matcher(rest)?
+                .and_then(|rest| rest.trim_start().strip_prefix(']'))
+                .and_then(|rest| rest.trim_start().strip_prefix('|'));
+            let Some(after_caller) = after_caller else {
+                return Ok(Cow::Borrowed(rule));
+            };
+            expanded.push_str(&rule[..rule.len() - after_caller.len()]);
+            input = after_caller;
+        }
+
+        let rest = self.expand_matchers(input, &mut expanded, &mut Vec::new())?;
+        expanded.push_str(rest);
+
+        Ok(Cow::Owned(expanded))
+    }
+
+    /// Appends the matchers at the start of `input` to `expanded`, replacing pattern references,
+    /// and returns the rest of the input.
+    ///
+    /// `stack` contains the names of the patterns that are currently being expanded.
+    fn expand_matchers<'i>(
+        &self,
+        mut input: &'i str,
+        expanded: &mut String,
+        stack: &mut Vec<&'a str>,
+    ) -> anyhow::Result<&'i str> {
+        loop {
+            if expanded.len() > MAX_EXPANDED_LEN {
+                bail!("patterns expand to more than {MAX_EXPANDED_LEN} bytes");
+            }
+
+            let trimmed = input.trim_start();
+            let whitespace = &input[..input.len() - trimmed.len()];
+
+            if let Some(reference) = trimmed.strip_prefix('@') {
+                let message = "invalid pattern reference";
+                let (name, rest) = ident(reference).context_at(InvalidPattern, trimmed, message)?;
+                if !rest.is_empty() && !rest.starts_with(char::is_whitespace) {
+                    return Err(SyntaxError::new(InvalidPattern, trimmed, message).into());
+                }
+                let (&name, &matchers) = self.definitions.get_key_value(name).ok_or_else(|| {
+                    let message = format!("unknown pattern `@{name}`");
+                    SyntaxError::new(InvalidPattern, trimmed, message)
+                })?;
+
+                if stack.contains(&name) {
+                    bail!("pattern `@{name}` refers to itself");
+                }
+                if stack.len() == MAX_DEPTH {
+                    bail!("patterns are nested more than {MAX_DEPTH} levels deep");
+                }
+
+                expanded.push_str(whitespace);
+                stack.push(name);
+                let after_matchers = self
+                    .expand_matchers(matchers, expanded, stack)
+                    .with_context(|| format!("in pattern `@{name}`"))?
+                    .trim_start();
+                stack.pop();
+
+                if !after_matchers.is_empty() && !after_matchers.starts_with('#') {
+                    bail!("pattern `@{name}` may only contain matchers, found `{after_matchers}`");
+                }
+
+                input = rest;
+            } else if let Some(rest) = skip_matcher(trimmed)? {
+                expanded.push_str(&input[..input.len() - rest.len()]);
+                input = rest;
+            } else {
+                return Ok(input);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::enhancers::{Cache, Enhancements, ParseErrorKind};
+
+    fn patterns<'a>(definitions: &[&'a str]) -> Patterns<'a> {
+        let mut patterns = Patterns::default();
+        for line in definitions {
+            assert!(Patterns::is_definition(line));
+            patterns.define(line).unwrap();
+        }
+        patterns
+    }
+
+    #[test]
+    fn expands_references() {
+        let patterns = patterns(&[
+            "@define app-code @native package:/opt/app/**",
+            "@define native family:native",
+        ]);
+
+        assert_eq!(
+            patterns.expand("@app-code function:main +app").unwrap(),
+            "family:native package:/opt/app/** function:main +app"
+        );
+        assert_eq!(
+            patterns.expand("[ function:a ] | @native -app").unwrap(),
+            "[ function:a ] | family:native -app"
+        );
+        assert_eq!(
+            patterns
+                .expand(r#"function:"@native" -app # @native"#)
+                .unwrap(),
+            r#"function:"@native" -app # @native"#
+        );
+        assert!(matches!(
+            patterns.expand("function:foo -app").unwrap(),
+            Cow::Borrowed(_)
+        ));
+    }
+
+    #[test]
+    fn rejects_invalid_patterns() {
+        let error = |definitions: &[&str], rule: &str| {
+            let err = patterns(definitions).expand(rule).unwrap_err();
+            format!("{err:#}")
+        };
+
+        assert_eq!(
+            error(&[], "@nope -app"),
+            "at `@nope -app`: unknown pattern `@nope`"
+        );
+        assert!(error(&["@define a @b", "@define b @a"], "@a -app")
+            .ends_with("pattern `@a` refers to itself"));
+        assert!(error(&["@define a function:foo +app"], "@a -app")
+            .ends_with("pattern `@a` may only contain matchers, found `+app`"));
+
+        let mut nested: Vec<_> = (0..10)
+            .map(|i| format!("@define p{i} @p{}", i + 1))
+            .collect();
+        nested.push("@define p10 function:foo".into());
+        let nested: Vec<_> = nested.iter().map(String::as_str).collect();
+        assert!(error(&nested, "@p0 -app").ends_with("nested more than 8 levels deep"));
+
+        // Each level multiplies the matchers of the next one, to 4^7 matchers in total.
+        let mut fan_out: Vec<_> = (0..7)
+            .map(|i| format!("@define p{i} {}", vec![format!("@p{}", i + 1); 4].join(" ")))
+            .collect();
+        fan_out.push("@define p7 function:foo".into());
+        let fan_out: Vec<_> = fan_out.iter().map(String::as_str).collect();
+        assert!(error(&fan_out, "@p0 -app").ends_with("patterns expand to more than 65536 bytes"));
+        // Fewer levels still expand.
+        assert_eq!(
+            patterns(&fan_out).expand("@p4 -app").unwrap().len(),
+            4usize.pow(3) * "function:foo ".len() + "-app".len()
+        );
+
+        let input = format!("{}\n@p0 -app", fan_out.join("\n"));
+        let err = Enhancements::parse(&input, &mut Cache::default()).unwrap_err();
+        let parse_error = err.parse_error().unwrap();
+        assert_eq!(parse_error.line, 9);
+        assert_eq!(parse_error.kind, ParseErrorKind::InvalidPattern);
+
+        let mut patterns = Patterns::default();
+        patterns.define("@define a function:foo").unwrap();
+        let err = patterns.define("@define a function:bar").unwrap_err();
+        assert_eq!(err.to_string(), "pattern `@a` is defined more than once");
+        let err = patterns.define("@define b").unwrap_err();
+        assert_eq!(err.to_string(), "pattern `@b` has no matchers");
+        let err = patterns.define("@define").unwrap_err();
+        assert_eq!(err.to_string(), "at `@define`: expected pattern name");
+    }
+}
@@ -0,0 +1,401 @@
+//! Optional bookkeeping while applying modifications.
+//!
+//! [`ModifyOptions`] can ask for information about how the rules changed the frames, in
+//! addition to applying them. Each kind of information is collected by its own recorder, and
+//! [`Recorder`] combines those the options ask for, so that applying the rules only has to
+//! notify it at a few points.
+
+use std::ops::ControlFlow;
+use std::time::Instant;
+
+use super::frame::{FrameField, FrameLike, StringField};
+use super::{
+    deadline, now, ApplyStats, Enhancements, FieldValue, FrameRange, InAppChanges, ModifyOptions,
+    ModifyResult, Rule, RuleId, RuleProfile, UndoEntry,
+};
+
+/// Collects the information [`ModifyOptions`] asks for while modifications are applied.
+///
+/// The hooks are called in this order: [`start`](Self::start) once, then for every rule
+/// [`start_rule`](Self::start_rule), [`matched_exception`](Self::matched_exception),
+/// [`before_actions`](Self::before_actions) if the rule matched the exception, and
+/// [`finish_rule`](Self::finish_rule), and finally [`finish`](Self::finish) once.
+#[derive(Debug, Default)]
+pub(crate) struct Recorder {
+    /// The instant at which the time budget is exhausted.
+    deadline: Option<Instant>,
+    /// Whether the ids of the applied rules are needed.
+    track_rule_ids: bool,
+    /// The position in the list of all rules to search the next rule id from.
+    next_rule_id: usize,
+    /// The id of the current rule, if rule ids are tracked.
+    rule_id: Option<RuleId>,
+    /// The first rule that wasn't applied because the time budget was exhausted.
+    budget_exhausted_at: Option<RuleId>,
+    collect_stats: bool,
+    stats: Option<ApplyStats>,
+    profiler: Option<Profiler>,
+    in_app: Option<InAppRecorder>,
+    undo_log: Option<UndoRecorder>,
+}
+
+impl Recorder {
+    /// Creates a recorder for the information `options` asks for.
+    pub(crate) fn new(options: &ModifyOptions) -> Self {
+        let track_in_app = options.track_in_app_flips
+            || options.summarize_in_app_changes
+            || options.track_in_app_last_changed;
+        let deadline = deadline(options.time_budget);
+        Self {
+            deadline,
+            // Modifier rules are in the same order as all rules, so their ids can be found by
+            // searching forward from the previous one. Identical rules may share their `Arc`, so
+            // the search has to visit every rule to tell their copies apart.
+            track_rule_ids: deadline.is_some() || options.track_in_app_last_changed,
+            collect_stats: options.collect_stats,
+            profiler: options.profile_rules.then(Profiler::default),
+            in_app: track_in_app.then(|| InAppRecorder {
+                flips: options.track_in_app_flips.then(Vec::new),
+                last_changed: options.track_in_app_last_changed.then(Vec::new),
+                changes: options.summarize_in_app_changes.then(Vec::new),
+                ..Default::default()
+            }),
+            undo_log: options.record_undo_log.then(UndoRecorder::default),
+            ..Default::default()
+        }
+    }
+
+    /// Called before any modifications are made to `frames`.
+    ///
+    /// `candidates` of the `rules` are applied to the frames in `frame_range`.
+    pub(crate) fn start<F: FrameLike>(
+        &mut self,
+        frames: &[F],
+        rules: usize,
+        candidates: usize,
+        frame_range: FrameRange,
+    ) {
+        if self.collect_stats {
+            self.stats = Some(ApplyStats::new(rules, candidates, frame_range));
+        }
+        if let Some(in_app) = &mut self.in_app {
+            in_app.start(frames.len());
+        }
+        if let Some(undo_log) = &mut self.undo_log {
+            undo_log.start(frames);
+        }
+    }
+
+    /// Called before `rule` is applied, which must not happen if this returns
+    /// [`ControlFlow::Break`] because the time budget is exhausted.
+    pub(crate) fn start_rule(
+        &mut self,
+        enhancements: &Enhancements,
+        rule: &Rule,
+    ) -> ControlFlow<()> {
+        self.rule_id = self
+            .track_rule_ids
+            .then(|| enhancements.find_rule_id(rule, &mut self.next_rule_id))
+            .flatten();
+        if self.deadline.is_some_and(|deadline| now() >= deadline) {
+            self.budget_exhausted_at = self.rule_id;
+            return ControlFlow::Break(());
+        }
+        if let Some(profiler) = &mut self.profiler {
+            profiler.start_rule();
+        }
+        ControlFlow::Continue(())
+    }
+
+    /// Called with whether the current rule's exception matchers matched.
+    pub(crate) fn matched_exception(&mut self, matches: bool) {
+        if let Some(stats) = &mut self.stats {
+            stats.record(matches);
+        }
+    }
+
+    /// Called before the actions of the current rule are applied to the frames at `matching`.
+    pub(crate) fn before_actions<F: FrameLike>(&mut self, frames: &[F], matching: &[usize]) {
+        if let Some(in_app) = &mut self.in_app {
+            if !matching.is_empty() {
+                in_app.before_actions(frames);
+            }
+        }
+    }
+
+    /// Called after `rule` was applied, having matched `matched_frames` frames.
+    pub(crate) fn finish_rule<F: FrameLike>(
+        &mut self,
+        rule: &Rule,
+        frames: &[F],
+        matched_frames: usize,
+    ) {
+        if let Some(in_app) = &mut self.in_app {
+            in_app.after_actions(rule, self.rule_id, frames);
+        }
+        if let Some(profiler) = &mut self.profiler {
+            profiler.finish_rule(rule, matched_frames);
+        }
+    }
+
+    /// Moves the collected information into `result`, `frames` being the modified frames.
+    pub(crate) fn finish<F: FrameLike>(self, frames: &[F], result: &mut ModifyResult) {
+        result.budget_exhausted_at = self.budget_exhausted_at;
+        result.stats = self.stats;
+        if let Some(profiler) = self.profiler {
+            result.rule_profiles = profiler.finish();
+        }
+        if let Some(in_app) = self.in_app {
+            result.in_app_flips = in_app.flips.unwrap_or_default();
+            result.in_app_last_changed = in_app.last_changed.unwrap_or_default();
+            result.in_app_changes = in_app.changes.unwrap_or_default();
+        }
+        if let Some(undo_log) = self.undo_log {
+            result.undo_log = undo_log.finish(frames);
+        }
+    }
+}
+
+/// Measures the time spent on every rule.
+#[derive(Debug, Default)]
+struct Profiler {
+    /// When the current rule started being applied.
+    started: Option<Instant>,
+    profiles: Vec<RuleProfile>,
+}
+
+impl Profiler {
+    fn start_rule(&mut self) {
+        self.started = Some(Instant::now());
+    }
+
+    fn finish_rule(&mut self, rule: &Rule, matched_frames: usize) {
+        if let Some(started) = self.started.take() {
+            self.profiles.push(RuleProfile {
+                rule: rule.clone(),
+                elapsed: started.elapsed(),
+                matched_frames,
+            });
+        }
+    }
+
+    /// Returns the profiles, most expensive first.
+    fn finish(mut self) -> Vec<RuleProfile> {
+        // Sorting is stable, so equally expensive rules stay in the order they were applied.
+        self.profiles
+            .sort_by_key(|profile| std::cmp::Reverse(profile.elapsed));
+        self.profiles
+    }
+}
+
+/// Tracks how rules change the `in_app` flags of frames.
+#[derive(Debug, Default)]
+struct InAppRecorder {
+    /// The `in_app` flags of all frames before applying the current rule, or empty if the
+    /// rule didn't match any frame.
+    before: Vec<Option<bool>>,
+    /// For every frame, the number of rules that flipped its flag.
+    flips: Option<Vec<usize>>,
+    /// For every frame, the rule that last changed its flag.
+    last_changed: Option<Vec<Option<RuleId>>>,
+    /// The rules that changed the flag of at least one frame.
+    changes: Option<Vec<InAppChanges>>,
+}
+
+impl InAppRecorder {
+    fn start(&mut self, frames: usize) {
+        if let Some(flips) = &mut self.flips {
+            *flips = vec![0; frames];
+        }
+        if let Some(last_changed) = &mut self.last_changed {
+            *last_changed = vec![None; frames];
+        }
+    }
+
+    fn before_actions<F: FrameLike>(&mut self, frames: &[F]) {
+        self.before.clear();
+        self.before.extend(frames.iter().map(|f| f.in_app()));
+    }
+
+    fn after_actions<F: FrameLike>(&mut self, rule: &Rule, rule_id: Option<RuleId>, frames: &[F]) {
+        if self.before.is_empty() {
+            return;
+        }
+        let changes = self.before.iter().zip(frames).map(|(before, frame)| {
+            let after = frame.in_app();
+            (*before, after)
+        });
+
+        if let Some(flips) = &mut self.flips {
+            for (count, (before, after)) in flips.iter_mut().zip(changes.clone()) {
+                if before.is_some_and(|before| Some(!before) == after) {
+                    *count += 1;
+                }
+            }
+        }
+
+        if let Some(last_changed) = &mut self.last_changed {
+            for (last_changed, (before, after)) in last_changed.iter_mut().zip(changes.clone()) {
+                if before != after {
+                    *last_changed = rule_id;
+                }
+            }
+        }
+
+        if let Some(summary) = &mut self.changes {
+            let changed = changes.filter(|(before, after)| before != after).count();
+            if changed > 0 {
+                summary.push(InAppChanges {
+                    rule: rule.clone(),
+                    frames: changed,
+                });
+            }
+        }
+
+        self.before.clear();
+    }
+}
+
+/// Records the original values of the frame fields that rules modify.
+#[derive(Debug, Default)]
+struct UndoRecorder {
+    /// The `in_app` flag, category, and `has_vars` flag of every frame.
+    original: Vec<(Option<bool>, Option<StringField>, Option<bool>)>,
+}
+
+impl UndoRecorder {
+    fn start<F: FrameLike>(&mut self, frames: &[F]) {
+        self.original = frames
+            .iter()
+            .map(|f| {
+                let category = f.get_field(FrameField::Category).map(StringField::new);
+                (f.in_app(), category, f.has_vars())
+            })
+            .collect();
+    }
+
+    /// Returns the entries for all fields of `frames` that differ from their original values.
+    fn finish<F: FrameLike>(self, frames: &[F]) -> Vec<UndoEntry> {
+        let mut undo_log = Vec::new();
+        for (idx, (frame, (in_app, category, has_vars))) in
+            frames.iter().zip(self.original).enumerate()
+        {
+            if frame.in_app() != in_app {
+                undo_log.push(UndoEntry {
+                    frame: idx,
+                    old_value: FieldValue::InApp(in_app),
+                });
+            }
+            if frame.get_field(FrameField::Category) != category.as_deref() {
+                undo_log.push(UndoEntry {
+                    frame: idx,
+                    old_value: FieldValue::Category(category),
+                });
+            }
+            if frame.has_vars() != has_vars {
+                undo_log.push(UndoEntry {
+                    frame: idx,
+                    old_value: FieldValue::HasVars(has_vars),
+                });
+            }
+        }
+        undo_log
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::enhancers::{Cache, Frame, FrameLikeMut};
+
+    fn frames(in_app: &[Option<bool>]) -> Vec<Frame> {
+        in_app
+            .iter()
+            .map(|in_app| Frame {
+                in_app: *in_app,
+                ..Default::default()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn records_in_app_changes() {
+        let enhancements = Enhancements::parse("function:foo +app", &mut Cache::default()).unwrap();
+        let rule = enhancements.rules().next().unwrap();
+        let rule_id = RuleId::new(0);
+
+        let mut recorder = InAppRecorder {
+            flips: Some(Vec::new()),
+            last_changed: Some(Vec::new()),
+            changes: Some(Vec::new()),
+            ..Default::default()
+        };
+        let mut frames = frames(&[Some(false), None, Some(true)]);
+        recorder.start(frames.len());
+
+        recorder.before_actions(&frames);
+        for frame in &mut frames {
+            frame.set_in_app(Some(true));
+        }
+        recorder.after_actions(rule, rule_id, &frames);
+
+        // A rule that didn't match any frame is not recorded.
+        recorder.after_actions(rule, None, &frames);
+
+        assert_eq!(recorder.flips.unwrap(), [1, 0, 0]);
+        assert_eq!(recorder.last_changed.unwrap(), [rule_id, rule_id, None]);
+        let changes = recorder.changes.unwrap();
+        assert_eq!(changes.len(), 1);
+        assert_eq!(changes[0].frames, 2);
+    }
+
+    #[test]
+    fn records_undo_log() {
+        let mut frames = frames(&[Some(false), None]);
+        let mut recorder = UndoRecorder::default();
+        recorder.start(&frames);
+
+        frames[0].set_in_app(Some(true));
+        frames[1].set_category(Some("foo".into()));
+        frames[1].set_has_vars(Some(false));
+
+        assert_eq!(
+            recorder.finish(&frames),
+            [
+                UndoEntry {
+                    frame: 0,
+                    old_value: FieldValue::InApp(Some(false)),
+                },
+                UndoEntry {
+                    frame: 1,
+                    old_value: FieldValue::Category(None),
+                },
+                UndoEntry {
+                    frame: 1,
+                    old_value: FieldValue::HasVars(None),
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn records_nothing_by_default() {
+        let enhancements = Enhancements::parse("function:foo +app", &mut Cache::default()).unwrap();
+        let rule = enhancements.rules().next().unwrap();
+        let frames = frames(&[None]);
+
+        let mut recorder = Recorder::new(&ModifyOptions::default());
+        recorder.start(&frames, 1, 1, FrameRange::new(frames.len(), None));
+        assert!(recorder.start_rule(&enhancements, rule).is_continue());
+        recorder.matched_exception(true);
+        recorder.before_actions(&frames, &[0]);
+        recorder.finish_rule(rule, &frames, 1);
+
+        let mut result = ModifyResult::default();
+        recorder.finish(&frames, &mut result);
+        assert!(result.stats.is_none());
+        assert!(result.rule_profiles.is_empty());
+        assert!(result.in_app_flips.is_empty());
+        assert!(result.undo_log.is_empty());
+    }
+}
@@ -0,0 +1,150 @@
+//! Memoization of compiled per-project enhancements.
+//!
+//! Projects typically use one of a handful of base configurations, extended with
+//! a small amount of custom rules. Since the same project sends many events, the
+//! merged [`Enhancements`] are memoized keyed by the base name and a hash of the
+//! custom rules.
+
+use std::collections::hash_map::DefaultHasher;
+use std::collections::HashMap;
+use std::hash::{Hash, Hasher};
+use std::sync::Arc;
+
+use lru::LruCache;
+use smol_str::SmolStr;
+
+use super::{Cache, Enhancements};
+use crate::{Error, Result};
+
+/// A registry of named base [`Enhancements`] that memoizes their combinations with custom rules.
+#[derive(Debug)]
+pub struct EnhancementsRegistry {
+    /// The registered base configurations.
+    bases: HashMap<SmolStr, Arc<Enhancements>>,
+    /// The memoized merged configurations, keyed by base name and hash of the custom rules.
+    compiled: Option<LruCache<(SmolStr, u64), Arc<Enhancements>>>,
+}
+
+impl EnhancementsRegistry {
+    /// Creates a new registry that memoizes up to `size` merged configurations.
+    ///
+    /// If `size` is 0, no memoization will be performed.
+    pub fn new(size: usize) -> Self {
+        Self {
+            bases: HashMap::new(),
+            compiled: size.try_into().ok().map(LruCache::new),
+        }
+    }
+
+    /// Registers `enhancements` as the base configuration called `name`.
+    ///
+    /// This replaces any previous base of the same name and invalidates all
+    /// memoized configurations derived from it.
+    pub fn register_base(&mut self, name: &str, enhancements: Arc<Enhancements>) {
+        let name = SmolStr::new(name);
+        if let Some(compiled) = self.compiled.as_mut() {
+            let stale: Vec<_> = compiled
+                .iter()
+                .filter(|((base, _), _)| *base == name)
+                .map(|(key, _)| key.clone())
+                .collect();
+            for key in stale {
+                compiled.pop(&key);
+            }
+        }
+        self.bases.insert(name, enhancements);
+    }
+
+    /// Returns the names of all registered base configurations, in no particular order.
+    pub fn base_names(&self) -> impl Iterator<Item = &str> {
+        self.bases.keys().map(SmolStr::as_str)
+    }
+
+    /// Returns the base configuration called `name`, if one is registered.
+    pub fn base(&self, name: &str) -> Option<&Arc<Enhancements>> {
+        self.bases.get(name)
+    }
+
+    /// Returns the combination of the base configuration `base` and the
+    /// `custom` rules, parsing and memoizing it if necessary.
+    ///
+    /// The custom rules are appended after the base rules, so they take precedence.
+    pub fn get_or_compile(
+        &mut self,
+        base: &str,
+        custom: &str,
+        cache: &mut Cache,
+    ) -> Result<Arc<Enhancements>> {
+        let Some(base_enhancements) = self.bases.get(base) else {
+            return Err(Error::UnknownBase(base.into()));
+        };
+
+        let key = (SmolStr::new(base), hash_rules(custom));
+        if let Some(enhancements) = self.compiled.as_mut().and_then(|c| c.get(&key)) {
+            return Ok(Arc::clone(enhancements));
+        }
+
+        let custom = Enhancements::parse(custom, cache)?;
+        let rules = base_enhancements.rules().chain(custom.rules()).cloned();
+        let enhancements = Arc::new(Enhancements::new(rules.collect()));
+
+        if let Some(compiled) = self.compiled.as_mut() {
+            compiled.put(key, Arc::clone(&enhancements));
+        }
+
+        Ok(enhancements)
+    }
+}
+
+/// Hashes the text of custom rules.
+fn hash_rules(rules: &str) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    rules.hash(&mut hasher);
+    hasher.finish()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn memoizes_merged_enhancements() {
+        let mut cache = Cache::default();
+        let mut registry = EnhancementsRegistry::new(10);
+
+        let base = Enhancements::parse("function:foo -app", &mut cache).unwrap();
+        registry.register_base("base", Arc::new(base));
+
+        let first = registry
+            .get_or_compile("base", "function:bar +app", &mut cache)
+            .unwrap();
+        let rules: Vec<_> = first.rules().map(|r| r.to_string()).collect();
+        assert_eq!(rules, ["function:foo -app", "function:bar +app"]);
+
+        let second = registry
+            .get_or_compile("base", "function:bar +app", &mut cache)
+            .unwrap();
+        assert!(Arc::ptr_eq(&first, &second));
+
+        let other = registry
+            .get_or_compile("base", "function:baz +app", &mut cache)
+            .unwrap();
+        assert!(!Arc::ptr_eq(&first, &other));
+
+        // Re-registering the base invalidates derived configurations.
+        let base = Enhancements::parse("function:qux -app", &mut cache).unwrap();
+        registry.register_base("base", Arc::new(base));
+        let third = registry
+            .get_or_compile("base", "function:bar +app", &mut cache)
+            .unwrap();
+        assert_eq!(
+            third.rules().next().unwrap().to_string(),
+            "function:qux -app"
+        );
+
+        assert!(matches!(
+            registry.get_or_compile("unknown", "", &mut cache),
+            Err(Error::UnknownBase(_))
+        ));
+    }
+}
@@ -0,0 +1,305 @@
+//! Memoizing the modifications of identical stack traces.
+//!
+//! Many events contain the very same stack trace, e.g. when an app crashes the same way on many
+//! devices. Applying the modifier rules to such a stack trace always has the same result, so a
+//! [`ResultCache`] remembers the changes they made, keyed by a fingerprint of the rules, the
+//! frames, and the exception data. On an exact repeat, the changes are applied without
+//! matching a single rule.
+
+use std::hash::{BuildHasher, Hash, Hasher, RandomState};
+
+use super::bounded::BoundedCache;
+use super::changes::Change;
+use super::{Enhancements, ExceptionData, FrameField, FrameLike, FrameLikeMut, StringField};
+
+/// The frame fields that rules read, besides the flags.
+const FINGERPRINT_FIELDS: [FrameField; 6] = [
+    FrameField::Category,
+    FrameField::Function,
+    FrameField::Module,
+    FrameField::Package,
+    FrameField::Path,
+    FrameField::Trust,
+];
+
+/// The changes to the frames of a stack trace, as pairs of frame indices and changes.
+type FrameChanges = Box<[(usize, Change)]>;
+
+/// An LRU cache of the changes [`Enhancements`] made to stack traces.
+///
+/// Entries are keyed by a 128-bit hash of everything rules can match on, computed with random
+/// keys that differ between caches. A single cache can be used with any number of
+/// `Enhancements`.
+#[derive(Debug)]
+pub struct ResultCache {
+    entries: Option<BoundedCache<u128, FrameChanges>>,
+    hashers: (RandomState, RandomState),
+    hits: u64,
+    misses: u64,
+}
+
+/// How often a [`ResultCache`] was able to skip applying the rules.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ResultCacheStats {
+    /// The number of stack traces whose changes were cached.
+    pub hits: u64,
+    /// The number of stack traces the rules had to be applied to.
+    pub misses: u64,
+    /// The number of stack traces currently in the cache.
+    pub entries: usize,
+}
+
+impl ResultCacheStats {
+    /// Returns the share of stack traces whose changes were cached, between 0 and 1.
+    ///
+    /// This is 0 if the cache wasn't used yet.
+    pub fn hit_rate(&self) -> f64 {
+        let lookups = self.hits + self.misses;
+        if lookups == 0 {
+            return 0.0;
+        }
+        self.hits as f64 / lookups as f64
+    }
+}
+
+impl ResultCache {
+    /// Creates a cache holding the changes to up to `size` stack traces.
+    ///
+    /// If `size` is 0, no caching will be performed, but lookups are still counted.
+    pub fn new(size: usize) -> Self {
+        Self {
+            entries: size.try_into().ok().map(BoundedCache::new),
+            hashers: (RandomState::new(), RandomState::new()),
+            hits: 0,
+            misses: 0,
+        }
+    }
+
+    /// Returns the number of hits and misses since this cache was created, and its size.
+    pub fn stats(&self) -> ResultCacheStats {
+        ResultCacheStats {
+            hits: self.hits,
+            misses: self.misses,
+            entries: self.entries.as_ref().map_or(0, BoundedCache::len),
+        }
+    }
+
+    /// Computes the key of applying `enhancements` to `frames` and `exception_data`.
+    fn key<F: FrameLike>(
+        &self,
+        enhancements: &Enhancements,
+        frames: &[F],
+        exception_data: &ExceptionData,
+    ) -> u128 {
+        let mut hasher = WideHasher(self.hashers.0.build_hasher(), self.hashers.1.build_hasher());
+
+        enhancements.modifier_rules.fingerprint().hash(&mut hasher);
+        frames.len().hash(&mut hasher);
+        for frame in frames {
+            for field in FINGERPRINT_FIELDS {
+                frame.get_field(field).hash(&mut hasher);
+            }
+            frame.family().hash(&mut hasher);
+            frame.raw_family().hash(&mut hasher);
+            frame.raw_function().hash(&mut hasher);
+            frame.in_app().hash(&mut hasher);
+            frame.orig_in_app().hash(&mut hasher);
+            frame.has_vars().hash(&mut hasher);
+        }
+        let ExceptionData {
+            ty,
+            module,
+            value,
+            mechanism,
+            thread,
+        } = exception_data;
+        for field in [ty, module, value, mechanism] {
+            field.as_deref().hash(&mut hasher);
+        }
+        thread.hash(&mut hasher);
+
+        hasher.finish_wide()
+    }
+}
+
+/// A hasher feeding two independently keyed hashers, to get a 128-bit hash.
+struct WideHasher<H>(H, H);
+
+impl<H: Hasher> WideHasher<H> {
+    fn finish_wide(&self) -> u128 {
+        u128::from(self.0.finish()) << 64 | u128::from(self.1.finish())
+    }
+}
+
+impl<H: Hasher> Hasher for WideHasher<H> {
+    fn write(&mut self, bytes: &[u8]) {
+        self.0.write(bytes);
+        self.1.write(bytes);
+    }
+
+    fn finish(&self) -> u64 {
+        self.0.finish()
+    }
+}
+
+impl Enhancements {
+    /// Applies modifications to `frames` like
+    /// [`apply_modifications_to_frames`](Self::apply_modifications_to_frames), reusing the
+    /// changes memoized in `cache` if the same rules were already applied to identical frames
+    /// and exception data.
+    pub fn apply_modifications_cached<F: FrameLikeMut>(
+        &self,
+        frames: &mut [F],
+        exception_data: &ExceptionData,
+        cache: &mut ResultCache,
+    ) {
+        let key = cache.key(self, frames, exception_data);
+        if let Some(changes) = cache.entries.as_mut().and_then(|e| e.get(&key)) {
+            for (idx, change) in changes.iter() {
+                change.clone().apply(&mut frames[*idx]);
+            }
+            cache.hits += 1;
+            return;
+        }
+        cache.misses += 1;
+
+        let original: Vec<_> = frames
+            .iter()
+            .map(|f| {
+                let category = f.get_field(FrameField::Category).map(StringField::new);
+                (f.in_app(), category, f.has_vars())
+            })
+            .collect();
+
+        self.apply_modifications_to_frames(frames, exception_data);
+
+        let Some(entries) = &mut cache.entries else {
+            return;
+        };
+        let mut changes = Vec::new();
+        for (idx, (frame, (in_app, category, has_vars))) in frames.iter().zip(original).enumerate()
+        {
+            if frame.in_app() != in_app {
+                changes.push((idx, Change::InApp(frame.in_app())));
+            }
+            let new_category = frame.get_field(FrameField::Category);
+            if new_category != category.as_deref() {
+                changes.push((idx, Change::Category(new_category.map(StringField::new))));
+            }
+            if frame.has_vars() != has_vars {
+                changes.push((idx, Change::HasVars(frame.has_vars())));
+            }
+        }
+        entries.put(key, changes.into());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+    use crate::enhancers::{Cache, Frame};
+
+    fn frames() -> Vec<Frame> {
+        vec![
+            Frame::from_test(&json!({"function": "foo"}), "native"),
+            Frame::from_test(&json!({"function": "bar", "in_app": true}), "native"),
+            Frame::from_test(&json!({"function": "baz"}), "native"),
+        ]
+    }
+
+    fn modified_fields(frames: &[Frame]) -> Vec<(Option<bool>, Option<&str>, Option<bool>)> {
+        frames
+            .iter()
+            .map(|f| (f.in_app, f.category.as_deref(), f.has_vars))
+            .collect()
+    }
+
+    #[test]
+    fn memoizes_changes() {
+        let mut cache = Cache::default();
+        let enhancements = Enhancements::parse(
+            "function:foo +app category=main\nfunction:bar -app\nfunction:baz -vars",
+            &mut cache,
+        )
+        .unwrap();
+        let exception_data = ExceptionData::default();
+
+        let mut expected = frames();
+        enhancements.apply_modifications_to_frames(&mut expected, &exception_data);
+
+        let mut results = ResultCache::new(10);
+        for _ in 0..3 {
+            let mut frames = frames();
+            enhancements.apply_modifications_cached(&mut frames, &exception_data, &mut results);
+            assert_eq!(modified_fields(&frames), modified_fields(&expected));
+        }
+        let stats = results.stats();
+        assert_eq!((stats.hits, stats.misses, stats.entries), (2, 1, 1));
+        assert!((stats.hit_rate() - 2.0 / 3.0).abs() < f64::EPSILON);
+
+        // Different frames, exception data, or rules make for different keys.
+        let mut other = frames();
+        other[2].in_app = Some(true);
+        enhancements.apply_modifications_cached(&mut other, &exception_data, &mut results);
+        let exception_data = ExceptionData {
+            ty: Some("Panic".into()),
+            ..Default::default()
+        };
+        enhancements.apply_modifications_cached(&mut frames(), &exception_data, &mut results);
+        let other_rules = Enhancements::parse("function:foo +app", &mut cache).unwrap();
+        let mut other = frames();
+        other_rules.apply_modifications_cached(&mut other, &exception_data, &mut results);
+        assert_eq!(
+            modified_fields(&other),
+            [
+                (Some(true), None, None),
+                (Some(true), None, None),
+                (None, None, None)
+            ]
+        );
+        assert_eq!(results.stats().misses, 4);
+
+        // Equal rules that were parsed separately share their entries.
+        let reparsed = Enhancements::parse("function:foo +app", &mut Cache::new(0)).unwrap();
+        let mut other = frames();
+        reparsed.apply_modifications_cached(&mut other, &exception_data, &mut results);
+        assert_eq!(modified_fields(&other)[0], (Some(true), None, None));
+        assert_eq!(results.stats().hits, 3);
+    }
+
+    #[test]
+    fn distinguishes_unknown_families() {
+        let mut results = ResultCache::new(10);
+        let exception_data = ExceptionData::default();
+        for (rules, in_app) in [("family:foo -app", Some(false)), ("family:bar -app", None)] {
+            let enhancements = Enhancements::parse(rules, &mut Cache::default()).unwrap();
+            let mut frames = vec![Frame::from_test(&json!({"function": "foo"}), "foo")];
+            enhancements.apply_modifications_cached(&mut frames, &exception_data, &mut results);
+            assert_eq!(frames[0].in_app, in_app, "{rules}");
+        }
+        assert_eq!(results.stats().misses, 2);
+    }
+
+    #[test]
+    fn counts_without_caching() {
+        let enhancements = Enhancements::parse("function:foo +app", &mut Cache::default()).unwrap();
+        let mut results = ResultCache::new(0);
+        for _ in 0..2 {
+            let mut frames = frames();
+            let exception_data = ExceptionData::default();
+            enhancements.apply_modifications_cached(&mut frames, &exception_data, &mut results);
+            assert_eq!(frames[0].in_app, Some(true));
+        }
+        assert_eq!(
+            results.stats(),
+            ResultCacheStats {
+                hits: 0,
+                misses: 2,
+                entries: 0,
+            }
+        );
+        assert_eq!(results.stats().hit_rate(), 0.0);
+    }
+}
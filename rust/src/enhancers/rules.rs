@@ -1,13 +1,45 @@
 //! Enhancement rules, which can match frames and exceptions according to the matchers they contain
 //! and perform actions on matching frames.
 
+use std::collections::hash_map::DefaultHasher;
 use std::fmt;
+use std::hash::{Hash, Hasher};
 use std::sync::Arc;
 
-use super::actions::Action;
-use super::frame::Frame;
-use super::matchers::{ExceptionMatcher, FrameMatcher, Matcher};
-use super::{Component, ExceptionData, StacktraceState};
+use super::actions::{Action, FlagAction, FlagActionType, FramePriorities, VarAction, VarKind};
+use super::cache::validate_glob;
+use super::description::RuleDescription;
+use super::families::Families;
+use super::frame::{FrameField, FrameLike, FrameLikeMut};
+use super::matchers::{
+    ExceptionMatcher, FrameMatcher, FrameOffset, MatchMemo, MatchOptions, Matcher,
+};
+use super::warnings::ParseWarning;
+use super::{Component, ExceptionData, MissingValuePolicy, ParseOptions, StacktraceState};
+
+/// The id of a [`Rule`] within an [`Enhancements`](super::Enhancements) collection.
+///
+/// Unlike a [`Rule`], an id is a plain number that is cheap to store and can be serialized.
+/// The rule it refers to can be looked up with [`Enhancements::rule`](super::Enhancements::rule).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(transparent)
+)]
+pub struct RuleId(u32);
+
+impl RuleId {
+    /// Creates the id of the rule at position `index`, or `None` if it doesn't fit into an id.
+    pub fn new(index: usize) -> Option<Self> {
+        u32::try_from(index).ok().map(Self)
+    }
+
+    /// Returns the position of the rule in its collection.
+    pub fn index(self) -> usize {
+        self.0 as usize
+    }
+}
 
 /// An enhancement rule, comprising exception matchers, frame matchers, and actions.
 #[derive(Debug, Clone)]
@@ -24,37 +56,87 @@ pub struct RuleInner {
     pub actions: Vec<Action>,
 }
 
+/// The alternate form (`{:#}`) of a rule can be parsed back into an equal rule.
 impl fmt::Display for Rule {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        let mut first = true;
-        for m in &self.0.exception_matchers {
-            if !first {
-                write!(f, " ")?;
-            }
-            write!(f, "{m}")?;
-            first = false;
-        }
+        let frame_matchers = |offset| {
+            self.0
+                .frame_matchers
+                .iter()
+                .filter(move |m| m.frame_offset() == offset)
+                .map(|m| m as &dyn fmt::Display)
+        };
+        let exception_matchers = self
+            .0
+            .exception_matchers
+            .iter()
+            .map(|m| m as &dyn fmt::Display);
 
-        for m in &self.0.frame_matchers {
-            if !first {
-                write!(f, " ")?;
-            }
-            write!(f, "{m}")?;
-            first = false;
+        let mut parts: Vec<&dyn fmt::Display> = Vec::new();
+        if f.alternate() {
+            // The grammar expects the caller matcher first and the callee matcher last.
+            parts.extend(frame_matchers(FrameOffset::Caller));
+            parts.extend(exception_matchers);
+            parts.extend(frame_matchers(FrameOffset::None));
+            parts.extend(frame_matchers(FrameOffset::Callee));
+        } else {
+            parts.extend(exception_matchers);
+            parts.extend(self.0.frame_matchers.iter().map(|m| m as &dyn fmt::Display));
         }
+        parts.extend(self.0.actions.iter().map(|a| a as &dyn fmt::Display));
 
-        for a in &self.0.actions {
-            if !first {
+        for (i, part) in parts.into_iter().enumerate() {
+            if i > 0 {
                 write!(f, " ")?;
             }
-            write!(f, "{a}")?;
-            first = false;
+            part.fmt(f)?;
         }
 
         Ok(())
     }
 }
 
+/// Rules are equal if they have the same matchers, in any order, and the same actions in the same order.
+///
+/// Since a rule only matches if all of its matchers do, their order has no effect,
+/// whereas later actions may override earlier ones.
+impl PartialEq for Rule {
+    fn eq(&self, other: &Self) -> bool {
+        Arc::ptr_eq(&self.0, &other.0)
+            || (self.0.actions == other.0.actions
+                && same_elements(&self.0.frame_matchers, &other.0.frame_matchers)
+                && same_elements(&self.0.exception_matchers, &other.0.exception_matchers))
+    }
+}
+
+impl Eq for Rule {}
+
+impl Hash for Rule {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        unordered_hash(&self.0.frame_matchers).hash(state);
+        unordered_hash(&self.0.exception_matchers).hash(state);
+        self.0.actions.hash(state);
+    }
+}
+
+/// Checks whether `a` and `b` contain the same elements with the same multiplicities, in any order.
+fn same_elements<T: PartialEq>(a: &[T], b: &[T]) -> bool {
+    let count = |items: &[T], item: &T| items.iter().filter(|i| *i == item).count();
+    a.len() == b.len() && a.iter().all(|item| count(a, item) == count(b, item))
+}
+
+/// Combines the hashes of `items` independently of their order.
+fn unordered_hash<T: Hash>(items: &[T]) -> u64 {
+    items
+        .iter()
+        .map(|item| {
+            let mut hasher = DefaultHasher::new();
+            item.hash(&mut hasher);
+            hasher.finish()
+        })
+        .fold(0, u64::wrapping_add)
+}
+
 impl Rule {
     /// Creates a `Rule` from a vector of [`Matchers`](Matcher) and a vector of [`Actions`](Action).
     ///
@@ -76,6 +158,34 @@ impl Rule {
         }))
     }
 
+    /// Returns this rule with all matchers treating missing values according to `policy`.
+    ///
+    /// This returns a clone of `self` if its matchers already follow `policy`.
+    pub(crate) fn with_missing_values(self, policy: MissingValuePolicy) -> Self {
+        let follows_policy = self.0.frame_matchers.iter().all(|m| {
+            m.missing_value() == policy.frames
+                && m.unset_in_app_is_false()
+                    .is_none_or(|v| v == policy.unset_in_app_is_false)
+        }) && self
+            .0
+            .exception_matchers
+            .iter()
+            .all(|m| m.missing_value() == policy.exceptions);
+        if follows_policy {
+            return self;
+        }
+
+        let mut inner = RuleInner::clone(&self.0);
+        for matcher in &mut inner.frame_matchers {
+            matcher.set_missing_value(policy.frames);
+            matcher.set_unset_in_app_is_false(policy.unset_in_app_is_false);
+        }
+        for matcher in &mut inner.exception_matchers {
+            matcher.set_missing_value(policy.exceptions);
+        }
+        Self(Arc::new(inner))
+    }
+
     /// Checks whether an exception matches this rule, i.e., if it matches all exception matchers.
     ///
     /// This defaults to `true` if no exception matcher exists.
@@ -89,11 +199,91 @@ impl Rule {
     /// Checks whether the frame at `frames[idx]` matches this rule, i.e., if it matches all frame matchers.
     ///
     /// This defaults to `true` if no frame matcher exists.
-    pub fn matches_frame(&self, frames: &[Frame], idx: usize) -> bool {
+    pub fn matches_frame<F: FrameLike>(&self, frames: &[F], idx: usize) -> bool {
+        self.matches_frame_with_options(frames, idx, MatchOptions::default())
+    }
+
+    /// Checks whether the frame at `frames[idx]` matches this rule, like
+    /// [`matches_frame`](Self::matches_frame), using the given [`MatchOptions`].
+    pub(crate) fn matches_frame_with_options<F: FrameLike>(
+        &self,
+        frames: &[F],
+        idx: usize,
+        options: MatchOptions,
+    ) -> bool {
+        self.0
+            .frame_matchers
+            .iter()
+            .all(|m| m.matches_frame(frames, idx, options))
+    }
+
+    /// Checks whether the frame at `frames[idx]` matches this rule, like
+    /// [`matches_frame`](Self::matches_frame), reusing results of other rules stored in `memo`.
+    pub(crate) fn matches_frame_memoized<F: FrameLike>(
+        &self,
+        frames: &[F],
+        idx: usize,
+        memo: &mut MatchMemo,
+    ) -> bool {
+        self.0
+            .frame_matchers
+            .iter()
+            .all(|m| m.matches_frame_memoized(frames, idx, memo))
+    }
+
+    /// Returns the frame families this rule can possibly match.
+    ///
+    /// This is the intersection of the families of all non-negated `family` matchers.
+    pub fn families(&self) -> Families {
         self.0
             .frame_matchers
             .iter()
-            .all(|m| m.matches_frame(frames, idx))
+            .filter_map(|m| m.required_families())
+            .fold(Families::ALL, Families::intersection)
+    }
+
+    /// Returns warnings about the matchers of this rule that can never match.
+    ///
+    /// See [`Enhancements::warnings`](super::Enhancements::warnings).
+    pub fn warnings(&self) -> Vec<ParseWarning> {
+        (self.0.frame_matchers.iter())
+            .filter_map(|matcher| {
+                let (kind, message) = matcher.warning()?;
+                Some(ParseWarning {
+                    rule: self.to_string(),
+                    matcher: matcher.to_string(),
+                    kind,
+                    message,
+                })
+            })
+            .collect()
+    }
+
+    /// Returns a structured description of this rule.
+    pub fn describe(&self) -> RuleDescription {
+        let frame_matchers = |offset| {
+            self.0
+                .frame_matchers
+                .iter()
+                .filter(move |m| m.frame_offset() == offset)
+                .map(FrameMatcher::describe)
+        };
+
+        let mut matchers: Vec<_> = frame_matchers(FrameOffset::Caller).collect();
+        matchers.extend(
+            self.0
+                .exception_matchers
+                .iter()
+                .map(ExceptionMatcher::describe),
+        );
+        matchers.extend(frame_matchers(FrameOffset::None));
+        matchers.extend(frame_matchers(FrameOffset::Callee));
+
+        RuleDescription {
+            text: self.to_string(),
+            matchers,
+            actions: self.0.actions.iter().map(Action::describe).collect(),
+        }
     }
 
     /// Returns true if this rule contains any actions that may modify the contents of frames.
@@ -106,6 +296,122 @@ impl Rule {
         self.0.actions.iter().any(|a| a.is_updater())
     }
 
+    /// Returns the glob patterns of this rule's matchers, each with whether it is a path
+    /// pattern and whether it consists of comma-separated alternatives.
+    pub(crate) fn globs(&self) -> impl Iterator<Item = (&str, bool, bool)> {
+        let frame_globs = self.0.frame_matchers.iter().filter_map(FrameMatcher::glob);
+        let exception_globs =
+            (self.0.exception_matchers.iter()).map(|m| (m.pattern(), false, false));
+        frame_globs.chain(exception_globs)
+    }
+
+    /// Checks whether this rule satisfies the restrictions imposed by `options`.
+    pub fn validate(&self, options: &ParseOptions) -> crate::Result<()> {
+        for (pattern, is_path, alternatives) in self.globs() {
+            validate_glob(pattern, is_path, alternatives, &options.glob)
+                .map_err(crate::Error::Parse)?;
+        }
+
+        for action in &self.0.actions {
+            if let Action::Var(VarAction::Category(category)) = action {
+                if !options.is_category_allowed(category) {
+                    return Err(crate::Error::Parse(anyhow::anyhow!(
+                        "unknown category `{category}`"
+                    )));
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Returns true if this rule contains a flag action setting the flag `ty`.
+    fn has_flag_action(&self, ty: FlagActionType) -> bool {
+        self.0
+            .actions
+            .iter()
+            .any(|a| matches!(a, Action::Flag(action) if action.ty == ty))
+    }
+
+    /// Returns true if this rule contains a frame matcher checking `field`.
+    pub(crate) fn reads_field(&self, field: FrameField) -> bool {
+        self.0.frame_matchers.iter().any(|m| m.reads_field(field))
+    }
+
+    /// Returns the frame fields modified by this rule's actions.
+    pub(crate) fn modified_fields(&self) -> impl Iterator<Item = FrameField> + '_ {
+        self.0.actions.iter().filter_map(|action| match action {
+            Action::Flag(FlagAction {
+                ty: FlagActionType::App,
+                ..
+            }) => Some(FrameField::App),
+            Action::Flag(FlagAction {
+                ty: FlagActionType::Vars,
+                ..
+            }) => Some(FrameField::HasVars),
+            Action::Var(VarAction::Category(_)) => Some(FrameField::Category),
+            _ => None,
+        })
+    }
+
+    /// Returns true if the outcome of applying this rule may depend on whether `other`
+    /// was applied before it.
+    ///
+    /// This is the case if this rule matches on frame fields that `other` modifies, or if
+    /// the hints this rule writes depend on the components' `contributes` flags and vice versa.
+    pub(crate) fn observes_effects_of(&self, other: &Rule) -> bool {
+        other.0.actions.iter().any(|action| match action {
+            Action::Flag(FlagAction {
+                ty: FlagActionType::App,
+                ..
+            }) => self.reads_field(FrameField::App) || self.has_flag_action(FlagActionType::Group),
+            Action::Flag(FlagAction {
+                ty: FlagActionType::Group,
+                ..
+            }) => self.has_flag_action(FlagActionType::App),
+            Action::Flag(FlagAction {
+                ty: FlagActionType::Vars,
+                ..
+            }) => self.reads_field(FrameField::HasVars),
+            Action::Var(VarAction::Category(_)) => self.reads_field(FrameField::Category),
+            Action::Var(_) => false,
+        })
+    }
+
+    /// Returns the priority of this rule, as set by its last `priority` action.
+    ///
+    /// Rules without a `priority` action have a priority of `0`. When applying
+    /// modifications, a rule does not overwrite the `in_app` flag or the category
+    /// of a frame that was set by a rule with a higher priority, even if that rule
+    /// came earlier.
+    pub fn priority(&self) -> usize {
+        self.0
+            .actions
+            .iter()
+            .rev()
+            .find_map(|a| match a {
+                Action::Var(VarAction::Priority(priority)) => Some(*priority),
+                _ => None,
+            })
+            .unwrap_or_default()
+    }
+
+    /// Returns true if this rule contains an action setting the variable `kind`.
+    pub fn sets_var(&self, kind: VarKind) -> bool {
+        self.0
+            .actions
+            .iter()
+            .any(|a| matches!(a, Action::Var(action) if action.kind() == kind))
+    }
+
+    /// Returns true if this rule contains a `category` action.
+    pub fn has_category_action(&self) -> bool {
+        self.0
+            .actions
+            .iter()
+            .any(|a| matches!(a, Action::Var(VarAction::Category(_))))
+    }
+
     /// Modifies a [`StacktraceState`] according to the actions contained in this rule.
     pub fn modify_stacktrace_state(&self, state: &mut StacktraceState) {
         for a in &self.0.actions {
@@ -114,17 +420,67 @@ impl Rule {
     }
 
     /// Applies all modifications from this rule's actions to `frames` at the index `idx`.
-    pub fn apply_modifications_to_frame(&self, frames: &mut [Frame], idx: usize) {
+    ///
+    /// Returns whether the value of any frame field changed.
+    pub fn apply_modifications_to_frame<F: FrameLikeMut>(
+        &self,
+        frames: &mut [F],
+        idx: usize,
+    ) -> bool {
+        let mut changed = false;
         for action in &self.0.actions {
-            action.apply_modifications_to_frame(frames, idx)
+            changed |= action.apply_modifications_to_frame(frames, idx);
+        }
+        changed
+    }
+
+    /// Applies all modifications from this rule's actions to `frames` at the index `idx`,
+    /// respecting the [priorities](Self::priority) of the rules that were applied before.
+    pub(crate) fn apply_modifications_with_priorities<F: FrameLikeMut>(
+        &self,
+        frames: &mut [F],
+        idx: usize,
+        priorities: &mut FramePriorities,
+    ) -> bool {
+        let priority = self.priority();
+        let mut changed = false;
+        for action in &self.0.actions {
+            changed |=
+                action.apply_modifications_with_priority(frames, idx, Some((priority, priorities)));
+        }
+        changed
+    }
+
+    /// Applies only the `category` actions of this rule to `frames` at the index `idx`.
+    pub fn apply_categories_to_frame<F: FrameLikeMut>(&self, frames: &mut [F], idx: usize) {
+        for action in &self.0.actions {
+            if matches!(action, Action::Var(VarAction::Category(_))) {
+                action.apply_modifications_to_frame(frames, idx);
+            }
+        }
+    }
+
+    /// Applies only the `category` actions of this rule like [`apply_categories_to_frame`](Self::apply_categories_to_frame),
+    /// respecting the [priorities](Self::priority) of the rules that were applied before.
+    pub(crate) fn apply_categories_with_priorities<F: FrameLikeMut>(
+        &self,
+        frames: &mut [F],
+        idx: usize,
+        priorities: &mut FramePriorities,
+    ) {
+        let priority = self.priority();
+        for action in &self.0.actions {
+            if matches!(action, Action::Var(VarAction::Category(_))) {
+                action.apply_modifications_with_priority(frames, idx, Some((priority, priorities)));
+            }
         }
     }
 
     /// Updates grouping component contribution information.
-    pub fn update_frame_components_contributions(
+    pub fn update_frame_components_contributions<F: FrameLike>(
         &self,
         components: &mut [Component],
-        frames: &[Frame],
+        frames: &[F],
         idx: usize,
     ) {
         for action in &self.0.actions {
@@ -132,3 +488,61 @@ impl Rule {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashSet;
+
+    use crate::enhancers::Cache;
+
+    #[test]
+    fn canonical_equality() {
+        let mut cache = Cache::new(0);
+        let mut parse = |rule: &str| cache.get_or_try_insert_rule(rule).unwrap();
+
+        let rule = parse("family:native,javascript function:foo -app +group");
+        let equal = [
+            "function:foo family:javascript,native -app +group",
+            "stack.function:foo   family:native,javascript -app +group",
+        ];
+        let different = [
+            "family:native function:foo -app +group",
+            "family:native,javascript function:foo +group -app",
+            "family:native,javascript !function:foo -app +group",
+            "[ function:foo ] | family:native,javascript -app +group",
+            "family:native,javascript path:foo -app +group",
+            "type:foo family:native,javascript function:foo -app +group",
+        ];
+
+        #[allow(clippy::mutable_key_type)]
+        let mut set = HashSet::new();
+        set.insert(rule.clone());
+
+        for other in equal {
+            let other = parse(other);
+            assert_eq!(rule, other, "{other}");
+            assert!(set.contains(&other), "{other}");
+        }
+
+        for other in different {
+            let other = parse(other);
+            assert_ne!(rule, other, "{other}");
+        }
+    }
+
+    #[test]
+    fn alternate_form_roundtrips() {
+        let mut cache = Cache::new(0);
+        for input in [
+            "[ function:foo ] | type:Error function:bar -app",
+            "function:bar | [ !module:foo* ] +group",
+            r#"path:"**/Program Files/**" value:"" category=foo"#,
+            r"function:a\\\\b -app",
+            r#"function:foo category="Lcom.foo bar" category="a\"b\\c""#,
+        ] {
+            let rule = cache.get_or_try_insert_rule(input).unwrap();
+            let reparsed = cache.get_or_try_insert_rule(&format!("{rule:#}")).unwrap();
+            assert_eq!(rule, reparsed, "{rule:#}");
+        }
+    }
+}
@@ -0,0 +1,181 @@
+//! Comparing the results of two [`Enhancements`] on the same stacktrace.
+//!
+//! This is meant for rolling out changes to rules or their implementation in shadow mode: the
+//! new version runs next to the current one, and the differences are reported as metrics.
+
+use std::num::NonZeroUsize;
+
+use super::frame::{FrameField, FrameLikeMut};
+use super::{AssembleOptions, Enhancements, ExceptionData, StringField};
+
+/// A difference between the results of a primary and a shadow [`Enhancements`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ShadowDifference {
+    /// The `in_app` flag of the frame at index `frame` differs.
+    InApp {
+        frame: usize,
+        primary: Option<bool>,
+        shadow: Option<bool>,
+    },
+    /// The category of the frame at index `frame` differs.
+    Category {
+        frame: usize,
+        primary: Option<StringField>,
+        shadow: Option<StringField>,
+    },
+    /// Whether the frame at index `frame` contains local variables differs.
+    HasVars {
+        frame: usize,
+        primary: Option<bool>,
+        shadow: Option<bool>,
+    },
+    /// The `max-frames` stacktrace variable differs.
+    MaxFrames {
+        primary: Option<NonZeroUsize>,
+        shadow: Option<NonZeroUsize>,
+    },
+    /// The `min-frames` stacktrace variable differs.
+    MinFrames {
+        primary: Option<NonZeroUsize>,
+        shadow: Option<NonZeroUsize>,
+    },
+    /// The `invert-stacktrace` stacktrace variable differs.
+    InvertStacktrace { primary: bool, shadow: bool },
+}
+
+impl Enhancements {
+    /// Applies both `self` and `shadow` to copies of `frames` and returns how their results differ.
+    ///
+    /// The modifications of the frames are compared, followed by the [stacktrace
+    /// state](Self::stacktrace_state) computed with `options` from the modified frames.
+    /// `frames` are not modified. The result is empty if both produce the same results.
+    pub fn shadow_diff<F: FrameLikeMut + Clone>(
+        &self,
+        shadow: &Enhancements,
+        frames: &[F],
+        exception_data: &ExceptionData,
+        options: &AssembleOptions,
+    ) -> Vec<ShadowDifference> {
+        let mut primary_frames = frames.to_vec();
+        self.apply_modifications_to_frames(&mut primary_frames, exception_data);
+        let mut shadow_frames = frames.to_vec();
+        shadow.apply_modifications_to_frames(&mut shadow_frames, exception_data);
+
+        let mut differences = Vec::new();
+        for (frame, (primary, shadow)) in primary_frames.iter().zip(&shadow_frames).enumerate() {
+            if primary.in_app() != shadow.in_app() {
+                differences.push(ShadowDifference::InApp {
+                    frame,
+                    primary: primary.in_app(),
+                    shadow: shadow.in_app(),
+                });
+            }
+            let category = |f: &F| f.get_field(FrameField::Category).map(StringField::new);
+            if primary.get_field(FrameField::Category) != shadow.get_field(FrameField::Category) {
+                differences.push(ShadowDifference::Category {
+                    frame,
+                    primary: category(primary),
+                    shadow: category(shadow),
+                });
+            }
+            if primary.has_vars() != shadow.has_vars() {
+                differences.push(ShadowDifference::HasVars {
+                    frame,
+                    primary: primary.has_vars(),
+                    shadow: shadow.has_vars(),
+                });
+            }
+        }
+
+        let primary = self.stacktrace_state(&primary_frames, exception_data, options);
+        let shadow = shadow.stacktrace_state(&shadow_frames, exception_data, options);
+        if primary.max_frames.value != shadow.max_frames.value {
+            differences.push(ShadowDifference::MaxFrames {
+                primary: primary.max_frames.value,
+                shadow: shadow.max_frames.value,
+            });
+        }
+        if primary.min_frames.value != shadow.min_frames.value {
+            differences.push(ShadowDifference::MinFrames {
+                primary: primary.min_frames.value,
+                shadow: shadow.min_frames.value,
+            });
+        }
+        if primary.invert_stacktrace.value != shadow.invert_stacktrace.value {
+            differences.push(ShadowDifference::InvertStacktrace {
+                primary: primary.invert_stacktrace.value,
+                shadow: shadow.invert_stacktrace.value,
+            });
+        }
+
+        differences
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::enhancers::{Cache, Frame};
+
+    #[test]
+    fn diffs_results() {
+        let mut cache = Cache::default();
+        let primary = Enhancements::parse(
+            "function:foo +app\nfunction:bar category=x\nfunction:foo max-frames=3",
+            &mut cache,
+        )
+        .unwrap();
+        let shadow = Enhancements::parse(
+            "function:foo +app\nfunction:bar category=y -app\nfunction:foo max-frames=3",
+            &mut cache,
+        )
+        .unwrap();
+        let frames: Vec<_> = ["foo", "bar"]
+            .into_iter()
+            .map(|function| Frame {
+                function: Some(function.into()),
+                ..Default::default()
+            })
+            .collect();
+
+        let options = AssembleOptions::default();
+        let differences = primary.shadow_diff(&shadow, &frames, &Default::default(), &options);
+        assert_eq!(
+            differences,
+            [
+                ShadowDifference::InApp {
+                    frame: 1,
+                    primary: None,
+                    shadow: Some(false),
+                },
+                ShadowDifference::Category {
+                    frame: 1,
+                    primary: Some("x".into()),
+                    shadow: Some("y".into()),
+                },
+            ]
+        );
+        assert_eq!(frames[1].category, None);
+
+        assert!(primary
+            .shadow_diff(&primary, &frames, &Default::default(), &options)
+            .is_empty());
+
+        let shadow = Enhancements::parse("function:foo max-frames=5", &mut cache).unwrap();
+        let differences = primary.shadow_diff(&shadow, &frames[..1], &Default::default(), &options);
+        assert_eq!(
+            differences,
+            [
+                ShadowDifference::InApp {
+                    frame: 0,
+                    primary: Some(true),
+                    shadow: None,
+                },
+                ShadowDifference::MaxFrames {
+                    primary: NonZeroUsize::new(3),
+                    shadow: NonZeroUsize::new(5),
+                },
+            ]
+        );
+    }
+}
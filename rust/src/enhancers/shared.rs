@@ -0,0 +1,141 @@
+//! A registry of enhancements that can be shared between threads and tasks.
+//!
+//! [`Enhancements`] are immutable once parsed and can be applied through `&self`, so an
+//! `Arc<Enhancements>` can be used from any number of threads or async tasks. Parsing them
+//! needs a `&mut` [`Cache`] though, and memoizing them needs a `&mut`
+//! [`EnhancementsRegistry`]. [`SharedRegistry`] keeps both behind locks, so that services can
+//! load and look up configurations through a shared reference.
+
+use std::sync::{Arc, Mutex, MutexGuard, PoisonError};
+
+use super::{Cache, Enhancements, EnhancementsRegistry};
+use crate::Result;
+
+/// An [`EnhancementsRegistry`] and the [`Cache`] used to parse its configurations, behind locks.
+///
+/// All methods take `&self`, so a `SharedRegistry` can be put into an `Arc` or a `static` and
+/// used from many threads or async tasks at once. Parsing holds the lock of the cache, so
+/// configurations are parsed one at a time. Applying the returned enhancements doesn't take
+/// any locks.
+#[derive(Debug)]
+pub struct SharedRegistry {
+    registry: Mutex<EnhancementsRegistry>,
+    cache: Mutex<Cache>,
+}
+
+impl SharedRegistry {
+    /// Creates a shared registry from `registry` and the `cache` to parse rules with.
+    pub fn new(registry: EnhancementsRegistry, cache: Cache) -> Self {
+        Self {
+            registry: Mutex::new(registry),
+            cache: Mutex::new(cache),
+        }
+    }
+
+    /// Parses `input` with the shared cache, like [`Enhancements::parse`].
+    pub fn parse(&self, input: &str) -> Result<Arc<Enhancements>> {
+        Ok(Arc::new(Enhancements::parse(input, &mut self.cache())?))
+    }
+
+    /// Parses the msgpack representation `input` with the shared cache, like
+    /// [`Enhancements::from_config_structure`].
+    pub fn from_config_structure(&self, input: &[u8]) -> Result<Arc<Enhancements>> {
+        Ok(Arc::new(Enhancements::from_config_structure(
+            input,
+            &mut self.cache(),
+        )?))
+    }
+
+    /// Registers `enhancements` as the base configuration called `name`.
+    ///
+    /// See [`EnhancementsRegistry::register_base`].
+    pub fn register_base(&self, name: &str, enhancements: Arc<Enhancements>) {
+        self.registry().register_base(name, enhancements);
+    }
+
+    /// Returns the base configuration called `name`, if one is registered.
+    pub fn base(&self, name: &str) -> Option<Arc<Enhancements>> {
+        self.registry().base(name).cloned()
+    }
+
+    /// Returns the combination of the base configuration `base` and the `custom` rules,
+    /// parsing and memoizing it if necessary.
+    ///
+    /// See [`EnhancementsRegistry::get_or_compile`].
+    pub fn get_or_compile(&self, base: &str, custom: &str) -> Result<Arc<Enhancements>> {
+        // The registry is always locked before the cache, so that this can't deadlock.
+        let mut registry = self.registry();
+        registry.get_or_compile(base, custom, &mut self.cache())
+    }
+
+    /// Calls `f` with the shared cache, e.g. to parse with options or to persist the cache.
+    ///
+    /// Don't call other methods of this registry from `f`, which would deadlock.
+    pub fn with_cache<R>(&self, f: impl FnOnce(&mut Cache) -> R) -> R {
+        f(&mut self.cache())
+    }
+
+    /// Locks the registry.
+    ///
+    /// A panic while the lock was held can't leave the registry in an inconsistent state, so
+    /// poisoning is ignored.
+    fn registry(&self) -> MutexGuard<'_, EnhancementsRegistry> {
+        self.registry.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    /// Locks the cache, ignoring poisoning like [`registry`](Self::registry).
+    fn cache(&self) -> MutexGuard<'_, Cache> {
+        self.cache.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::thread;
+
+    use serde_json::json;
+
+    use super::*;
+    use crate::enhancers::{ExceptionData, Frame};
+
+    fn assert_send_sync<T: Send + Sync>() {}
+
+    #[test]
+    fn shares_enhancements_between_threads() {
+        assert_send_sync::<Enhancements>();
+        assert_send_sync::<SharedRegistry>();
+
+        let registry = Arc::new(SharedRegistry::new(
+            EnhancementsRegistry::new(10),
+            Cache::new(100).with_lazy_regexes(true),
+        ));
+        let base = registry.parse("function:foo -app").unwrap();
+        registry.register_base("base", base);
+
+        let handles: Vec<_> = (0..4)
+            .map(|_| {
+                let registry = Arc::clone(&registry);
+                thread::spawn(move || {
+                    let enhancements = registry.get_or_compile("base", "function:b* +app").unwrap();
+                    let mut frames = vec![
+                        Frame::from_test(&json!({"function": "foo"}), "native"),
+                        Frame::from_test(&json!({"function": "bar"}), "native"),
+                    ];
+                    enhancements
+                        .apply_modifications_to_frames(&mut frames, &ExceptionData::default());
+                    (
+                        enhancements,
+                        frames.iter().map(|f| f.in_app).collect::<Vec<_>>(),
+                    )
+                })
+            })
+            .collect();
+
+        let results: Vec<_> = handles.into_iter().map(|h| h.join().unwrap()).collect();
+        for (enhancements, in_app) in &results {
+            assert!(Arc::ptr_eq(enhancements, &results[0].0));
+            assert_eq!(in_app, &[Some(false), Some(true)]);
+        }
+        assert_eq!(registry.with_cache(|cache| cache.cached_rules()), 2);
+    }
+}
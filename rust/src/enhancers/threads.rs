@@ -0,0 +1,157 @@
+//! The thread a stack trace belongs to, which `thread.*` matchers check.
+//!
+//! Like the exception matchers, thread matchers apply to all frames of a stack trace alike.
+//! They let rules single out e.g. the main thread of an app that stopped responding, which
+//! could otherwise only be approximated by matching the functions it typically runs:
+//!
+//! ```text
+//! thread.name:com.apple.main-thread function:*Runloop* -group
+//! thread.main:yes thread.crashed:no max-frames=5
+//! ```
+
+use super::StringField;
+
+/// The thread a stack trace belongs to.
+///
+/// With the `serde` feature, thread data (de)serializes in the shape of a thread in Sentry's
+/// event JSON, with an `id` that is either a number or a string.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Hash)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(default)
+)]
+pub struct ThreadData {
+    /// The thread's id, which is matched as a string.
+    #[cfg_attr(
+        feature = "serde",
+        serde(
+            deserialize_with = "deserialize_id",
+            skip_serializing_if = "Option::is_none"
+        )
+    )]
+    pub id: Option<StringField>,
+    /// The thread's name, e.g. `com.apple.main-thread`.
+    #[cfg_attr(feature = "serde", serde(skip_serializing_if = "Option::is_none"))]
+    pub name: Option<StringField>,
+    /// Whether this is the main thread of the process.
+    #[cfg_attr(feature = "serde", serde(skip_serializing_if = "Option::is_none"))]
+    pub main: Option<bool>,
+    /// Whether this thread crashed or raised the exception of the event.
+    #[cfg_attr(feature = "serde", serde(skip_serializing_if = "Option::is_none"))]
+    pub crashed: Option<bool>,
+}
+
+impl ThreadData {
+    /// Returns true if nothing is known about the thread.
+    pub fn is_empty(&self) -> bool {
+        *self == Self::default()
+    }
+}
+
+/// Deserializes a thread id, which Sentry allows to be a number or a string.
+#[cfg(feature = "serde")]
+fn deserialize_id<'de, D: serde::Deserializer<'de>>(
+    deserializer: D,
+) -> Result<Option<StringField>, D::Error> {
+    use serde::Deserialize;
+
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum Id {
+        Number(u64),
+        String(StringField),
+    }
+
+    Ok(Option::<Id>::deserialize(deserializer)?.map(|id| match id {
+        Id::Number(id) => StringField::new(id.to_string()),
+        Id::String(id) => id,
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::enhancers::{Cache, Encoding, Enhancements, ExceptionData, Frame, MatcherKind};
+
+    fn main_thread() -> ExceptionData {
+        ExceptionData {
+            thread: ThreadData {
+                id: Some("259".into()),
+                name: Some("com.apple.main-thread".into()),
+                main: Some(true),
+                crashed: Some(false),
+            },
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn matches_threads() {
+        let enhancements = Enhancements::parse(
+            "thread.name:com.apple.*-thread function:a +app\n\
+             thread.main:yes !thread.crashed:true function:b +app\n\
+             thread.id:25? function:c +app\n\
+             thread.main:no function:d +app",
+            &mut Cache::default(),
+        )
+        .unwrap();
+
+        let frames = || {
+            ["a", "b", "c", "d"].map(|function| Frame {
+                function: Some(function.into()),
+                ..Default::default()
+            })
+        };
+        let in_app = |exception_data: &ExceptionData| {
+            let mut frames = frames();
+            enhancements.apply_modifications_to_frames(&mut frames, exception_data);
+            frames.map(|f| f.in_app)
+        };
+
+        let some = Some(true);
+        assert_eq!(in_app(&main_thread()), [some, some, some, None]);
+        // Threads without a field match like exceptions without it.
+        assert_eq!(in_app(&ExceptionData::default()), [None, None, None, None]);
+
+        let rules: Vec<_> = enhancements.rules().map(|r| format!("{r:#}")).collect();
+        assert_eq!(
+            rules[1],
+            "thread.main:yes !thread.crashed:true function:b +app"
+        );
+        let reparsed = Enhancements::parse(&rules.join("\n"), &mut Cache::default()).unwrap();
+        assert!(reparsed.rules().eq(enhancements.rules()));
+        // Sentry doesn't know thread matchers, so only the extended encoding represents them.
+        assert!(enhancements.to_config_structure().is_err());
+        let encoded = enhancements
+            .to_config_structure_with_encoding(Encoding::Extended)
+            .unwrap();
+        let decoded = Enhancements::from_config_structure(&encoded, &mut Cache::default()).unwrap();
+        assert!(decoded.rules().eq(enhancements.rules()));
+        assert_eq!(
+            enhancements.rules().next().unwrap().describe().matchers[0].kind,
+            MatcherKind::Thread
+        );
+
+        let err = Enhancements::parse("thread.main:maybe -app", &mut Cache::default());
+        assert!(format!("{:#}", err.unwrap_err()).contains("invalid boolean value"));
+    }
+
+    #[test]
+    #[cfg(feature = "serde")]
+    fn deserializes_event_threads() {
+        let exception_data: ExceptionData = serde_json::from_value(serde_json::json!({
+            "type": "App Hanging",
+            "thread": {"id": 259, "name": "com.apple.main-thread", "main": true, "crashed": false},
+        }))
+        .unwrap();
+        assert_eq!(exception_data.thread, main_thread().thread);
+
+        let thread: ThreadData = serde_json::from_str(r#"{"id": "worker-1"}"#).unwrap();
+        assert_eq!(thread.id.as_deref(), Some("worker-1"));
+        assert_eq!(
+            serde_json::to_string(&thread).unwrap(),
+            r#"{"id":"worker-1"}"#
+        );
+    }
+}
@@ -0,0 +1,168 @@
+//! The `app` and `system` grouping variants of a stack trace.
+//!
+//! Sentry groups every stack trace twice: the `system` variant considers all frames, while the
+//! `app` variant ignores frames that aren't in-app before the rules are applied. Assembling
+//! both here saves converting the frames and components between Python and Rust twice.
+
+use super::{
+    AssembleOptions, AssembleResult, Component, Enhancements, ExceptionData, FrameLike, Hint,
+};
+
+/// The hint of components the `app` variant ignores because their frame isn't in-app.
+pub const NON_APP_FRAME_HINT: &str = "non app frame";
+
+/// The components and `stacktrace` component attributes of one grouping variant.
+#[derive(Debug, Clone, Default)]
+pub struct StacktraceVariant {
+    /// The frame components, updated by the rules.
+    pub components: Vec<Component>,
+    /// The attributes of the `stacktrace` component.
+    pub result: AssembleResult,
+}
+
+/// The `app` and `system` grouping variants of a stack trace.
+#[derive(Debug, Clone, Default)]
+pub struct Variants {
+    /// The variant in which only in-app frames contribute, unless a rule un-ignores them.
+    pub app: StacktraceVariant,
+    /// The variant in which all frames may contribute.
+    pub system: StacktraceVariant,
+}
+
+impl Enhancements {
+    /// Assembles the `app` and `system` variants of the `stacktrace` grouping component out of
+    /// the given frame `components` and `frames`.
+    ///
+    /// The `system` variant is what [`assemble_stacktrace_component_with_options`] returns for
+    /// the components. In the `app` variant, the components of frames that aren't in-app
+    /// first stop contributing, with the hint [`NON_APP_FRAME_HINT`], like in Sentry.
+    ///
+    /// [`assemble_stacktrace_component_with_options`]: Self::assemble_stacktrace_component_with_options
+    pub fn assemble_variants<F: FrameLike>(
+        &self,
+        frames: &[F],
+        components: &[Component],
+        exception_data: &ExceptionData,
+        options: &AssembleOptions,
+    ) -> Variants {
+        let mut app = components.to_vec();
+        for (component, frame) in app.iter_mut().zip(frames) {
+            if !frame.in_app().unwrap_or_default() {
+                component.contributes = Some(false);
+                component
+                    .hints
+                    .retain(|hint| hint.text != NON_APP_FRAME_HINT);
+                component.hints.push(Hint::new(NON_APP_FRAME_HINT));
+            }
+        }
+        let mut system = components.to_vec();
+
+        // Both variants share the scratch space of matching.
+        let mut memo_results = Vec::new();
+        let mut assemble = |components: &mut [Component]| {
+            self.assemble(
+                components,
+                frames,
+                exception_data,
+                options,
+                None,
+                &mut memo_results,
+            )
+            .assemble
+        };
+        let app_result = assemble(&mut app);
+        let system_result = assemble(&mut system);
+
+        Variants {
+            app: StacktraceVariant {
+                components: app,
+                result: app_result,
+            },
+            system: StacktraceVariant {
+                components: system,
+                result: system_result,
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+    use crate::enhancers::{Cache, Frame};
+
+    #[test]
+    fn assembles_variants() {
+        let enhancements = Enhancements::parse(
+            "function:lib_helper +group\n\
+             function:app_noise -group",
+            &mut Cache::default(),
+        )
+        .unwrap();
+        let frames = [
+            Frame::from_test(&json!({"function": "main", "in_app": true}), "native"),
+            Frame::from_test(&json!({"function": "app_noise", "in_app": true}), "native"),
+            Frame::from_test(&json!({"function": "lib_helper"}), "native"),
+            Frame::from_test(&json!({"function": "lib_other"}), "native"),
+        ];
+        let components = vec![
+            Component {
+                contributes: Some(true),
+                ..Default::default()
+            };
+            frames.len()
+        ];
+
+        let variants = enhancements.assemble_variants(
+            &frames,
+            &components,
+            &ExceptionData::default(),
+            &AssembleOptions::default(),
+        );
+
+        let contributes = |variant: &StacktraceVariant| -> Vec<_> {
+            variant.components.iter().map(|c| c.contributes).collect()
+        };
+        assert_eq!(
+            contributes(&variants.system),
+            [Some(true), Some(false), Some(true), Some(true)]
+        );
+        assert_eq!(
+            contributes(&variants.app),
+            [Some(true), Some(false), Some(true), Some(false)]
+        );
+
+        let hints: Vec<_> = (variants.app.components.iter())
+            .map(Component::primary_hint)
+            .collect();
+        assert_eq!(
+            hints,
+            [
+                None,
+                Some("ignored by stack trace rule (function:app_noise -group)"),
+                Some("un-ignored by stack trace rule (function:lib_helper +group)"),
+                Some(NON_APP_FRAME_HINT),
+            ]
+        );
+        assert!(variants.app.result.contributes && variants.system.result.contributes);
+
+        // The system variant is the same as assembling the components on their own.
+        let mut expected = components.clone();
+        let result = enhancements.assemble_stacktrace_component(
+            &mut expected,
+            &frames,
+            &ExceptionData::default(),
+        );
+        let hints = |components: &[Component]| -> Vec<_> {
+            components.iter().map(|c| c.hints.clone()).collect()
+        };
+        assert_eq!(
+            contributes(&variants.system),
+            expected.iter().map(|c| c.contributes).collect::<Vec<_>>()
+        );
+        assert_eq!(hints(&variants.system.components), hints(&expected));
+        assert_eq!(variants.system.result.hint, result.hint);
+    }
+}
@@ -0,0 +1,168 @@
+//! Runs the conformance test vectors in `tests/vectors`.
+//!
+//! The same vectors are run against the Python bindings by `tests/test_vectors.py`. See
+//! `tests/vectors/README.md` for their format.
+
+use std::path::Path;
+
+use serde::Deserialize;
+use serde_json::Value;
+
+use super::{Cache, Component, Enhancements, ExceptionData, Frame, StringField};
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct TestVector {
+    name: String,
+    rules: String,
+    #[serde(default)]
+    platform: Option<String>,
+    #[serde(default)]
+    exception: VectorException,
+    frames: Vec<Value>,
+    #[serde(default)]
+    components: Option<Vec<VectorComponent>>,
+    expected: Expected,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct VectorException {
+    #[serde(rename = "type")]
+    ty: Option<String>,
+    value: Option<String>,
+    module: Option<String>,
+    mechanism: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct VectorComponent {
+    contributes: bool,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct Expected {
+    #[serde(default)]
+    frames: Option<Vec<ExpectedFrame>>,
+    #[serde(default)]
+    components: Option<Vec<ExpectedComponent>>,
+    #[serde(default)]
+    stacktrace: Option<ExpectedStacktrace>,
+}
+
+#[derive(Debug, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct ExpectedFrame {
+    in_app: Option<bool>,
+    category: Option<String>,
+}
+
+#[derive(Debug, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct ExpectedComponent {
+    contributes: bool,
+    hint: Option<String>,
+}
+
+#[derive(Debug, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct ExpectedStacktrace {
+    contributes: bool,
+    hint: Option<String>,
+    invert_stacktrace: bool,
+}
+
+fn run(vector: &TestVector, cache: &mut Cache) {
+    let name = &vector.name;
+    let enhancements = Enhancements::parse(&vector.rules, cache).unwrap();
+
+    let platform = vector.platform.as_deref().unwrap_or("other");
+    let mut frames: Vec<_> = vector
+        .frames
+        .iter()
+        .map(|frame| {
+            let mut frame = Frame::from_test(frame, platform);
+            // Frames without an `in_app` flag are out of app, like in Sentry.
+            frame.in_app.get_or_insert(false);
+            frame
+        })
+        .collect();
+    let mut components: Vec<_> = match &vector.components {
+        Some(components) => components
+            .iter()
+            .map(|c| Component {
+                contributes: Some(c.contributes),
+                ..Default::default()
+            })
+            .collect(),
+        None => vec![
+            Component {
+                contributes: Some(true),
+                ..Default::default()
+            };
+            frames.len()
+        ],
+    };
+    let exception_data = ExceptionData {
+        ty: vector.exception.ty.as_deref().map(StringField::new),
+        value: vector.exception.value.as_deref().map(StringField::new),
+        module: vector.exception.module.as_deref().map(StringField::new),
+        mechanism: vector.exception.mechanism.as_deref().map(StringField::new),
+        ..Default::default()
+    };
+
+    let result = enhancements.apply_all(&mut frames, &mut components, &exception_data);
+
+    if let Some(expected) = &vector.expected.frames {
+        let frames: Vec<_> = frames
+            .iter()
+            .map(|frame| ExpectedFrame {
+                in_app: frame.in_app,
+                category: frame.category.as_ref().map(|c| c.as_str().to_owned()),
+            })
+            .collect();
+        assert_eq!(&frames, expected, "frames of `{name}`");
+    }
+    if let Some(expected) = &vector.expected.components {
+        let components: Vec<_> = components
+            .iter()
+            .map(|component| ExpectedComponent {
+                contributes: component.contributes.unwrap_or_default(),
+                hint: component.primary_hint().map(str::to_owned),
+            })
+            .collect();
+        assert_eq!(&components, expected, "components of `{name}`");
+    }
+    if let Some(expected) = &vector.expected.stacktrace {
+        let stacktrace = ExpectedStacktrace {
+            contributes: result.assemble.contributes,
+            hint: result.assemble.hint,
+            invert_stacktrace: result.assemble.invert_stacktrace,
+        };
+        assert_eq!(&stacktrace, expected, "stack trace of `{name}`");
+    }
+}
+
+#[test]
+fn conformance_vectors() {
+    let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("../tests/vectors");
+    let mut paths: Vec<_> = std::fs::read_dir(dir)
+        .unwrap()
+        .map(|entry| entry.unwrap().path())
+        .filter(|path| path.extension().is_some_and(|ext| ext == "json"))
+        .collect();
+    paths.sort();
+    assert!(!paths.is_empty());
+
+    let mut cache = Cache::new(1_000);
+    for path in paths {
+        let contents = std::fs::read_to_string(&path).unwrap();
+        let vectors: Vec<TestVector> = serde_json::from_str(&contents)
+            .unwrap_or_else(|err| panic!("{}: {err}", path.display()));
+        for vector in &vectors {
+            run(vector, &mut cache);
+        }
+    }
+}
@@ -0,0 +1,107 @@
+//! Warnings about rules that parse, but don't do what their author likely intended.
+//!
+//! Like Sentry, the parser accepts matchers with invalid glob patterns or non-boolean `app`
+//! and `has_vars` values and turns them into no-op matchers that never match, or match every
+//! frame when negated. Such rules are almost always mistakes, so they are reported as
+//! [`ParseWarning`]s, no matter whether the rules were parsed from text or from the msgpack
+//! representation.
+
+use serde::Serialize;
+
+use super::Enhancements;
+
+/// What is wrong with a matcher a [`ParseWarning`] is about.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum WarningKind {
+    /// The glob pattern of the matcher can't be compiled.
+    InvalidPattern,
+    /// The pattern of an `app` or `has_vars` matcher isn't a boolean.
+    InvalidFlag,
+}
+
+impl WarningKind {
+    /// Returns the name of this kind, as it is serialized.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            WarningKind::InvalidPattern => "invalid_pattern",
+            WarningKind::InvalidFlag => "invalid_flag",
+        }
+    }
+}
+
+/// A warning about a matcher that never matches.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize)]
+pub struct ParseWarning {
+    /// The text of the rule containing the matcher.
+    pub rule: String,
+    /// The matcher, e.g. `function:[foo`.
+    pub matcher: String,
+    /// What is wrong with the matcher.
+    pub kind: WarningKind,
+    /// A human-readable description of the problem.
+    pub message: String,
+}
+
+impl Enhancements {
+    /// Returns warnings about all matchers of this collection that never match, in the order
+    /// of their rules.
+    ///
+    /// This compiles the regexes of rules parsed with a lazy [`Cache`](super::Cache), to find out
+    /// whether they are valid.
+    pub fn warnings(&self) -> Vec<ParseWarning> {
+        self.rules().flat_map(|rule| rule.warnings()).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::enhancers::config_structure::{EncodedEnhancements, EncodedRule, Encoding};
+    use crate::enhancers::{Cache, Frame};
+
+    #[test]
+    fn warns_about_noop_matchers() {
+        let input = "function:[foo -app\n\
+                     app:maybe +group\n\
+                     module:foo* -app";
+        for lazy in [false, true] {
+            let mut cache = Cache::new(10).with_lazy_regexes(lazy);
+            let enhancements = Enhancements::parse(input, &mut cache).unwrap();
+            let warnings = enhancements.warnings();
+            assert_eq!(warnings.len(), 2, "lazy: {lazy}");
+
+            assert_eq!(warnings[0].rule, "function:[foo -app");
+            assert_eq!(warnings[0].matcher, "function:[foo");
+            assert_eq!(warnings[0].kind, WarningKind::InvalidPattern);
+            assert!(warnings[0]
+                .message
+                .starts_with("unclosed character class in `[foo`"));
+
+            assert_eq!(warnings[1].matcher, "app:maybe");
+            assert_eq!(warnings[1].kind, WarningKind::InvalidFlag);
+        }
+
+        // Rules decoded from the msgpack representation are checked the same way.
+        let mut cache = Cache::default();
+        let enhancements = Enhancements::parse(input, &mut cache).unwrap();
+        let rules = enhancements
+            .rules()
+            .map(|rule| EncodedRule::from_rule(rule, Encoding::Sentry).unwrap())
+            .collect();
+        let encoded = rmp_serde::to_vec(&EncodedEnhancements(2, vec![], rules)).unwrap();
+        let decoded = Enhancements::from_config_structure(&encoded, &mut cache).unwrap();
+        assert_eq!(decoded.warnings(), enhancements.warnings());
+
+        // Negated, the no-op matcher matches every frame that has the field.
+        let enhancements = Enhancements::parse("!function:[foo -app", &mut cache).unwrap();
+        let mut frames = [Frame {
+            function: Some("bar".into()),
+            in_app: Some(true),
+            ..Default::default()
+        }];
+        enhancements.apply_modifications_to_frames(&mut frames, &Default::default());
+        assert_eq!(frames[0].in_app, Some(false));
+        assert_eq!(enhancements.warnings().len(), 1);
+    }
+}
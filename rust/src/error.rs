@@ -0,0 +1,122 @@
+//! The error type shared by all public APIs of this crate.
+
+use std::fmt;
+
+use crate::enhancers::ParseError;
+
+/// A specialized [`Result`](std::result::Result) type for this crate's [`Error`].
+pub type Result<T, E = Error> = std::result::Result<T, E>;
+
+/// An error produced by one of this crate's public APIs.
+///
+/// The wrapped errors are transparent: an `Error` displays as its
+/// inner error and reports the same [`source`](std::error::Error::source).
+/// Converting it into an [`anyhow::Error`] via `?` or `From` is always possible.
+#[derive(Debug)]
+#[non_exhaustive]
+pub enum Error {
+    /// Rules in the human-readable text format could not be parsed or are invalid.
+    Parse(anyhow::Error),
+    /// Rules in the compact msgpack format could not be decoded or are invalid.
+    ConfigStructure(anyhow::Error),
+    /// A glob pattern could not be compiled.
+    Glob(globset::Error),
+    /// A regex translated from a glob pattern could not be compiled.
+    Regex(regex::Error),
+    /// A base configuration that was never registered was requested.
+    UnknownBase(String),
+    /// A default configuration that is not embedded in this crate was requested.
+    UnknownDefaultConfig(String),
+    /// A persisted cache could not be read or written.
+    Io(std::io::Error),
+    /// An encoded change-set could not be decoded or doesn't fit the frames it is applied to.
+    Changes(anyhow::Error),
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::Parse(e) | Error::ConfigStructure(e) | Error::Changes(e) => e.fmt(f),
+            Error::Glob(e) => e.fmt(f),
+            Error::Regex(e) => e.fmt(f),
+            Error::UnknownBase(name) => write!(f, "unknown base enhancements `{name}`"),
+            Error::UnknownDefaultConfig(version) => {
+                write!(f, "unknown default enhancements `{version}`")
+            }
+            Error::Io(e) => e.fmt(f),
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::Parse(e) | Error::ConfigStructure(e) | Error::Changes(e) => e.source(),
+            Error::Glob(e) => e.source(),
+            Error::Regex(e) => e.source(),
+            Error::Io(e) => e.source(),
+            Error::UnknownBase(_) | Error::UnknownDefaultConfig(_) => None,
+        }
+    }
+}
+
+impl Error {
+    /// Returns the location of the error in the text of the rules, if it is a parse error
+    /// of a single line.
+    pub fn parse_error(&self) -> Option<&ParseError> {
+        match self {
+            Error::Parse(e) => e.downcast_ref(),
+            _ => None,
+        }
+    }
+}
+
+impl From<ParseError> for Error {
+    fn from(e: ParseError) -> Self {
+        Error::Parse(e.into())
+    }
+}
+
+impl From<globset::Error> for Error {
+    fn from(e: globset::Error) -> Self {
+        Error::Glob(e)
+    }
+}
+
+impl From<regex::Error> for Error {
+    fn from(e: regex::Error) -> Self {
+        Error::Regex(e)
+    }
+}
+
+impl From<std::io::Error> for Error {
+    fn from(e: std::io::Error) -> Self {
+        Error::Io(e)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::enhancers::{Cache, Enhancements};
+
+    use super::*;
+
+    #[test]
+    fn error_kinds() {
+        let mut cache = Cache::default();
+
+        let err = Enhancements::parse("function:foo +nope", &mut cache).unwrap_err();
+        assert!(matches!(err, Error::Parse(_)));
+        let anyhow_err = anyhow::Error::from(err);
+        assert_eq!(
+            anyhow_err.root_cause().to_string(),
+            "at `nope`: invalid flag name `nope`"
+        );
+
+        let err = Enhancements::from_config_structure(b"\x00", &mut cache).unwrap_err();
+        assert!(matches!(err, Error::ConfigStructure(_)));
+
+        let err = cache.get_or_try_insert_regex("a[", false).unwrap_err();
+        assert!(matches!(err, Error::Glob(_)));
+    }
+}
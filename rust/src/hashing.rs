@@ -0,0 +1,113 @@
+//! Deterministic string hashes for use across languages.
+//!
+//! Hashes like grouping fingerprints have to be identical no matter whether they are computed
+//! by Sentry's Python code or by Rust. The helpers in this module fix how strings are turned
+//! into bytes, so that both sides can call into the same implementation instead of hoping that
+//! their normalizations agree.
+//!
+//! Every helper hashes the concatenation of the UTF-8 encodings of all `parts`, after
+//! lowercasing each part as configured by [`Lowercase`]. There are no separators between
+//! parts, so `["ab", "c"]` and `["a", "bc"]` have the same hash.
+
+pub use crate::lowercase::Lowercase;
+
+/// Calls `f` with the normalized bytes of every part.
+fn for_each_part<I>(parts: I, lowercase: Lowercase, mut f: impl FnMut(&[u8]))
+where
+    I: IntoIterator,
+    I::Item: AsRef<str>,
+{
+    for part in parts {
+        f(lowercase.apply(part.as_ref()).as_bytes());
+    }
+}
+
+/// Returns the MD5 digest of `parts` as a lowercase hex string.
+///
+/// Without lowercasing, this is the hash Sentry computes for a list of fingerprint values.
+pub fn md5_hex<I>(parts: I, lowercase: Lowercase) -> String
+where
+    I: IntoIterator,
+    I::Item: AsRef<str>,
+{
+    let mut context = md5::Context::new();
+    for_each_part(parts, lowercase, |bytes| context.consume(bytes));
+    format!("{:x}", context.compute())
+}
+
+/// Returns the CRC-32 (ISO-HDLC) checksum of `parts`, like Python's `zlib.crc32`.
+pub fn crc32<I>(parts: I, lowercase: Lowercase) -> u32
+where
+    I: IntoIterator,
+    I::Item: AsRef<str>,
+{
+    let mut hasher = crc32fast::Hasher::new();
+    for_each_part(parts, lowercase, |bytes| hasher.update(bytes));
+    hasher.finalize()
+}
+
+/// Returns the 32-bit FNV-1a hash of `parts`.
+pub fn fnv1a_32<I>(parts: I, lowercase: Lowercase) -> u32
+where
+    I: IntoIterator,
+    I::Item: AsRef<str>,
+{
+    let mut hash: u32 = 0x811c_9dc5;
+    for_each_part(parts, lowercase, |bytes| {
+        for &byte in bytes {
+            hash = (hash ^ u32::from(byte)).wrapping_mul(0x0100_0193);
+        }
+    });
+    hash
+}
+
+/// Returns the 64-bit FNV-1a hash of `parts`.
+pub fn fnv1a_64<I>(parts: I, lowercase: Lowercase) -> u64
+where
+    I: IntoIterator,
+    I::Item: AsRef<str>,
+{
+    let mut hash: u64 = 0xcbf2_9ce4_8422_2325;
+    for_each_part(parts, lowercase, |bytes| {
+        for &byte in bytes {
+            hash = (hash ^ u64::from(byte)).wrapping_mul(0x0100_0000_01b3);
+        }
+    });
+    hash
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn hashes_known_values() {
+        assert_eq!(
+            md5_hex(["hello", "world"], Lowercase::None),
+            "fc5e038d38a57032085441e7fe7010b0"
+        );
+        assert_eq!(
+            md5_hex([] as [&str; 0], Lowercase::None),
+            "d41d8cd98f00b204e9800998ecf8427e"
+        );
+        assert_eq!(crc32(["hello"], Lowercase::None), 0x3610_a686);
+        assert_eq!(fnv1a_32(["hello"], Lowercase::None), 0x4f9f_2cab);
+        assert_eq!(fnv1a_64(["hello"], Lowercase::None), 0xa430_d846_80aa_bd0b);
+    }
+
+    #[test]
+    fn lowercases_parts() {
+        assert_eq!(
+            crc32(["HeLLo"], Lowercase::Ascii),
+            crc32(["hello"], Lowercase::None)
+        );
+        assert_eq!(
+            md5_hex(["ÄÖ", "Ü"], Lowercase::Ascii),
+            md5_hex(["ÄÖÜ"], Lowercase::None)
+        );
+        assert_eq!(
+            md5_hex(["STRASSE ΣΑΣ"], Lowercase::Unicode),
+            md5_hex(["strasse σας"], Lowercase::None)
+        );
+    }
+}
@@ -1 +1,7 @@
 pub mod enhancers;
+mod error;
+pub mod hashing;
+mod lowercase;
+
+pub use error::{Error, Result};
+pub use lowercase::Lowercase;
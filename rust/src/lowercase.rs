@@ -0,0 +1,29 @@
+//! Lowercasing policies shared by hashing and path normalization.
+
+use std::borrow::Cow;
+
+/// How strings are lowercased, e.g. before they are hashed or matched against paths.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub enum Lowercase {
+    /// Strings are kept as they are.
+    #[default]
+    None,
+    /// Only ASCII letters are lowercased.
+    Ascii,
+    /// All characters are lowercased according to Unicode, like Python's `str.lower`.
+    Unicode,
+}
+
+impl Lowercase {
+    /// Lowercases `part` according to this policy, borrowing it if nothing changes.
+    pub fn apply(self, part: &str) -> Cow<'_, str> {
+        match self {
+            Lowercase::None => Cow::Borrowed(part),
+            Lowercase::Ascii if !part.bytes().any(|b| b.is_ascii_uppercase()) => {
+                Cow::Borrowed(part)
+            }
+            Lowercase::Ascii => Cow::Owned(part.to_ascii_lowercase()),
+            Lowercase::Unicode => Cow::Owned(part.to_lowercase()),
+        }
+    }
+}
@@ -1,7 +1,26 @@
+import json
+import pickle
+from pathlib import Path
 from typing import Any, Mapping, Optional, Sequence, Union
 
 import pytest
-from sentry_ophio.enhancers import Cache, Enhancements
+from sentry_ophio.enhancers import (
+    Cache,
+    Component,
+    Enhancements,
+    EnhancementsRegistry,
+    InvalidConfigStructure,
+    InvalidEnhancerConfig,
+    RegexTooComplex,
+    ResultCache,
+    RuleView,
+    apply_encoded_changes,
+    default_in_app,
+    migrate,
+    normalize_exception_value,
+    otel_frame,
+    trim_function_name,
+)
 
 # TODO: all this is copied from Sentry, and the Sentry side should still
 # be responsible for the `create_match_frame`
@@ -78,11 +97,1064 @@ def test_simple_enhancer():
     modified_frames = enhancer.apply_modifications_to_frames(frames, exception_data)
     print(modified_frames)
 
+def test_in_app_summary():
+    enhancer = Enhancements.parse("function:foo -app\nfunction:* +app", cache)
+
+    frames = [
+        create_match_frame({"function": "foo", "in_app": True}, "native"),
+        create_match_frame({"function": "bar"}, "native"),
+    ]
+    exception_data = {"ty": None, "value": None, "mechanism": None}
+
+    result = enhancer.apply_modifications(
+        frames, exception_data, summarize_in_app_changes=True
+    )
+    assert result.frames == [(None, True), (None, True)]
+    assert result.in_app_changes == [("function:foo -app", 1), ("function:* +app", 2)]
+    assert enhancer.apply_modifications(frames, exception_data).in_app_changes == []
+
+    # Summaries combine with the other options.
+    frames = [create_match_frame({"function": "foo"}, "native") for _ in range(5)]
+    result = enhancer.apply_modifications(
+        frames, exception_data, summarize_in_app_changes=True, frame_limit=2
+    )
+    assert [in_app for _, in_app in result.frames] == [True, True, False, True, True]
+    assert result.in_app_changes == [("function:* +app", 4)]
+    assert result == enhancer.apply_modifications(
+        frames, exception_data, summarize_in_app_changes=True, frame_limit=2
+    )
+
+
+def test_profile_apply():
+    enhancer = Enhancements.parse(
+        "function:foo -app\nfunction:* +app\nfunction:bar +group", cache
+    )
+
+    frames = [
+        create_match_frame({"function": "foo"}, "native"),
+        create_match_frame({"function": "bar"}, "native"),
+    ]
+    exception_data = {"ty": None, "value": None, "mechanism": None}
+
+    modified_frames, profile = enhancer.profile_apply(frames, exception_data)
+    assert modified_frames == [(None, True), (None, True)]
+    assert sorted((rule, matched) for rule, _, matched in profile) == [
+        ("function:* +app", 2),
+        ("function:foo -app", 1),
+    ]
+    assert all(elapsed >= 0 for _, elapsed, _ in profile)
+
+    _, profile = enhancer.profile_apply(frames, exception_data, top_n=1)
+    assert len(profile) == 1
+
+
+def test_apply_stats():
+    enhancer = Enhancements.parse(
+        "family:javascript function:foo -app\n"
+        "type:Error function:foo -app\n"
+        "function:foo +app",
+        cache,
+    )
+
+    frames = [create_match_frame({"function": "foo"}, "native")]
+    exception_data = {"ty": b"Panic", "value": None, "mechanism": None}
+
+    modified_frames, stats = enhancer.apply_modifications_to_frames_with_stats(
+        frames, exception_data
+    )
+    assert modified_frames == [(None, True)]
+    assert stats.skipped_by_families == 1
+    assert stats.skipped_by_exception == 1
+    assert stats.evaluated == 1
+
+
+def test_in_app_attribution():
+    enhancer = Enhancements.parse("function:foo +app\nfunction:f* -app", cache)
+
+    frames = [
+        create_match_frame({"function": "foo", "in_app": True}, "native"),
+        create_match_frame({"function": "bar"}, "native"),
+    ]
+    exception_data = {"ty": None, "value": None, "mechanism": None}
+
+    result = enhancer.apply_modifications(
+        frames, exception_data, track_in_app_last_changed=True
+    )
+    assert result.frames == [(None, False), (None, False)]
+    assert result.in_app_last_changed == [1, None]
+    assert enhancer[result.in_app_last_changed[0]].text == "function:f* -app"
+    assert enhancer.apply_modifications(frames, exception_data).in_app_last_changed == []
+
+
+def test_unset_in_app():
+    enhancer = Enhancements.parse("app:no function:foo +app\napp:unset function:bar -app", cache)
+    frames = [create_match_frame({"function": name}, "native") for name in ("foo", "bar")]
+    for frame in frames:
+        frame["in_app"] = None
+    exception_data = {"ty": None, "value": None, "mechanism": None}
+
+    # An unset flag doesn't match `app:no`, unlike `False`.
+    assert enhancer.apply_modifications_to_frames(frames, exception_data) == [
+        (None, None),
+        (None, False),
+    ]
+    frames[0]["in_app"] = False
+    assert enhancer.apply_modifications_to_frames(frames, exception_data)[0] == (None, True)
+
+
+def test_normalize_exception_value():
+    value = 'Object at 0x7ffe12 has no key "foo" (42 tries)'
+    assert (
+        normalize_exception_value(value)
+        == "Object at <hex> has no key <str> (<int> tries)"
+    )
+
+    enhancer = Enhancements.parse('error.value:"Object at <hex> *" -app', cache)
+    frames = [create_match_frame({"function": "foo", "in_app": True}, "native")]
+    exception_data = {"ty": None, "value": value.encode(), "mechanism": None}
+
+    assert enhancer.apply_modifications_to_frames(frames, exception_data) == [
+        (None, True)
+    ]
+    assert enhancer.apply_modifications_to_frames(
+        frames, exception_data, normalize_exception_value=True
+    ) == [(None, False)]
+
+
+def test_trim_function_name():
+    assert trim_function_name("void foo<int>(bar) const", "native") == "foo<T>"
+    assert (
+        trim_function_name("main::{lambda(int)#1}::operator()", "native")
+        == "main::lambda::operator()"
+    )
+    assert trim_function_name("foo(bar)", "javascript") == "foo(bar)"
+
+
+def test_frame_limit():
+    enhancer = Enhancements.parse("function:foo +app", cache)
+
+    frames = [create_match_frame({"function": "foo"}, "native") for _ in range(5)]
+    exception_data = {"ty": None, "value": None, "mechanism": None}
+
+    modified_frames, stats = enhancer.apply_modifications_to_frames_with_stats(
+        frames, exception_data, frame_limit=2
+    )
+    assert [in_app for _, in_app in modified_frames] == [
+        True,
+        True,
+        False,
+        True,
+        True,
+    ]
+    assert stats.truncated
+
+    _, stats = enhancer.apply_modifications_to_frames_with_stats(
+        frames, exception_data
+    )
+    assert not stats.truncated
+
+
+def test_invalid_utf8_values():
+    enhancer = Enhancements.parse("path:**/libfoo* +app", cache)
+
+    frame = create_match_frame({"function": "foo"}, "native")
+    frame["path"] = b"/usr/lib/libfoo\xff.so"
+    exception_data = {"ty": b"Error\xfe", "value": None, "mechanism": None}
+
+    modified_frames = enhancer.apply_modifications_to_frames([frame], exception_data)
+    assert modified_frames == [(None, True)]
+
+
+def test_time_budget():
+    enhancer = Enhancements.parse("function:foo +app\nfunction:foo -group", cache)
+
+    frames = [create_match_frame({"function": "foo"}, "native")]
+    exception_data = {"ty": None, "value": None, "mechanism": None}
+
+    modified_frames, stats = enhancer.apply_modifications_to_frames_with_stats(
+        frames, exception_data, time_budget_ms=60_000
+    )
+    assert modified_frames == [(None, True)]
+    assert stats.budget_exhausted_at is None
+
+    components = [Component(contributes=True)]
+    result = enhancer.assemble_stacktrace_component(
+        frames, exception_data, components, time_budget_ms=60_000
+    )
+    assert result.budget_exhausted_at is None
+    assert not components[0].contributes
+
+
+def test_matching_rules():
+    enhancer = Enhancements.parse(
+        "function:foo -app\nfunction:bar +app\n[ function:bar ] | function:foo -group",
+        cache,
+    )
+
+    frames = [
+        create_match_frame({"function": "bar"}, "native"),
+        create_match_frame({"function": "foo"}, "native"),
+    ]
+    exception_data = {"ty": None, "value": None, "mechanism": None}
+
+    assert enhancer.matching_rules(frames, 1, exception_data) == [0, 2]
+    assert [enhancer[i].text for i in enhancer.matching_rules(frames, 0, exception_data)] == [
+        "function:bar +app"
+    ]
+    with pytest.raises(IndexError):
+        enhancer.matching_rules(frames, 2, exception_data)
+
+
+def test_encoded_changes():
+    enhancer = Enhancements.parse("function:foo +app category=main", cache)
+
+    frames = [
+        create_match_frame({"function": "foo"}, "native"),
+        create_match_frame({"function": "bar"}, "native"),
+    ]
+    exception_data = {"ty": None, "value": None, "mechanism": None}
+
+    modified_frames, changes = enhancer.apply_and_encode_changes(frames, exception_data)
+    assert modified_frames == [("main", True), (None, False)]
+    assert isinstance(changes, bytes)
+
+    assert apply_encoded_changes(frames, changes) == modified_frames
+
+    with pytest.raises(ValueError, match="encoded for 2 frames, but applied to 1"):
+        apply_encoded_changes(frames[:1], changes)
+
+
+def test_shadow_diff():
+    primary = Enhancements.parse("function:foo +app\nfunction:* max-frames=3", cache)
+    shadow = Enhancements.parse("function:* max-frames=5", cache)
+
+    frames = [
+        create_match_frame({"function": "bar"}, "native"),
+        create_match_frame({"function": "foo"}, "native"),
+    ]
+    exception_data = {"ty": None, "value": None, "mechanism": None}
+
+    assert primary.shadow_diff(shadow, frames, exception_data) == [
+        ("in_app", 1, True, False),
+        ("max_frames", None, 3, 5),
+    ]
+    assert frames[1]["in_app"] is False
+    assert primary.shadow_diff(primary, frames, exception_data) == []
+
+
+def test_default_in_app():
+    system_frame = create_match_frame({"package": "/usr/lib/libc.so.6"}, "native")
+    app_frame = create_match_frame({"package": "/opt/app/server"}, "native")
+    assert default_in_app(system_frame) is False
+    assert default_in_app(app_frame) is None
+
+    system_frame["in_app"] = None
+    enhancer = Enhancements.parse("function:main +app", cache)
+    modified_frames = enhancer.apply_modifications_to_frames(
+        [system_frame],
+        {"ty": None, "value": None, "mechanism": None},
+        derive_default_in_app=True,
+    )
+    assert modified_frames == [(None, False)]
+
+
+def test_otel_frame():
+    frame = otel_frame(
+        {
+            "code.function.name": "handleRequest",
+            "code.namespace": "app.views",
+            "code.filepath": "/srv/node_modules/app/views.js",
+            "code.lineno": 42,
+        },
+        "nodejs",
+    )
+    assert frame["family"] == b"javascript"
+    assert frame["function"] == b"handleRequest"
+    assert frame["module"] == b"app.views"
+    assert frame["path"] == b"/srv/node_modules/app/views.js"
+    assert frame["in_app"] is None
+
+    enhancer = Enhancements.parse("family:javascript path:**/node_modules/** -app", cache)
+    modified_frames = enhancer.apply_modifications_to_frames(
+        [frame, otel_frame({}, None)],
+        {"ty": None, "value": None, "mechanism": None},
+    )
+    assert modified_frames == [(None, False), (None, None)]
+
+
+def test_apply_with_changed():
+    enhancer = Enhancements.parse("function:foo +app category=foo", cache)
+    exception_data = {"ty": None, "value": None, "mechanism": None}
+
+    def apply(frames):
+        result = enhancer.apply_modifications(frames, exception_data)
+        return result.frames, result.matched, result.changed
+
+    frames = [create_match_frame({"function": "foo"}, "native")]
+    assert apply(frames) == ([("foo", True)], True, True)
+
+    frames = [create_match_frame({"function": "foo", "in_app": True}, "native")]
+    frames[0]["category"] = b"foo"
+    assert apply(frames) == ([("foo", True)], True, False)
+
+    frames = [create_match_frame({"function": "bar"}, "native")]
+    assert apply(frames) == ([(None, False)], False, False)
+
+
+def test_literal_patterns():
+    enhancer = Enhancements.parse("function:=-[UIApplication* +app", cache)
+    frames = [
+        create_match_frame({"function": "-[UIApplication sendEvent:]"}, "native"),
+        create_match_frame({"function": "+[UIApplication sharedApplication]"}, "native"),
+    ]
+    exception_data = {"ty": None, "value": None, "mechanism": None}
+    assert enhancer.apply_modifications_to_frames(frames, exception_data) == [
+        (None, True),
+        (None, False),
+    ]
+
+
+def test_rule_priorities():
+    enhancer = Enhancements.parse(
+        "function:foo +app priority=10\n"
+        "function:* -app",
+        cache,
+    )
+
+    frames = [
+        create_match_frame({"function": "foo"}, "native"),
+        create_match_frame({"function": "bar"}, "native"),
+    ]
+    exception_data = {"ty": None, "value": None, "mechanism": None}
+
+    modified_frames = enhancer.apply_modifications_to_frames(frames, exception_data)
+    assert modified_frames == [(None, True), (None, False)]
+    assert enhancer[0].actions[-1] == {"type": "var", "name": "priority", "value": 10}
+
+
+def test_apply_modifications_with_vars():
+    enhancer = Enhancements.parse("stack.has_vars:yes function:secret* -vars", cache)
+
+    frames = [
+        create_match_frame({"function": "secret_key"}, "python"),
+        create_match_frame({"function": "secret_key"}, "python"),
+        create_match_frame({"function": "other"}, "python"),
+    ]
+    frames[0]["has_vars"] = True
+    frames[2]["has_vars"] = True
+    exception_data = {"ty": None, "value": None, "mechanism": None}
+
+    result = enhancer.apply_modifications(frames, exception_data)
+    assert result.frames == [(None, False)] * 3
+    assert result.has_vars == [False, None, True]
+
+
+def test_match_raw_function():
+    enhancer = Enhancements.parse("function:foo<int> -app", cache)
+
+    frames = [create_match_frame({"function": "foo", "in_app": True}, "native")]
+    frames[0]["raw_function"] = b"foo<int>"
+    exception_data = {"ty": None, "value": None, "mechanism": None}
+
+    assert enhancer.apply_modifications_to_frames(frames, exception_data) == [(None, True)]
+    assert enhancer.apply_modifications_to_frames(
+        frames, exception_data, match_raw_function=True
+    ) == [(None, False)]
+
+
+def test_trust_matcher():
+    enhancer = Enhancements.parse("stack.trust:scan -app", cache)
+
+    frames = [
+        create_match_frame({"function": "foo", "in_app": True}, "native"),
+        create_match_frame({"function": "foo", "in_app": True}, "native"),
+    ]
+    frames[0]["trust"] = b"scan"
+    exception_data = {"ty": None, "value": None, "mechanism": None}
+
+    assert enhancer.apply_modifications_to_frames(frames, exception_data) == [
+        (None, False),
+        (None, True),
+    ]
+
+
+def test_apply_categories():
+    enhancer = Enhancements.parse("function:foo category=foo -app", cache)
+
+    frames = [create_match_frame({"function": "foo", "in_app": True}, "native")]
+    exception_data = {"ty": None, "value": None, "mechanism": None}
+
+    assert enhancer.apply_categories(frames, exception_data) == ["foo"]
+
+
+def test_dedup():
+    enhancer = Enhancements.parse(
+        "function:foo -app\nfunction:foo +app\nfunction:foo -app", cache
+    )
+    enhancer.dedup()
+
+    frames = [create_match_frame({"function": "foo"}, "native")]
+    exception_data = {"ty": None, "value": None, "mechanism": None}
+
+    assert enhancer.apply_modifications_to_frames(frames, exception_data) == [(None, False)]
+
+
+def test_copy():
+    enhancer = Enhancements.parse("function:foo -app", cache)
+    other = Enhancements.parse("function:foo +app", cache)
+
+    frames = [create_match_frame({"function": "foo"}, "native")]
+    exception_data = {"ty": None, "value": None, "mechanism": None}
+
+    combined = enhancer.with_additional_rules(other)
+    assert len(enhancer) == 1
+    assert len(combined) == 2
+    assert combined.apply_modifications_to_frames(frames, exception_data) == [(None, True)]
+
+    copy = enhancer.copy()
+    copy.extend_from(other)
+    assert len(enhancer) == 1
+    assert len(copy) == 2
+
+
+def test_stacktrace_state():
+    enhancer = Enhancements.parse(
+        "function:foo max-frames=3\nfunction:bar invert-stacktrace=1", cache
+    )
+    frames = [create_match_frame({"function": "foo"}, "native")]
+    exception_data = {"ty": None, "value": None, "mechanism": None}
+
+    state = enhancer.stacktrace_state(frames, exception_data, min_frames=2)
+    assert state.max_frames == 3
+    assert state.max_frames_setter == "function:foo max-frames=3"
+    assert state.min_frames == 2
+    assert state.min_frames_setter is None
+    assert state.invert_stacktrace is False
+    assert state.invert_stacktrace_setter is None
+
+
+def test_rules_setting():
+    enhancer = Enhancements.parse(
+        "function:foo max-frames=3\nfunction:bar +app\nfunction:baz max-frames=5",
+        cache,
+    )
+    assert enhancer.rules_setting("max-frames") == [
+        "function:foo max-frames=3",
+        "function:baz max-frames=5",
+    ]
+    assert enhancer.rules_setting("category") == []
+
+    with pytest.raises(ValueError, match="unknown variable"):
+        enhancer.rules_setting("max_frames")
+
+
+def test_apply_all():
+    enhancer = Enhancements.parse(
+        "function:foo +app max-frames=3\nfunction:bar -group", cache
+    )
+    frames = [
+        create_match_frame({"function": "foo"}, "native"),
+        create_match_frame({"function": "bar"}, "native"),
+    ]
+    exception_data = {"ty": None, "value": None, "mechanism": None}
+    components = [Component(contributes=True), Component(contributes=True)]
+
+    modifications, result, state = enhancer.apply_all(frames, exception_data, components)
+    assert modifications == [(None, True), (None, False)]
+    assert [c.contributes for c in components] == [True, False]
+    assert result.contributes
+    assert state.max_frames == 3
+    assert state.max_frames_setter == "function:foo +app max-frames=3"
+
+
+def test_result_cache():
+    enhancer = Enhancements.parse("function:foo +app category=main\nfunction:bar -app", cache)
+    frames = [
+        create_match_frame({"function": "foo"}, "native"),
+        create_match_frame({"function": "bar", "in_app": True}, "native"),
+    ]
+    exception_data = {"ty": None, "value": None, "mechanism": None}
+    expected = enhancer.apply_modifications_to_frames(frames, exception_data)
+
+    result_cache = ResultCache(100)
+    for _ in range(3):
+        result = enhancer.apply_modifications_to_frames_cached(frames, exception_data, result_cache)
+        assert result == expected
+    stats = result_cache.stats()
+    assert (stats["hits"], stats["misses"], stats["entries"]) == (2, 1, 1)
+    assert abs(stats["hit_rate"] - 2 / 3) < 1e-9
+    assert repr(result_cache) == "ResultCache(hits=2, misses=1, entries=1)"
+
+
+def test_assemble_variants():
+    enhancer = Enhancements.parse("function:lib_helper +group\nfunction:app_noise -group", cache)
+    frames = [
+        create_match_frame({"function": "app_noise", "in_app": True}, "native"),
+        create_match_frame({"function": "lib_helper"}, "native"),
+        create_match_frame({"function": "lib_other"}, "native"),
+    ]
+    exception_data = {"ty": None, "value": None, "mechanism": None}
+    components = [Component(contributes=True) for _ in frames]
+
+    variants = enhancer.assemble_variants(frames, exception_data, components)
+    assert [c.contributes for c in components] == [True, True, True]
+
+    app_components, app_result = variants["app"]
+    assert [c.contributes for c in app_components] == [False, True, False]
+    assert app_components[2].hint == "non app frame"
+    assert app_result.contributes
+
+    system_components, system_result = variants["system"]
+    assert [c.contributes for c in system_components] == [False, True, True]
+    assert system_components[0].hint == "ignored by stack trace rule (function:app_noise -group)"
+    assert system_result.contributes
+
+
+def test_default_config():
+    enhancer = Enhancements.default("newstyle:2023-01-11", cache)
+    frames = [create_match_frame({"function": "foo"}, "native")]
+    exception_data = {"ty": None, "value": None, "mechanism": None}
+    assert len(enhancer.apply_modifications_to_frames(frames, exception_data)) == 1
+
+    with pytest.raises(RuntimeError, match="unknown default enhancements"):
+        Enhancements.default("newstyle:1970-01-01", cache)
+
+
+def test_pickle():
+    enhancer = Enhancements.parse(
+        '[ function:foo ] | path:"**/Program Files/**" -app\nfunction:bar +group',
+        cache,
+    )
+    frames = [
+        create_match_frame({"function": "foo"}, "native"),
+        create_match_frame({"function": "bar", "abs_path": "C:/Program Files/x"}, "native"),
+    ]
+    exception_data = {"ty": None, "value": None, "mechanism": None}
+
+    unpickled = pickle.loads(pickle.dumps(enhancer))
+    assert unpickled.apply_modifications_to_frames(
+        frames, exception_data
+    ) == enhancer.apply_modifications_to_frames(frames, exception_data)
+
+    assert isinstance(pickle.loads(pickle.dumps(cache)), Cache)
+
+
+def test_cache_persistence(tmp_path):
+    path = tmp_path / "cache.bin"
+    saved_cache = Cache(10)
+    Enhancements.parse("function:foo -app\nfunction:bar +app", saved_cache)
+    assert saved_cache.save_to(path) == 2
+
+    loaded_cache = Cache(10)
+    assert loaded_cache.load_from(str(path)) == 2
+
+    with pytest.raises(OSError):
+        loaded_cache.load_from(tmp_path / "missing.bin")
+
+
+def test_lazy_regexes():
+    lazy_cache = Cache(1_000, lazy_regexes=True)
+    enhancer = Enhancements.parse("function:foo* -app\nfunction:bar +app", lazy_cache)
+
+    frames = [
+        create_match_frame({"function": "foobar", "in_app": True}, "native"),
+        create_match_frame({"function": "bar"}, "native"),
+    ]
+    exception_data = {"ty": None, "value": None, "mechanism": None}
+
+    assert enhancer.apply_modifications_to_frames(frames, exception_data) == [
+        (None, False),
+        (None, True),
+    ]
+
+    unpickled = pickle.loads(pickle.dumps(lazy_cache))
+    assert unpickled.__reduce__()[1] == (1_000, True)
+
+
+def test_cache_policies():
+    policy_cache = Cache(2, ttl=3600, admission=True)
+    assert repr(policy_cache) == (
+        "Cache(size=2, lazy_regexes=False, ttl=3600.0, admission=True, "
+        "cached_rules=0, cached_regexes=0)"
+    )
+    unpickled = pickle.loads(pickle.dumps(policy_cache))
+    assert repr(unpickled) == repr(policy_cache)
+
+    # Rules requested only once don't evict rules that were requested more often.
+    for _ in range(3):
+        Enhancements.parse("function:foo -app\nfunction:bar -app", policy_cache)
+    Enhancements.parse("function:baz -app", policy_cache)
+    assert repr(policy_cache).endswith("cached_rules=2, cached_regexes=2)")
+
+    expiring_cache = Cache(10, ttl=0)
+    Enhancements.parse("function:foo -app", expiring_cache)
+    assert repr(expiring_cache).endswith("cached_rules=0, cached_regexes=0)")
+
+    with pytest.raises(ValueError):
+        Cache(10, ttl=-1)
+
+
+def test_to_json():
+    enhancer = Enhancements.parse("!family:native function:foo ~+app", cache)
+    assert json.loads(enhancer.to_json()) == [
+        {
+            "text": "!family:native function:foo ~+app",
+            "matchers": [
+                {
+                    "kind": "frame",
+                    "key": "family",
+                    "pattern": "native",
+                    "negated": True,
+                    "offset": None,
+                },
+                {
+                    "kind": "frame",
+                    "key": "function",
+                    "pattern": "foo",
+                    "negated": False,
+                    "offset": None,
+                },
+            ],
+            "actions": [{"type": "flag", "flag": "app", "value": True, "range": "siblings"}],
+        }
+    ]
+
+
+def test_pattern_census():
+    enhancer = Enhancements.parse(
+        "path:**/vendor/** function:malloc,calloc -app\n"
+        "[ !module:std::* ] | function:=operator[] +app",
+        cache,
+    )
+    census = enhancer.pattern_census()
+    assert census.rules == 2
+    assert census.matchers == {"function": 2, "module": 1, "path": 1}
+    assert census.negated_matchers == 1
+    assert census.caller_matchers == 1
+    assert census.callee_matchers == 0
+    assert census.glob_features == {
+        "alternatives": 1,
+        "literal": 1,
+        "recursive_wildcard": 1,
+        "wildcard": 1,
+    }
+    assert census == enhancer.pattern_census()
+    assert repr(census).startswith("PatternCensus(rules=2, matchers={")
+
+
+def test_warnings():
+    enhancer = Enhancements.parse("function:[foo -app\napp:maybe +group\nmodule:foo -app", cache)
+    warnings = enhancer.warnings()
+    assert [(w["rule"], w["matcher"], w["kind"]) for w in warnings] == [
+        ("function:[foo -app", "function:[foo", "invalid_pattern"),
+        ("app:maybe +group", "app:maybe", "invalid_flag"),
+    ]
+    assert "unclosed character class" in warnings[0]["message"]
+
+    # The msgpack encoding of `function:[foo -app`.
+    encoded = b"\x93\x02\x90\x91\x92\x91\xa5f[foo\x91\xcd\x03\x01"
+    decoded = Enhancements.from_config_structure(encoded, cache)
+    assert decoded.warnings() == warnings[:1]
+
+
+def test_rule_view():
+    enhancer = Enhancements.parse(
+        "[ function:foo ] | function:bar -app\nfunction:baz max-frames=3", cache
+    )
+    assert len(enhancer) == 2
+
+    rule = enhancer[0]
+    assert isinstance(rule, RuleView)
+    assert rule.text == "[function:foo] | function:bar -app"
+    assert rule.matchers[0] == {
+        "kind": "frame",
+        "key": "function",
+        "pattern": "foo",
+        "negated": False,
+        "offset": "caller",
+    }
+    assert rule.actions == [{"type": "flag", "flag": "app", "value": False, "range": None}]
+    assert rule.is_modifier and rule.is_updater
+
+    rule = enhancer[-1]
+    assert rule.actions == [{"type": "var", "name": "max-frames", "value": 3}]
+    assert rule.is_updater and not rule.is_modifier
+    assert repr(rule) == "RuleView('function:baz max-frames=3')"
+
+    with pytest.raises(IndexError):
+        enhancer[2]
+
+
+def test_migrate():
+    text, transformations = migrate(
+        "# comment\nstack.abs_path:**/foo.js   -app\nfunction:foo +app\n", cache
+    )
+    assert text == "# comment\npath:**/foo.js -app\nfunction:foo +app\n"
+    assert transformations == [
+        (1, "renamed matcher `stack.abs_path` to `path`"),
+        (1, "reformatted rule"),
+    ]
+
+
+def test_registry():
+    registry = EnhancementsRegistry(10)
+    registry.register_base("base", Enhancements.parse("function:foo -app", cache))
+
+    enhancer = registry.get("base", "function:bar +app", cache)
+    frames = [
+        create_match_frame({"function": "foo"}, "native"),
+        create_match_frame({"function": "bar"}, "native"),
+    ]
+    exception_data = {"ty": None, "value": None, "mechanism": None}
+
+    modified_frames = enhancer.apply_modifications_to_frames(frames, exception_data)
+    assert modified_frames == [(None, False), (None, True)]
+
+    with pytest.raises(RuntimeError, match="unknown base"):
+        registry.get("unknown", "", cache)
+
+
+def test_component_dict():
+    component = Component(contributes=True, hint="some hint")
+    assert component.to_dict() == {
+        "contributes": True,
+        "hint": "some hint",
+        "hints": ["some hint"],
+    }
+    assert repr(component) == "Component(contributes=True, hint='some hint')"
+
+    component = Component.from_dict({"contributes": False})
+    assert component.contributes is False
+    assert component.hint is None
+    assert component.hints == []
+    assert not component.is_sentinel_frame
+
+    component = Component.from_dict({"contributes": False, "hint": "b", "hints": ["a", "b"]})
+    assert component.hints == ["a", "b"]
+    assert Component.from_dict(component.to_dict()) == component
+
+    component = Component(
+        contributes=False, is_sentinel_frame=True, is_prefix_frame=True, id="frame"
+    )
+    assert component.to_dict() == {
+        "contributes": False,
+        "hint": None,
+        "hints": [],
+        "is_sentinel_frame": True,
+        "is_prefix_frame": True,
+        "id": "frame",
+    }
+    assert Component.from_dict(component.to_dict()) == component
+
+
+def test_reprs_and_equality():
+    local_cache = Cache(10)
+    enhancer = Enhancements.parse("function:foo -app\nfunction:bar max-frames=3", local_cache)
+    assert repr(local_cache) == (
+        "Cache(size=10, lazy_regexes=False, cached_rules=2, cached_regexes=2)"
+    )
+    assert repr(enhancer) == "Enhancements(rules=2, modifier_rules=1, updater_rules=2)"
+    assert enhancer == Enhancements.parse("function:foo  -app\nfunction:bar max-frames=3", cache)
+    assert enhancer != Enhancements.empty()
+    with pytest.raises(TypeError):
+        hash(enhancer)
+
+    assert enhancer[0] == Enhancements.parse("function:foo -app", cache)[0]
+    assert len({enhancer[0], enhancer[0], enhancer[1]}) == 2
+
+    component = Component(contributes=True, is_sentinel_frame=True, values=["a"])
+    assert repr(component) == (
+        "Component(contributes=True, hint=None, is_sentinel_frame=True, values=['a'])"
+    )
+    assert component == Component(contributes=True, is_sentinel_frame=True, values=["a"])
+    assert component != Component(contributes=True)
+
+    frames = [create_match_frame({"function": "bar"}, "native")]
+    exception_data = {"ty": None, "value": None, "mechanism": None}
+    _, result, state = enhancer.apply_all(frames, exception_data, [Component()])
+    assert repr(result) == (
+        "AssembleResult(contributes=False, hint=None, invert_stacktrace=False, "
+        "budget_exhausted_at=None)"
+    )
+    assert repr(state) == "StacktraceState(max_frames=3, min_frames=0, invert_stacktrace=False)"
+    _, same_result, same_state = enhancer.apply_all(frames, exception_data, [Component()])
+    assert {result, same_result} == {result}
+    assert {state, same_state} == {state}
+
+    registry = EnhancementsRegistry(10)
+    registry.register_base("b", enhancer)
+    registry.register_base("a", enhancer)
+    assert repr(registry) == "EnhancementsRegistry(bases=['a', 'b'])"
+
+
+def test_component_values():
+    enhancer = Enhancements.parse("function:bar -group", cache)
+    frames = [
+        create_match_frame({"function": f}, "native") for f in ["foo", "foo", "bar"]
+    ]
+    exception_data = {"ty": None, "value": None, "mechanism": None}
+    components = [
+        Component(id="frame", values=["foo"]),
+        Component(id="frame", values=[]),
+        Component.from_dict({"id": "frame", "values": ["bar"]}),
+    ]
+
+    result = enhancer.assemble_stacktrace_component(frames, exception_data, components)
+    assert result.contributes
+    assert [c.contributes for c in components] == [True, False, False]
+    assert components[0].to_dict() == {
+        "contributes": True,
+        "hint": None,
+        "hints": [],
+        "id": "frame",
+        "values": ["foo"],
+    }
+
+
+def test_trim_to_sentinel():
+    enhancer = Enhancements.empty()
+    frames = [create_match_frame({"function": f}, "native") for f in "abc"]
+    exception_data = {"ty": None, "value": None, "mechanism": None}
+    components = [
+        Component(contributes=True, is_sentinel_frame=True),
+        Component(contributes=True, is_prefix_frame=True),
+        Component(contributes=True),
+    ]
+
+    enhancer.assemble_stacktrace_component(
+        frames, exception_data, components, trim_to_sentinel=True
+    )
+    assert [c.contributes for c in components] == [True, True, False]
+    assert components[2].hint == "ignored because it is above the sentinel frame"
+
+
+def test_default_contributes():
+    enhancer = Enhancements.parse("function:foo +app", cache)
+    enhancer = enhancer.with_default_contributes("newstyle:2023-01-11", cache)
+    assert len(enhancer) == 4
+    assert enhancer[0].text == "family:native !function:* -group"
+
+    frames = [
+        create_match_frame({"function": "foo"}, "native"),
+        create_match_frame({"package": "/usr/lib/libc.so"}, "native"),
+    ]
+    exception_data = {"ty": None, "value": None, "mechanism": None}
+    components = [Component(contributes=True), Component(contributes=True)]
+    enhancer.assemble_stacktrace_component(frames, exception_data, components)
+    assert [c.contributes for c in components] == [True, False]
+
+    assert len(Enhancements.default_contributes("newstyle:2023-01-11", cache)) == 3
+    with pytest.raises(RuntimeError, match="unknown default enhancements"):
+        Enhancements.default_contributes("newstyle:1970-01-01", cache)
+
+
+def test_component_hints():
+    enhancer = Enhancements.parse("function:foo -group\nfunction:foo -app", cache)
+    frames = [create_match_frame({"function": "foo", "in_app": True}, "native")]
+    exception_data = {"ty": None, "value": None, "mechanism": None}
+    component = Component(contributes=True, hint="given")
+    assert component.hints == ["given"]
+
+    enhancer.assemble_stacktrace_component(frames, exception_data, [component])
+    assert component.hint == "ignored by stack trace rule (function:foo -group)"
+    assert component.hints == [
+        "ignored by stack trace rule (function:foo -group)",
+        "marked in-app by stack trace rule (function:foo -app)",
+    ]
+
+
+def test_path_normalization():
+    enhancer = Enhancements.parse("path:**/src/*.js -app", cache)
+    frame = create_match_frame({"function": "foo", "in_app": True}, "javascript")
+    frame["path"] = b"C:\\Project\\SRC\\App.js"
+    exception_data = {"ty": None, "value": None, "mechanism": None}
+
+    for _ in range(2):
+        assert enhancer.apply_modifications_to_frames([frame], exception_data) == [
+            (None, False)
+        ]
+
+
 def test_parsing_errors():
     with pytest.raises(RuntimeError, match="failed to parse matchers"):
         Enhancements.parse("invalid.message:foo -> bar", cache)
 
 
+def test_pattern_definitions():
+    enhancer = Enhancements.parse(
+        "@define std family:native function:std::*\n@std -app", cache
+    )
+    assert enhancer[0].text == "family:native function:std::* -app"
+
+    with pytest.raises(InvalidEnhancerConfig, match="unknown pattern `@sdt`"):
+        Enhancements.parse("@define std function:std::*\n@sdt -app", cache)
+
+
+def test_glob_syntax():
+    rules = "path:**/{foo,bar}/** +app"
+    enhancer = Enhancements.parse(rules, cache)
+    frames = [create_match_frame({"abs_path": "/src/foo/main.py"}, "python")]
+    exception_data = {"ty": None, "value": None, "mechanism": None}
+    assert enhancer.apply_modifications_to_frames(frames, exception_data) == [
+        (None, True)
+    ]
+
+    with pytest.raises(InvalidEnhancerConfig, match="uses brace expansion, which is disabled"):
+        Enhancements.parse(rules, cache, glob_braces=False)
+    with pytest.raises(InvalidEnhancerConfig, match="uses a character class, which is disabled"):
+        Enhancements.parse("module:[a-z]* -app", cache, glob_classes=False)
+
+    Enhancements.parse("function:-[* -app", cache)
+    Enhancements.parse("function:=-[* -app", cache, strict_globs=True)
+    with pytest.raises(InvalidEnhancerConfig, match="unclosed character class in `-\\[\\*`"):
+        Enhancements.parse("function:-[* -app", cache, strict_globs=True)
+
+
+def test_resolve_bases():
+    fixtures = Path(__file__).parent / "fixtures"
+    default = (fixtures / "newstyle@2023-01-11.bin").read_bytes()
+    # The msgpack encoding of a config with the base "default" and no rules of its own.
+    derived = b"\x93\x02\x91\xa7default\x90"
+
+    def resolve_base(name: str) -> bytes | None:
+        return {"default": default}.get(name)
+
+    enhancer = Enhancements.from_config_structure(derived, cache, resolve_base=resolve_base)
+    assert enhancer == Enhancements.from_config_structure(default, cache)
+    assert len(Enhancements.from_config_structure(derived, cache)) == 0
+
+    with pytest.raises(InvalidConfigStructure, match="unknown base enhancements `default`"):
+        Enhancements.from_config_structure(derived, cache, resolve_base=lambda name: None)
+    with pytest.raises(InvalidConfigStructure, match="base `default` names itself"):
+        Enhancements.from_config_structure(derived, cache, resolve_base=lambda name: derived)
+
+    def failing_resolve_base(name: str) -> bytes | None:
+        raise KeyError(name)
+
+    with pytest.raises(KeyError):
+        Enhancements.from_config_structure(derived, cache, resolve_base=failing_resolve_base)
+
+
+def test_config_structure_buffers():
+    fixtures = Path(__file__).parent / "fixtures"
+    default = (fixtures / "newstyle@2023-01-11.bin").read_bytes()
+    enhancer = Enhancements.from_config_structure(default, cache)
+
+    assert Enhancements.from_config_structure(bytearray(default), cache) == enhancer
+    assert Enhancements.from_config_structure(memoryview(default), cache) == enhancer
+
+    derived = memoryview(b"\x00\x93\x02\x91\xa7default\x90")[1:]
+    resolved = Enhancements.from_config_structure(
+        derived, cache, resolve_base=lambda name: bytearray(default)
+    )
+    assert resolved == enhancer
+
+    with pytest.raises(TypeError, match="expected bytes, bytearray, or memoryview, got str"):
+        Enhancements.from_config_structure("not msgpack", cache)
+
+
+def test_to_config_structure():
+    fixtures = Path(__file__).parent / "fixtures"
+    default = (fixtures / "newstyle@2023-01-11.bin").read_bytes()
+    enhancer = Enhancements.from_config_structure(default, cache)
+
+    encoded = enhancer.to_config_structure()
+    assert isinstance(encoded, bytes)
+    assert Enhancements.from_config_structure(encoded, cache) == enhancer
+
+    with pytest.raises(InvalidConfigStructure, match="unable to encode matcher `has_vars`"):
+        Enhancements.parse("has_vars:yes -app", cache).to_config_structure()
+    with pytest.raises(InvalidConfigStructure, match="unable to encode matcher `thread.main`"):
+        Enhancements.parse("thread.main:yes -app", cache).to_config_structure()
+
+
+def test_thread_matchers():
+    enhancer = Enhancements.parse(
+        """
+        thread.name:com.apple.main-thread function:a +app
+        thread.main:yes !thread.crashed:yes function:b +app
+        thread.id:259 function:c +app
+        """,
+        cache,
+    )
+    frames = [create_match_frame({"function": name}, "native") for name in ("a", "b", "c")]
+
+    def in_app(thread: Optional[dict[str, Any]]) -> list[Optional[bool]]:
+        exception_data: dict[str, Any] = {"ty": None, "value": None, "mechanism": None}
+        if thread is not None:
+            exception_data["thread"] = thread
+        modified = enhancer.apply_modifications_to_frames(frames, exception_data)
+        return [app for _category, app in modified]
+
+    main_thread = {"name": b"com.apple.main-thread", "id": 259, "main": True, "crashed": False}
+    assert in_app(main_thread) == [True, True, True]
+    assert in_app({**main_thread, "id": b"259"}) == [True, True, True]
+    assert in_app({"name": None, "main": False}) == [False, False, False]
+    assert in_app(None) == [False, False, False]
+
+    matchers = json.loads(enhancer.to_json())[1]["matchers"]
+    assert [(m["kind"], m["key"]) for m in matchers] == [
+        ("thread", "thread.main"),
+        ("thread", "thread.crashed"),
+        ("frame", "function"),
+    ]
+
+    with pytest.raises(InvalidEnhancerConfig, match="invalid boolean value `maybe`"):
+        Enhancements.parse("thread.main:maybe -app", cache)
+
+
+def test_error_classes():
+    with pytest.raises(InvalidEnhancerConfig, match="invalid flag name"):
+        Enhancements.parse("function:foo +nope", cache)
+
+    with pytest.raises(InvalidConfigStructure):
+        Enhancements.from_config_structure(b"\x00", cache)
+
+    with pytest.raises(RegexTooComplex):
+        Enhancements.parse("type:" + "?" * 200_000 + " -app", Cache(0))
+
+    # All of them are still `RuntimeError`s.
+    with pytest.raises(RuntimeError):
+        Enhancements.parse("function:foo +nope", cache)
+
+
+def test_error_location():
+    with pytest.raises(InvalidEnhancerConfig) as excinfo:
+        Enhancements.parse("function:foo -app\n  function:bar +nope", cache)
+    err = excinfo.value
+    assert (err.line, err.column_start, err.column_end) == (2, 16, 20)
+    assert (err.token, err.kind) == ("nope", "invalid_action")
+
+    with pytest.raises(InvalidEnhancerConfig) as excinfo:
+        Enhancements.parse("thread.state:x -app", cache)
+    assert (excinfo.value.token, excinfo.value.kind) == ("thread.state:x", "invalid_matcher")
+
+    # Errors of other functions have no location.
+    with pytest.raises(InvalidEnhancerConfig) as excinfo:
+        migrate("function:foo +nope", cache)
+    assert (excinfo.value.line, excinfo.value.kind) == (None, None)
+
+
+def test_allowed_categories():
+    Enhancements.parse("function:foo category=ui", cache, allowed_categories=["ui"])
+
+    with pytest.raises(RuntimeError, match="unknown category `iu`"):
+        Enhancements.parse("function:foo category=iu", cache, allowed_categories=["ui"])
+
+
+def test_parse_lenient():
+    enhancements, errors = Enhancements.parse_lenient(
+        "function:foo +app\nfunction:bar +nope\n\nfunction:baz category=iu\nfunction:qux -app",
+        cache,
+        allowed_categories=["ui"],
+    )
+    rules = [enhancements[i].text for i in range(len(enhancements))]
+    assert rules == ["function:foo +app", "function:qux -app"]
+    assert [line for line, _ in errors] == [2, 4]
+    assert "invalid flag name `nope`" in errors[0][1]
+    assert "unknown category `iu`" in errors[1][1]
+
+
 def test_caller_recursion():
     # Remove this test when CallerMatch can be applied recursively
     with pytest.raises(RuntimeError, match="failed to parse matchers"):
@@ -0,0 +1,23 @@
+import hashlib
+import zlib
+
+import pytest
+from sentry_ophio.hashing import crc32, fnv1a_32, fnv1a_64, md5_hex
+
+
+def test_matches_python_hashes():
+    parts = ["hello", "wörld"]
+    data = "".join(parts).encode("utf-8")
+    assert md5_hex(parts) == hashlib.md5(data).hexdigest()
+    assert crc32(parts) == zlib.crc32(data)
+    assert fnv1a_32(["hello"]) == 0x4F9F2CAB
+    assert fnv1a_64(["hello"]) == 0xA430D84680AABD0B
+
+
+def test_lowercasing():
+    assert md5_hex(["HeLLo"], lowercase="ascii") == md5_hex(["hello"])
+    assert md5_hex(["ÄB"], lowercase="ascii") == md5_hex(["Äb"])
+    assert md5_hex(["STRASSE ΣΑΣ"], lowercase="unicode") == md5_hex(["STRASSE ΣΑΣ".lower()])
+
+    with pytest.raises(ValueError, match="invalid lowercase mode"):
+        crc32(["hello"], lowercase="upper")  # type: ignore[arg-type]
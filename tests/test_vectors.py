@@ -0,0 +1,66 @@
+import json
+from pathlib import Path
+
+import pytest
+from sentry_ophio.enhancers import Cache, Component, Enhancements
+
+from test_enhancer import create_match_frame
+
+VECTORS_DIR = Path(__file__).parent / "vectors"
+
+cache = Cache(1_000)
+
+
+def load_vectors() -> list[dict]:
+    vectors = []
+    for path in sorted(VECTORS_DIR.glob("*.json")):
+        vectors.extend(json.loads(path.read_text()))
+    return vectors
+
+
+def encode(value: str | None) -> bytes | None:
+    return value.encode("utf-8") if value is not None else None
+
+
+@pytest.mark.parametrize("vector", load_vectors(), ids=lambda vector: vector["name"])
+def test_vector(vector):
+    enhancer = Enhancements.parse(vector["rules"], cache)
+
+    frames = []
+    for frame in vector["frames"]:
+        match_frame = create_match_frame(frame, vector.get("platform", "other"))
+        for key in ("raw_function", "trust"):
+            if key in frame:
+                match_frame[key] = encode(frame[key])
+        frames.append(match_frame)
+
+    exception = vector.get("exception", {})
+    exception_data = {
+        "ty": encode(exception.get("type")),
+        "value": encode(exception.get("value")),
+        "module": encode(exception.get("module")),
+        "mechanism": encode(exception.get("mechanism")),
+    }
+    components = [
+        Component(contributes=component["contributes"])
+        for component in vector.get("components", [{"contributes": True}] * len(frames))
+    ]
+
+    modified_frames, result, _ = enhancer.apply_all(frames, exception_data, components)
+
+    expected = vector["expected"]
+    if "frames" in expected:
+        assert [
+            {"in_app": in_app, "category": category} for category, in_app in modified_frames
+        ] == expected["frames"]
+    if "components" in expected:
+        assert [
+            {"contributes": component.contributes, "hint": component.hint}
+            for component in components
+        ] == expected["components"]
+    if "stacktrace" in expected:
+        assert {
+            "contributes": result.contributes,
+            "hint": result.hint,
+            "invert_stacktrace": result.invert_stacktrace,
+        } == expected["stacktrace"]